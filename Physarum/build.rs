@@ -0,0 +1,29 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Passes the current git commit to the run manifest as `PHYSARUM_GIT_COMMIT`. Builds
+/// outside a git checkout simply leave it unset.
+fn main() {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|stdout| stdout.trim().to_string())
+    };
+    if let Some(commit) = git(&["rev-parse", "HEAD"]) {
+        println!("cargo:rustc-env=PHYSARUM_GIT_COMMIT={}", commit);
+    }
+    // Rebuild when HEAD moves, directly or through the branch it points to.
+    let branch = git(&["symbolic-ref", "-q", "HEAD"]);
+    for path in ["HEAD", "packed-refs"].into_iter().chain(branch.as_deref()) {
+        if let Some(path) = git(&["rev-parse", "--git-path", path]) {
+            // Missing files would rerun the script on every build.
+            if Path::new(&path).exists() {
+                println!("cargo:rerun-if-changed={}", path);
+            }
+        }
+    }
+}
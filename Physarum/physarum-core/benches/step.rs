@@ -0,0 +1,50 @@
+//! Times `PhysarumModel::step` on a large grid, with and without sorting the agents by
+//! cell and with fast math. Run with `cargo bench -p physarum-core`.
+
+use physarum_core::physarum_model::PhysarumModel;
+use std::time::Instant;
+
+const SIZE: usize = 1024;
+const PARTICLES: usize = 1 << 20;
+const WARMUP_STEPS: usize = 20;
+const STEPS: usize = 50;
+
+/// Milliseconds per step once the trails have formed.
+fn time_steps(agent_sort_interval: usize, fast_math: bool) -> f64 {
+    let mut model = PhysarumModel::builder()
+        .size(SIZE, SIZE)
+        .particles(PARTICLES)
+        .populations(3)
+        .seed(1)
+        .agent_sort_interval(agent_sort_interval)
+        .fast_math(fast_math)
+        .build()
+        .unwrap();
+    for _ in 0..WARMUP_STEPS {
+        model.step();
+    }
+    let start = Instant::now();
+    for _ in 0..STEPS {
+        model.step();
+    }
+    start.elapsed().as_secs_f64() * 1000.0 / STEPS as f64
+}
+
+fn main() {
+    println!(
+        "{}x{} grid, {} agents in 3 populations",
+        SIZE, SIZE, PARTICLES
+    );
+    for (interval, fast_math) in [(0, false), (50, false), (10, false), (0, true)] {
+        let label = match (interval, fast_math) {
+            (0, false) => "unsorted".to_string(),
+            (0, true) => "fast math".to_string(),
+            (n, _) => format!("sorted every {} steps", n),
+        };
+        println!(
+            "{:>24}: {:.2} ms per step",
+            label,
+            time_steps(interval, fast_math)
+        );
+    }
+}
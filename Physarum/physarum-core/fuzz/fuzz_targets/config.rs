@@ -0,0 +1,34 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use physarum_core::builder::PhysarumModelBuilder;
+use physarum_core::config::SimulationConfig;
+use physarum_core::image::DynamicImage;
+
+/// Valid configs beyond this are only validated, since simulating them would just be slow.
+const MAX_CELLS: usize = 1 << 14;
+const MAX_PARTICLES: usize = 1 << 12;
+const MAX_POPULATIONS: usize = 8;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(config) = SimulationConfig::from_reader(data) else {
+        return;
+    };
+    let builder = PhysarumModelBuilder::from_config(&config).seed(0);
+    if builder.validate().is_err()
+        || config.width.saturating_mul(config.height) > MAX_CELLS
+        || config.n_particles > MAX_PARTICLES
+        || config.n_populations > MAX_POPULATIONS
+    {
+        return;
+    }
+    let mut model = builder.build().unwrap();
+    let _ = model.set_population_configs(config.populations.clone());
+    let _ = model.set_render_settings(config.render.clone());
+    model.step();
+    let mut image = DynamicImage::new_rgb8(config.width as u32, config.height as u32);
+    model.save_to_image(&mut image);
+    if config.render.ink.layers > 0 {
+        model.ink_layers(config.render.ink.layers, config.render.ink.method);
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use physarum_core::lut::Lut;
+
+fuzz_target!(|text: &str| {
+    if let Ok(lut) = Lut::parse(text) {
+        lut.apply([0.0, 0.5, 1.0]);
+    }
+});
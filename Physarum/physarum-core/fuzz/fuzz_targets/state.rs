@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use physarum_core::image::DynamicImage;
+use physarum_core::physarum_model::PhysarumModel;
+use physarum_core::state::ModelState;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(state) = ModelState::from_reader(data) else {
+        return;
+    };
+    let (width, height) = (state.width(), state.height());
+    let Ok(mut model) = PhysarumModel::from_state(state) else {
+        return;
+    };
+    model.step();
+    let mut image = DynamicImage::new_rgb8(width as u32, height as u32);
+    model.save_to_image(&mut image);
+});
@@ -0,0 +1,189 @@
+use super::par::*;
+use super::particle;
+pub use super::particle::Particle;
+use alloc::vec::Vec;
+
+/// The agents of all populations as a struct of arrays: one array per attribute instead of
+/// one `Particle` per agent. Passes that only need some attributes, like depositing at the
+/// positions or filtering by population, only pull those through the cache, and the
+/// movement update runs over plain slices of floats the compiler can vectorize.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Agents {
+    x: Vec<f32>,
+    y: Vec<f32>,
+    angle: Vec<f32>,
+    id: Vec<usize>,
+}
+
+/// Bytes one agent takes.
+pub const AGENT_BYTES: usize = 3 * core::mem::size_of::<f32>() + core::mem::size_of::<usize>();
+
+impl Agents {
+    pub fn new() -> Self {
+        Agents::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Agents {
+            x: Vec::with_capacity(capacity),
+            y: Vec::with_capacity(capacity),
+            angle: Vec::with_capacity(capacity),
+            id: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.id.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> Option<Particle> {
+        (i < self.len()).then(|| Particle {
+            x: self.x[i],
+            y: self.y[i],
+            angle: self.angle[i],
+            id: self.id[i],
+        })
+    }
+
+    pub fn push(&mut self, agent: Particle) {
+        self.x.push(agent.x);
+        self.y.push(agent.y);
+        self.angle.push(agent.angle);
+        self.id.push(agent.id);
+    }
+
+    /// Copies of the agents, in order.
+    pub fn iter(&self) -> impl Iterator<Item = Particle> + '_ {
+        (0..self.len()).map(|i| Particle {
+            x: self.x[i],
+            y: self.y[i],
+            angle: self.angle[i],
+            id: self.id[i],
+        })
+    }
+
+    /// The agents of one population.
+    pub fn of_population(&self, population: usize) -> impl Iterator<Item = Particle> + '_ {
+        self.id
+            .iter()
+            .enumerate()
+            .filter(move |(_, &id)| id == population)
+            .map(|(i, _)| self.get(i).unwrap())
+    }
+
+    /// Keeps the agents `keep` returns true for, in order.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Particle) -> bool) {
+        *self = self.iter().filter(|agent| keep(agent)).collect();
+    }
+
+    /// Moves every agent to the population `population` maps its own to.
+    pub(crate) fn relabel(&mut self, population: impl Fn(usize) -> usize) {
+        self.id.iter_mut().for_each(|id| *id = population(*id));
+    }
+
+    /// The positions and populations of the agents, in order.
+    pub(crate) fn columns(&self) -> (&[f32], &[f32], &[usize]) {
+        (&self.x, &self.y, &self.id)
+    }
+
+    /// Moves every agent to its position in `positions`.
+    pub(crate) fn move_to(&mut self, positions: &[(f32, f32)]) {
+        assert_eq!(positions.len(), self.len());
+        self.x
+            .par_iter_mut()
+            .zip(self.y.par_iter_mut())
+            .zip(positions.par_iter())
+            .for_each(|((x, y), &to)| (*x, *y) = to);
+    }
+
+    /// Calls `f` on every agent in parallel, writing back where it moved and turned to.
+    pub(crate) fn par_for_each(&mut self, f: impl Fn(&mut Particle) + Sync + Send) {
+        self.x
+            .par_iter_mut()
+            .zip(self.y.par_iter_mut())
+            .zip(self.angle.par_iter_mut())
+            .zip(self.id.par_iter())
+            .for_each(|(((x, y), angle), &id)| {
+                let mut agent = Particle {
+                    x: *x,
+                    y: *y,
+                    angle: *angle,
+                    id,
+                };
+                f(&mut agent);
+                (*x, *y, *angle) = (agent.x, agent.y, agent.angle);
+            });
+    }
+
+    /// Reorders the agents along a Z-order curve through the cells they are in, see
+    /// `PhysarumModel::set_agent_sort_interval`. The sort is stable, which keeps its
+    /// result the same on any number of threads.
+    pub(crate) fn sort_by_cell(&mut self) {
+        let mut order: Vec<usize> = (0..self.len()).collect();
+        let (x, y) = (&self.x, &self.y);
+        order.par_sort_by_key(|&i| particle::morton(x[i] as u32, y[i] as u32));
+        self.x = order.iter().map(|&i| self.x[i]).collect();
+        self.y = order.iter().map(|&i| self.y[i]).collect();
+        self.angle = order.iter().map(|&i| self.angle[i]).collect();
+        self.id = order.iter().map(|&i| self.id[i]).collect();
+    }
+
+    pub fn to_vec(&self) -> Vec<Particle> {
+        self.iter().collect()
+    }
+}
+
+impl Extend<Particle> for Agents {
+    fn extend<I: IntoIterator<Item = Particle>>(&mut self, agents: I) {
+        for agent in agents {
+            self.push(agent);
+        }
+    }
+}
+
+impl FromIterator<Particle> for Agents {
+    fn from_iter<I: IntoIterator<Item = Particle>>(agents: I) -> Self {
+        let mut all = Agents::new();
+        all.extend(agents);
+        all
+    }
+}
+
+impl From<Vec<Particle>> for Agents {
+    fn from(agents: Vec<Particle>) -> Self {
+        agents.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    #[test]
+    fn agents_survive_sorting_and_retaining() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let originals: Vec<Particle> = (0..100)
+            .map(|i| Particle::new(64, 64, i % 3, &mut rng))
+            .collect();
+        let mut agents = Agents::from(originals.clone());
+        assert_eq!(agents.to_vec(), originals);
+
+        agents.sort_by_cell();
+        let mut sorted = agents.to_vec();
+        let mut expected = originals.clone();
+        let key = |agent: &Particle| (agent.id, agent.x.to_bits(), agent.y.to_bits());
+        sorted.sort_by_key(key);
+        expected.sort_by_key(key);
+        assert_eq!(sorted, expected);
+
+        agents.retain(|agent| agent.id != 1);
+        assert_eq!(agents.len(), 67);
+        assert!(agents.iter().all(|agent| agent.id != 1));
+        assert_eq!(agents.of_population(2).count(), 33);
+    }
+}
@@ -0,0 +1,204 @@
+use super::agents::Particle;
+use super::error::ConfigError;
+use alloc::format;
+use alloc::string::String;
+use core::f32::consts::PI;
+use core::fmt;
+use core::str::FromStr;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// A temperature that adds random turns to every agent's steering and cools down as the
+/// simulation runs, like simulated annealing: runs start out chaotic, with the agents
+/// wandering off the trails, and crystallize into crisp networks as it drops.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct Annealing {
+    /// Temperature at the first step. At temperature 1, agents turn by up to a whole
+    /// rotation angle either way on top of their rule's turn.
+    pub start: f32,
+    /// Temperature from `steps` on.
+    pub end: f32,
+    /// Steps it takes to cool down from `start` to `end`.
+    pub steps: u64,
+    pub cooling: Cooling,
+    /// Iteration the schedule starts at, e.g. the one it was switched on at in the app.
+    pub begin: u64,
+}
+
+impl Default for Annealing {
+    fn default() -> Self {
+        Annealing {
+            start: 1.0,
+            end: 0.0,
+            steps: 2000,
+            cooling: Cooling::default(),
+            begin: 0,
+        }
+    }
+}
+
+/// How the temperature falls between the start and the end of a schedule.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Cooling {
+    /// At a constant rate.
+    Linear,
+    /// Quickly at first and ever more slowly, covering 99% of the way by the end.
+    #[default]
+    Exponential,
+    /// Slowly at both ends, staying hot for a while and then settling gently.
+    Cosine,
+}
+
+impl FromStr for Cooling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Cooling::Linear),
+            "exponential" => Ok(Cooling::Exponential),
+            "cosine" => Ok(Cooling::Cosine),
+            _ => Err(format!(
+                "Unknown cooling {:?}, expected linear, exponential or cosine",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Cooling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cooling::Linear => write!(f, "linear"),
+            Cooling::Exponential => write!(f, "exponential"),
+            Cooling::Cosine => write!(f, "cosine"),
+        }
+    }
+}
+
+impl Annealing {
+    /// Requires finite, non-negative temperatures.
+    pub(super) fn check(&self) -> Result<(), ConfigError> {
+        let valid = |temperature: f32| temperature >= 0.0 && temperature.is_finite();
+        if valid(self.start) && valid(self.end) {
+            Ok(())
+        } else {
+            Err(ConfigError::Annealing)
+        }
+    }
+
+    /// The temperature at step `iteration`, `start` until the schedule begins.
+    pub fn temperature(&self, iteration: u64) -> f32 {
+        let elapsed = iteration.saturating_sub(self.begin);
+        if elapsed >= self.steps {
+            return self.end;
+        }
+        let progress = elapsed as f32 / self.steps as f32;
+        let remaining = match self.cooling {
+            Cooling::Linear => 1.0 - progress,
+            Cooling::Exponential => libm::powf(0.01, progress),
+            Cooling::Cosine => (1.0 + libm::cosf(PI * progress)) / 2.0,
+        };
+        self.end + (self.start - self.end) * remaining
+    }
+}
+
+/// A random turn of up to `temperature` either way, in multiples of the rotation angle.
+/// It is drawn from the agent's position and heading, so it doesn't depend on the order
+/// or the thread agents are moved in.
+pub(super) fn jitter(agent: &Particle, temperature: f32) -> f32 {
+    if temperature <= 0.0 {
+        return 0.0;
+    }
+    let bits = (u64::from(agent.x.to_bits()) << 32 | u64::from(agent.y.to_bits()))
+        ^ u64::from(agent.angle.to_bits()).rotate_left(17);
+    let mut rng = SmallRng::seed_from_u64(bits);
+    temperature * rng.gen_range(-1.0..=1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::population_config::PopulationConfig;
+    use crate::step::Simulation;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn schedules_cool_from_start_to_end() {
+        for cooling in [Cooling::Linear, Cooling::Exponential, Cooling::Cosine] {
+            let annealing = Annealing {
+                start: 2.0,
+                end: 0.5,
+                steps: 100,
+                cooling,
+                begin: 0,
+            };
+            assert_eq!(annealing.temperature(0), 2.0);
+            assert_eq!(annealing.temperature(100), 0.5);
+            assert_eq!(annealing.temperature(u64::MAX), 0.5);
+            let temperatures: Vec<f32> = (0..=100).map(|i| annealing.temperature(i)).collect();
+            assert!(
+                temperatures.windows(2).all(|w| w[0] >= w[1]),
+                "{:?}",
+                cooling
+            );
+        }
+        let halfway = |cooling| {
+            Annealing {
+                cooling,
+                ..Annealing::default()
+            }
+            .temperature(1000)
+        };
+        assert!(halfway(Cooling::Exponential) < halfway(Cooling::Linear));
+        assert!((halfway(Cooling::Cosine) - halfway(Cooling::Linear)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn jitter_scales_with_the_temperature() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let agents: Vec<_> = (0..200)
+            .map(|_| Particle::new(64, 64, 0, &mut rng))
+            .collect();
+        assert!(agents.iter().all(|agent| jitter(agent, 0.0) == 0.0));
+        let turns: Vec<f32> = agents.iter().map(|agent| jitter(agent, 0.5)).collect();
+        assert!(turns.iter().all(|turn| turn.abs() <= 0.5));
+        assert!(turns.iter().any(|&turn| turn > 0.25) && turns.iter().any(|&turn| turn < -0.25));
+        assert_eq!(jitter(&agents[0], 0.5), turns[0]);
+    }
+
+    #[test]
+    fn only_hot_schedules_change_the_run() {
+        let run = |annealing: Option<Annealing>| {
+            let mut rng = SmallRng::seed_from_u64(3);
+            let config = PopulationConfig::new(&mut rng);
+            let mut simulation =
+                Simulation::new(32, 32, 200, vec![config], vec![vec![1.0]], 1, &mut rng).unwrap();
+            simulation.set_annealing(annealing).unwrap();
+            for _ in 0..20 {
+                simulation.step();
+            }
+            simulation.grids[0].data().to_vec()
+        };
+        let cold = Annealing {
+            start: 0.0,
+            ..Annealing::default()
+        };
+        assert_eq!(run(Some(cold)), run(None));
+        assert_ne!(run(Some(Annealing::default())), run(None));
+        let invalid = Annealing {
+            start: f32::NAN,
+            ..Annealing::default()
+        };
+        let mut rng = SmallRng::seed_from_u64(3);
+        let config = PopulationConfig::new(&mut rng);
+        let mut simulation =
+            Simulation::new(8, 8, 8, vec![config], vec![vec![1.0]], 1, &mut rng).unwrap();
+        assert_eq!(
+            simulation.set_annealing(Some(invalid)),
+            Err(ConfigError::Annealing)
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use super::par::*;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+
+/// What the trail layers are drawn over, instead of black.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BackgroundFill {
+    #[default]
+    None,
+    Solid {
+        color: [u8; 3],
+    },
+    /// Blends from `from` to `to` across the frame along `angle`, in radians from +x.
+    LinearGradient {
+        from: [u8; 3],
+        to: [u8; 3],
+        angle: f32,
+    },
+    /// Blends from `inner` at the frame's center to `outer` at its corners.
+    RadialGradient {
+        inner: [u8; 3],
+        outer: [u8; 3],
+    },
+    /// An image stretched over the frame, e.g. a painting to grow the trails on.
+    Image {
+        path: PathBuf,
+    },
+}
+
+/// How the trail layers are combined with the background.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    /// Trails add light, as on the default black background.
+    #[default]
+    Add,
+    /// Like `Add`, but saturating softly instead of clipping.
+    Screen,
+    /// Trails tint the background, which only shows where they are bright.
+    Multiply,
+    /// Trails remove light like ink on paper, for light backgrounds.
+    Subtract,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct Background {
+    pub fill: BackgroundFill,
+    pub blend: BlendMode,
+}
+
+impl Background {
+    pub fn is_active(&self) -> bool {
+        self.fill != BackgroundFill::None
+    }
+
+    /// The fill's colors for a `width` by `height` frame in [0, 1], or `None` for black.
+    pub fn render(&self, width: usize, height: usize) -> io::Result<Option<Vec<[f32; 3]>>> {
+        let color = |c: [u8; 3]| c.map(|c| c as f32 / 255.0);
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| {
+            let t = t.clamp(0.0, 1.0);
+            [0, 1, 2].map(|c| a[c] + (b[c] - a[c]) * t)
+        };
+        // Pixel centers relative to the frame's center, in pixels.
+        let offsets = move |i: usize| {
+            let x = (i % width) as f32 + 0.5 - width as f32 * 0.5;
+            let y = (i / width) as f32 + 0.5 - height as f32 * 0.5;
+            (x, y)
+        };
+        let pixels = match &self.fill {
+            BackgroundFill::None => return Ok(None),
+            BackgroundFill::Solid { color: c } => vec![color(*c); width * height],
+            BackgroundFill::LinearGradient { from, to, angle } => {
+                let (from, to) = (color(*from), color(*to));
+                let (dx, dy) = (angle.cos(), angle.sin());
+                // Half the frame's extent along the gradient, so it spans corner to corner.
+                let reach = (width as f32 * dx.abs() + height as f32 * dy.abs()) * 0.5;
+                (0..width * height)
+                    .into_par_iter()
+                    .map(|i| {
+                        let (x, y) = offsets(i);
+                        lerp(from, to, 0.5 + (x * dx + y * dy) / reach.max(1.0) * 0.5)
+                    })
+                    .collect()
+            }
+            BackgroundFill::RadialGradient { inner, outer } => {
+                let (inner, outer) = (color(*inner), color(*outer));
+                let reach = (width as f32).hypot(height as f32) * 0.5;
+                (0..width * height)
+                    .into_par_iter()
+                    .map(|i| {
+                        let (x, y) = offsets(i);
+                        lerp(inner, outer, x.hypot(y) / reach.max(1.0))
+                    })
+                    .collect()
+            }
+            BackgroundFill::Image { path } => load_image(path, width, height)?,
+        };
+        Ok(Some(pixels))
+    }
+
+    /// Composites the trail colors in `pixels` onto `background`.
+    pub fn blend(&self, pixels: &mut [[f32; 3]], background: &[[f32; 3]]) {
+        let blend = self.blend;
+        pixels
+            .par_iter_mut()
+            .zip(background)
+            .for_each(|(pixel, back)| {
+                for (t, b) in pixel.iter_mut().zip(back) {
+                    *t = match blend {
+                        BlendMode::Add => b + *t,
+                        BlendMode::Screen => 1.0 - (1.0 - b) * (1.0 - t.min(1.0)),
+                        BlendMode::Multiply => b * *t,
+                        BlendMode::Subtract => b - *t,
+                    };
+                }
+            });
+    }
+}
+
+#[cfg(feature = "image-export")]
+pub(super) fn load_image(
+    path: &std::path::Path,
+    width: usize,
+    height: usize,
+) -> io::Result<Vec<[f32; 3]>> {
+    use image::imageops::{self, FilterType};
+
+    let image = image::open(path).map_err(io::Error::other)?.to_rgb8();
+    let image = imageops::resize(&image, width as u32, height as u32, FilterType::Triangle);
+    Ok(image
+        .pixels()
+        .map(|pixel| pixel.0.map(|c| c as f32 / 255.0))
+        .collect())
+}
+
+/// The luma of the image at `path` resized to `width` by `height`.
+pub(super) fn load_brightness(
+    path: &std::path::Path,
+    width: usize,
+    height: usize,
+) -> io::Result<Vec<f32>> {
+    Ok(load_image(path, width, height)?
+        .into_iter()
+        .map(|[r, g, b]| 0.2126 * r + 0.7152 * g + 0.0722 * b)
+        .collect())
+}
+
+#[cfg(not(feature = "image-export"))]
+pub(super) fn load_image(_: &std::path::Path, _: usize, _: usize) -> io::Result<Vec<[f32; 3]>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Images need the image-export feature",
+    ))
+}
@@ -0,0 +1,36 @@
+use super::error::ConfigError;
+use super::grid;
+use serde::{Deserialize, Serialize};
+
+/// Difference of two Gaussian blurs of a grid, which keeps only the structure between the
+/// two scales. Populations sense the others' trails through it, so they react to the veins
+/// and gaps of the neighbouring networks rather than to their raw density, while still
+/// following their own trails unfiltered. Needs two more buffers per grid.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Bandpass {
+    /// Deviation of the narrow blur in cells, which removes detail finer than it.
+    pub inner: f32,
+    /// Deviation of the wide blur, which is subtracted and removes coarser structure.
+    pub outer: f32,
+}
+
+impl Default for Bandpass {
+    fn default() -> Self {
+        Bandpass {
+            inner: 2.0,
+            outer: 8.0,
+        }
+    }
+}
+
+impl Bandpass {
+    /// Requires `0 < inner < outer` with the wide blur fitting inside the grid.
+    pub(super) fn check(&self, width: usize, height: usize) -> Result<(), ConfigError> {
+        if self.inner > 0.0 && self.inner < self.outer && grid::blur_fits(width, height, self.outer)
+        {
+            Ok(())
+        } else {
+            Err(ConfigError::Bandpass)
+        }
+    }
+}
@@ -1,5 +1,9 @@
+#[cfg(not(any(feature = "std", test)))]
+use super::math::Float;
+use super::par::*;
+use alloc::vec;
+use alloc::vec::Vec;
 use itertools::multizip;
-use rayon::prelude::*;
 
 pub struct Blur {
     row_buffer: Vec<f32>,
@@ -26,7 +30,7 @@ impl Blur {
         self.box_blur(src, buf, width, height, boxes[1], decay);
     }
 
-    fn boxes_for_gaussian<const N: usize>(sigma: f32) -> ([usize; N]) {
+    pub(super) fn boxes_for_gaussian<const N: usize>(sigma: f32) -> [usize; N] {
         let w_ideal = (12.0 * sigma * sigma / N as f32 + 1.0).sqrt();
         let mut w = w_ideal as usize;
         w -= 1 - (w & 1);
@@ -54,7 +58,7 @@ impl Blur {
         self.box_blur_v(buf, src, width, height, radius, decay);
     }
 
-    fn box_blur_h(&mut self, src: &[f32], dst: &mut [f32], width: usize, radius: usize) {
+    pub(super) fn box_blur_h(&mut self, src: &[f32], dst: &mut [f32], width: usize, radius: usize) {
         let weight = 1.0 / (2 * radius + 1) as f32;
 
         src.par_chunks_exact(width)
@@ -0,0 +1,502 @@
+use super::agents::AGENT_BYTES;
+use super::annealing::Annealing;
+use super::bandpass::Bandpass;
+use super::cities::CitySettings;
+use super::config::SimulationConfig;
+use super::crowding::Crowding;
+use super::error::ConfigError;
+use super::food::Food;
+use super::grid;
+use super::heat::Heat;
+use super::initial::InitialTrails;
+use super::operators;
+use super::palette::Palette;
+use super::physarum_model::{AttractionDistribution, PhysarumModel};
+use super::sensing::{self, Sensing};
+use super::spacing::Spacing;
+use super::steering;
+use super::step;
+use core::fmt;
+use core::mem::size_of;
+use rand::{rngs::SmallRng, thread_rng, RngCore, SeedableRng};
+
+/// Bytes a model would take, estimated from its parameters without allocating anything.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    /// The trail grids of all populations with their blur buffers, and their band-passed
+    /// copies when sensing through a band-pass.
+    pub grids: usize,
+    pub agents: usize,
+    /// A frame and the brightness fields of all populations rendered into it.
+    pub frame: usize,
+}
+
+impl MemoryEstimate {
+    pub fn total(&self) -> usize {
+        self.grids
+            .saturating_add(self.agents)
+            .saturating_add(self.frame)
+    }
+}
+
+impl fmt::Display for MemoryEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} for the grids, {} for the agents and {} for the frame, {} in total",
+            Bytes(self.grids),
+            Bytes(self.agents),
+            Bytes(self.frame),
+            Bytes(self.total())
+        )
+    }
+}
+
+/// A number of bytes in the largest binary unit that keeps it above 1.
+struct Bytes(usize);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let units = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+        let mut size = self.0 as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit + 1 < units.len() {
+            size /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 {
+            write!(f, "{} B", self.0)
+        } else {
+            write!(f, "{:.1} {}", size, units[unit])
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum PaletteChoice {
+    Name(String),
+    Custom(Palette),
+    Unknown,
+}
+
+/// Collects the parameters of a `PhysarumModel` and checks them all before anything is
+/// allocated, so a bad config file is reported instead of crashing the simulation.
+///
+/// Starts from the same defaults as `SimulationConfig`:
+///
+/// ```no_run
+/// use physarum_core::palette::Palette;
+/// use physarum_core::physarum_model::PhysarumModel;
+///
+/// let model = PhysarumModel::builder()
+///     .size(1024, 1024)
+///     .populations(3)
+///     .particles(1_000_000)
+///     .palette(Palette::by_name("dusk"))
+///     .seed(42)
+///     .build()?;
+/// # Ok::<(), physarum_core::error::ConfigError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct PhysarumModelBuilder {
+    width: usize,
+    height: usize,
+    n_particles: usize,
+    n_populations: usize,
+    diffusity: usize,
+    palette: PaletteChoice,
+    attraction_table: Option<Vec<Vec<f32>>>,
+    attraction: AttractionDistribution,
+    deposition_table: Option<Vec<Vec<f32>>>,
+    bandpass: Option<Bandpass>,
+    sensing: Vec<Sensing>,
+    steering: Vec<String>,
+    deposit_ops: Vec<String>,
+    diffuse_ops: Vec<String>,
+    time_scale: f32,
+    annealing: Option<Annealing>,
+    crowding: Option<Crowding>,
+    spacing: Option<Spacing>,
+    heat: Option<Heat>,
+    food: Option<Food>,
+    cities: Option<CitySettings>,
+    initial_trails: InitialTrails,
+    seed: Option<u64>,
+    strict_determinism: bool,
+    fast_math: bool,
+    agent_sort_interval: usize,
+}
+
+impl Default for PhysarumModelBuilder {
+    fn default() -> Self {
+        Self::from_config(&SimulationConfig::default())
+    }
+}
+
+impl PhysarumModelBuilder {
+    pub fn from_config(config: &SimulationConfig) -> Self {
+        PhysarumModelBuilder {
+            width: config.width,
+            height: config.height,
+            n_particles: config.n_particles,
+            n_populations: config.n_populations,
+            diffusity: config.diffusivity,
+            palette: PaletteChoice::Name(config.palette.clone()),
+            attraction_table: config.attraction_table.clone(),
+            attraction: config.attraction,
+            deposition_table: config.deposition_table.clone(),
+            bandpass: config.bandpass,
+            sensing: config.sensing.clone(),
+            steering: config.steering.clone(),
+            deposit_ops: config.deposit_ops.clone(),
+            diffuse_ops: config.diffuse_ops.clone(),
+            time_scale: config.time_scale,
+            annealing: config.annealing,
+            crowding: config.crowding,
+            spacing: config.spacing,
+            heat: config.heat,
+            food: config.food,
+            cities: config.cities.clone(),
+            initial_trails: config.initial_trails.clone(),
+            seed: config.seed,
+            strict_determinism: config.strict_determinism,
+            fast_math: config.fast_math,
+            agent_sort_interval: config.agent_sort_interval,
+        }
+    }
+
+    pub fn size(mut self, width: usize, height: usize) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn particles(mut self, n_particles: usize) -> Self {
+        self.n_particles = n_particles;
+        self
+    }
+
+    pub fn populations(mut self, n_populations: usize) -> Self {
+        self.n_populations = n_populations;
+        self
+    }
+
+    pub fn diffusity(mut self, diffusity: usize) -> Self {
+        self.diffusity = diffusity;
+        self
+    }
+
+    /// Takes a palette or the result of `Palette::by_name`, where `None` fails the build.
+    pub fn palette(mut self, palette: impl Into<Option<Palette>>) -> Self {
+        self.palette = match palette.into() {
+            Some(palette) => PaletteChoice::Custom(palette),
+            None => PaletteChoice::Unknown,
+        };
+        self
+    }
+
+    /// Picks a built-in palette by name, checked when building.
+    pub fn palette_name(mut self, name: &str) -> Self {
+        self.palette = PaletteChoice::Name(name.into());
+        self
+    }
+
+    /// See `PhysarumModel::set_attraction_table`, random if unset.
+    pub fn attraction_table(mut self, table: Vec<Vec<f32>>) -> Self {
+        self.attraction_table = Some(table);
+        self
+    }
+
+    /// Distributions the attraction table is drawn from when none is given.
+    pub fn attraction_distribution(mut self, distribution: AttractionDistribution) -> Self {
+        self.attraction = distribution;
+        self
+    }
+
+    /// See `PhysarumModel::set_deposition_table`.
+    pub fn deposition_table(mut self, table: Vec<Vec<f32>>) -> Self {
+        self.deposition_table = Some(table);
+        self
+    }
+
+    /// See `PhysarumModel::set_bandpass`.
+    pub fn bandpass(mut self, bandpass: Bandpass) -> Self {
+        self.bandpass = Some(bandpass);
+        self
+    }
+
+    /// See `PhysarumModel::set_sensing`.
+    pub fn sensing(mut self, sensing: Vec<Sensing>) -> Self {
+        self.sensing = sensing;
+        self
+    }
+
+    /// See `PhysarumModel::set_steering`.
+    pub fn steering(mut self, steering: Vec<String>) -> Self {
+        self.steering = steering;
+        self
+    }
+
+    /// See `PhysarumModel::set_deposit_ops`.
+    pub fn deposit_ops(mut self, ops: Vec<String>) -> Self {
+        self.deposit_ops = ops;
+        self
+    }
+
+    /// See `PhysarumModel::set_diffuse_ops`.
+    pub fn diffuse_ops(mut self, ops: Vec<String>) -> Self {
+        self.diffuse_ops = ops;
+        self
+    }
+
+    /// See `PhysarumModel::set_time_scale`.
+    pub fn time_scale(mut self, time_scale: f32) -> Self {
+        self.time_scale = time_scale;
+        self
+    }
+
+    /// See `PhysarumModel::set_annealing`.
+    pub fn annealing(mut self, annealing: Option<Annealing>) -> Self {
+        self.annealing = annealing;
+        self
+    }
+
+    /// See `PhysarumModel::set_crowding`.
+    pub fn crowding(mut self, crowding: Option<Crowding>) -> Self {
+        self.crowding = crowding;
+        self
+    }
+
+    /// See `PhysarumModel::set_spacing`.
+    pub fn spacing(mut self, spacing: Option<Spacing>) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// See `PhysarumModel::set_heat`.
+    pub fn heat(mut self, heat: Option<Heat>) -> Self {
+        self.heat = heat;
+        self
+    }
+
+    /// See `PhysarumModel::set_food`.
+    pub fn food(mut self, food: Option<Food>) -> Self {
+        self.food = food;
+        self
+    }
+
+    /// See `PhysarumModel::load_cities`.
+    pub fn cities(mut self, cities: Option<CitySettings>) -> Self {
+        self.cities = cities;
+        self
+    }
+
+    /// See `PhysarumModel::fill_trails`.
+    pub fn initial_trails(mut self, initial_trails: InitialTrails) -> Self {
+        self.initial_trails = initial_trails;
+        self
+    }
+
+    /// Seeds the generator `build` draws agents and grids from, random if unset.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// See `PhysarumModel::set_strict_determinism`.
+    pub fn strict_determinism(mut self, strict_determinism: bool) -> Self {
+        self.strict_determinism = strict_determinism;
+        self
+    }
+
+    /// See `PhysarumModel::set_fast_math`.
+    pub fn fast_math(mut self, fast_math: bool) -> Self {
+        self.fast_math = fast_math;
+        self
+    }
+
+    /// See `PhysarumModel::set_agent_sort_interval`.
+    pub fn agent_sort_interval(mut self, interval: usize) -> Self {
+        self.agent_sort_interval = interval;
+        self
+    }
+
+    fn resolve_palette(&self) -> Result<Palette, ConfigError> {
+        match &self.palette {
+            PaletteChoice::Name(name) => Palette::by_name(name).ok_or(ConfigError::UnknownPalette),
+            PaletteChoice::Custom(palette) => Ok(*palette),
+            PaletteChoice::Unknown => Err(ConfigError::UnknownPalette),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        grid::check_dimensions(self.width, self.height)?;
+        if self.n_populations == 0 {
+            return Err(ConfigError::NoPopulations);
+        }
+        if self.n_particles < self.n_populations {
+            return Err(ConfigError::TooFewParticles {
+                particles: self.n_particles,
+                populations: self.n_populations,
+            });
+        }
+        grid::check_capacity(
+            self.width,
+            self.height,
+            self.n_populations,
+            self.n_particles,
+        )?;
+        grid::check_diffusity(self.width, self.height, self.diffusity)?;
+        if !self.attraction.is_valid() {
+            return Err(ConfigError::AttractionDistribution);
+        }
+        if let Some(bandpass) = &self.bandpass {
+            bandpass.check(self.width, self.height)?;
+        }
+        sensing::check(&self.sensing, self.n_populations)?;
+        steering::resolve(&self.steering)?;
+        operators::resolve_deposit(&self.deposit_ops)?;
+        operators::resolve_diffuse(&self.diffuse_ops)?;
+        step::check_time_scale(self.time_scale)?;
+        if let Some(annealing) = &self.annealing {
+            annealing.check()?;
+        }
+        if let Some(crowding) = &self.crowding {
+            crowding.check(self.width, self.height)?;
+        }
+        if let Some(spacing) = &self.spacing {
+            spacing.check()?;
+        }
+        if let Some(heat) = &self.heat {
+            heat.check(self.width, self.height)?;
+        }
+        if let Some(food) = &self.food {
+            food.check()?;
+        }
+        if let Some(cities) = &self.cities {
+            cities.check()?;
+        }
+        self.initial_trails.check()?;
+        if let Some(table) = &self.deposition_table {
+            step::check_deposition_table(table, self.n_populations)?;
+        }
+        if let Some(table) = &self.attraction_table {
+            if table.len() != self.n_populations
+                || table.iter().any(|row| row.len() != self.n_populations)
+            {
+                return Err(ConfigError::AttractionTableSize {
+                    populations: self.n_populations,
+                });
+            }
+        }
+        self.resolve_palette().map(|_| ())
+    }
+
+    /// The memory the model would take, for refusing sizes that don't fit before trying.
+    /// Saturates instead of overflowing, so that even absurd sizes get an estimate.
+    pub fn memory_estimate(&self) -> MemoryEstimate {
+        let cells = self.width.saturating_mul(self.height);
+        // The trails and the buffer they are blurred through, plus the band-passed trails.
+        let grids_per_population = if self.bandpass.is_some() { 3 } else { 2 };
+        let grid = cells
+            .saturating_mul(grids_per_population * size_of::<f32>())
+            .saturating_add(self.width.saturating_mul(size_of::<f32>()));
+        MemoryEstimate {
+            grids: grid.saturating_mul(self.n_populations),
+            agents: self.n_particles.saturating_mul(AGENT_BYTES),
+            frame: cells.saturating_mul(3 + self.n_populations.saturating_mul(size_of::<f32>())),
+        }
+    }
+
+    pub fn build(&self) -> Result<PhysarumModel, ConfigError> {
+        let seed = self.seed.unwrap_or_else(|| thread_rng().next_u64());
+        self.build_with_rng(&mut SmallRng::seed_from_u64(seed))
+    }
+
+    /// Builds the model drawing from `rng`, ignoring `seed`, for callers that keep using
+    /// the same generator afterwards.
+    pub fn build_with_rng(&self, rng: &mut SmallRng) -> Result<PhysarumModel, ConfigError> {
+        self.validate()?;
+        // Drawn even when a fixed table replaces it, so that the rest of the seeded
+        // generator's sequence stays the same.
+        let random_table =
+            PhysarumModel::random_attraction_table(self.n_populations, &self.attraction, rng);
+        let mut model = PhysarumModel::new(
+            self.width,
+            self.height,
+            self.n_particles,
+            self.diffusity,
+            self.resolve_palette()?,
+            self.attraction_table.clone().unwrap_or(random_table),
+            rng,
+        )?;
+        model.set_bandpass(self.bandpass)?;
+        model.set_sensing(self.sensing.clone())?;
+        model.set_steering(self.steering.clone())?;
+        model.set_deposit_ops(self.deposit_ops.clone())?;
+        model.set_diffuse_ops(self.diffuse_ops.clone())?;
+        model.set_deposition_table(self.deposition_table.clone())?;
+        model.set_time_scale(self.time_scale)?;
+        model.set_annealing(self.annealing)?;
+        model.set_crowding(self.crowding)?;
+        model.set_spacing(self.spacing)?;
+        model.set_heat(self.heat)?;
+        model.set_food(self.food)?;
+        model.load_cities(self.cities.clone())?;
+        model.set_strict_determinism(self.strict_determinism);
+        model.set_fast_math(self.fast_math);
+        model.set_agent_sort_interval(self.agent_sort_interval);
+        model.fill_trails(&self.initial_trails)?;
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_estimates_grow_with_the_grids() {
+        let small = PhysarumModelBuilder::default()
+            .size(256, 256)
+            .populations(2)
+            .memory_estimate();
+        let large = PhysarumModelBuilder::default()
+            .size(512, 512)
+            .populations(4)
+            .memory_estimate();
+        assert_eq!(small.grids, 2 * (256 * 256 * 8 + 256 * 4));
+        assert!(large.grids > 7 * small.grids);
+        assert_eq!(large.agents, small.agents);
+        let absurd = PhysarumModelBuilder::default()
+            .size(1 << 40, 1 << 40)
+            .memory_estimate();
+        assert_eq!(absurd.total(), usize::MAX);
+    }
+
+    #[test]
+    fn unreadable_files_fail_the_build() {
+        let builder = || PhysarumModelBuilder::default().size(32, 32).particles(20);
+        let cities = CitySettings {
+            path: "missing/cities.csv".into(),
+            ..CitySettings::default()
+        };
+        assert!(matches!(
+            builder().cities(Some(cities)).build(),
+            Err(ConfigError::Unreadable {
+                setting: "cities",
+                ..
+            })
+        ));
+        let image = InitialTrails::Image {
+            path: "missing/trails.png".into(),
+        };
+        assert!(matches!(
+            builder().initial_trails(image).build(),
+            Err(ConfigError::Unreadable {
+                setting: "initial trails",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,260 @@
+//! Cities as permanent food sources, for the experiments in which Physarum grows a rail
+//! network between oat flakes laid out like the cities around Tokyo. With the land of the
+//! map as a confining mask and the network exported, runs compare with real networks.
+
+use super::error::ConfigError;
+use super::food;
+use super::grid::Grid;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Where the cities come from and how strongly they draw the agents.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct CitySettings {
+    /// CSV of `name,longitude,latitude[,weight]` rows, or GeoJSON with a Point feature per
+    /// city, named by a `name` property and weighted by a `weight` one.
+    pub path: PathBuf,
+    /// West, south, east and north edge of the grid in the file's coordinates. The
+    /// cities' bounds with a tenth of their size around them if unset, so masks of the land
+    /// should set them to the bounds of their map.
+    pub bounds: Option<[f32; 4]>,
+    /// Radius in cells of every city.
+    pub radius: f32,
+    /// Trail a city of weight 1 adds per step to every cell it covers.
+    pub scent: f32,
+}
+
+impl Default for CitySettings {
+    fn default() -> Self {
+        CitySettings {
+            path: PathBuf::new(),
+            bounds: None,
+            radius: 3.0,
+            scent: 5.0,
+        }
+    }
+}
+
+/// A city at (x, y) in cells, scenting the trails in proportion to its `weight`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct City {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    pub weight: f32,
+}
+
+impl CitySettings {
+    /// Requires a positive radius, a non-negative scent and bounds that aren't empty.
+    pub fn check(&self) -> Result<(), ConfigError> {
+        let bounds = self.bounds.is_none_or(|[west, south, east, north]| {
+            west < east && south < north && [west, south, east, north].iter().all(|b| b.is_finite())
+        });
+        if self.radius > 0.0 && self.radius.is_finite() && self.scent >= 0.0 && bounds {
+            Ok(())
+        } else {
+            Err(ConfigError::Cities)
+        }
+    }
+
+    /// The cities of `path`, projected from the bounds onto a `width` by `height` grid
+    /// with the north at the top.
+    pub fn load(&self, width: usize, height: usize) -> io::Result<Vec<City>> {
+        let text = fs::read_to_string(&self.path)?;
+        let places = match self.path.extension().and_then(|ext| ext.to_str()) {
+            Some("json" | "geojson") => parse_geojson(&text)?,
+            _ => parse_csv(&text)?,
+        };
+        if places.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no cities in it",
+            ));
+        }
+        let [west, south, east, north] = self.bounds.unwrap_or_else(|| {
+            let fold = |pick: fn(&City) -> f32| {
+                let values = places.iter().map(pick);
+                let (lo, hi) =
+                    values.fold((f32::MAX, f32::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)));
+                let margin = ((hi - lo) * 0.1).max(1e-3);
+                (lo - margin, hi + margin)
+            };
+            let ((west, east), (south, north)) = (fold(|c| c.x), fold(|c| c.y));
+            [west, south, east, north]
+        });
+        Ok(places
+            .into_iter()
+            .map(|city| City {
+                x: (city.x - west) / (east - west) * width as f32,
+                y: (north - city.y) / (north - south) * height as f32,
+                ..city
+            })
+            .filter(|city| {
+                (0.0..width as f32).contains(&city.x) && (0.0..height as f32).contains(&city.y)
+            })
+            .collect())
+    }
+}
+
+/// Cities with their longitude as `x` and latitude as `y`, skipping a header, blank lines
+/// and `#` comments.
+fn parse_csv(text: &str) -> io::Result<Vec<City>> {
+    let mut cities = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let number = |k: usize| fields.get(k).and_then(|field| field.parse::<f32>().ok());
+        match (number(1), number(2)) {
+            (Some(x), Some(y)) => cities.push(City {
+                name: fields[0].to_string(),
+                x,
+                y,
+                weight: number(3).unwrap_or(1.0),
+            }),
+            _ if cities.is_empty() && i == 0 => continue,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: expected name,longitude,latitude[,weight]", i + 1),
+                ))
+            }
+        }
+    }
+    Ok(cities)
+}
+
+/// The Point features of a GeoJSON feature collection, or of a single feature.
+fn parse_geojson(text: &str) -> io::Result<Vec<City>> {
+    let json: Value = serde_json::from_str(text)?;
+    let features = match json["features"].as_array() {
+        Some(features) => features.iter().collect(),
+        None => vec![&json],
+    };
+    Ok(features
+        .into_iter()
+        .filter(|feature| feature["geometry"]["type"] == "Point")
+        .filter_map(|feature| {
+            let coordinates = &feature["geometry"]["coordinates"];
+            let properties = &feature["properties"];
+            Some(City {
+                name: properties["name"].as_str().unwrap_or_default().to_string(),
+                x: coordinates[0].as_f64()? as f32,
+                y: coordinates[1].as_f64()? as f32,
+                weight: properties["weight"].as_f64().unwrap_or(1.0) as f32,
+            })
+        })
+        .collect())
+}
+
+/// The trail the cities add to every cell they cover each step, worked out once since
+/// cities stay put.
+pub(crate) struct CityScent {
+    pub cities: Vec<City>,
+    pub settings: CitySettings,
+    stamp: Vec<(usize, f32)>,
+}
+
+impl CityScent {
+    pub fn new(cities: Vec<City>, settings: CitySettings, width: usize, height: usize) -> Self {
+        let stamp = cities
+            .iter()
+            .flat_map(|city| {
+                let amount = settings.scent * city.weight;
+                food::disc(width, height, city.x, city.y, settings.radius)
+                    .map(move |cell| (cell, amount))
+            })
+            .collect();
+        CityScent {
+            cities,
+            settings,
+            stamp,
+        }
+    }
+
+    /// Scents every grid for a step covering `time_scale` steps.
+    pub fn lay(&self, grids: &mut [Grid], time_scale: f32) {
+        for grid in grids {
+            let trails = grid.data_mut();
+            for &(cell, amount) in &self.stamp {
+                trails[cell] += amount * time_scale;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cities_are_projected_from_csv_and_geojson() {
+        let dir = std::env::temp_dir().join("physarum_cities_test");
+        fs::create_dir_all(&dir).unwrap();
+        let csv = dir.join("cities.csv");
+        fs::write(
+            &csv,
+            "name,lon,lat,weight\n# the corners\nwest,10,45\neast,20,50,2\n\nmiddle,15,45\n",
+        )
+        .unwrap();
+        let settings = CitySettings {
+            path: csv,
+            bounds: Some([10.0, 40.0, 20.0, 50.0]),
+            ..CitySettings::default()
+        };
+        settings.check().unwrap();
+        // The east corner lies on the edge just past the grid.
+        let cities = settings.load(100, 50).unwrap();
+        assert_eq!(cities.len(), 2);
+        assert_eq!(
+            (cities[0].x, cities[0].y, cities[0].weight),
+            (0.0, 25.0, 1.0)
+        );
+        assert_eq!(cities[0].name, "west");
+        assert_eq!((cities[1].x, cities[1].y), (50.0, 25.0));
+
+        let geojson = dir.join("cities.geojson");
+        fs::write(
+            &geojson,
+            r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature", "properties": {"name": "a", "weight": 3},
+                 "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}},
+                {"type": "Feature", "properties": {},
+                 "geometry": {"type": "LineString", "coordinates": [[0, 0], [1, 1]]}},
+                {"type": "Feature", "properties": {"name": "b"},
+                 "geometry": {"type": "Point", "coordinates": [3.0, 4.0]}}]}"#,
+        )
+        .unwrap();
+        let settings = CitySettings {
+            path: geojson,
+            ..CitySettings::default()
+        };
+        let cities = settings.load(64, 64).unwrap();
+        assert_eq!(cities.len(), 2);
+        assert_eq!((cities[0].name.as_str(), cities[0].weight), ("a", 3.0));
+        // By their own bounds, with a margin, the cities sit at opposite corners.
+        assert!(cities[0].x < 10.0 && cities[0].y > 54.0);
+        assert!(cities[1].x > 54.0 && cities[1].y < 10.0);
+
+        let scent = CityScent::new(cities, settings, 64, 64);
+        let heavy = scent
+            .stamp
+            .iter()
+            .filter(|&&(_, amount)| amount == 15.0)
+            .count();
+        assert!((25..=33).contains(&heavy));
+        assert_eq!(scent.stamp.len() - heavy, heavy);
+        assert!(CitySettings {
+            bounds: Some([1.0, 0.0, 0.0, 1.0]),
+            ..CitySettings::default()
+        }
+        .check()
+        .is_err());
+    }
+}
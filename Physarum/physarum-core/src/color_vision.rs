@@ -0,0 +1,166 @@
+use super::math::Math;
+use super::par::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Color vision the render is shown with, to check that a piece stays legible to viewers
+/// with a color-vision deficiency.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorVision {
+    #[default]
+    Normal,
+    /// No red cones, the rarer form of red-green blindness.
+    Protanopia,
+    /// No green cones, the most common form of red-green blindness.
+    Deuteranopia,
+    /// No blue cones, confusing blues with greens and yellows with violets.
+    Tritanopia,
+    /// No color at all, only luminance.
+    Achromatopsia,
+}
+
+pub const COLOR_VISIONS: [ColorVision; 5] = [
+    ColorVision::Normal,
+    ColorVision::Protanopia,
+    ColorVision::Deuteranopia,
+    ColorVision::Tritanopia,
+    ColorVision::Achromatopsia,
+];
+
+impl ColorVision {
+    /// Linear RGB transform of the full deficiency, after Machado, Oliveira and Fernandes
+    /// (2009), or `None` for normal vision.
+    fn matrix(self) -> Option<[[f32; 3]; 3]> {
+        match self {
+            ColorVision::Normal => None,
+            ColorVision::Protanopia => Some([
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ]),
+            ColorVision::Deuteranopia => Some([
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ]),
+            ColorVision::Tritanopia => Some([
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ]),
+            ColorVision::Achromatopsia => Some([[0.2126, 0.7152, 0.0722]; 3]),
+        }
+    }
+
+    /// Replaces the gamma encoded colors of `pixels` with those a viewer with this color
+    /// vision would perceive.
+    pub(super) fn simulate(self, pixels: &mut [[f32; 3]], math: Math) {
+        let Some(matrix) = self.matrix() else {
+            return;
+        };
+        pixels.par_iter_mut().for_each(|pixel| {
+            let linear = pixel.map(|c| (math.powf)(c.clamp(0.0, 1.0), 2.2));
+            *pixel = matrix.map(|row| {
+                let c = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+                (math.powf)(c.clamp(0.0, 1.0), 1.0 / 2.2)
+            });
+        });
+    }
+}
+
+impl FromStr for ColorVision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        COLOR_VISIONS
+            .into_iter()
+            .find(|vision| vision.to_string() == s)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown color vision {:?}, expected one of normal, protanopia, \
+                     deuteranopia, tritanopia or achromatopsia",
+                    s
+                )
+            })
+    }
+}
+
+impl fmt::Display for ColorVision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorVision::Normal => "normal",
+            ColorVision::Protanopia => "protanopia",
+            ColorVision::Deuteranopia => "deuteranopia",
+            ColorVision::Tritanopia => "tritanopia",
+            ColorVision::Achromatopsia => "achromatopsia",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::{Palette, COLORBLIND_SAFE};
+
+    /// CIELAB coordinates of a gamma encoded color.
+    fn lab([r, g, b]: [f32; 3]) -> [f32; 3] {
+        let [r, g, b] = [r, g, b].map(|c| c.powf(2.2));
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.9505;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.089;
+        let f = |t: f32| {
+            if t > 0.008856 {
+                t.cbrt()
+            } else {
+                7.787 * t + 16.0 / 116.0
+            }
+        };
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+        [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+    }
+
+    /// Smallest CIELAB distance between two of the palette's colors as seen with `vision`.
+    fn min_distance(palette: &Palette, vision: ColorVision) -> f32 {
+        let mut colors = palette
+            .colors
+            .map(|color| color.0.map(|c| c as f32 / 255.0));
+        vision.simulate(&mut colors, Math::new(false));
+        let colors = colors.map(lab);
+        let mut min = f32::MAX;
+        for (i, a) in colors.iter().enumerate() {
+            for b in &colors[i + 1..] {
+                let d = a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum::<f32>();
+                min = min.min(d.sqrt());
+            }
+        }
+        min
+    }
+
+    #[test]
+    fn colorblind_safe_palettes_stay_distinct() {
+        for name in COLORBLIND_SAFE {
+            let palette = Palette::by_name(name).unwrap();
+            for vision in [ColorVision::Protanopia, ColorVision::Deuteranopia] {
+                let distance = min_distance(&palette, vision);
+                assert!(distance > 12.0, "{} under {}: {}", name, vision, distance);
+            }
+        }
+    }
+
+    #[test]
+    fn red_green_palettes_merge_without_green_cones() {
+        let sunrise = Palette::by_name("sunrise").unwrap();
+        assert!(min_distance(&sunrise, ColorVision::Normal) > 12.0);
+        assert!(min_distance(&sunrise, ColorVision::Deuteranopia) < 12.0);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for vision in COLOR_VISIONS {
+            assert_eq!(vision.to_string().parse(), Ok(vision));
+        }
+    }
+}
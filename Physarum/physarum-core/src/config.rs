@@ -0,0 +1,162 @@
+use super::annealing::Annealing;
+use super::bandpass::Bandpass;
+use super::cities::CitySettings;
+use super::crowding::Crowding;
+use super::drift::DriftParameter;
+use super::food::Food;
+use super::heat::Heat;
+use super::initial::InitialTrails;
+use super::palette::PALETTE_NAMES;
+use super::physarum_model::AttractionDistribution;
+use super::population_config::PopulationConfig;
+use super::render::RenderSettings;
+use super::sensing::Sensing;
+use super::spacing::Spacing;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read};
+use std::path::Path;
+
+/// Everything needed to recreate a simulation, as stored in config files and archives.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct SimulationConfig {
+    pub seed: Option<u64>,
+    pub width: usize,
+    pub height: usize,
+    pub n_particles: usize,
+    pub n_populations: usize,
+    pub diffusivity: usize,
+    /// Name of a built-in palette, see `Palette::by_name`. Older configs stored an index
+    /// into `PALETTE_NAMES` as `palette_idx`, which is still read.
+    #[serde(alias = "palette_idx", deserialize_with = "palette_name")]
+    pub palette: String,
+    pub populations: Vec<PopulationConfig>,
+    /// How much each population follows the others' trails, see
+    /// `PhysarumModel::set_attraction_table`. Drawn from `attraction` when unset.
+    pub attraction_table: Option<Vec<Vec<f32>>>,
+    pub attraction: AttractionDistribution,
+    /// How much each population deposits into the others' grids, see
+    /// `PhysarumModel::set_deposition_table`. Only into their own when unset.
+    pub deposition_table: Option<Vec<Vec<f32>>>,
+    /// Sense the other populations' trails through a band-pass, see `Bandpass`.
+    pub bandpass: Option<Bandpass>,
+    /// Which trails each population steers by, the combined field for missing entries.
+    pub sensing: Vec<Sensing>,
+    /// Names of the rule each population turns by, see `PhysarumModel::set_steering`.
+    pub steering: Vec<String>,
+    /// Names of the operator each population deposits through, see
+    /// `PhysarumModel::set_deposit_ops`. Tiled runs only support the default.
+    pub deposit_ops: Vec<String>,
+    /// Names of the operator each population diffuses through, see
+    /// `PhysarumModel::set_diffuse_ops`. Tiled runs only support the default.
+    pub diffuse_ops: Vec<String>,
+    /// Steps of the dynamics covered by each step, see `PhysarumModel::set_time_scale`.
+    pub time_scale: f32,
+    /// Random turns that cool down over the run, see `PhysarumModel::set_annealing`.
+    pub annealing: Option<Annealing>,
+    /// Agents sensing how crowded it is around them, see `PhysarumModel::set_crowding`.
+    pub crowding: Option<Crowding>,
+    /// Agents of every population keeping apart, see `PhysarumModel::set_spacing`.
+    pub spacing: Option<Spacing>,
+    /// Heat speeding up or slowing down the decay, see `PhysarumModel::set_heat`.
+    pub heat: Option<Heat>,
+    /// Food sources coming and going over the run, see `PhysarumModel::set_food`.
+    pub food: Option<Food>,
+    /// Cities drawing the agents like food that never runs out, see
+    /// `PhysarumModel::load_cities`.
+    pub cities: Option<CitySettings>,
+    /// Bounds and speeds of the parameters the window's drift walks, see
+    /// `DriftController`. `DriftController::default_parameters` when unset.
+    pub drift: Option<Vec<DriftParameter>>,
+    /// What the trails start out as, see `PhysarumModel::fill_trails`.
+    pub initial_trails: InitialTrails,
+    pub render: RenderSettings,
+    /// Use portable math so a seed gives the same frames on every platform, see `step::step`.
+    pub strict_determinism: bool,
+    /// Move the agents with approximate trigonometry for speed, see
+    /// `PhysarumModel::set_fast_math`.
+    pub fast_math: bool,
+    /// Steps between sorting the agents by cell for speed, never for 0, see
+    /// `PhysarumModel::set_agent_sort_interval`.
+    pub agent_sort_interval: usize,
+    /// Steps between the frames rendered for the window, its recording and headless frame
+    /// sequences, which skips the costly coloring while time-lapses simulate. 0 counts as 1.
+    pub render_every_n_steps: usize,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            seed: None,
+            width: 1024,
+            height: 1024,
+            n_particles: 200000,
+            n_populations: 2,
+            diffusivity: 1,
+            palette: PALETTE_NAMES[0].to_string(),
+            populations: vec![],
+            attraction_table: None,
+            attraction: AttractionDistribution::default(),
+            deposition_table: None,
+            bandpass: None,
+            sensing: vec![],
+            steering: vec![],
+            deposit_ops: vec![],
+            diffuse_ops: vec![],
+            time_scale: 1.0,
+            annealing: None,
+            crowding: None,
+            spacing: None,
+            heat: None,
+            food: None,
+            cities: None,
+            drift: None,
+            initial_trails: InitialTrails::default(),
+            render: RenderSettings::default(),
+            strict_determinism: false,
+            fast_math: false,
+            agent_sort_interval: 0,
+            render_every_n_steps: 1,
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn from_reader(reader: impl Read) -> io::Result<Self> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+}
+
+fn palette_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NameOrIndex {
+        Name(String),
+        Index(usize),
+    }
+
+    match NameOrIndex::deserialize(deserializer)? {
+        NameOrIndex::Name(name) => Ok(name),
+        NameOrIndex::Index(index) => PALETTE_NAMES
+            .get(index)
+            .map(|name| name.to_string())
+            .ok_or_else(|| {
+                D::Error::custom(format!(
+                    "palette index {} is out of range, there are {} palettes",
+                    index,
+                    PALETTE_NAMES.len()
+                ))
+            }),
+    }
+}
@@ -0,0 +1,202 @@
+use super::blur::Blur;
+use super::grid;
+use super::palette::Rgb;
+use super::par::*;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Draws every population as the isolines of its brightness, like the contours of a
+/// topographic map, instead of as filled densities.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct ContourSettings {
+    pub enabled: bool,
+    /// Brightnesses in [0, 1] to draw an isoline at, after normalization.
+    pub levels: Vec<f32>,
+    /// Width of the lines in pixels.
+    pub line_width: f32,
+    /// Deviation in cells of a blur applied before tracing, which rounds off isolines
+    /// that would otherwise follow every grain of the trails.
+    pub smoothing: f32,
+}
+
+impl Default for ContourSettings {
+    fn default() -> Self {
+        ContourSettings {
+            enabled: false,
+            levels: evenly_spaced(4),
+            line_width: 1.0,
+            smoothing: 2.0,
+        }
+    }
+}
+
+/// `n` levels splitting [0, 1] into equal steps, without the ends.
+pub fn evenly_spaced(n: usize) -> Vec<f32> {
+    (1..=n).map(|k| k as f32 / (n + 1) as f32).collect()
+}
+
+/// A copy of a row-major field blurred by `sigma` cells, or unchanged when it doesn't fit.
+pub(super) fn smooth(field: &[f32], width: usize, height: usize, sigma: f32) -> Vec<f32> {
+    let mut smoothed = field.to_vec();
+    if sigma > 0.0 && grid::blur_fits(width, height, sigma) {
+        let mut buf = vec![0.0; field.len()];
+        Blur::new(width).run(&mut smoothed, &mut buf, width, height, sigma, 1.0);
+    }
+    smoothed
+}
+
+/// A straight piece of an isoline between two points in pixel coordinates.
+pub type Segment = [(f32, f32); 2];
+
+/// Coverage in [0, 1] of the isolines through every cell of a row-major field. The
+/// distance to the nearest isoline is estimated from the field's gradient, which gives
+/// smooth lines of an even width without tracing them.
+pub(super) fn rasterize(
+    field: &[f32],
+    width: usize,
+    height: usize,
+    settings: &ContourSettings,
+) -> Vec<f32> {
+    let half_width = settings.line_width.max(0.0) * 0.5;
+    (0..width * height)
+        .into_par_iter()
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            // Central differences, wrapping around the edges like the grids do.
+            let at = |x: usize, y: usize| field[(y % height) * width + x % width];
+            let dx = (at(x + 1, y) - at(x + width - 1, y)) * 0.5;
+            let dy = (at(x, y + 1) - at(x, y + height - 1)) * 0.5;
+            let gradient = (dx * dx + dy * dy).sqrt();
+            let value = field[i];
+            settings
+                .levels
+                .iter()
+                .map(|&level| {
+                    let distance = (value - level).abs() / gradient.max(f32::EPSILON);
+                    (half_width + 0.5 - distance).clamp(0.0, 1.0)
+                })
+                .fold(0.0, f32::max)
+        })
+        .collect()
+}
+
+/// Traces the isoline of a row-major field at `level` with marching squares, through the
+/// squares between the centers of neighbouring cells. Ties between the diagonals of a
+/// square are broken by its mean value.
+pub fn isolines(field: &[f32], width: usize, height: usize, level: f32) -> Vec<Segment> {
+    let mut segments = vec![];
+    for y in 0..height.saturating_sub(1) {
+        for x in 0..width.saturating_sub(1) {
+            let corners = [
+                field[y * width + x],
+                field[y * width + x + 1],
+                field[(y + 1) * width + x + 1],
+                field[(y + 1) * width + x],
+            ];
+            let case = corners
+                .iter()
+                .enumerate()
+                .fold(0, |case, (k, &v)| case | (((v > level) as usize) << k));
+            // Where the isoline crosses edge k, from corner k to the next one clockwise.
+            let cross = |k: usize| {
+                let (a, b) = (corners[k], corners[(k + 1) % 4]);
+                let t = ((level - a) / (b - a)).clamp(0.0, 1.0);
+                let offsets = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+                let (from, to) = (offsets[k], offsets[(k + 1) % 4]);
+                (
+                    x as f32 + 0.5 + from.0 + (to.0 - from.0) * t,
+                    y as f32 + 0.5 + from.1 + (to.1 - from.1) * t,
+                )
+            };
+            let mean_above = corners.iter().sum::<f32>() * 0.25 > level;
+            let edges: &[(usize, usize)] = match case {
+                0 | 15 => &[],
+                1 | 14 => &[(3, 0)],
+                2 | 13 => &[(0, 1)],
+                3 | 12 => &[(3, 1)],
+                4 | 11 => &[(1, 2)],
+                6 | 9 => &[(0, 2)],
+                7 | 8 => &[(2, 3)],
+                5 if mean_above => &[(3, 2), (0, 1)],
+                5 => &[(3, 0), (1, 2)],
+                10 if mean_above => &[(3, 0), (1, 2)],
+                _ => &[(3, 2), (0, 1)],
+            };
+            segments.extend(edges.iter().map(|&(a, b)| [cross(a), cross(b)]));
+        }
+    }
+    segments
+}
+
+/// An SVG of the isolines of each layer, stroked in the layer's color over a black
+/// background.
+pub fn svg(
+    layers: &[(Rgb<u8>, Vec<Segment>)],
+    width: usize,
+    height: usize,
+    line_width: f32,
+) -> String {
+    let mut svg = svg_open(width, height);
+    for (color, segments) in layers {
+        let [r, g, b] = color.0;
+        let _ = write!(
+            svg,
+            "<path fill=\"none\" stroke=\"#{:02x}{:02x}{:02x}\" stroke-width=\"{}\" \
+             stroke-linecap=\"round\" d=\"",
+            r, g, b, line_width
+        );
+        for [(x0, y0), (x1, y1)] in segments {
+            let _ = write!(svg, "M{:.2} {:.2}L{:.2} {:.2}", x0, y0, x1, y1);
+        }
+        svg.push_str("\"/>\n");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// The start of an SVG document of the grid's size, up to its black background.
+pub(super) fn svg_open(width: usize, height: usize) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\">\n<rect width=\"{w}\" height=\"{h}\" fill=\"black\"/>\n",
+        w = width,
+        h = height
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rises by 0.1 per column.
+    fn ramp(width: usize, height: usize) -> Vec<f32> {
+        (0..width * height)
+            .map(|i| (i % width) as f32 * 0.1)
+            .collect()
+    }
+
+    #[test]
+    fn isolines_cross_a_ramp_where_it_reaches_the_level() {
+        let segments = isolines(&ramp(8, 4), 8, 4, 0.25);
+        assert_eq!(segments.len(), 3);
+        for [(x0, y0), (x1, y1)] in segments {
+            assert!((x0 - 3.0).abs() < 1e-5 && (x1 - 3.0).abs() < 1e-5);
+            assert_eq!((y1 - y0).abs(), 1.0);
+        }
+    }
+
+    #[test]
+    fn lines_cover_cells_near_the_level() {
+        let settings = ContourSettings {
+            enabled: true,
+            levels: vec![0.3],
+            line_width: 1.0,
+            smoothing: 0.0,
+        };
+        let coverage = rasterize(&ramp(8, 4), 8, 4, &settings);
+        assert_eq!(coverage[8 + 3], 1.0);
+        assert_eq!(coverage[8 + 1], 0.0);
+        assert_eq!(coverage[8 + 5], 0.0);
+    }
+}
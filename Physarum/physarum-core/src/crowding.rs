@@ -0,0 +1,121 @@
+use super::agents::Agents;
+use super::error::ConfigError;
+use super::grid;
+use alloc::vec;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+/// A second sense beside the trails: agents count the agents of every population in a
+/// uniform grid of square bins and sense how crowded it is at their sensors, which lowers
+/// what they sense there. They spread out of crowds whatever and however much they
+/// deposit, or with a negative strength gather in them.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct Crowding {
+    /// Side of the bins in cells, a power of two no larger than the grid.
+    pub bin_size: usize,
+    /// How much a crowd as dense as the average over the grid lowers a sensor's sample,
+    /// in the units of the trails.
+    pub strength: f32,
+}
+
+impl Default for Crowding {
+    fn default() -> Self {
+        Crowding {
+            bin_size: 8,
+            strength: 1.0,
+        }
+    }
+}
+
+impl Crowding {
+    pub(super) fn check(&self, width: usize, height: usize) -> Result<(), ConfigError> {
+        let fits = self.bin_size.is_power_of_two() && self.bin_size <= width.min(height);
+        if fits && self.strength.is_finite() {
+            Ok(())
+        } else {
+            Err(ConfigError::Crowding)
+        }
+    }
+}
+
+/// The agents per bin of a `Crowding`, relative to their average over the grid.
+pub(super) struct Density {
+    bin_size: usize,
+    columns: usize,
+    rows: usize,
+    bins: Vec<f32>,
+}
+
+impl Density {
+    pub(super) fn count(agents: &Agents, width: usize, height: usize, bin_size: usize) -> Self {
+        let (columns, rows) = (width / bin_size, height / bin_size);
+        let mut bins = vec![0.0; columns * rows];
+        for agent in agents.iter() {
+            let cell = grid::cell(width, height, agent.x, agent.y);
+            let (x, y) = (cell % width / bin_size, cell / width / bin_size);
+            bins[y * columns + x] += 1.0;
+        }
+        let scale = bins.len() as f32 / agents.len().max(1) as f32;
+        bins.iter_mut().for_each(|bin| *bin *= scale);
+        Density {
+            bin_size,
+            columns,
+            rows,
+            bins,
+        }
+    }
+
+    /// The density at (x, y), interpolated between the centers of the bins around it so it
+    /// changes smoothly from bin to bin, wrapping around the edges.
+    pub(super) fn at(&self, x: f32, y: f32) -> f32 {
+        let (u, v) = (
+            x / self.bin_size as f32 - 0.5,
+            y / self.bin_size as f32 - 0.5,
+        );
+        let (i, j) = (grid::floor(u), grid::floor(v));
+        let (fu, fv) = (u - i as f32, v - j as f32);
+        let bin = |di: i64, dj: i64| {
+            let x = (i + di).rem_euclid(self.columns as i64) as usize;
+            let y = (j + dj).rem_euclid(self.rows as i64) as usize;
+            self.bins[y * self.columns + x]
+        };
+        let top = bin(0, 0) * (1.0 - fu) + bin(1, 0) * fu;
+        let bottom = bin(0, 1) * (1.0 - fu) + bin(1, 1) * fu;
+        top * (1.0 - fv) + bottom * fv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    #[test]
+    fn density_relative_to_the_average() {
+        let agent = |x, y| Particle {
+            x,
+            y,
+            angle: 0.0,
+            id: 0,
+        };
+        // Three agents in the first of four bins, one in the last.
+        let agents: Agents = [agent(1.0, 1.0), agent(2.0, 3.0), agent(3.0, 0.5)]
+            .into_iter()
+            .chain([agent(6.0, 7.5)])
+            .collect();
+        let density = Density::count(&agents, 8, 8, 4);
+        assert_eq!(density.bins, vec![3.0, 0.0, 0.0, 1.0]);
+        assert_eq!(density.at(2.0, 2.0), 3.0);
+        assert_eq!(density.at(4.0, 2.0), 1.5);
+        // Between the last bin and the first one across the corner.
+        assert_eq!(density.at(0.0, 0.0), 1.0);
+
+        assert!(Crowding::default().check(8, 8).is_ok());
+        let odd = Crowding {
+            bin_size: 3,
+            ..Crowding::default()
+        };
+        assert_eq!(odd.check(8, 8), Err(ConfigError::Crowding));
+    }
+}
@@ -0,0 +1,95 @@
+//! Signed differences between two snapshots of a population's trails, for seeing what a
+//! single parameter change did to a run.
+
+use super::util;
+use image::{Rgb, RgbImage};
+
+/// Share of the cells whose change saturates `heatmap` at the scale of `heatmap_scale`,
+/// so that a few hot cells don't wash out the rest.
+const SATURATED: f32 = 0.01;
+
+/// How much the trails changed between two snapshots.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Difference {
+    /// The average change, positive when the trails grew overall.
+    pub mean: f32,
+    /// The average size of the changes either way.
+    pub mean_abs: f32,
+    /// The largest change either way.
+    pub max_abs: f32,
+}
+
+impl Difference {
+    pub fn between(before: &[f32], after: &[f32]) -> Self {
+        let n = before.len().max(1) as f32;
+        let (mut sum, mut sum_abs, mut max_abs) = (0.0f64, 0.0f64, 0.0f32);
+        for (b, a) in before.iter().zip(after) {
+            let d = a - b;
+            sum += d as f64;
+            sum_abs += d.abs() as f64;
+            max_abs = max_abs.max(d.abs());
+        }
+        Difference {
+            mean: (sum / n as f64) as f32,
+            mean_abs: (sum_abs / n as f64) as f32,
+            max_abs,
+        }
+    }
+}
+
+/// The change at which `heatmap` saturates, all but the largest hundredth of them.
+pub fn heatmap_scale(before: &[f32], after: &[f32]) -> f32 {
+    let changes: Vec<f32> = before
+        .iter()
+        .zip(after)
+        .map(|(b, a)| (a - b).abs())
+        .collect();
+    if changes.is_empty() {
+        return 0.0;
+    }
+    util::quantile(&changes, 1.0 - SATURATED)
+}
+
+/// Renders `after - before` as an image of `width` by `height`: orange where the trails
+/// grew, blue where they shrank and black where nothing changed, saturating at changes of
+/// `scale`. The square root of the change sets the brightness, so that small changes
+/// still show.
+pub fn heatmap(before: &[f32], after: &[f32], width: usize, height: usize, scale: f32) -> RgbImage {
+    let mut image = RgbImage::new(width as u32, height as u32);
+    for (pixel, (b, a)) in image.pixels_mut().zip(before.iter().zip(after)) {
+        let d = a - b;
+        let t = if scale > 0.0 {
+            (d.abs() / scale).min(1.0).sqrt()
+        } else {
+            0.0
+        };
+        let [r, g, b] = if d > 0.0 {
+            [1.0, 0.45, 0.05]
+        } else {
+            [0.1, 0.45, 1.0]
+        };
+        *pixel = Rgb([r, g, b].map(|c| (c * t * 255.0).round() as u8));
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heatmaps_sign_the_changes() {
+        let before = [1.0, 1.0, 1.0, 1.0];
+        let after = [3.0, 1.0, 0.0, 1.0];
+        let difference = Difference::between(&before, &after);
+        assert_eq!(difference.mean, 0.25);
+        assert_eq!(difference.mean_abs, 0.75);
+        assert_eq!(difference.max_abs, 2.0);
+
+        let image = heatmap(&before, &after, 2, 2, 2.0);
+        assert_eq!(image.get_pixel(0, 0), &Rgb([255, 115, 13]));
+        assert_eq!(image.get_pixel(1, 0), &Rgb([0, 0, 0]));
+        let shrunk = image.get_pixel(0, 1);
+        assert!(shrunk[2] > shrunk[0] && shrunk[2] < 255);
+    }
+}
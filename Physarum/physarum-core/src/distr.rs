@@ -0,0 +1,46 @@
+//! The normal distribution from `rand_distr`, or a Box-Muller stand-in when building
+//! without it. The stand-in draws different values, so seeds only reproduce a
+//! simulation between builds with the same features.
+
+#[cfg(feature = "rand_distr")]
+pub(crate) use rand_distr::{Distribution, Normal};
+
+#[cfg(not(feature = "rand_distr"))]
+pub(crate) use box_muller::*;
+
+#[cfg(not(feature = "rand_distr"))]
+mod box_muller {
+    use rand::Rng;
+
+    pub trait Distribution<T> {
+        fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> T;
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct Normal {
+        mean: f32,
+        std_dev: f32,
+    }
+
+    #[derive(Debug)]
+    pub struct BadVariance;
+
+    impl Normal {
+        pub fn new(mean: f32, std_dev: f32) -> Result<Self, BadVariance> {
+            if std_dev.is_finite() && std_dev >= 0.0 {
+                Ok(Normal { mean, std_dev })
+            } else {
+                Err(BadVariance)
+            }
+        }
+    }
+
+    impl Distribution<f32> for Normal {
+        fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+            let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+            let u2: f32 = rng.gen();
+            let z = (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos();
+            self.mean + self.std_dev * z
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub enum DitherMethod {
+    /// Error diffusion, with the fine organic grain of a screen print.
+    #[default]
+    FloydSteinberg,
+    /// Thresholding against a tiled blue-noise mask, which keeps the dots evenly spaced
+    /// and avoids the worm artifacts of error diffusion.
+    BlueNoise,
+}
+
+impl FromStr for DitherMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "floyd-steinberg" => Ok(DitherMethod::FloydSteinberg),
+            "blue-noise" => Ok(DitherMethod::BlueNoise),
+            _ => Err(format!(
+                "Unknown dither method {:?}, expected floyd-steinberg or blue-noise",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DitherMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DitherMethod::FloydSteinberg => write!(f, "floyd-steinberg"),
+            DitherMethod::BlueNoise => write!(f, "blue-noise"),
+        }
+    }
+}
+
+/// Quantizes a row-major density field in [0, 1] to one bit per cell, true where ink goes.
+pub fn dither(density: &[f32], width: usize, height: usize, method: DitherMethod) -> Vec<bool> {
+    match method {
+        DitherMethod::FloydSteinberg => floyd_steinberg(density, width, height),
+        DitherMethod::BlueNoise => {
+            let mask = blue_noise_mask();
+            density
+                .iter()
+                .enumerate()
+                .map(|(i, &v)| {
+                    let (x, y) = (i % width % MASK_SIZE, i / width % MASK_SIZE);
+                    v > mask[y * MASK_SIZE + x]
+                })
+                .collect()
+        }
+    }
+}
+
+fn floyd_steinberg(density: &[f32], width: usize, height: usize) -> Vec<bool> {
+    let mut values = density.to_vec();
+    let mut ink = vec![false; density.len()];
+    for y in 0..height {
+        // Serpentine scanning keeps the error from piling up along one side.
+        let forward = y % 2 == 0;
+        for step in 0..width {
+            let x = if forward { step } else { width - 1 - step };
+            let i = y * width + x;
+            ink[i] = values[i] > 0.5;
+            let error = values[i] - if ink[i] { 1.0 } else { 0.0 };
+
+            let ahead = if forward { x + 1 < width } else { x > 0 };
+            let behind = if forward { x > 0 } else { x + 1 < width };
+            let (next, prev) = if forward {
+                (i + 1, i.wrapping_sub(1))
+            } else {
+                (i - 1, i + 1)
+            };
+            if ahead {
+                values[next] += error * 7.0 / 16.0;
+            }
+            if y + 1 < height {
+                let below = i + width;
+                if behind {
+                    values[prev + width] += error * 3.0 / 16.0;
+                }
+                values[below] += error * 5.0 / 16.0;
+                if ahead {
+                    values[next + width] += error * 1.0 / 16.0;
+                }
+            }
+        }
+    }
+    ink
+}
+
+const MASK_SIZE: usize = 64;
+
+/// Thresholds in (0, 1) of a toroidal blue-noise mask, built once with the
+/// void-and-cluster method.
+fn blue_noise_mask() -> &'static [f32] {
+    static MASK: OnceLock<Vec<f32>> = OnceLock::new();
+    MASK.get_or_init(|| {
+        let n = MASK_SIZE * MASK_SIZE;
+        let sigma = 1.5_f32;
+        let kernel: Vec<f32> = (0..n)
+            .map(|i| {
+                let wrap = |d: usize| d.min(MASK_SIZE - d) as f32;
+                let (dx, dy) = (wrap(i % MASK_SIZE), wrap(i / MASK_SIZE));
+                (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+            })
+            .collect();
+        let mut energy = vec![0.0_f32; n];
+        let mut points = vec![false; n];
+        let toggle = |energy: &mut [f32], points: &mut [bool], p: usize| {
+            points[p] = !points[p];
+            let sign = if points[p] { 1.0 } else { -1.0 };
+            let (px, py) = (p % MASK_SIZE, p / MASK_SIZE);
+            for (i, e) in energy.iter_mut().enumerate() {
+                let dx = (i % MASK_SIZE + MASK_SIZE - px) % MASK_SIZE;
+                let dy = (i / MASK_SIZE + MASK_SIZE - py) % MASK_SIZE;
+                *e += sign * kernel[dy * MASK_SIZE + dx];
+            }
+        };
+        let tightest_cluster = |energy: &[f32], points: &[bool]| {
+            (0..n)
+                .filter(|&i| points[i])
+                .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+                .unwrap()
+        };
+        let largest_void = |energy: &[f32], points: &[bool]| {
+            (0..n)
+                .filter(|&i| !points[i])
+                .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+                .unwrap()
+        };
+
+        // Start from a sparse, deterministic scatter of points and relax it by moving the
+        // tightest cluster into the largest void until that stops changing anything.
+        let initial = n / 10;
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        while points.iter().filter(|&&p| p).count() < initial {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let p = (state % n as u64) as usize;
+            if !points[p] {
+                toggle(&mut energy, &mut points, p);
+            }
+        }
+        loop {
+            let cluster = tightest_cluster(&energy, &points);
+            toggle(&mut energy, &mut points, cluster);
+            let void = largest_void(&energy, &points);
+            toggle(&mut energy, &mut points, void);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut rank = vec![0; n];
+        let (saved_energy, saved_points) = (energy.clone(), points.clone());
+        for r in (0..initial).rev() {
+            let cluster = tightest_cluster(&energy, &points);
+            toggle(&mut energy, &mut points, cluster);
+            rank[cluster] = r;
+        }
+        let (mut energy, mut points) = (saved_energy, saved_points);
+        for r in initial..n {
+            let void = largest_void(&energy, &points);
+            toggle(&mut energy, &mut points, void);
+            rank[void] = r;
+        }
+        rank.iter().map(|&r| (r as f32 + 0.5) / n as f32).collect()
+    })
+}
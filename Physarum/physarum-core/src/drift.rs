@@ -0,0 +1,203 @@
+use super::distr::{Distribution, Normal};
+use super::error::ConfigError;
+use super::population_config::PopulationConfig;
+use rand::{rngs::SmallRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// Population parameter that can be moved by the drift controller.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum DriftTarget {
+    SensorDistance,
+    StepDistance,
+    SensorAngle,
+    RotationAngle,
+    DecayFactor,
+    DepositionAmount,
+}
+
+impl DriftTarget {
+    fn value_mut(self, config: &mut PopulationConfig) -> &mut f32 {
+        match self {
+            DriftTarget::SensorDistance => &mut config.sensor_distance,
+            DriftTarget::StepDistance => &mut config.step_distance,
+            DriftTarget::SensorAngle => &mut config.sensor_angle,
+            DriftTarget::RotationAngle => &mut config.rotation_angle,
+            DriftTarget::DecayFactor => &mut config.decay_factor,
+            DriftTarget::DepositionAmount => &mut config.deposition_amount,
+        }
+    }
+}
+
+/// Bounds and speed of a single drifting parameter.
+///
+/// `rate` is the standard deviation of the random walk after one hour,
+/// `day_amplitude` adds a sinusoidal time-of-day offset as a fraction of the range.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct DriftParameter {
+    pub target: DriftTarget,
+    pub min: f32,
+    pub max: f32,
+    pub rate: f32,
+    #[serde(default)]
+    pub day_amplitude: f32,
+}
+
+impl DriftParameter {
+    pub fn new(target: DriftTarget, min: f32, max: f32, rate: f32) -> Self {
+        DriftParameter {
+            target,
+            min,
+            max,
+            rate,
+            day_amplitude: 0.0,
+        }
+    }
+
+    fn check(&self) -> Result<(), ConfigError> {
+        let valid = |value: f32| value >= 0.0 && value.is_finite();
+        if self.min.is_finite()
+            && self.max.is_finite()
+            && self.min <= self.max
+            && valid(self.rate)
+            && valid(self.day_amplitude)
+        {
+            Ok(())
+        } else {
+            Err(ConfigError::Drift)
+        }
+    }
+
+    fn reflect(&self, mut value: f32) -> f32 {
+        let range = self.max - self.min;
+        if range <= 0.0 {
+            return self.min;
+        }
+        // Mirror the walk back into the bounds instead of clamping, so it doesn't stick to the edges.
+        value = (value - self.min).rem_euclid(2.0 * range);
+        if value > range {
+            value = 2.0 * range - value;
+        }
+        self.min + value
+    }
+}
+
+/// Slowly random-walks selected population parameters within bounds for long unattended runs.
+pub struct DriftController {
+    pub parameters: Vec<DriftParameter>,
+    base_values: Vec<Vec<f32>>,
+    /// The values the last update wrote, to tell when they were changed since.
+    written: Vec<Vec<f32>>,
+    rng: SmallRng,
+}
+
+impl DriftController {
+    /// Fails for parameters with empty or non-finite bounds or a negative or non-finite
+    /// rate or amplitude.
+    pub fn new(parameters: Vec<DriftParameter>, seed: u64) -> Result<Self, ConfigError> {
+        for parameter in &parameters {
+            parameter.check()?;
+        }
+        Ok(DriftController {
+            parameters,
+            base_values: vec![],
+            written: vec![],
+            rng: SmallRng::seed_from_u64(seed),
+        })
+    }
+
+    pub fn default_parameters() -> Vec<DriftParameter> {
+        vec![
+            DriftParameter::new(DriftTarget::SensorDistance, 4.0, 64.0, 8.0),
+            DriftParameter::new(DriftTarget::SensorAngle, 0.05, 2.1, 0.3),
+            DriftParameter::new(DriftTarget::RotationAngle, 0.05, 2.1, 0.3),
+            DriftParameter::new(DriftTarget::StepDistance, 0.2, 2.0, 0.2),
+        ]
+    }
+
+    /// Advances the walk by `dt` seconds. `time_of_day` is the fraction of the local day in [0, 1).
+    /// Values changed since the last update, e.g. with the sliders, are walked on from.
+    pub fn update(&mut self, configs: &mut [PopulationConfig], dt: f32, time_of_day: f32) {
+        let n_parameters = self.parameters.len();
+        if self.written.len() != configs.len()
+            || self
+                .written
+                .iter()
+                .any(|values| values.len() != n_parameters)
+        {
+            // Nothing matches NaN, so the walks start from the configs.
+            self.base_values = vec![vec![0.0; n_parameters]; configs.len()];
+            self.written = vec![vec![f32::NAN; n_parameters]; configs.len()];
+        }
+
+        let hours = dt / 3600.0;
+        let day_phase = (time_of_day * TAU).sin();
+        let walks = self.base_values.iter_mut().zip(self.written.iter_mut());
+        for (config, (base_values, written)) in configs.iter_mut().zip(walks) {
+            let values = base_values.iter_mut().zip(written.iter_mut());
+            for (parameter, (base, written)) in self.parameters.iter().zip(values) {
+                let value = parameter.target.value_mut(config);
+                if *value != *written {
+                    *base = parameter.reflect(*value);
+                }
+                let std = parameter.rate * hours.sqrt();
+                if let Ok(distr) = Normal::new(0.0, std) {
+                    *base = parameter.reflect(*base + distr.sample(&mut self.rng));
+                }
+                let offset = parameter.day_amplitude * (parameter.max - parameter.min) * day_phase;
+                *value = (*base + offset).clamp(parameter.min, parameter.max);
+                *written = *value;
+            }
+        }
+    }
+
+    /// Forgets the walk state so the next update restarts from the given configs.
+    pub fn reset(&mut self) {
+        self.base_values.clear();
+        self.written.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_parameters_drift_within_their_bounds() {
+        let parameters: Vec<DriftParameter> =
+            serde_json::from_str(r#"[{"target":"DecayFactor","min":0.1,"max":0.2,"rate":10.0}]"#)
+                .unwrap();
+        assert_eq!(
+            parameters,
+            [DriftParameter::new(
+                DriftTarget::DecayFactor,
+                0.1,
+                0.2,
+                10.0
+            )]
+        );
+        let mut configs = vec![PopulationConfig::new(&mut SmallRng::seed_from_u64(0)); 2];
+        let sensor_distance = configs[0].sensor_distance;
+        let mut drift = DriftController::new(parameters, 0).unwrap();
+        for _ in 0..10 {
+            drift.update(&mut configs, 3600.0, 0.0);
+            for config in &configs {
+                assert!((0.1..=0.2).contains(&config.decay_factor));
+                assert_eq!(config.sensor_distance, sensor_distance);
+            }
+        }
+
+        // An edit made between updates is where the walk goes on from.
+        configs[1].decay_factor = 0.15;
+        let walked = configs[0].decay_factor;
+        drift.update(&mut configs, 0.0, 0.0);
+        assert_eq!(configs[0].decay_factor, walked);
+        assert_eq!(configs[1].decay_factor, 0.15);
+
+        let inverted = DriftParameter::new(DriftTarget::StepDistance, 2.0, 1.0, 0.1);
+        assert_eq!(
+            DriftController::new(vec![inverted], 0).err(),
+            Some(ConfigError::Drift)
+        );
+    }
+}
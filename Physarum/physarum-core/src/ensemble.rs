@@ -0,0 +1,137 @@
+use super::par::*;
+use super::physarum_model::PhysarumModel;
+use alloc::{vec, vec::Vec};
+
+/// Several independent models stepped side by side on one pool of worker threads, so that
+/// running simulations in parallel doesn't start a thread per model on top of the ones
+/// each model already steps its agents on. Models advance in rounds of one step each,
+/// which keeps them in lockstep however unevenly the pool's threads are shared out.
+pub struct Ensemble {
+    models: Vec<PhysarumModel>,
+    /// Rayon's global pool when None.
+    #[cfg(feature = "parallel")]
+    pool: Option<rayon::ThreadPool>,
+}
+
+impl Ensemble {
+    pub fn new(models: Vec<PhysarumModel>) -> Self {
+        Ensemble {
+            models,
+            #[cfg(feature = "parallel")]
+            pool: None,
+        }
+    }
+
+    /// Runs on a pool of its own with `threads` threads instead of rayon's global one, to
+    /// leave cores to the rest of the process.
+    #[cfg(feature = "parallel")]
+    pub fn with_threads(mut self, threads: usize) -> Result<Self, rayon::ThreadPoolBuildError> {
+        self.pool = Some(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()?,
+        );
+        Ok(self)
+    }
+
+    pub fn models(&self) -> &[PhysarumModel] {
+        &self.models
+    }
+
+    pub fn models_mut(&mut self) -> &mut [PhysarumModel] {
+        &mut self.models
+    }
+
+    pub fn into_models(self) -> Vec<PhysarumModel> {
+        self.models
+    }
+
+    pub fn len(&self) -> usize {
+        self.models.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    pub fn push(&mut self, model: PhysarumModel) {
+        self.models.push(model);
+    }
+
+    pub fn remove(&mut self, index: usize) -> PhysarumModel {
+        self.models.remove(index)
+    }
+
+    /// Steps every model once.
+    pub fn step(&mut self) {
+        self.each(|model| model.step());
+    }
+
+    /// Calls `f` on every model at the same time, on the ensemble's pool.
+    pub fn each(&mut self, f: impl Fn(&mut PhysarumModel) + Sync + Send) {
+        let mut data = vec![(); self.models.len()];
+        self.each_with(&mut data, |model, _| f(model));
+    }
+
+    /// Calls `f` on every model together with its entry of `data`, which holds whatever
+    /// each model's caller keeps alongside it, at the same time on the ensemble's pool.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `data` doesn't have an entry per model.
+    pub fn each_with<T: Send>(
+        &mut self,
+        data: &mut [T],
+        f: impl Fn(&mut PhysarumModel, &mut T) + Sync + Send,
+    ) {
+        assert_eq!(data.len(), self.models.len(), "one entry of data per model");
+        #[cfg(feature = "parallel")]
+        if let Some(pool) = &self.pool {
+            return pool.install(|| zip_each(&mut self.models, data, &f));
+        }
+        zip_each(&mut self.models, data, &f)
+    }
+}
+
+fn zip_each<T: Send>(
+    models: &mut [PhysarumModel],
+    data: &mut [T],
+    f: &(impl Fn(&mut PhysarumModel, &mut T) + Sync),
+) {
+    models
+        .par_iter_mut()
+        .zip(data.par_iter_mut())
+        .for_each(|(model, entry)| f(model, entry));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(seed: u64) -> PhysarumModel {
+        PhysarumModel::builder()
+            .size(32, 32)
+            .particles(500)
+            .populations(2)
+            .seed(seed)
+            .strict_determinism(true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn models_step_as_they_would_alone() {
+        let mut alone = model(3);
+        let mut ensemble = Ensemble::new(vec![model(1), model(3)]);
+        let mut steps = vec![0; 2];
+        for _ in 0..20 {
+            alone.step();
+            ensemble.each_with(&mut steps, |model, steps| {
+                model.step();
+                *steps += 1;
+            });
+        }
+        assert_eq!(steps, [20, 20]);
+        assert_eq!(ensemble.models()[1].state().grids, alone.state().grids);
+    }
+}
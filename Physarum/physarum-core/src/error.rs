@@ -0,0 +1,295 @@
+use super::grid;
+use super::operators;
+use super::palette::PALETTE_NAMES;
+use super::spacing;
+use super::steering;
+use alloc::string::String;
+use core::fmt;
+
+/// Reasons a simulation can't be built from the given parameters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Grids wrap with bit masks, so both sides must be powers of two.
+    InvalidDimensions {
+        width: usize,
+        height: usize,
+    },
+    /// Grid data doesn't hold exactly `width * height` values.
+    DataLength {
+        expected: usize,
+        actual: usize,
+    },
+    NoPopulations,
+    /// Fewer particles than populations would leave some populations empty.
+    TooFewParticles {
+        particles: usize,
+        populations: usize,
+    },
+    /// `Palette::by_name` didn't know the name given to the builder.
+    UnknownPalette,
+    /// The attraction table must be square with a row per population.
+    AttractionTableSize {
+        populations: usize,
+    },
+    /// The deposition table doesn't have a row of one amount per population for every
+    /// population.
+    DepositionTableSize {
+        populations: usize,
+    },
+    /// The attraction or repulsion distribution has a non-finite mean or a negative or
+    /// non-finite deviation.
+    AttractionDistribution,
+    /// The band-pass blurs aren't ordered `0 < inner < outer` or the outer one doesn't fit
+    /// inside the grid.
+    Bandpass,
+    /// A population's self-repulsion isn't finite or its attraction range doesn't fit
+    /// inside the grid.
+    SelfAvoidance,
+    /// A population's erosion rate isn't between 0 and 1.
+    Erosion,
+    /// The time scale isn't positive and finite.
+    TimeScale,
+    /// An annealing temperature that is negative or not finite.
+    Annealing,
+    /// Crowding bins that aren't a power of two fitting inside the grid, or a strength that
+    /// isn't finite.
+    Crowding,
+    /// A spacing radius that isn't positive or exceeds `spacing::MAX_SPACING_RADIUS`, or a
+    /// strength that isn't between 0 and 1.
+    Spacing,
+    /// Heat with an invalid noise, a negative or non-finite strength, a diffusion that
+    /// doesn't fit inside the grid, a relaxation that isn't between 0 and 1 or a non-finite
+    /// drift.
+    Heat,
+    /// Drift parameters with empty or non-finite bounds, or a negative or non-finite rate or
+    /// day amplitude.
+    Drift,
+    /// Cities without a positive radius, with a negative scent or with empty bounds.
+    Cities,
+    /// Food sources without a positive radius and capacity, or with negative rates.
+    Food,
+    /// A pattern of initial trails with a size that isn't positive and finite.
+    InitialTrails,
+    /// A file named by a setting couldn't be read or made sense of, for `reason`.
+    Unreadable {
+        setting: &'static str,
+        reason: String,
+    },
+    /// Custom sensing weights without one weight per population.
+    SensingWeights {
+        populations: usize,
+    },
+    /// A population index past the model's populations.
+    UnknownPopulation {
+        population: usize,
+        populations: usize,
+    },
+    /// Agents added to `population` that belong to another one.
+    ForeignAgents {
+        population: usize,
+    },
+    /// Fewer population configs than the model has grids.
+    MissingPopulationConfigs {
+        expected: usize,
+        actual: usize,
+    },
+    /// The grids of all populations together would exceed `grid::MAX_CELLS`.
+    TooManyCells {
+        cells: usize,
+    },
+    /// `steering::by_name` didn't know a population's steering rule.
+    UnknownSteeringRule {
+        name: String,
+    },
+    /// `operators::deposit_by_name` didn't know a population's deposit operator.
+    UnknownDepositOp {
+        name: String,
+    },
+    /// `operators::diffuse_by_name` didn't know a population's diffuse operator.
+    UnknownDiffuseOp {
+        name: String,
+    },
+    /// More agents than `grid::MAX_PARTICLES`.
+    TooManyParticles {
+        particles: usize,
+    },
+    /// A number of tiles that doesn't split the grid's height into equal bands.
+    TileCount {
+        tiles: usize,
+        height: usize,
+    },
+    /// Tiles thinner than the halo their agents sense and diffuse into.
+    TileHalo {
+        rows: usize,
+        halo: usize,
+    },
+    /// Blurring by more than the grid's size would wrap around it several times.
+    DiffusivityTooLarge {
+        diffusivity: usize,
+        width: usize,
+        height: usize,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidDimensions { width, height } => {
+                write!(f, "Grid dims must be 2^n, got {}x{}", width, height)
+            }
+            ConfigError::DataLength { expected, actual } => write!(
+                f,
+                "Grid data must contain width * height = {} values, got {}",
+                expected, actual
+            ),
+            ConfigError::NoPopulations => write!(f, "Expected at least one population"),
+            ConfigError::TooFewParticles {
+                particles,
+                populations,
+            } => write!(
+                f,
+                "{} particles can't be split between {} populations",
+                particles, populations
+            ),
+            ConfigError::UnknownPalette => write!(
+                f,
+                "Unknown palette, expected one of {}",
+                PALETTE_NAMES.join(", ")
+            ),
+            ConfigError::AttractionTableSize { populations } => write!(
+                f,
+                "Expected a {}x{} attraction table",
+                populations, populations
+            ),
+            ConfigError::DepositionTableSize { populations } => write!(
+                f,
+                "Expected a {}x{} deposition table",
+                populations, populations
+            ),
+            ConfigError::AttractionDistribution => write!(
+                f,
+                "Attraction means must be finite and their deviations finite and non-negative"
+            ),
+            ConfigError::Bandpass => write!(
+                f,
+                "Band-pass blurs must satisfy 0 < inner < outer and fit inside the grid"
+            ),
+            ConfigError::SelfAvoidance => write!(
+                f,
+                "Self-repulsion must be finite and its attraction range fit inside the grid"
+            ),
+            ConfigError::Erosion => write!(f, "Erosion rates must be between 0 and 1"),
+            ConfigError::TimeScale => write!(f, "The time scale must be positive and finite"),
+            ConfigError::Crowding => write!(
+                f,
+                "Crowding bins must be a power of two no larger than the grid, with a finite strength"
+            ),
+            ConfigError::Spacing => write!(
+                f,
+                "Spacing radii must be positive and at most {}, with a strength from 0 to 1",
+                spacing::MAX_SPACING_RADIUS
+            ),
+            ConfigError::Heat => write!(
+                f,
+                "Heat needs valid noise, a non-negative strength, a diffusion fitting inside the grid and a relaxation from 0 to 1"
+            ),
+            ConfigError::Annealing => {
+                write!(f, "Annealing temperatures must be non-negative and finite")
+            }
+            ConfigError::Drift => write!(
+                f,
+                "Drift parameters need finite bounds with min <= max and a non-negative rate and day amplitude"
+            ),
+            ConfigError::Cities => write!(
+                f,
+                "Cities need a positive radius, a non-negative scent and non-empty bounds"
+            ),
+            ConfigError::Food => write!(
+                f,
+                "Food sources need a positive radius and capacity and non-negative rates"
+            ),
+            ConfigError::InitialTrails => write!(
+                f,
+                "Initial trail patterns need positive, finite sizes and at least one octave"
+            ),
+            ConfigError::Unreadable { setting, reason } => {
+                write!(f, "Failed to read the {}: {}", setting, reason)
+            }
+            ConfigError::SensingWeights { populations } => write!(
+                f,
+                "Expected {} weights for every population sensing custom weights",
+                populations
+            ),
+            ConfigError::UnknownPopulation {
+                population,
+                populations,
+            } => write!(
+                f,
+                "No population {} among {} populations",
+                population, populations
+            ),
+            ConfigError::ForeignAgents { population } => write!(
+                f,
+                "Agents added to population {} must all belong to it",
+                population
+            ),
+            ConfigError::MissingPopulationConfigs { expected, actual } => write!(
+                f,
+                "Expected {} population configs, got {}",
+                expected, actual
+            ),
+            ConfigError::TooManyCells { cells } => write!(
+                f,
+                "{} grid cells across all populations, at most {} are supported",
+                cells,
+                grid::MAX_CELLS
+            ),
+            ConfigError::UnknownSteeringRule { name } => write!(
+                f,
+                "Unknown steering rule {:?}, expected one of {} or a registered one",
+                name,
+                steering::BUILT_IN.join(", ")
+            ),
+            ConfigError::UnknownDepositOp { name } => write!(
+                f,
+                "Unknown deposit operator {:?}, expected one of {} or a registered one",
+                name,
+                operators::DEPOSIT_BUILT_IN.join(", ")
+            ),
+            ConfigError::UnknownDiffuseOp { name } => write!(
+                f,
+                "Unknown diffuse operator {:?}, expected one of {} or a registered one",
+                name,
+                operators::DIFFUSE_BUILT_IN.join(", ")
+            ),
+            ConfigError::TooManyParticles { particles } => write!(
+                f,
+                "{} particles, at most {} are supported",
+                particles,
+                grid::MAX_PARTICLES
+            ),
+            ConfigError::TileCount { tiles, height } => write!(
+                f,
+                "{} tiles can't split a grid of height {} into equal bands",
+                tiles, height
+            ),
+            ConfigError::TileHalo { rows, halo } => write!(
+                f,
+                "Tiles of {} rows are too thin for a halo of {} rows",
+                rows, halo
+            ),
+            ConfigError::DiffusivityTooLarge {
+                diffusivity,
+                width,
+                height,
+            } => write!(
+                f,
+                "Diffusivity {} is too large for a {}x{} grid",
+                diffusivity, width, height
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConfigError {}
@@ -0,0 +1,103 @@
+use super::agents::Agents;
+use super::blur::Blur;
+use super::grid;
+use super::math::Math;
+use super::par::*;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+pub const MAX_FLOW_LENGTH: usize = 64;
+/// Deviation in cells of the blur smoothing the summed headings into a field.
+const HEADING_SMOOTHING: f32 = 2.0;
+
+/// The headings of the agents of `population` summed up as unit vectors in the cells they
+/// occupy, then smoothed. Agents moving together add up to long vectors, while crowds
+/// heading every way cancel out.
+pub(super) fn heading_field(
+    agents: &Agents,
+    population: usize,
+    width: usize,
+    height: usize,
+    math: Math,
+) -> [Vec<f32>; 2] {
+    let mut field = [vec![0.0; width * height], vec![0.0; width * height]];
+    for agent in agents.of_population(population) {
+        let x = (agent.x as usize).min(width - 1);
+        let y = (agent.y as usize).min(height - 1);
+        field[0][y * width + x] += (math.cos)(agent.angle);
+        field[1][y * width + x] += (math.sin)(agent.angle);
+    }
+    if grid::blur_fits(width, height, HEADING_SMOOTHING) {
+        let mut blur = Blur::new(width);
+        let mut buf = vec![0.0; width * height];
+        for component in &mut field {
+            blur.run(component, &mut buf, width, height, HEADING_SMOOTHING, 1.0);
+        }
+    }
+    field
+}
+
+/// Line integral convolution of white noise along a vector field: every cell averages
+/// the noise along the streamline through it, `length` cells each way, which smears the
+/// noise into strokes tracing the flow. The averaging flattens the noise towards grey, so
+/// its contrast is stretched back, and the result fades out where the field is weaker than
+/// its mean so that only moving crowds show up.
+pub(super) fn lic(field: &[Vec<f32>; 2], width: usize, height: usize, length: usize) -> Vec<f32> {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let noise: Vec<f32> = (0..width * height).map(|_| rng.gen()).collect();
+    let length = length.min(MAX_FLOW_LENGTH);
+    let (w, h) = (width as f32, height as f32);
+    let strengths: Vec<f32> = field[0]
+        .iter()
+        .zip(&field[1])
+        .map(|(x, y)| x.hypot(*y))
+        .collect();
+    let moving = strengths.iter().filter(|&&s| s > 0.0).count().max(1);
+    let mean_strength = strengths.iter().sum::<f32>() / moving as f32;
+    let contrast = ((2 * length + 1) as f32).sqrt();
+    let at = |x: f32, y: f32| (y as usize).min(height - 1) * width + (x as usize).min(width - 1);
+    (0..width * height)
+        .into_par_iter()
+        .map(|i| {
+            let strength = strengths[i];
+            if strength == 0.0 {
+                return 0.0;
+            }
+            let mut sum = noise[i];
+            for direction in [1.0, -1.0] {
+                let (mut x, mut y) = ((i % width) as f32 + 0.5, (i / width) as f32 + 0.5);
+                for _ in 0..length {
+                    let j = at(x, y);
+                    let (vx, vy) = (field[0][j], field[1][j]);
+                    let norm = vx.hypot(vy);
+                    if norm == 0.0 {
+                        break;
+                    }
+                    x = (x + direction * vx / norm).rem_euclid(w);
+                    y = (y + direction * vy / norm).rem_euclid(h);
+                    sum += noise[at(x, y)];
+                }
+            }
+            let stroke = (0.5 + (sum / (2 * length + 1) as f32 - 0.5) * contrast).clamp(0.0, 1.0);
+            stroke * strength / (strength + mean_strength)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strokes_follow_the_flow() {
+        let (width, height) = (64, 64);
+        let field = [vec![1.0; width * height], vec![0.0; width * height]];
+        let image = lic(&field, width, height, 8);
+        let roughness = |step: usize| -> f32 {
+            (0..width * height - step)
+                .map(|i| (image[i] - image[i + step]).abs())
+                .sum()
+        };
+        // Neighbours along the rows share most of their streamline, those across them none.
+        assert!(roughness(1) * 2.0 < roughness(width));
+    }
+}
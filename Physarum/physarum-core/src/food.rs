@@ -0,0 +1,271 @@
+use super::agents::Agents;
+use super::error::ConfigError;
+use super::grid::{self, Grid};
+use super::par::*;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// Point food sources that come and go, like the oat flakes of the classic Physarum maze
+/// experiments. Every source lays a scent into all grids that fades as the agents inside
+/// it eat it up, until it is gone and the network re-routes towards the newer ones.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct Food {
+    /// Sources appearing per thousand steps, at random places.
+    pub spawn_rate: f32,
+    /// Sources at the first step.
+    pub initial: usize,
+    /// No more sources spawn while this many are left.
+    pub max_sources: usize,
+    /// Radius in cells of every source.
+    pub radius: f32,
+    /// Food a fresh source holds.
+    pub capacity: f32,
+    /// Food every agent inside a source eats per step.
+    pub consumption: f32,
+    /// Trail a full source adds per step to every cell it covers, less as it depletes.
+    pub scent: f32,
+    /// Places the sources elsewhere for other seeds.
+    pub seed: u64,
+}
+
+impl Default for Food {
+    fn default() -> Self {
+        Food {
+            spawn_rate: 5.0,
+            initial: 4,
+            max_sources: 16,
+            radius: 6.0,
+            capacity: 50000.0,
+            consumption: 1.0,
+            scent: 2.0,
+            seed: 0,
+        }
+    }
+}
+
+impl Food {
+    /// Requires a positive radius and capacity and rates that are non-negative, all finite.
+    pub fn check(&self) -> Result<(), ConfigError> {
+        let positive = |v: f32| v > 0.0 && v.is_finite();
+        let rate = |v: f32| v >= 0.0 && v.is_finite();
+        if positive(self.radius)
+            && positive(self.capacity)
+            && rate(self.spawn_rate)
+            && rate(self.consumption)
+            && rate(self.scent)
+        {
+            Ok(())
+        } else {
+            Err(ConfigError::Food)
+        }
+    }
+}
+
+/// A food source at (x, y) with `remaining` of its capacity left.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FoodSource {
+    pub x: f32,
+    pub y: f32,
+    pub remaining: f32,
+}
+
+/// Marks cells outside every source in `Pantry::owner`.
+const NO_SOURCE: u32 = u32::MAX;
+
+/// The sources of a run and the generator placing new ones.
+pub(crate) struct Pantry {
+    pub food: Food,
+    pub sources: Vec<FoodSource>,
+    rng: SmallRng,
+    /// The source covering every cell, `NO_SOURCE` outside all of them.
+    owner: Vec<u32>,
+    width: usize,
+    height: usize,
+}
+
+impl Pantry {
+    pub fn new(food: Food, width: usize, height: usize) -> Self {
+        let mut pantry = Pantry {
+            food,
+            sources: Vec::new(),
+            rng: SmallRng::seed_from_u64(food.seed),
+            owner: vec![NO_SOURCE; width * height],
+            width,
+            height,
+        };
+        for _ in 0..food.initial {
+            pantry.spawn();
+        }
+        pantry
+    }
+
+    /// Keeps the sources when only the parameters change, with no more food than the new
+    /// capacity.
+    pub fn set_food(&mut self, food: Food) {
+        for source in &mut self.sources {
+            source.remaining = source.remaining.min(food.capacity);
+        }
+        self.food = food;
+    }
+
+    fn spawn(&mut self) {
+        self.sources.push(FoodSource {
+            x: self.rng.gen_range(0.0..self.width as f32),
+            y: self.rng.gen_range(0.0..self.height as f32),
+            remaining: self.food.capacity,
+        });
+    }
+
+    /// Lays the scent of every source, lets the agents inside eat from it, clears away the
+    /// empty ones and spawns new ones, all scaled to steps covering `time_scale`.
+    pub fn step(&mut self, grids: &mut [Grid], agents: &Agents, time_scale: f32) {
+        let Food {
+            spawn_rate,
+            max_sources,
+            capacity,
+            consumption,
+            scent,
+            ..
+        } = self.food;
+        self.cover();
+        let amounts: Vec<f32> = self
+            .sources
+            .iter()
+            .map(|source| scent * time_scale * source.remaining / capacity)
+            .collect();
+        for grid in grids.iter_mut() {
+            grid.data_mut()
+                .par_iter_mut()
+                .zip(self.owner.par_iter())
+                .filter(|(_, &owner)| owner != NO_SOURCE)
+                .for_each(|(trail, &owner)| *trail += amounts[owner as usize]);
+        }
+
+        let mut eaters = vec![0usize; self.sources.len()];
+        for agent in agents.iter() {
+            let owner = self.owner[grid::cell(self.width, self.height, agent.x, agent.y)];
+            if owner != NO_SOURCE {
+                eaters[owner as usize] += 1;
+            }
+        }
+        for (source, eaters) in self.sources.iter_mut().zip(eaters) {
+            source.remaining -= eaters as f32 * consumption * time_scale;
+        }
+        self.sources.retain(|source| source.remaining > 0.0);
+
+        // Drawn every step, so that a cap reached doesn't shift the later sources.
+        let spawns = self
+            .rng
+            .gen_bool((spawn_rate * time_scale / 1000.0).min(1.0) as f64);
+        if spawns && self.sources.len() < max_sources {
+            self.spawn();
+        }
+    }
+
+    /// Marks the cells within the radius of every source, with later sources covering the
+    /// overlaps.
+    fn cover(&mut self) {
+        self.owner.fill(NO_SOURCE);
+        for (k, source) in self.sources.iter().enumerate() {
+            let disc = disc(
+                self.width,
+                self.height,
+                source.x,
+                source.y,
+                self.food.radius,
+            );
+            for cell in disc {
+                self.owner[cell] = k as u32;
+            }
+        }
+    }
+}
+
+/// The cells whose centers are within `radius` of (x, y), wrapping around the edges.
+pub(super) fn disc(
+    width: usize,
+    height: usize,
+    x: f32,
+    y: f32,
+    radius: f32,
+) -> impl Iterator<Item = usize> {
+    let reach = radius.ceil() as isize;
+    let (cx, cy) = (x.floor() as isize, y.floor() as isize);
+    (-reach..=reach)
+        .flat_map(move |dy| (-reach..=reach).map(move |dx| (cx + dx, cy + dy)))
+        .filter(move |&(i, j)| {
+            let (ox, oy) = (i as f32 + 0.5 - x, j as f32 + 0.5 - y);
+            ox * ox + oy * oy <= radius * radius
+        })
+        .map(move |(i, j)| {
+            j.rem_euclid(height as isize) as usize * width + i.rem_euclid(width as isize) as usize
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+    use crate::population_config::PopulationConfig;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    #[test]
+    fn sources_scent_the_grids_and_deplete_under_agents() {
+        let (width, height) = (32, 32);
+        let food = Food {
+            spawn_rate: 0.0,
+            initial: 1,
+            capacity: 10.0,
+            consumption: 1.0,
+            ..Food::default()
+        };
+        let mut pantry = Pantry::new(food, width, height);
+        let source = pantry.sources[0];
+        let config = PopulationConfig::new(&mut SmallRng::seed_from_u64(0));
+        let mut grids =
+            vec![Grid::from_data(width, height, config, vec![0.0; width * height]).unwrap()];
+        let mut agents = Agents::new();
+        for _ in 0..4 {
+            agents.push(Particle {
+                x: source.x,
+                y: source.y,
+                angle: 0.0,
+                id: 0,
+            });
+        }
+
+        pantry.step(&mut grids, &agents, 1.0);
+        let center = grid::cell(width, height, source.x, source.y);
+        assert_eq!(grids[0].data()[center], food.scent);
+        let far = grid::cell(width, height, source.x + 16.0, source.y + 16.0);
+        assert_eq!(grids[0].data()[far], 0.0);
+        assert_eq!(pantry.sources[0].remaining, 6.0);
+
+        pantry.step(&mut grids, &agents, 1.0);
+        assert_eq!(grids[0].data()[center], food.scent * 1.6);
+        pantry.step(&mut grids, &agents, 1.0);
+        assert!(pantry.sources.is_empty());
+
+        let mut spawning = Pantry::new(
+            Food {
+                spawn_rate: 1000.0,
+                initial: 0,
+                max_sources: 3,
+                ..food
+            },
+            width,
+            height,
+        );
+        for _ in 0..5 {
+            spawning.step(&mut grids, &Agents::new(), 1.0);
+        }
+        assert_eq!(spawning.sources.len(), 3);
+        assert!(Food {
+            radius: 0.0,
+            ..food
+        }
+        .check()
+        .is_err());
+    }
+}
@@ -0,0 +1,469 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+use super::bandpass::Bandpass;
+use super::blur::Blur;
+use super::error::ConfigError;
+use super::operators::Trail;
+use super::par::*;
+use super::population_config::PopulationConfig;
+use super::util;
+use rand::Rng;
+
+use rand::distributions::Uniform;
+use rand::rngs::SmallRng;
+
+pub struct Grid {
+    pub config: PopulationConfig,
+    pub width: usize,
+    pub height: usize,
+    data: Vec<f32>,
+    buf: Vec<f32>,
+    /// The band-passed trails, then the own trails as sensed with self-repulsion, each only
+    /// allocated once `combine` needs them.
+    band: Vec<f32>,
+    blur: Blur,
+}
+
+/// Cells in the grids of all populations together, 8 GiB with their blur buffers.
+pub const MAX_CELLS: usize = 1 << 30;
+/// Agents of all populations together, 4 GiB of them.
+pub const MAX_PARTICLES: usize = 1 << 28;
+
+pub(super) fn check_dimensions(width: usize, height: usize) -> Result<(), ConfigError> {
+    if !width.is_power_of_two() || !height.is_power_of_two() {
+        return Err(ConfigError::InvalidDimensions { width, height });
+    }
+    check_capacity(width, height, 1, 0)
+}
+
+/// Rejects simulations too large to allocate, before the allocation aborts the process.
+pub(super) fn check_capacity(
+    width: usize,
+    height: usize,
+    populations: usize,
+    particles: usize,
+) -> Result<(), ConfigError> {
+    let cells = width.saturating_mul(height).saturating_mul(populations);
+    if cells > MAX_CELLS {
+        return Err(ConfigError::TooManyCells { cells });
+    }
+    if particles > MAX_PARTICLES {
+        return Err(ConfigError::TooManyParticles { particles });
+    }
+    Ok(())
+}
+
+/// The box blur wraps around the grid at most once, so its boxes have to fit inside.
+pub(super) fn blur_fits(width: usize, height: usize, sigma: f32) -> bool {
+    let side = width.min(height);
+    sigma >= 0.0
+        && (sigma as usize) < side
+        && Blur::boxes_for_gaussian::<2>(sigma)
+            .iter()
+            .all(|&radius| radius < side)
+}
+
+pub(super) fn check_diffusity(
+    width: usize,
+    height: usize,
+    diffusity: usize,
+) -> Result<(), ConfigError> {
+    if blur_fits(width, height, diffusity as f32) {
+        Ok(())
+    } else {
+        Err(ConfigError::DiffusivityTooLarge {
+            diffusivity: diffusity,
+            width,
+            height,
+        })
+    }
+}
+
+/// Self-repulsion has to be finite with its attraction range fitting inside the grid, and
+/// erosion can't take more than the whole trail.
+pub(super) fn check_population(
+    width: usize,
+    height: usize,
+    config: &PopulationConfig,
+) -> Result<(), ConfigError> {
+    if !config.self_repulsion.is_finite() || !blur_fits(width, height, config.self_attraction_range)
+    {
+        return Err(ConfigError::SelfAvoidance);
+    }
+    if !(0.0..=1.0).contains(&config.erosion) {
+        return Err(ConfigError::Erosion);
+    }
+    Ok(())
+}
+
+impl Grid {
+    pub fn new(
+        width: usize,
+        height: usize,
+        config: PopulationConfig,
+        rng: &mut SmallRng,
+    ) -> Result<Self, ConfigError> {
+        check_dimensions(width, height)?;
+        let range = Uniform::from(0.0..1.0);
+        let data = rng.sample_iter(range).take(width * height).collect();
+        Self::from_data(width, height, config, data)
+    }
+
+    pub fn from_data(
+        width: usize,
+        height: usize,
+        config: PopulationConfig,
+        data: Vec<f32>,
+    ) -> Result<Self, ConfigError> {
+        check_dimensions(width, height)?;
+        if data.len() != width * height {
+            return Err(ConfigError::DataLength {
+                expected: width * height,
+                actual: data.len(),
+            });
+        }
+
+        Ok(Grid {
+            width,
+            height,
+            data,
+            config,
+            buf: vec![0.0; width * height],
+            band: vec![],
+            blur: Blur::new(width),
+        })
+    }
+
+    fn index(&self, x: f32, y: f32) -> usize {
+        cell(self.width, self.height, x, y)
+    }
+
+    pub fn get_buf(&self, x: f32, y: f32) -> f32 {
+        self.buf[self.index(x, y)]
+    }
+
+    /// Adds `amount` to the trail at (x, y), normally the config's `deposition_amount`.
+    pub fn deposit(&mut self, x: f32, y: f32, amount: f32) {
+        let idx = self.index(x, y);
+        self.data[idx] += amount;
+    }
+
+    /// Consumes `rate` of the trail at (x, y).
+    pub fn erode(&mut self, x: f32, y: f32, rate: f32) {
+        let idx = self.index(x, y);
+        self.data[idx] *= 1.0 - rate;
+    }
+
+    /// Adds `amount` of trail at (x, y), negative amounts erasing it down to nothing.
+    pub fn mark(&mut self, x: f32, y: f32, amount: f32) {
+        let idx = self.index(x, y);
+        self.data[idx] = (self.data[idx] + amount).max(0.0);
+    }
+
+    /// Blurs the trails and multiplies them by `decay`, the config's `decay_factor` scaled
+    /// to the time step.
+    pub fn diffuse(&mut self, radius: usize, decay: f32) {
+        self.blur.run(
+            &mut self.data,
+            &mut self.buf,
+            self.width,
+            self.height,
+            radius as f32,
+            decay,
+        );
+    }
+
+    /// Band-passes the trails into the front half of `band`, blurring copies of them with
+    /// `buf` as scratch space.
+    fn band_pass(&mut self, bandpass: Bandpass) {
+        let cells = self.data.len();
+        self.band.resize(2 * cells, 0.0);
+        let (narrow, wide) = self.band.split_at_mut(cells);
+        narrow.copy_from_slice(&self.data);
+        self.blur.run(
+            narrow,
+            &mut self.buf,
+            self.width,
+            self.height,
+            bandpass.inner,
+            1.0,
+        );
+        wide.copy_from_slice(&self.data);
+        self.blur.run(
+            wide,
+            &mut self.buf,
+            self.width,
+            self.height,
+            bandpass.outer,
+            1.0,
+        );
+        narrow
+            .par_iter_mut()
+            .zip(wide.par_iter())
+            .for_each(|(narrow, wide)| *narrow -= wide);
+    }
+
+    /// Stores the own trails blurred over `self_attraction_range`, weighted by
+    /// `attraction`, minus the unblurred trails weighted by `self_repulsion` in the back
+    /// half of `band`. Has to follow `band_pass`, which uses that half as scratch space.
+    fn self_avoidance(&mut self, attraction: f32) {
+        let cells = self.data.len();
+        self.band.resize(2 * cells, 0.0);
+        let own = &mut self.band[cells..];
+        own.copy_from_slice(&self.data);
+        self.blur.run(
+            own,
+            &mut self.buf,
+            self.width,
+            self.height,
+            self.config.self_attraction_range,
+            1.0,
+        );
+        let repulsion = self.config.self_repulsion;
+        own.par_iter_mut()
+            .zip(self.data.par_iter())
+            .for_each(|(own, data)| *own = attraction * *own - repulsion * data);
+    }
+
+    pub fn quantile(&self, fraction: f32) -> f32 {
+        util::quantile(&self.data, fraction)
+    }
+
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub(super) fn data_mut(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+
+    /// The trails for a `DepositOp` or `DiffuseOp`, with `buf` as their scratch space.
+    pub(crate) fn trail(&mut self) -> Trail<'_> {
+        Trail::new(
+            self.width,
+            self.height,
+            &mut self.data,
+            &mut self.buf,
+            &mut self.blur,
+        )
+    }
+}
+
+impl Display for PopulationConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{{\n  Sensor Distance: {},\n  Step Distance: {},\n  Sensor Angle: {},\n  Rotation Angle: {},\n  Decay Factor: {},\n  Deposition Amount: {},\n  Bias Angle: {},\n  Bias Strength: {},\n  Self Repulsion: {},\n  Self Attraction Range: {},\n  Erosion: {},\n  Max Substep: {},\n}}",
+            self.sensor_distance,
+            self.step_distance,
+            self.sensor_angle,
+            self.rotation_angle,
+            self.decay_factor,
+            self.deposition_amount,
+            self.bias_angle,
+            self.bias_strength,
+            self.self_repulsion,
+            self.self_attraction_range,
+            self.erosion,
+            self.max_substep
+        )
+    }
+}
+
+/// The index of the cell containing (x, y) in a grid of `width` by `height`, wrapping
+/// around the edges any number of times. NaN falls in the first cell and infinities in an
+/// edge cell instead of panicking.
+#[inline(always)]
+pub(super) fn cell(width: usize, height: usize, x: f32, y: f32) -> usize {
+    let i = floor(x) & (width - 1) as i64;
+    let j = floor(y) & (height - 1) as i64;
+    j as usize * width + i as usize
+}
+
+/// Rounds towards negative infinity, saturating like `as` for values out of range.
+#[inline(always)]
+pub(super) fn floor(x: f32) -> i64 {
+    let truncated = x as i64;
+    truncated.wrapping_sub((x < truncated as f32) as i64)
+}
+
+/// Sums the trails of all populations weighted by `attraction_table` into each grid's `buf`.
+/// With a `bandpass`, the trails of the other populations are band-passed first, and
+/// populations with a `self_repulsion` sense their own through `Grid::self_avoidance`.
+pub fn combine<T>(grids: &mut [Grid], attraction_table: &[T], bandpass: Option<Bandpass>)
+where
+    T: AsRef<[f32]> + Sync,
+{
+    if let Some(bandpass) = bandpass {
+        grids
+            .par_iter_mut()
+            .for_each(|grid| grid.band_pass(bandpass));
+    }
+    grids.par_iter_mut().enumerate().for_each(|(i, grid)| {
+        if grid.config.self_repulsion != 0.0 {
+            grid.self_avoidance(attraction_table[i].as_ref()[i]);
+        }
+    });
+    // The sums go into buffers taken out of the grids, so that each can be written on a
+    // thread of its own while every grid is read.
+    let mut bufs: Vec<Vec<f32>> = grids
+        .iter_mut()
+        .map(|grid| core::mem::take(&mut grid.buf))
+        .collect();
+    let sources = &*grids;
+    bufs.par_iter_mut().enumerate().for_each(|(i, buf)| {
+        buf.fill(0.0);
+        for (j, grid) in sources.iter().enumerate() {
+            let cells = grid.data.len();
+            let (other, multiplier) = if i != j {
+                let other = match bandpass {
+                    Some(_) => &grid.band[..cells],
+                    None => &grid.data[..],
+                };
+                (other, attraction_table[i].as_ref()[j])
+            } else if grid.config.self_repulsion != 0.0 {
+                (&grid.band[cells..], 1.0)
+            } else {
+                (&grid.data[..], attraction_table[i].as_ref()[j])
+            };
+            buf.par_iter_mut()
+                .zip(other.par_iter())
+                .for_each(|(to, from)| *to += from * multiplier);
+        }
+    });
+    for (grid, buf) in grids.iter_mut().zip(bufs) {
+        grid.buf = buf;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+
+    fn grid(width: usize, height: usize) -> Grid {
+        let config = PopulationConfig::new(&mut SmallRng::seed_from_u64(0));
+        Grid::from_data(width, height, config, vec![0.0; width * height]).unwrap()
+    }
+
+    #[test]
+    fn index_inside() {
+        let grid = grid(8, 4);
+        assert_eq!(grid.index(0.0, 0.0), 0);
+        assert_eq!(grid.index(3.7, 0.0), 3);
+        assert_eq!(grid.index(0.0, 2.5), 16);
+        assert_eq!(grid.index(7.999, 3.999), 31);
+    }
+
+    #[test]
+    fn index_negative() {
+        let grid = grid(8, 4);
+        assert_eq!(grid.index(-0.5, 0.0), 7);
+        assert_eq!(grid.index(-1e-6, 0.0), 7);
+        assert_eq!(grid.index(-8.0, 0.0), 0);
+        assert_eq!(grid.index(-8.5, 0.0), 7);
+        assert_eq!(grid.index(-20.0, -5.0), 3 * 8 + 4);
+    }
+
+    #[test]
+    fn index_beyond_one_period() {
+        let grid = grid(8, 4);
+        assert_eq!(grid.index(8.0, 4.0), 0);
+        assert_eq!(grid.index(17.5, 0.0), 1);
+        assert_eq!(grid.index(0.0, 4_000_001.0), 8);
+    }
+
+    #[test]
+    fn index_extreme_floats() {
+        let grid = grid(8, 4);
+        for x in [
+            f32::MAX,
+            f32::MIN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NAN,
+        ] {
+            assert!(grid.index(x, x) < 32);
+        }
+        assert_eq!(grid.index(f32::NAN, f32::NAN), 0);
+    }
+
+    #[test]
+    fn deposit_wraps() {
+        let mut grid = grid(8, 4);
+        grid.deposit(-0.5, -0.5, grid.config.deposition_amount);
+        assert_eq!(grid.data()[31], grid.config.deposition_amount);
+    }
+
+    #[test]
+    fn mark_erases_down_to_zero() {
+        let mut grid = grid(8, 4);
+        grid.mark(1.0, 1.0, 3.0);
+        grid.mark(1.0, 1.0, -2.0);
+        assert_eq!(grid.data()[9], 1.0);
+        grid.mark(1.0, 1.0, -2.0);
+        assert_eq!(grid.data()[9], 0.0);
+    }
+
+    #[test]
+    fn bandpass_ignores_uniform_neighbours() {
+        let config = PopulationConfig::new(&mut SmallRng::seed_from_u64(0));
+        let mut grids = [
+            Grid::from_data(16, 16, config, vec![2.0; 256]).unwrap(),
+            Grid::from_data(16, 16, config, vec![3.0; 256]).unwrap(),
+        ];
+        let table = [[1.0, -1.0], [-1.0, 1.0]];
+        combine(&mut grids, &table, None);
+        assert!(grids[0].buf.iter().all(|&v| (v - -1.0).abs() < 1e-5));
+
+        let bandpass = Bandpass {
+            inner: 1.0,
+            outer: 3.0,
+        };
+        combine(&mut grids, &table, Some(bandpass));
+        assert!(grids[0].buf.iter().all(|&v| (v - 2.0).abs() < 1e-5));
+        assert!(grids[1].buf.iter().all(|&v| (v - 3.0).abs() < 1e-5));
+    }
+
+    #[test]
+    fn self_repulsion_offsets_own_attraction() {
+        let mut config = PopulationConfig::new(&mut SmallRng::seed_from_u64(0));
+        config.self_repulsion = 0.75;
+        let mut grids = [Grid::from_data(16, 16, config, vec![2.0; 256]).unwrap()];
+        combine(&mut grids, &[[1.0]], None);
+        assert!(grids[0].buf.iter().all(|&v| (v - 0.5).abs() < 1e-5));
+
+        grids[0].config.self_attraction_range = 16.0;
+        assert_eq!(
+            check_population(16, 16, &grids[0].config),
+            Err(ConfigError::SelfAvoidance)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn index_in_bounds(x in any::<f32>(), y in any::<f32>()) {
+            prop_assert!(grid(16, 8).index(x, y) < 16 * 8);
+        }
+
+        #[test]
+        fn index_is_periodic(x in -1e4_f32..1e4, y in -1e4_f32..1e4, k in -100_i32..100) {
+            let grid = grid(16, 8);
+            // Quarter cells keep the shifted coordinates exact.
+            let (x, y) = ((x * 4.0).round() / 4.0, (y * 4.0).round() / 4.0);
+            let (dx, dy) = (16.0 * k as f32, 8.0 * k as f32);
+            prop_assert_eq!(grid.index(x + dx, y + dy), grid.index(x, y));
+        }
+
+        #[test]
+        fn index_matches_floor(x in -1e4_f32..1e4, y in -1e4_f32..1e4) {
+            let grid = grid(16, 8);
+            let i = (x.floor() as i64).rem_euclid(16) as usize;
+            let j = (y.floor() as i64).rem_euclid(8) as usize;
+            prop_assert_eq!(grid.index(x, y), j * 16 + i);
+        }
+    }
+}
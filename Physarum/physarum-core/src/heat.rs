@@ -0,0 +1,232 @@
+use super::blur::Blur;
+use super::error::ConfigError;
+use super::food;
+use super::grid::{self, Grid};
+use super::noise::Fbm;
+use super::par::*;
+use alloc::vec;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+/// A temperature over the grids that scales how fast the trails decay under it: they
+/// evaporate faster in hot regions and linger in cold ones, laying out regions of dense and
+/// sparse network larger than the agents could on their own. The heat spreads, drifts and
+/// relaxes back to its pattern, and can be painted on with `PhysarumModel::paint_heat`.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct Heat {
+    /// Noise the heat starts as and relaxes back to, from 0 (cold) to 1 (hot). `None`
+    /// keeps it at a neutral 0.5 everywhere, to paint on.
+    pub noise: Option<Fbm>,
+    /// How much the heat changes the decay: a cell at heat h loses `1 + strength (2h - 1)`
+    /// times the trails it loses without heat, so 1 makes the hottest cells lose twice as
+    /// much and the coldest none.
+    pub strength: f32,
+    /// Deviation in cells of the blur the heat spreads by each step, 0 to keep it in place.
+    pub diffusion: f32,
+    /// Fraction of the way back to its pattern the heat goes each step, from 0 to 1, so that
+    /// painted heat fades and the heat follows a drifting pattern.
+    pub relaxation: f32,
+    /// Cells per step the pattern moves along x, wrapping around.
+    pub drift: f32,
+}
+
+impl Default for Heat {
+    fn default() -> Self {
+        Heat {
+            noise: Some(Fbm::new(128.0, 3, 0)),
+            strength: 0.5,
+            diffusion: 1.0,
+            relaxation: 0.01,
+            drift: 0.0,
+        }
+    }
+}
+
+impl Heat {
+    pub(super) fn check(&self, width: usize, height: usize) -> Result<(), ConfigError> {
+        let noise = self.noise.is_none_or(|fbm| {
+            let valid = |size: f32| size > 0.0 && size.is_finite();
+            valid(fbm.scale) && fbm.octaves >= 1 && valid(fbm.lacunarity) && valid(fbm.gain)
+        });
+        if noise
+            && self.strength >= 0.0
+            && self.strength.is_finite()
+            && grid::blur_fits(width, height, self.diffusion)
+            && (0.0..=1.0).contains(&self.relaxation)
+            && self.drift.is_finite()
+        {
+            Ok(())
+        } else {
+            Err(ConfigError::Heat)
+        }
+    }
+}
+
+/// The heat of a `Heat` over a grid, cell by cell.
+pub(super) struct HeatField {
+    pub(super) heat: Heat,
+    /// The pattern before it drifted, `None` for the neutral one.
+    pattern: Option<Vec<f32>>,
+    pub(super) field: Vec<f32>,
+    width: usize,
+    height: usize,
+    buf: Vec<f32>,
+}
+
+impl HeatField {
+    pub(super) fn new(heat: Heat, width: usize, height: usize) -> Self {
+        let pattern = heat.noise.map(|fbm| {
+            let mut noise = fbm.field(width, height);
+            // The noise stays within about ±1.
+            noise
+                .par_iter_mut()
+                .for_each(|v| *v = ((*v + 1.0) / 2.0).clamp(0.0, 1.0));
+            noise
+        });
+        let mut field = HeatField {
+            heat,
+            pattern,
+            field: vec![],
+            width,
+            height,
+            buf: vec![0.0; width * height],
+        };
+        field.field = field.pattern_at(0);
+        field
+    }
+
+    /// The pattern at step `iteration`, moved `drift` cells along x per step and
+    /// interpolated between the cells it falls between.
+    fn pattern_at(&self, iteration: u64) -> Vec<f32> {
+        let Some(pattern) = &self.pattern else {
+            return vec![0.5; self.width * self.height];
+        };
+        let width = self.width;
+        let shift = libm::fmod(self.heat.drift as f64 * iteration as f64, width as f64) as f32;
+        let whole = libm::floorf(shift);
+        let (offset, t) = (whole as i64, shift - whole);
+        (0..width * self.height)
+            .into_par_iter()
+            .map(|i| {
+                let (x, row) = ((i % width) as i64, i - i % width);
+                let at =
+                    |dx: i64| pattern[row + (x - offset - dx).rem_euclid(width as i64) as usize];
+                at(0) * (1.0 - t) + at(1) * t
+            })
+            .collect()
+    }
+
+    /// Spreads the heat and relaxes it toward the pattern of step `iteration`, for a step
+    /// covering `time_scale` steps.
+    pub(super) fn step(&mut self, iteration: u64, time_scale: f32) {
+        let (width, height) = (self.width, self.height);
+        if self.heat.diffusion > 0.0 {
+            let scaled = self.heat.diffusion * libm::sqrtf(time_scale);
+            let sigma = if grid::blur_fits(width, height, scaled) {
+                scaled
+            } else {
+                self.heat.diffusion
+            };
+            Blur::new(width).run(&mut self.field, &mut self.buf, width, height, sigma, 1.0);
+        }
+        if self.heat.relaxation > 0.0 {
+            let keep = libm::powf(1.0 - self.heat.relaxation, time_scale);
+            let pattern = self.pattern_at(iteration);
+            self.field
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(i, h)| *h = pattern[i] + (*h - pattern[i]) * keep);
+        }
+    }
+
+    /// Adds `amount` to the heat of the cells within `radius` of (x, y), keeping it
+    /// between 0 and 1.
+    pub(super) fn paint(&mut self, x: f32, y: f32, radius: f32, amount: f32) {
+        for cell in food::disc(self.width, self.height, x, y, radius) {
+            self.field[cell] = (self.field[cell] + amount).clamp(0.0, 1.0);
+        }
+    }
+
+    /// Makes the trails of every grid lose as much as the heat under them has them lose
+    /// after the step decayed them, for a step covering `time_scale` steps.
+    pub(super) fn cool(&self, grids: &mut [Grid], time_scale: f32) {
+        let strength = self.heat.strength;
+        for grid in grids {
+            let decay = libm::powf(grid.config.decay_factor, time_scale);
+            if decay <= 0.0 || decay >= 1.0 {
+                continue;
+            }
+            let field = &self.field;
+            grid.data_mut()
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(i, v)| {
+                    let loss = (1.0 - decay) * (1.0 + strength * (2.0 * field[i] - 1.0)).max(0.0);
+                    *v *= (1.0 - loss).max(0.0) / decay;
+                });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::population_config::PopulationConfig;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    #[test]
+    fn trails_linger_where_it_is_cold() {
+        let heat = Heat {
+            noise: None,
+            strength: 1.0,
+            diffusion: 0.0,
+            relaxation: 0.5,
+            drift: 0.0,
+        };
+        let mut field = HeatField::new(heat, 8, 8);
+        assert!(field.field.iter().all(|&h| h == 0.5));
+        field.paint(0.5, 0.5, 0.5, 1.0);
+        field.paint(4.5, 4.5, 0.5, -1.0);
+        assert_eq!((field.field[0], field.field[36]), (1.0, 0.0));
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let config = PopulationConfig {
+            decay_factor: 0.8,
+            ..PopulationConfig::new(&mut rng)
+        };
+        let mut grids = vec![Grid::new(8, 8, config, &mut rng).unwrap()];
+        // The trails as the step's decay left them.
+        grids[0].data_mut().fill(0.8);
+        field.cool(&mut grids, 1.0);
+        let trails = grids[0].data();
+        // Twice the loss where it is hot, none where it is cold, the usual one elsewhere.
+        assert!((trails[0] - 0.6).abs() < 1e-6);
+        assert!((trails[36] - 1.0).abs() < 1e-6);
+        assert!((trails[1] - 0.8).abs() < 1e-6);
+
+        field.step(1, 1.0);
+        assert_eq!((field.field[0], field.field[36]), (0.75, 0.25));
+
+        // A drifting pattern moves whole cells at whole steps.
+        let drifting = HeatField::new(
+            Heat {
+                noise: Some(Fbm::new(4.0, 1, 0)),
+                drift: 1.0,
+                ..heat
+            },
+            8,
+            8,
+        );
+        let moved = drifting.pattern_at(3);
+        assert_eq!(moved[3], drifting.field[0]);
+        assert_eq!(moved[2], drifting.field[7]);
+
+        assert!(Heat::default().check(64, 64).is_ok());
+        let hot = Heat {
+            relaxation: 2.0,
+            ..heat
+        };
+        assert_eq!(hot.check(8, 8), Err(ConfigError::Heat));
+    }
+}
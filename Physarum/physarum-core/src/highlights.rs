@@ -0,0 +1,330 @@
+//! Moments of a run worth cutting to, found among its frames: spikes in how much the
+//! trails change from one frame to the next, and the network gaining or losing loops.
+//! Written as FFmpeg chapters and a cut list to edit the video of the frames with.
+
+use super::network::Network;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// When a frame counts as a highlight.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct HighlightSettings {
+    /// Standard deviations above the average of the frames before that a frame's change
+    /// in the trails, or in the loops of the network, has to reach to be flagged.
+    pub threshold: f32,
+    /// Frames observed before any highlight is flagged, while the averages settle.
+    pub warmup: usize,
+    /// Loops the network has to gain or lose since the last frame, at least, to flag it,
+    /// however steady the loops were before.
+    pub min_loop_change: usize,
+}
+
+impl Default for HighlightSettings {
+    fn default() -> Self {
+        HighlightSettings {
+            threshold: 3.0,
+            warmup: 5,
+            min_loop_change: 3,
+        }
+    }
+}
+
+/// Why a frame is a highlight.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum HighlightReason {
+    /// The trails changed by `score` standard deviations more than they usually do.
+    Spike,
+    /// The network gained `score` loops, or lost them when negative, far more than it
+    /// usually does.
+    Loops,
+}
+
+/// A highlight at the frame with index `frame`, showing step `step`.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Highlight {
+    pub frame: usize,
+    pub step: u64,
+    pub reason: HighlightReason,
+    pub score: f32,
+}
+
+impl Highlight {
+    fn title(&self) -> String {
+        match self.reason {
+            HighlightReason::Spike => {
+                format!("Step {}: change {:.1} sigma", self.step, self.score)
+            }
+            HighlightReason::Loops if self.score >= 0.0 => {
+                format!("Step {}: {} loops formed", self.step, self.score)
+            }
+            HighlightReason::Loops => format!("Step {}: {} loops broke", self.step, -self.score),
+        }
+    }
+}
+
+/// The mean and spread of a series, updated by Welford's method.
+#[derive(Default)]
+struct Spread {
+    count: usize,
+    mean: f64,
+    /// Sum of the squared deviations from the mean.
+    m2: f64,
+}
+
+impl Spread {
+    /// Standard deviations `value` lies above the mean of the values before, once there
+    /// are `warmup` of them, and adds it to them.
+    fn score(&mut self, value: f64, warmup: usize) -> Option<f32> {
+        let score = (self.count >= warmup.max(2)).then(|| {
+            let deviation = (self.m2 / (self.count - 1) as f64).sqrt().max(1e-9);
+            ((value - self.mean) / deviation) as f32
+        });
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        score
+    }
+}
+
+/// Looks for highlights in the frames it observes, in order.
+pub struct HighlightDetector {
+    settings: HighlightSettings,
+    previous: Option<Vec<f32>>,
+    changes: Spread,
+    loops: Option<usize>,
+    loop_changes: Spread,
+    highlights: Vec<Highlight>,
+}
+
+impl HighlightDetector {
+    pub fn new(settings: HighlightSettings) -> Self {
+        HighlightDetector {
+            settings,
+            previous: None,
+            changes: Spread::default(),
+            loops: None,
+            loop_changes: Spread::default(),
+            highlights: vec![],
+        }
+    }
+
+    /// Observes the frame with index `frame` at step `step`, with `field` the trails it
+    /// shows, e.g. `PhysarumModel::combined_field`, and the network they form if its
+    /// topology should count. Returns the highlights the frame has.
+    pub fn observe(
+        &mut self,
+        frame: usize,
+        step: u64,
+        field: &[f32],
+        network: Option<&Network>,
+    ) -> &[Highlight] {
+        let found = self.highlights.len();
+        let HighlightSettings {
+            threshold,
+            warmup,
+            min_loop_change,
+        } = self.settings;
+        let highlight = |reason, score| Highlight {
+            frame,
+            step,
+            reason,
+            score,
+        };
+        if let Some(previous) = &self.previous {
+            let scale = field
+                .iter()
+                .map(|&v| v.abs() as f64)
+                .sum::<f64>()
+                .max(f64::EPSILON);
+            let change = previous
+                .iter()
+                .zip(field)
+                .map(|(a, b)| (b - a).abs() as f64)
+                .sum::<f64>()
+                / scale;
+            match self.changes.score(change, warmup) {
+                Some(score) if score > threshold => self
+                    .highlights
+                    .push(highlight(HighlightReason::Spike, score)),
+                _ => {}
+            }
+        }
+        self.previous = Some(field.to_vec());
+
+        if let Some(network) = network {
+            let loops = loops(network);
+            if let Some(before) = self.loops {
+                let change = loops as f32 - before as f32;
+                let score = self.loop_changes.score(change.abs() as f64, warmup);
+                let enough = change.abs() >= (min_loop_change as f32).max(1.0);
+                if enough && score.is_some_and(|score| score > threshold) {
+                    self.highlights
+                        .push(highlight(HighlightReason::Loops, change));
+                }
+            }
+            self.loops = Some(loops);
+        }
+        &self.highlights[found..]
+    }
+
+    pub fn highlights(&self) -> &[Highlight] {
+        &self.highlights
+    }
+
+    /// FFmpeg metadata with a chapter from every highlight to the next, after one from the
+    /// start, for a video of `frames` frames at `fps`. Highlights of the same frame share
+    /// a chapter.
+    pub fn chapters(&self, frames: usize, fps: f32) -> String {
+        let millis = |frame: usize| (frame as f64 * 1000.0 / fps as f64).round() as u64;
+        let mut starts: Vec<(usize, String)> = vec![(0, "Start".to_string())];
+        for highlight in &self.highlights {
+            match starts.last_mut() {
+                Some((frame, title)) if *frame == highlight.frame => {
+                    if *frame > 0 || title != "Start" {
+                        title.push_str(", ");
+                        title.push_str(&highlight.title());
+                    } else {
+                        *title = highlight.title();
+                    }
+                }
+                _ => starts.push((highlight.frame, highlight.title())),
+            }
+        }
+        let mut metadata = String::from(";FFMETADATA1\n");
+        for (k, (frame, title)) in starts.iter().enumerate() {
+            let end = starts.get(k + 1).map_or(frames, |(next, _)| *next);
+            let title = title.replace(['=', ';', '#', '\\', '\n'], " ");
+            let _ = write!(
+                metadata,
+                "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+                millis(*frame),
+                millis(end.max(*frame)),
+                title
+            );
+        }
+        metadata
+    }
+
+    /// A row with the frame, step, time in seconds at `fps`, reason and score of every
+    /// highlight, after a header.
+    pub fn cut_list(&self, fps: f32) -> String {
+        let mut csv = String::from("frame,step,seconds,reason,score\n");
+        for highlight in &self.highlights {
+            let reason = match highlight.reason {
+                HighlightReason::Spike => "spike",
+                HighlightReason::Loops => "loops",
+            };
+            let _ = writeln!(
+                csv,
+                "{},{},{:.3},{},{:.2}",
+                highlight.frame,
+                highlight.step,
+                highlight.frame as f32 / fps,
+                reason,
+                highlight.score
+            );
+        }
+        csv
+    }
+
+    /// Writes `chapters.txt` and `cuts.csv` to `dir`.
+    pub fn save(&self, dir: &Path, frames: usize, fps: f32) -> io::Result<()> {
+        fs::write(dir.join("chapters.txt"), self.chapters(frames, fps))?;
+        fs::write(dir.join("cuts.csv"), self.cut_list(fps))
+    }
+}
+
+/// The independent loops of the network, its edges less its nodes plus its pieces.
+fn loops(network: &Network) -> usize {
+    let mut parent: Vec<usize> = (0..network.nodes.len()).collect();
+    fn root(parent: &mut [usize], mut node: usize) -> usize {
+        while parent[node] != node {
+            parent[node] = parent[parent[node]];
+            node = parent[node];
+        }
+        node
+    }
+    let mut pieces = network.nodes.len();
+    for edge in &network.edges {
+        let (a, b) = (
+            root(&mut parent, edge.source),
+            root(&mut parent, edge.target),
+        );
+        if a != b {
+            parent[a] = b;
+            pieces -= 1;
+        }
+    }
+    (network.edges.len() + pieces).saturating_sub(network.nodes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{Edge, Node};
+
+    #[test]
+    fn spikes_and_loops_become_chapters() {
+        let mut detector = HighlightDetector::new(HighlightSettings::default());
+        let mut field = vec![1.0; 100];
+        for frame in 0..10 {
+            // Small steady changes, then a jump at frame 8.
+            field[frame] += if frame == 8 {
+                50.0
+            } else {
+                0.1 + 0.01 * frame as f32
+            };
+            let found = detector
+                .observe(frame, frame as u64 * 10, &field, None)
+                .to_vec();
+            assert_eq!(found.len(), usize::from(frame == 8), "frame {}", frame);
+        }
+        assert_eq!(detector.highlights()[0].reason, HighlightReason::Spike);
+
+        let node = |x| Node { x, y: 0.0 };
+        let edge = |source, target| Edge {
+            source,
+            target,
+            length: 1.0,
+            thickness: 1.0,
+        };
+        let mut network = Network {
+            width: 10,
+            height: 10,
+            nodes: (0..6).map(|i| node(i as f32)).collect(),
+            edges: vec![edge(0, 1), edge(1, 2)],
+        };
+        assert_eq!(loops(&network), 0);
+        // A loop comes and goes every frame, then three more form at once.
+        for frame in 10..20 {
+            if frame % 2 == 0 {
+                network.edges.push(edge(2, 0));
+            } else {
+                network.edges.pop();
+            }
+            let found = detector.observe(frame, frame as u64 * 10, &field, Some(&network));
+            assert!(found.is_empty(), "frame {}", frame);
+        }
+        network
+            .edges
+            .extend([edge(2, 0), edge(3, 4), edge(4, 5), edge(5, 3), edge(0, 1)]);
+        assert_eq!(loops(&network), 3);
+        let found = detector.observe(20, 200, &field, Some(&network));
+        assert_eq!(
+            (found[0].reason, found[0].score),
+            (HighlightReason::Loops, 3.0)
+        );
+
+        let chapters = detector.chapters(30, 10.0);
+        assert!(
+            chapters.starts_with(";FFMETADATA1\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=800\n")
+        );
+        assert!(chapters.contains("START=2000\nEND=3000\ntitle=Step 200: 3 loops formed\n"));
+        assert_eq!(detector.cut_list(10.0).lines().count(), 3);
+    }
+}
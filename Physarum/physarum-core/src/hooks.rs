@@ -0,0 +1,117 @@
+use super::physarum_model::{PhysarumModel, RemovedAgents};
+#[cfg(feature = "image-export")]
+use image::DynamicImage;
+#[cfg(feature = "image-export")]
+use std::path::Path;
+
+/// A setting of a `PhysarumModel` that was just changed, passed to the hooks of
+/// `PhysarumModel::on_parameter_change`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parameter {
+    AttractionTable,
+    DepositionTable,
+    PopulationConfigs,
+    /// The number of agents of a population.
+    PopulationSize(usize),
+    /// A population was added after the others, see `PhysarumModel::add_population`.
+    NewPopulation,
+    /// A population was removed, see `PhysarumModel::remove_population`.
+    RemovedPopulation {
+        population: usize,
+        agents: RemovedAgents,
+    },
+    /// A population was merged into another, see `PhysarumModel::merge_populations`.
+    MergedPopulations {
+        from: usize,
+        into: usize,
+    },
+    Bandpass,
+    Sensing,
+    Steering,
+    DepositOps,
+    DiffuseOps,
+    TimeScale,
+    Annealing,
+    Crowding,
+    Spacing,
+    Heat,
+    /// Heat was painted on, see `PhysarumModel::paint_heat`.
+    HeatPainted {
+        x: f32,
+        y: f32,
+        radius: f32,
+        amount: f32,
+    },
+    /// The heat of every cell was replaced, see `PhysarumModel::set_heat_field`.
+    HeatField,
+    Food,
+    RenderSettings,
+    Palette,
+}
+
+/// A frame the model just rendered or wrote, passed to the hooks of
+/// `PhysarumModel::on_frame_exported`.
+#[cfg(feature = "image-export")]
+#[derive(Clone, Copy, Debug)]
+pub enum Export<'a> {
+    /// Rendered by `save_to_image`.
+    Image(&'a DynamicImage),
+    /// Written by `save_ora`.
+    Ora(&'a Path),
+    /// Written by `save_svg`.
+    Svg(&'a Path),
+    /// Written by `save_tiff`.
+    Tiff(&'a Path),
+}
+
+type StepHook = Box<dyn FnMut(&PhysarumModel) + Send>;
+#[cfg(feature = "image-export")]
+type ExportHook = Box<dyn for<'a> FnMut(&PhysarumModel, Export<'a>) + Send>;
+type ParameterHook = Box<dyn FnMut(&PhysarumModel, Parameter) + Send>;
+
+/// The callbacks subscribed to a model's events, called in the order they were added.
+#[derive(Default)]
+pub(crate) struct Hooks {
+    pub step: Vec<StepHook>,
+    #[cfg(feature = "image-export")]
+    pub frame_exported: Vec<ExportHook>,
+    pub parameter_change: Vec<ParameterHook>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "image-export")]
+        if !self.frame_exported.is_empty() {
+            return false;
+        }
+        self.step.is_empty() && self.parameter_change.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn hooks_hear_of_steps_and_changes() {
+        let mut model = PhysarumModel::builder()
+            .size(32, 32)
+            .particles(100)
+            .seed(1)
+            .build()
+            .unwrap();
+        let events = Arc::new(Mutex::new(vec![]));
+        let log = events.clone();
+        model.on_step(move |model| log.lock().unwrap().push(format!("{}", model.iteration())));
+        let log = events.clone();
+        model.on_parameter_change(move |_, parameter| {
+            log.lock().unwrap().push(format!("{:?}", parameter))
+        });
+        model.step();
+        model.set_time_scale(2.0).unwrap();
+        assert!(model.set_time_scale(0.0).is_err());
+        model.step();
+        assert_eq!(*events.lock().unwrap(), ["1", "TimeScale", "2"]);
+    }
+}
@@ -0,0 +1,206 @@
+use super::palette::Palette;
+use image::{imageops, Rgb, RgbImage};
+
+/// Bins of the brightness histograms.
+pub(super) const HISTOGRAM_BINS: usize = 64;
+const BACKGROUND: Rgb<u8> = Rgb([16, 16, 16]);
+const TEXT: Rgb<u8> = Rgb([220, 220, 220]);
+
+/// What is drawn into the strip below a debug export.
+pub(super) struct StripInfo<'a> {
+    /// Text written below the graphics, wrapped at the strip's width and upper-cased.
+    pub lines: Vec<String>,
+    /// Counts of every population's brightness in `HISTOGRAM_BINS` bins, in its color.
+    pub histograms: Vec<(Rgb<u8>, Vec<u32>)>,
+    pub palette: &'a Palette,
+    pub attraction_table: &'a [Vec<f32>],
+}
+
+/// The frame with the strip of `info` below it: the brightness histograms of all
+/// populations overlaid on a log scale, the palette's swatches, the attraction table as
+/// a grid of cells from red for repulsion to green for attraction, and the text. Everything
+/// is scaled up with the frame so that it stays legible on large renders.
+pub(super) fn append(frame: &RgbImage, info: &StripInfo) -> RgbImage {
+    let width = frame.width();
+    let scale = (width / 512).max(1);
+    let margin = 4 * scale;
+    let histogram_height = 32 * scale;
+    let cell = 8 * scale;
+    let n_populations = info.attraction_table.len() as u32;
+    let text = wrap(&info.lines, ((width - 2 * margin) / (4 * scale)) as usize);
+    let swatches_height = cell * n_populations.max(1);
+    let text_top = margin * 3 + histogram_height + swatches_height;
+    let height = text_top + text.len() as u32 * 6 * scale + margin;
+
+    let mut image = RgbImage::from_pixel(width, frame.height() + height, BACKGROUND);
+    imageops::replace(&mut image, frame, 0, 0);
+    let top = frame.height();
+    let rect = |image: &mut RgbImage, x: u32, y: u32, w: u32, h: u32, color: Rgb<u8>| {
+        for py in top + y..(top + y + h).min(image.height()) {
+            for px in x..(x + w).min(width) {
+                image.put_pixel(px, py, color);
+            }
+        }
+    };
+
+    let pitch = (width - 2 * margin) / HISTOGRAM_BINS as u32;
+    for (color, counts) in &info.histograms {
+        let max = counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+        for (bin, &count) in counts.iter().enumerate() {
+            let bar = ((count as f32).ln_1p() / max.ln_1p() * histogram_height as f32) as u32;
+            let x = margin + bin as u32 * pitch;
+            for py in margin + histogram_height - bar..margin + histogram_height {
+                for px in x..x + pitch.saturating_sub(1).max(1) {
+                    let pixel = image.get_pixel_mut(px, top + py);
+                    for (c, add) in pixel.0.iter_mut().zip(color.0) {
+                        *c = c.saturating_add(add);
+                    }
+                }
+            }
+        }
+    }
+
+    let row_top = margin * 2 + histogram_height;
+    for (k, color) in info.palette.colors.iter().enumerate() {
+        let x = margin + k as u32 * (cell + scale);
+        rect(&mut image, x, row_top, cell, cell, *color);
+    }
+    let table_left = margin + 6 * (cell + scale) + margin;
+    for (i, row) in info.attraction_table.iter().enumerate() {
+        for (j, &value) in row.iter().enumerate() {
+            let strength = (value.abs().min(1.0) * 255.0) as u8;
+            let color = if value < 0.0 {
+                Rgb([strength, 0, 0])
+            } else {
+                Rgb([0, strength, 0])
+            };
+            let (x, y) = (table_left + j as u32 * cell, row_top + i as u32 * cell);
+            rect(&mut image, x, y, cell - scale, cell - scale, color);
+        }
+    }
+
+    for (n, line) in text.iter().enumerate() {
+        let y = top + text_top + n as u32 * 6 * scale;
+        for (k, c) in line.chars().enumerate() {
+            let x = margin + k as u32 * 4 * scale;
+            for (dy, bits) in glyph(c).iter().enumerate() {
+                for dx in 0..3 {
+                    if bits & (0b100 >> dx) != 0 {
+                        for (sx, sy) in (0..scale).flat_map(|sx| (0..scale).map(move |sy| (sx, sy)))
+                        {
+                            let (px, py) = (x + dx * scale + sx, y + dy as u32 * scale + sy);
+                            if px < width {
+                                image.put_pixel(px, py, TEXT);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    image
+}
+
+/// Breaks the lines at spaces to fit `columns` characters, upper-casing them for the font.
+fn wrap(lines: &[String], columns: usize) -> Vec<String> {
+    let mut wrapped = vec![];
+    for line in lines {
+        let mut current = String::new();
+        for word in line.to_uppercase().split(' ') {
+            if !current.is_empty() && current.len() + 1 + word.len() > columns {
+                wrapped.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        wrapped.push(current);
+    }
+    wrapped
+}
+
+/// Rows of a 3x5 pixel glyph, the high bit on the left.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b011, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0; 5],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_frame_stays_on_top() {
+        let frame = RgbImage::from_pixel(64, 32, Rgb([1, 2, 3]));
+        let palette = Palette::by_name("dusk").unwrap();
+        let info = StripInfo {
+            lines: vec!["Population 1: sensor 12.5".into()],
+            histograms: vec![(Rgb([255, 0, 0]), vec![1; HISTOGRAM_BINS])],
+            palette: &palette,
+            attraction_table: &[vec![1.0]],
+        };
+        let image = append(&frame, &info);
+        assert_eq!(image.width(), 64);
+        assert!(image.height() > 32);
+        assert!((0..32).all(|y| (0..64).all(|x| image.get_pixel(x, y).0 == [1, 2, 3])));
+    }
+
+    #[test]
+    fn wrap_breaks_at_spaces() {
+        let lines = wrap(&["sensor 12.5 step 1.2".into()], 11);
+        assert_eq!(lines, ["SENSOR 12.5", "STEP 1.2"]);
+    }
+}
@@ -0,0 +1,154 @@
+use super::background;
+use super::error::ConfigError;
+use super::noise::Fbm;
+use super::par::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+use std::io;
+use std::path::PathBuf;
+
+/// What the trail grids hold before the first step. The early structure grows out of it,
+/// so smooth or patterned fields give runs a different start than plain noise. Every
+/// pattern spans [0, 1], like the noise.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InitialTrails {
+    /// Independent uniform noise in every cell.
+    #[default]
+    Uniform,
+    /// Empty grids, leaving the agents to lay every trail.
+    Zeros,
+    /// Fractal Perlin noise with blobs about `scale` cells across, adding `octaves` ever
+    /// finer layers of detail. It tiles with the grids and differs between populations.
+    Noise(Fbm),
+    /// An image's brightness stretched over the grids, so the network grows along it.
+    Image { path: PathBuf },
+    /// Concentric rings around the grid's center, `spacing` cells apart.
+    Rings { spacing: f32 },
+    /// Parallel stripes `spacing` cells apart, along `angle` in radians from +x.
+    Stripes { spacing: f32, angle: f32 },
+}
+
+impl InitialTrails {
+    /// Requires positive, finite sizes.
+    pub(super) fn check(&self) -> Result<(), ConfigError> {
+        let valid = |size: f32| size > 0.0 && size.is_finite();
+        let ok = match self {
+            InitialTrails::Noise(fbm) => {
+                valid(fbm.scale) && fbm.octaves >= 1 && valid(fbm.lacunarity) && valid(fbm.gain)
+            }
+            InitialTrails::Rings { spacing } => valid(*spacing),
+            InitialTrails::Stripes { spacing, angle } => valid(*spacing) && angle.is_finite(),
+            _ => true,
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(ConfigError::InitialTrails)
+        }
+    }
+
+    /// The trails of `population` on a `width` by `height` grid, or `None` for the noise
+    /// the grids start out with anyway.
+    pub fn render(
+        &self,
+        width: usize,
+        height: usize,
+        population: usize,
+    ) -> io::Result<Option<Vec<f32>>> {
+        let cells = move |i: usize| ((i % width) as f32 + 0.5, (i / width) as f32 + 0.5);
+        let trails = match self {
+            InitialTrails::Uniform => return Ok(None),
+            InitialTrails::Zeros => vec![0.0; width * height],
+            InitialTrails::Noise(fbm) => {
+                let seed = fbm.seed.wrapping_add(population as u64);
+                stretched(Fbm { seed, ..*fbm }.field(width, height))
+            }
+            InitialTrails::Image { path } => background::load_brightness(path, width, height)?,
+            InitialTrails::Rings { spacing } => (0..width * height)
+                .into_par_iter()
+                .map(|i| {
+                    let (x, y) = cells(i);
+                    let r = (x - width as f32 * 0.5).hypot(y - height as f32 * 0.5);
+                    0.5 + 0.5 * (TAU * r / spacing).cos()
+                })
+                .collect(),
+            InitialTrails::Stripes { spacing, angle } => {
+                let (dx, dy) = (angle.cos(), angle.sin());
+                (0..width * height)
+                    .into_par_iter()
+                    .map(|i| {
+                        let (x, y) = cells(i);
+                        0.5 + 0.5 * (TAU * (x * dx + y * dy) / spacing).cos()
+                    })
+                    .collect()
+            }
+        };
+        Ok(Some(trails))
+    }
+}
+
+/// `field` stretched to [0, 1].
+fn stretched(mut field: Vec<f32>) -> Vec<f32> {
+    let (min, max) = field
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    let range = (max - min).max(f32::EPSILON);
+    field.par_iter_mut().for_each(|v| *v = (*v - min) / range);
+    field
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_span_the_unit_range_and_noise_tiles() {
+        let (width, height) = (64, 32);
+        let patterns = [
+            InitialTrails::Zeros,
+            InitialTrails::Noise(Fbm::new(16.0, 3, 0)),
+            InitialTrails::Rings { spacing: 8.0 },
+            InitialTrails::Stripes {
+                spacing: 8.0,
+                angle: 0.3,
+            },
+        ];
+        for pattern in &patterns {
+            pattern.check().unwrap();
+            let trails = pattern.render(width, height, 0).unwrap().unwrap();
+            assert_eq!(trails.len(), width * height);
+            assert!(
+                trails.iter().all(|v| (0.0..=1.0).contains(v)),
+                "{:?}",
+                pattern
+            );
+        }
+        assert_eq!(
+            InitialTrails::Uniform.render(width, height, 0).unwrap(),
+            None
+        );
+
+        let noise = |population| {
+            patterns[1]
+                .render(width, height, population)
+                .unwrap()
+                .unwrap()
+        };
+        let field = noise(0);
+        // Neighbours across the wrapped edges are as close as neighbours inside.
+        let step = |a: usize, b: usize| (field[a] - field[b]).abs();
+        let across: f32 = (0..height)
+            .map(|y| step(y * width, y * width + width - 1))
+            .sum();
+        let inside: f32 = (0..height)
+            .map(|y| step(y * width + 31, y * width + 32))
+            .sum();
+        assert!(across < 3.0 * inside + 0.1);
+        assert_ne!(noise(1), field);
+        assert_eq!(noise(0), field);
+
+        let flat = InitialTrails::Noise(Fbm::new(0.0, 1, 0));
+        assert_eq!(flat.check(), Err(ConfigError::InitialTrails));
+    }
+}
@@ -0,0 +1,111 @@
+//! The Physarum simulation without any windowing: agents, trail grids, blurring, palettes
+//! and rendering frames into images.
+//!
+//! Without the default `std` feature the crate is `no_std` and only needs `alloc`. What
+//! remains is `step::Simulation`, the agents and grids without any rendering.
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(dead_code)]
+
+extern crate alloc;
+#[cfg(test)]
+extern crate std;
+
+pub mod agents;
+pub mod annealing;
+#[cfg(feature = "std")]
+pub mod background;
+pub mod bandpass;
+mod blur;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod cities;
+#[cfg(feature = "std")]
+pub mod color_vision;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod contour;
+pub mod crowding;
+#[cfg(feature = "image-export")]
+pub mod difference;
+#[cfg(feature = "std")]
+mod distr;
+#[cfg(feature = "std")]
+pub mod dither;
+#[cfg(feature = "std")]
+pub mod drift;
+#[cfg(feature = "std")]
+pub mod ensemble;
+pub mod error;
+#[cfg(feature = "std")]
+mod flow;
+#[cfg(feature = "std")]
+pub mod food;
+mod grid;
+#[cfg(feature = "std")]
+pub mod heat;
+#[cfg(feature = "std")]
+pub mod highlights;
+#[cfg(feature = "std")]
+pub mod hooks;
+#[cfg(feature = "image-export")]
+mod info_strip;
+#[cfg(feature = "std")]
+pub mod initial;
+#[cfg(feature = "std")]
+pub mod look;
+#[cfg(feature = "std")]
+pub mod lut;
+#[cfg(feature = "std")]
+pub mod mask;
+mod math;
+#[cfg(feature = "std")]
+pub mod matrix;
+#[cfg(feature = "std")]
+pub mod network;
+pub mod noise;
+#[cfg(feature = "std")]
+pub mod normalization;
+pub mod operators;
+#[cfg(feature = "image-export")]
+pub mod ora;
+pub mod palette;
+mod par;
+mod particle;
+#[cfg(feature = "std")]
+pub mod physarum_model;
+pub mod population_config;
+#[cfg(feature = "std")]
+pub mod postprocess;
+#[cfg(feature = "std")]
+pub mod print;
+#[cfg(feature = "std")]
+pub mod probe;
+#[cfg(feature = "std")]
+pub mod quantile;
+#[cfg(feature = "std")]
+pub mod render;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod scenario;
+pub mod sensing;
+pub mod spacing;
+#[cfg(feature = "std")]
+pub mod state;
+#[cfg(feature = "std")]
+pub mod statistics;
+pub mod steering;
+pub mod step;
+#[cfg(feature = "std")]
+pub mod stipple;
+#[cfg(feature = "std")]
+pub mod tiled;
+#[cfg(feature = "std")]
+pub mod tournament;
+mod trig;
+mod util;
+
+#[cfg(feature = "image-export")]
+pub use image;
@@ -0,0 +1,108 @@
+use super::config::SimulationConfig;
+use super::error::ConfigError;
+use super::palette::Palette;
+use super::physarum_model::PhysarumModel;
+use super::render::RenderSettings;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read};
+use std::path::Path;
+
+/// How a simulation is drawn, saved apart from the config so that the same run or saved
+/// state can be rendered again with different palettes, tone mapping, layering and
+/// backgrounds.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Look {
+    /// Name of a built-in palette, see `Palette::by_name`. Applying a look without one
+    /// keeps the palette already in use.
+    pub palette: Option<String>,
+    pub render: RenderSettings,
+}
+
+impl Look {
+    /// The look of `config`.
+    pub fn of(config: &SimulationConfig) -> Self {
+        Look {
+            palette: Some(config.palette.clone()),
+            render: config.render.clone(),
+        }
+    }
+
+    /// Replaces how `config` is drawn, leaving the simulation as it is.
+    pub fn apply(&self, config: &mut SimulationConfig) {
+        if let Some(palette) = &self.palette {
+            config.palette = palette.clone();
+        }
+        config.render = self.render.clone();
+    }
+
+    /// Draws `model` with this look from now on.
+    pub fn apply_to(&self, model: &mut PhysarumModel) -> Result<(), ConfigError> {
+        if let Some(name) = &self.palette {
+            model.set_palette(Palette::by_name(name).ok_or(ConfigError::UnknownPalette)?);
+        }
+        model.set_render_settings(self.render.clone())
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn from_reader(reader: impl Read) -> io::Result<Self> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::LayerOrder;
+
+    #[test]
+    fn looks_only_change_how_runs_are_drawn() {
+        let mut config = SimulationConfig {
+            n_particles: 1234,
+            ..SimulationConfig::default()
+        };
+        let look: Look = serde_json::from_str(
+            r#"{ "palette": "dusk", "render": { "order": { "mode": "auto" } } }"#,
+        )
+        .unwrap();
+        look.apply(&mut config);
+        assert_eq!(config.palette, "dusk");
+        assert_eq!(config.render.order, LayerOrder::Auto);
+        assert_eq!(config.n_particles, 1234);
+        assert_eq!(Look::of(&config).palette.as_deref(), Some("dusk"));
+
+        let unnamed = Look::default();
+        unnamed.apply(&mut config);
+        assert_eq!(config.palette, "dusk");
+        assert_eq!(config.render.order, LayerOrder::Grid);
+
+        let mut model = PhysarumModel::builder()
+            .size(16, 16)
+            .particles(10)
+            .build()
+            .unwrap();
+        let unknown = Look {
+            palette: Some("plaid".to_string()),
+            ..Look::default()
+        };
+        assert_eq!(
+            unknown.apply_to(&mut model),
+            Err(ConfigError::UnknownPalette)
+        );
+        look.apply_to(&mut model).unwrap();
+        assert_eq!(
+            model.palette().colors,
+            Palette::by_name("dusk").unwrap().colors
+        );
+        assert_eq!(model.render_settings().order, LayerOrder::Auto);
+    }
+}
@@ -0,0 +1,105 @@
+use std::io;
+use std::path::Path;
+
+/// A 3D color lookup table as stored in Adobe/Resolve `.cube` files.
+pub struct Lut {
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    /// `size`³ output colors with red varying fastest, then green, then blue.
+    table: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Lut::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let triple = |values: &[&str]| -> io::Result<[f32; 3]> {
+            match values {
+                [r, g, b] => {
+                    let parse = |v: &str| {
+                        v.parse::<f32>()
+                            .map_err(|err| invalid(format!("Bad LUT value {:?}: {}", v, err)))
+                    };
+                    Ok([parse(r)?, parse(g)?, parse(b)?])
+                }
+                _ => Err(invalid(format!(
+                    "Expected three LUT values, got {:?}",
+                    values
+                ))),
+            }
+        };
+
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = vec![];
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words: Vec<_> = line.split_whitespace().collect();
+            match words[0] {
+                "TITLE" => {}
+                "LUT_3D_SIZE" => {
+                    let n = words.get(1).and_then(|n| n.parse::<usize>().ok());
+                    size = Some(
+                        n.filter(|&n| n >= 2)
+                            .ok_or_else(|| invalid(format!("Bad LUT_3D_SIZE line {:?}", line)))?,
+                    );
+                }
+                "LUT_1D_SIZE" => return Err(invalid("1D LUTs are not supported".to_string())),
+                "DOMAIN_MIN" => domain_min = triple(&words[1..])?,
+                "DOMAIN_MAX" => domain_max = triple(&words[1..])?,
+                _ => table.push(triple(&words)?),
+            }
+        }
+
+        let size = size.ok_or_else(|| invalid("Missing LUT_3D_SIZE".to_string()))?;
+        if size.checked_pow(3) != Some(table.len()) {
+            return Err(invalid(format!(
+                "Expected {}^3 LUT entries, got {}",
+                size,
+                table.len()
+            )));
+        }
+        Ok(Lut {
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    /// Maps a color through the table with trilinear interpolation.
+    pub fn apply(&self, color: [f32; 3]) -> [f32; 3] {
+        let max = (self.size - 1) as f32;
+        let mut base = [0; 3];
+        let mut frac = [0.0; 3];
+        for c in 0..3 {
+            let range = (self.domain_max[c] - self.domain_min[c]).max(f32::EPSILON);
+            let t = ((color[c] - self.domain_min[c]) / range).clamp(0.0, 1.0) * max;
+            base[c] = (t as usize).min(self.size - 2);
+            frac[c] = t - base[c] as f32;
+        }
+
+        let mut result = [0.0; 3];
+        for corner in 0..8 {
+            let mut index = 0;
+            let mut weight = 1.0;
+            for c in (0..3).rev() {
+                let step = (corner >> c) & 1;
+                index = index * self.size + base[c] + step;
+                weight *= if step == 1 { frac[c] } else { 1.0 - frac[c] };
+            }
+            for (r, v) in result.iter_mut().zip(self.table[index]) {
+                *r += weight * v;
+            }
+        }
+        result
+    }
+}
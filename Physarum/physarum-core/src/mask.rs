@@ -0,0 +1,624 @@
+//! Output masks clipping the frame to a shape, e.g. a circle for a vinyl sticker or a logo
+//! traced from an SVG, and optionally keeping the agents inside it.
+
+use super::background;
+use super::blur::Blur;
+use super::par::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Rows sampled per cell, each covering the cells it crosses exactly.
+const SUBROWS: usize = 4;
+/// Segments every Bézier curve of a path is flattened into.
+const CURVE_SEGMENTS: usize = 32;
+/// Segments a full turn of an arc or circle is flattened into.
+const TURN_SEGMENTS: usize = 256;
+
+/// The outline the frame is clipped to.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MaskShape {
+    #[default]
+    None,
+    /// The largest circle centered on the frame.
+    Circle,
+    /// The frame with rounded corners, `radius` being a fraction of its shorter side.
+    RoundedRect { radius: f32 },
+    /// SVG path data, e.g. "M 0 0 H 10 L 5 8 Z", scaled to fit the frame. Subpaths are
+    /// filled by the nonzero rule.
+    Path { data: String },
+    /// All the paths of an SVG file, scaled together to fit the frame. Transforms and any
+    /// other elements are ignored.
+    Svg { path: PathBuf },
+    /// The brightness of an image stretched over the frame, inside where it's bright, e.g.
+    /// the land of a map. The margin doesn't apply.
+    Image { path: PathBuf },
+}
+
+/// Clips the rendered frame to a shape, for pieces that aren't rectangles. Outside the
+/// shape the frame is black, or transparent with `RenderSettings::alpha`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct MaskSettings {
+    pub shape: MaskShape,
+    /// Space left around the shape, as a fraction of the frame's shorter side.
+    pub margin: f32,
+    /// Width in cells of a soft edge, 0 for a crisp antialiased one.
+    pub feather: f32,
+    /// Keeps the agents inside the shape as well, turning back any that would leave it.
+    /// Agents that are outside when it's set wander until they find their way in.
+    pub confine: bool,
+}
+
+impl MaskSettings {
+    pub fn is_active(&self) -> bool {
+        self.shape != MaskShape::None
+    }
+
+    /// How much of every cell of a `width` by `height` frame the shape covers, in [0, 1],
+    /// or `None` without a shape.
+    pub fn render(&self, width: usize, height: usize) -> io::Result<Option<Vec<f32>>> {
+        let short = width.min(height) as f32;
+        let margin = self.margin.clamp(0.0, 0.5) * short;
+        let (x0, y0) = (margin, margin);
+        let (x1, y1) = (width as f32 - margin, height as f32 - margin);
+        let polygons = match &self.shape {
+            MaskShape::None => return Ok(None),
+            MaskShape::Circle => {
+                let center = (width as f32 * 0.5, height as f32 * 0.5);
+                let radius = short * 0.5 - margin;
+                vec![(0..TURN_SEGMENTS)
+                    .map(|k| {
+                        let t = TAU * k as f32 / TURN_SEGMENTS as f32;
+                        (center.0 + radius * t.cos(), center.1 + radius * t.sin())
+                    })
+                    .collect()]
+            }
+            MaskShape::RoundedRect { radius } => {
+                let radius = (radius * short).clamp(0.0, (x1 - x0).min(y1 - y0) * 0.5);
+                let corners = [
+                    (x1 - radius, y1 - radius),
+                    (x0 + radius, y1 - radius),
+                    (x0 + radius, y0 + radius),
+                    (x1 - radius, y0 + radius),
+                ];
+                let quarter = TURN_SEGMENTS / 4;
+                vec![corners
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, &(cx, cy))| {
+                        (0..=quarter).map(move |k| {
+                            let t = TAU * (i * quarter + k) as f32 / TURN_SEGMENTS as f32;
+                            (cx + radius * t.cos(), cy + radius * t.sin())
+                        })
+                    })
+                    .collect()]
+            }
+            MaskShape::Path { data } => fit(parse_path(data)?, (x0, y0), (x1, y1))?,
+            MaskShape::Svg { path } => {
+                let svg = std::fs::read_to_string(path)?;
+                let mut polygons = vec![];
+                for data in svg_path_data(&svg) {
+                    polygons.extend(parse_path(data)?);
+                }
+                fit(polygons, (x0, y0), (x1, y1))?
+            }
+            MaskShape::Image { path } => {
+                let coverage = background::load_brightness(path, width, height)?;
+                return Ok(Some(self.feathered(coverage, width, height)));
+            }
+        };
+        let coverage = rasterize(&polygons, width, height);
+        Ok(Some(self.feathered(coverage, width, height)))
+    }
+
+    fn feathered(&self, mut coverage: Vec<f32>, width: usize, height: usize) -> Vec<f32> {
+        if self.feather > 0.0 {
+            let mut buf = vec![0.0; coverage.len()];
+            Blur::new(width).run(
+                &mut coverage,
+                &mut buf,
+                width,
+                height,
+                self.feather * 0.5,
+                1.0,
+            );
+        }
+        coverage
+    }
+}
+
+impl fmt::Display for MaskShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MaskShape::None => write!(f, "none"),
+            MaskShape::Circle => write!(f, "circle"),
+            MaskShape::RoundedRect { .. } => write!(f, "rounded rect"),
+            MaskShape::Path { .. } => write!(f, "path"),
+            MaskShape::Svg { path } | MaskShape::Image { path } => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Parses "none", "circle", "rounded-rect" or the path of an `.svg` or image file, as
+/// given on the command line.
+impl FromStr for MaskShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(MaskShape::None),
+            "circle" => Ok(MaskShape::Circle),
+            "rounded-rect" => Ok(MaskShape::RoundedRect { radius: 0.1 }),
+            _ if s.to_ascii_lowercase().ends_with(".svg") => Ok(MaskShape::Svg {
+                path: PathBuf::from(s),
+            }),
+            _ if [".png", ".jpg", ".jpeg"]
+                .iter()
+                .any(|ext| s.to_ascii_lowercase().ends_with(ext)) =>
+            {
+                Ok(MaskShape::Image {
+                    path: PathBuf::from(s),
+                })
+            }
+            _ => Err(format!(
+                "Unknown mask {:?}, expected none, circle, rounded-rect, an .svg or an image",
+                s
+            )),
+        }
+    }
+}
+
+/// Scales and centers `polygons` to fit the box from `min` to `max`, keeping their aspect.
+fn fit(
+    mut polygons: Vec<Vec<(f32, f32)>>,
+    min: (f32, f32),
+    max: (f32, f32),
+) -> io::Result<Vec<Vec<(f32, f32)>>> {
+    let points = || polygons.iter().flatten();
+    let (mut lo, mut hi) = ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN));
+    for &(x, y) in points() {
+        lo = (lo.0.min(x), lo.1.min(y));
+        hi = (hi.0.max(x), hi.1.max(y));
+    }
+    let size = (hi.0 - lo.0, hi.1 - lo.1);
+    if !(size.0 > 0.0 && size.1 > 0.0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The mask's path has no area",
+        ));
+    }
+    let scale = ((max.0 - min.0) / size.0).min((max.1 - min.1) / size.1);
+    let offset = (
+        (min.0 + max.0 - size.0 * scale) * 0.5,
+        (min.1 + max.1 - size.1 * scale) * 0.5,
+    );
+    for point in polygons.iter_mut().flatten() {
+        *point = (
+            (point.0 - lo.0) * scale + offset.0,
+            (point.1 - lo.1) * scale + offset.1,
+        );
+    }
+    Ok(polygons)
+}
+
+/// The share of every cell inside the closed `polygons` by the nonzero rule, from the
+/// exact spans `SUBROWS` rows through every cell cover.
+fn rasterize(polygons: &[Vec<(f32, f32)>], width: usize, height: usize) -> Vec<f32> {
+    let mut coverage = vec![0.0; width * height];
+    let weight = 1.0 / SUBROWS as f32;
+    coverage
+        .par_chunks_exact_mut(width)
+        .enumerate()
+        .for_each(|(j, row)| {
+            let mut crossings = vec![];
+            for s in 0..SUBROWS {
+                let y = j as f32 + (s as f32 + 0.5) * weight;
+                crossings.clear();
+                for polygon in polygons {
+                    let edges = polygon.iter().zip(polygon.iter().cycle().skip(1));
+                    for (&(ax, ay), &(bx, by)) in edges {
+                        if (ay <= y) != (by <= y) {
+                            let x = ax + (y - ay) / (by - ay) * (bx - ax);
+                            crossings.push((x, if by > ay { 1 } else { -1 }));
+                        }
+                    }
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+                let (mut winding, mut start) = (0, 0.0);
+                for &(x, direction) in &crossings {
+                    if winding == 0 {
+                        start = x;
+                    }
+                    winding += direction;
+                    if winding == 0 {
+                        let first = start.max(0.0) as usize;
+                        let last = (x.ceil().max(0.0) as usize).min(width);
+                        for (i, cell) in row.iter_mut().enumerate().take(last).skip(first) {
+                            let covered = x.min(i as f32 + 1.0) - start.max(i as f32);
+                            *cell += covered.max(0.0) * weight;
+                        }
+                    }
+                }
+            }
+        });
+    coverage
+}
+
+/// The `d` attributes of the `<path>` elements of an SVG document.
+fn svg_path_data(svg: &str) -> impl Iterator<Item = &str> {
+    svg.match_indices("<path").filter_map(move |(start, _)| {
+        let tag = &svg[start..];
+        let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+        let at = tag.char_indices().find_map(|(i, c)| {
+            (c.is_whitespace() && tag[i + 1..].starts_with("d=")).then_some(i + 3)
+        })?;
+        let quote = tag[at..].chars().next()?;
+        let value = &tag[at + 1..];
+        Some(&value[..value.find(quote)?])
+    })
+}
+
+/// Flattens SVG path data into closed polygons, one per subpath.
+fn parse_path(data: &str) -> io::Result<Vec<Vec<(f32, f32)>>> {
+    let mut tokens = PathTokens {
+        data: data.as_bytes(),
+        at: 0,
+    };
+    let mut polygons = vec![];
+    let mut current: Vec<(f32, f32)> = vec![];
+    let (mut pen, mut start) = ((0.0, 0.0), (0.0, 0.0));
+    // The last control point of a curve, for the smooth curves reflecting it.
+    let mut control = None;
+    let mut previous = None;
+    while let Some(command) = tokens.command(previous)? {
+        let relative = command.is_ascii_lowercase();
+        let origin = if relative { pen } else { (0.0, 0.0) };
+        let point = |tokens: &mut PathTokens| -> io::Result<(f32, f32)> {
+            Ok((origin.0 + tokens.number()?, origin.1 + tokens.number()?))
+        };
+        let reflected = |smooth: &[u8]| match (previous, control) {
+            (Some(p), Some((x, y))) if smooth.contains(&p.to_ascii_uppercase()) => {
+                (2.0 * pen.0 - x, 2.0 * pen.1 - y)
+            }
+            _ => pen,
+        };
+        let mut next_control = None;
+        match command.to_ascii_uppercase() {
+            b'M' => {
+                if current.len() > 1 {
+                    polygons.push(std::mem::take(&mut current));
+                }
+                pen = point(&mut tokens)?;
+                start = pen;
+                current = vec![pen];
+            }
+            b'L' => {
+                pen = point(&mut tokens)?;
+                current.push(pen);
+            }
+            b'H' => {
+                pen.0 = origin.0 + tokens.number()?;
+                current.push(pen);
+            }
+            b'V' => {
+                pen.1 = origin.1 + tokens.number()?;
+                current.push(pen);
+            }
+            b'C' | b'S' => {
+                let c1 = if command.eq_ignore_ascii_case(&b'C') {
+                    point(&mut tokens)?
+                } else {
+                    reflected(b"CS")
+                };
+                let (c2, end) = (point(&mut tokens)?, point(&mut tokens)?);
+                current.extend(bezier(&[pen, c1, c2, end]));
+                (pen, next_control) = (end, Some(c2));
+            }
+            b'Q' | b'T' => {
+                let c = if command.eq_ignore_ascii_case(&b'Q') {
+                    point(&mut tokens)?
+                } else {
+                    reflected(b"QT")
+                };
+                let end = point(&mut tokens)?;
+                current.extend(bezier(&[pen, c, end]));
+                (pen, next_control) = (end, Some(c));
+            }
+            b'A' => {
+                let radii = (tokens.number()?.abs(), tokens.number()?.abs());
+                let rotation = tokens.number()?.to_radians();
+                let (large, sweep) = (tokens.flag()?, tokens.flag()?);
+                let end = point(&mut tokens)?;
+                arc(&mut current, pen, radii, rotation, large, sweep, end);
+                pen = end;
+            }
+            b'Z' => {
+                if current.len() > 1 {
+                    polygons.push(std::mem::take(&mut current));
+                }
+                pen = start;
+                current = vec![pen];
+            }
+            _ => return Err(tokens.invalid()),
+        }
+        control = next_control;
+        previous = Some(command);
+    }
+    if current.len() > 1 {
+        polygons.push(current);
+    }
+    Ok(polygons)
+}
+
+/// Points along the Bézier curve with `points` as its controls, after the first.
+fn bezier(points: &[(f32, f32)]) -> impl Iterator<Item = (f32, f32)> + '_ {
+    (1..=CURVE_SEGMENTS).map(move |k| {
+        let t = k as f32 / CURVE_SEGMENTS as f32;
+        // De Casteljau's algorithm, with at most four controls.
+        let mut controls = [(0.0, 0.0); 4];
+        controls[..points.len()].copy_from_slice(points);
+        for n in (1..points.len()).rev() {
+            for i in 0..n {
+                let (a, b) = (controls[i], controls[i + 1]);
+                controls[i] = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+            }
+        }
+        controls[0]
+    })
+}
+
+/// Appends points along an SVG elliptical arc from `from` to `to`, after converting it to
+/// its center as the SVG specification describes.
+fn arc(
+    points: &mut Vec<(f32, f32)>,
+    from: (f32, f32),
+    (mut rx, mut ry): (f32, f32),
+    rotation: f32,
+    large: bool,
+    sweep: bool,
+    to: (f32, f32),
+) {
+    if from == to {
+        return;
+    }
+    if rx == 0.0 || ry == 0.0 {
+        points.push(to);
+        return;
+    }
+    let (sin, cos) = rotation.sin_cos();
+    let (dx, dy) = ((from.0 - to.0) * 0.5, (from.1 - to.1) * 0.5);
+    let (x, y) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+    // Radii too small to reach are scaled up until they just do.
+    let reach = (x * x) / (rx * rx) + (y * y) / (ry * ry);
+    if reach > 1.0 {
+        rx *= reach.sqrt();
+        ry *= reach.sqrt();
+    }
+    let (rx2, ry2) = (rx * rx, ry * ry);
+    let den = rx2 * y * y + ry2 * x * x;
+    let mut coef = ((rx2 * ry2 - den) / den).max(0.0).sqrt();
+    if large == sweep {
+        coef = -coef;
+    }
+    let (cx, cy) = (coef * rx * y / ry, -coef * ry * x / rx);
+    let center = (
+        cos * cx - sin * cy + (from.0 + to.0) * 0.5,
+        sin * cx + cos * cy + (from.1 + to.1) * 0.5,
+    );
+    let angle =
+        |(ux, uy): (f32, f32), (vx, vy): (f32, f32)| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+    let u = ((x - cx) / rx, (y - cy) / ry);
+    let v = ((-x - cx) / rx, (-y - cy) / ry);
+    let start = angle((1.0, 0.0), u);
+    let mut delta = angle(u, v);
+    if !sweep && delta > 0.0 {
+        delta -= TAU;
+    } else if sweep && delta < 0.0 {
+        delta += TAU;
+    }
+    let segments = ((delta.abs() / TAU * TURN_SEGMENTS as f32).ceil() as usize).max(1);
+    for k in 1..=segments {
+        let t = start + delta * k as f32 / segments as f32;
+        let (px, py) = (rx * t.cos(), ry * t.sin());
+        points.push((
+            cos * px - sin * py + center.0,
+            sin * px + cos * py + center.1,
+        ));
+    }
+}
+
+/// The commands, numbers and flags of SVG path data.
+struct PathTokens<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl PathTokens<'_> {
+    fn skip_separators(&mut self) {
+        while self
+            .data
+            .get(self.at)
+            .is_some_and(|c| c.is_ascii_whitespace() || *c == b',')
+        {
+            self.at += 1;
+        }
+    }
+
+    fn invalid(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid SVG path data at byte {}", self.at),
+        )
+    }
+
+    /// The next command, repeating `previous` when only its arguments follow, or `None`
+    /// at the end.
+    fn command(&mut self, previous: Option<u8>) -> io::Result<Option<u8>> {
+        self.skip_separators();
+        match self.data.get(self.at) {
+            None => Ok(None),
+            Some(&c) if c.is_ascii_alphabetic() => {
+                self.at += 1;
+                Ok(Some(c))
+            }
+            // Points following a move are lines.
+            _ => match previous {
+                Some(b'M') => Ok(Some(b'L')),
+                Some(b'm') => Ok(Some(b'l')),
+                Some(b'Z' | b'z') | None => Err(self.invalid()),
+                previous => Ok(previous),
+            },
+        }
+    }
+
+    fn number(&mut self) -> io::Result<f32> {
+        self.skip_separators();
+        let start = self.at;
+        let digits = |tokens: &mut Self| {
+            while tokens.data.get(tokens.at).is_some_and(u8::is_ascii_digit) {
+                tokens.at += 1;
+            }
+        };
+        if matches!(self.data.get(self.at), Some(b'+' | b'-')) {
+            self.at += 1;
+        }
+        digits(self);
+        if self.data.get(self.at) == Some(&b'.') {
+            self.at += 1;
+            digits(self);
+        }
+        if matches!(self.data.get(self.at), Some(b'e' | b'E')) {
+            self.at += 1;
+            if matches!(self.data.get(self.at), Some(b'+' | b'-')) {
+                self.at += 1;
+            }
+            digits(self);
+        }
+        std::str::from_utf8(&self.data[start..self.at])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .filter(|number: &f32| number.is_finite())
+            .ok_or_else(|| self.invalid())
+    }
+
+    /// Arc flags, which need no separator after them.
+    fn flag(&mut self) -> io::Result<bool> {
+        self.skip_separators();
+        let flag = match self.data.get(self.at) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(self.invalid()),
+        };
+        self.at += 1;
+        Ok(flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ConfigError;
+    use crate::physarum_model::PhysarumModel;
+
+    fn area(coverage: &[f32]) -> f32 {
+        coverage.iter().sum()
+    }
+
+    #[test]
+    fn circles_cover_their_area() {
+        let mask = MaskSettings {
+            shape: MaskShape::Circle,
+            margin: 0.25,
+            ..MaskSettings::default()
+        };
+        let coverage = mask.render(64, 64).unwrap().unwrap();
+        assert_eq!(coverage[32 * 64 + 32], 1.0);
+        assert_eq!(coverage[0], 0.0);
+        let expected = std::f32::consts::PI * 16.0 * 16.0;
+        assert!((area(&coverage) - expected).abs() < expected * 0.01);
+        assert!(coverage.iter().all(|c| (0.0..=1.0).contains(c)));
+
+        let arcs = MaskSettings {
+            shape: MaskShape::Path {
+                data: "M0 5a5 5 0 1 1 10 0 5 5 0 1 1-10 0z".to_string(),
+            },
+            margin: 0.25,
+            ..MaskSettings::default()
+        };
+        let traced = arcs.render(64, 64).unwrap().unwrap();
+        assert!((area(&traced) - expected).abs() < expected * 0.01);
+    }
+
+    #[test]
+    fn paths_fill_by_the_nonzero_rule_and_fit_the_frame() {
+        // A square with a hole wound the other way, centered in a wider frame.
+        let path = "M0 0 H4 V4 H0 Z m1 1 v2 h2 v-2 z";
+        let mask = MaskSettings {
+            shape: MaskShape::Path {
+                data: path.to_string(),
+            },
+            ..MaskSettings::default()
+        };
+        let coverage = mask.render(8, 4).unwrap().unwrap();
+        #[rustfmt::skip]
+        let expected = [
+            0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0,
+        ];
+        assert_eq!(coverage, expected);
+
+        let svg = format!(
+            "<svg viewBox=\"0 0 4 4\"><path style=\"fill:red\"\n d='{}'/></svg>",
+            path
+        );
+        assert_eq!(svg_path_data(&svg).collect::<Vec<_>>(), [path]);
+        assert!(parse_path("M0 0 L1").is_err());
+        assert!(parse_path("0 0").is_err());
+    }
+
+    #[test]
+    fn confined_agents_stay_inside() {
+        let mut model = PhysarumModel::builder()
+            .size(64, 64)
+            .particles(400)
+            .seed(5)
+            .build()
+            .unwrap();
+        let mut settings = model.render_settings().clone();
+        settings.mask = MaskSettings {
+            shape: MaskShape::Circle,
+            confine: true,
+            ..MaskSettings::default()
+        };
+        model.set_render_settings(settings).unwrap();
+        let inside = |x: f32, y: f32| (x - 32.0).hypot(y - 32.0) < 31.0;
+        let started: Vec<bool> = model.agents().iter().map(|a| inside(a.x, a.y)).collect();
+        for _ in 0..50 {
+            model.step();
+        }
+        for (agent, started) in model.agents().iter().zip(started) {
+            assert!(!started || (agent.x - 32.0).hypot(agent.y - 32.0) < 32.5);
+        }
+
+        // A mask that can't be read fails, keeping the one before.
+        let mut missing = model.render_settings().clone();
+        missing.mask.shape = MaskShape::Svg {
+            path: "missing/mask.svg".into(),
+        };
+        assert!(matches!(
+            model.set_render_settings(missing),
+            Err(ConfigError::Unreadable {
+                setting: "mask",
+                ..
+            })
+        ));
+        assert_eq!(model.render_settings().mask.shape, MaskShape::Circle);
+    }
+}
@@ -0,0 +1,82 @@
+//! Float functions that `core` lacks, taken from `libm` when building without std. With
+//! std the inherent methods of `f32` are used instead.
+
+use super::trig;
+
+#[cfg(not(any(feature = "std", test)))]
+pub(crate) trait Float {
+    fn sqrt(self) -> Self;
+    fn round(self) -> Self;
+    fn fract(self) -> Self;
+    fn ceil(self) -> Self;
+}
+
+#[cfg(not(any(feature = "std", test)))]
+impl Float for f32 {
+    fn sqrt(self) -> f32 {
+        libm::sqrtf(self)
+    }
+
+    fn round(self) -> f32 {
+        libm::roundf(self)
+    }
+
+    fn fract(self) -> f32 {
+        self - libm::truncf(self)
+    }
+
+    fn ceil(self) -> f32 {
+        libm::ceilf(self)
+    }
+}
+
+/// The transcendental functions results depend on. The platform's math library may round
+/// them differently between operating systems, C libraries and CPUs, while `libm` is plain
+/// Rust and returns the same bits everywhere, which `strict_determinism` relies on. Basic
+/// arithmetic and `sqrt` are exactly rounded by IEEE 754 and need no such care.
+#[derive(Clone, Copy)]
+pub(crate) struct Math {
+    pub cos: fn(f32) -> f32,
+    pub sin: fn(f32) -> f32,
+    pub powf: fn(f32, f32) -> f32,
+    pub exp: fn(f32) -> f32,
+    pub hypot: fn(f32, f32) -> f32,
+}
+
+impl Math {
+    const PORTABLE: Math = Math {
+        cos: libm::cosf,
+        sin: libm::sinf,
+        powf: libm::powf,
+        exp: libm::expf,
+        hypot: libm::hypotf,
+    };
+
+    #[cfg(any(feature = "std", test))]
+    const NATIVE: Math = Math {
+        cos: f32::cos,
+        sin: f32::sin,
+        powf: f32::powf,
+        exp: f32::exp,
+        hypot: f32::hypot,
+    };
+
+    #[cfg_attr(not(any(feature = "std", test)), allow(unused_variables))]
+    pub(crate) fn new(strict_determinism: bool) -> Self {
+        #[cfg(any(feature = "std", test))]
+        if !strict_determinism {
+            return Self::NATIVE;
+        }
+        Self::PORTABLE
+    }
+
+    /// With `trig::cos` and `trig::sin` in place of the exact functions, for `fast_math`.
+    /// They are plain arithmetic, so they stay portable under `strict_determinism`.
+    pub(crate) fn fast_trig(self) -> Self {
+        Math {
+            cos: trig::cos,
+            sin: trig::sin,
+            ..self
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Trail fields as plain matrices of floats, written as NumPy `.npy` or CSV files for
+//! post-processing in Python, Julia or a spreadsheet.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// A `width` by `height` field of floats in rows from the top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f32>,
+}
+
+impl Matrix {
+    pub fn new(width: usize, height: usize, data: Vec<f32>) -> Self {
+        assert_eq!(data.len(), width * height);
+        Matrix {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// The means of `factor` by `factor` blocks of cells, with the blocks cut off at the
+    /// right and bottom edges averaging the cells they have.
+    pub fn downsampled(&self, factor: usize) -> Matrix {
+        if factor <= 1 {
+            return self.clone();
+        }
+        let (width, height) = (self.width.div_ceil(factor), self.height.div_ceil(factor));
+        let mut sums = vec![0.0f64; width * height];
+        let mut counts = vec![0u32; width * height];
+        for (i, &v) in self.data.iter().enumerate() {
+            let (x, y) = (i % self.width / factor, i / self.width / factor);
+            sums[y * width + x] += v as f64;
+            counts[y * width + x] += 1;
+        }
+        let data = sums
+            .iter()
+            .zip(&counts)
+            .map(|(&sum, &count)| (sum / count as f64) as f32)
+            .collect();
+        Matrix::new(width, height, data)
+    }
+
+    /// Writes the matrix as NumPy's `.npy` when the path ends in `.npy`, and as CSV
+    /// otherwise.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if path.extension().is_some_and(|ext| ext == "npy") {
+            self.write_npy(&mut writer)?;
+        } else {
+            self.write_csv(&mut writer)?;
+        }
+        writer.flush()
+    }
+
+    /// Writes a version 1.0 `.npy` array of little-endian 32-bit floats, `height` rows of
+    /// `width`, which `numpy.load` reads as is.
+    pub fn write_npy(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut header = format!(
+            "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+            self.height, self.width
+        );
+        // The magic, version and length take 10 bytes, and the data starts 64-byte aligned.
+        let padded = (10 + header.len() + 1).div_ceil(64) * 64 - 10;
+        header.extend(std::iter::repeat_n(' ', padded - header.len() - 1));
+        header.push('\n');
+        writer.write_all(b"\x93NUMPY\x01\x00")?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+        for v in &self.data {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Writes a row of comma-separated values per row of cells, each as few digits as
+    /// read back to the same float.
+    pub fn write_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+        for row in self.data.chunks(self.width.max(1)) {
+            for (x, v) in row.iter().enumerate() {
+                if x > 0 {
+                    writer.write_all(b",")?;
+                }
+                write!(writer, "{}", v)?;
+            }
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downsampled_matrices_in_npy_and_csv() {
+        let matrix = Matrix::new(3, 3, (0..9).map(|v| v as f32).collect());
+        let small = matrix.downsampled(2);
+        assert_eq!((small.width, small.height), (2, 2));
+        assert_eq!(small.data, vec![2.0, 3.5, 6.5, 8.0]);
+        assert_eq!(matrix.downsampled(1), matrix);
+
+        let mut csv = vec![];
+        small.write_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "2,3.5\n6.5,8\n");
+
+        let mut npy = vec![];
+        small.write_npy(&mut npy).unwrap();
+        assert_eq!(&npy[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&npy[10..10 + header_len]).unwrap();
+        assert!(header.contains("'shape': (2, 2)") && header.ends_with('\n'));
+        let data = &npy[10 + header_len..];
+        assert_eq!(data.len(), 16);
+        assert_eq!(data[4..8], 3.5f32.to_le_bytes());
+    }
+}
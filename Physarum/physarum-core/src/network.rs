@@ -0,0 +1,633 @@
+//! The transport network the trails form, as a graph: the trails above a threshold are
+//! thinned down to their center lines, junctions and loose ends become nodes and the lines
+//! between them edges with a length and a thickness. Exported as JSON or GraphML for
+//! network analysis tools, and searched for shortest paths like Physarum itself does.
+
+use super::par::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet, VecDeque};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// How `PhysarumModel::network` tells the network from the background.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// Fraction of the cells below the network, so 0.7 keeps the densest 30%.
+    pub quantile: f32,
+    /// Blur in cells the trails are smoothed with first, so that speckles neither break
+    /// up the lines nor sprout spurs.
+    pub smoothing: f32,
+    /// Dead ends shorter than this many cells are dropped as noise.
+    pub min_spur: f32,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        NetworkSettings {
+            quantile: 0.7,
+            smoothing: 1.5,
+            min_spur: 6.0,
+        }
+    }
+}
+
+/// A junction or the loose end of a line, at the center of its cells.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Node {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A line of the network between two nodes, the same for loops.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Edge {
+    pub source: usize,
+    pub target: usize,
+    /// Length in cells along the line.
+    pub length: f32,
+    /// Mean width in cells of the trails around the line.
+    pub thickness: f32,
+}
+
+/// A network extracted by `Network::extract`, on a grid that wraps around its edges.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Network {
+    pub width: usize,
+    pub height: usize,
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// The eight neighbours of a cell, clockwise from the one above.
+const RING: [(isize, isize); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+impl Network {
+    /// Extracts the network of the cells of `field` above `threshold`, dropping dead ends
+    /// and specks shorter than `min_spur` cells.
+    pub fn extract(
+        field: &[f32],
+        width: usize,
+        height: usize,
+        threshold: f32,
+        min_spur: f32,
+    ) -> Self {
+        let cells = Cells { width, height };
+        let mask: Vec<bool> = field.par_iter().map(|&v| v > threshold).collect();
+        let depth = cells.depth(&mask);
+        let skeleton = cells.thin(mask);
+        let mut graph = cells.trace(&skeleton, &depth);
+        graph.prune(min_spur);
+        graph.into_network(width, height)
+    }
+
+    /// Writes the network as GraphML for paths ending in `.graphml`, otherwise as JSON.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if path.extension().is_some_and(|ext| ext == "graphml") {
+            fs::write(path, self.to_graphml())
+        } else {
+            Ok(serde_json::to_writer_pretty(
+                io::BufWriter::new(fs::File::create(path)?),
+                self,
+            )?)
+        }
+    }
+
+    /// The network as an undirected GraphML graph with the positions of the nodes and the
+    /// length and thickness of the edges as attributes.
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"x\" for=\"node\" attr.name=\"x\" attr.type=\"double\"/>\n",
+            "  <key id=\"y\" for=\"node\" attr.name=\"y\" attr.type=\"double\"/>\n",
+            "  <key id=\"length\" for=\"edge\" attr.name=\"length\" attr.type=\"double\"/>\n",
+            "  <key id=\"thickness\" for=\"edge\" attr.name=\"thickness\" attr.type=\"double\"/>\n",
+            "  <graph id=\"network\" edgedefault=\"undirected\">\n",
+        ));
+        for (i, node) in self.nodes.iter().enumerate() {
+            let _ = writeln!(
+                xml,
+                "    <node id=\"n{}\"><data key=\"x\">{}</data><data key=\"y\">{}</data></node>",
+                i, node.x, node.y
+            );
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                xml,
+                "    <edge source=\"n{}\" target=\"n{}\"><data key=\"length\">{}</data>\
+                 <data key=\"thickness\">{}</data></edge>",
+                edge.source, edge.target, edge.length, edge.thickness
+            );
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
+    /// The total length of the edges in cells.
+    pub fn length(&self) -> f32 {
+        self.edges.iter().map(|edge| edge.length).sum()
+    }
+
+    /// The node closest to (x, y), across the wrapped edges.
+    pub fn nearest_node(&self, x: f32, y: f32) -> Option<usize> {
+        let wrapped = |d: f32, size: usize| {
+            let d = d.abs() % size as f32;
+            d.min(size as f32 - d)
+        };
+        let distance =
+            |node: &Node| wrapped(node.x - x, self.width).hypot(wrapped(node.y - y, self.height));
+        (0..self.nodes.len()).min_by(|&a, &b| {
+            distance(&self.nodes[a])
+                .partial_cmp(&distance(&self.nodes[b]))
+                .unwrap_or(Ordering::Equal)
+        })
+    }
+
+    /// The nodes along the shortest path from `from` to `to` and its length, or `None`
+    /// when the network doesn't connect them.
+    pub fn shortest_path(&self, from: usize, to: usize) -> Option<(Vec<usize>, f32)> {
+        let mut adjacent = vec![vec![]; self.nodes.len()];
+        for edge in &self.edges {
+            adjacent[edge.source].push((edge.target, edge.length));
+            adjacent[edge.target].push((edge.source, edge.length));
+        }
+        let mut distance = vec![f32::INFINITY; self.nodes.len()];
+        let mut previous = vec![usize::MAX; self.nodes.len()];
+        let mut queue = BinaryHeap::new();
+        *distance.get_mut(from)? = 0.0;
+        queue.push(Visit(0.0, from));
+        while let Some(Visit(d, node)) = queue.pop() {
+            if node == to {
+                let mut path = vec![to];
+                while *path.last().unwrap() != from {
+                    path.push(previous[*path.last().unwrap()]);
+                }
+                path.reverse();
+                return Some((path, d));
+            }
+            if d > distance[node] {
+                continue;
+            }
+            for &(next, length) in &adjacent[node] {
+                if d + length < distance[next] {
+                    distance[next] = d + length;
+                    previous[next] = node;
+                    queue.push(Visit(d + length, next));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A node reached at a distance, ordered for a min-heap.
+#[derive(PartialEq)]
+struct Visit(f32, usize);
+
+impl Eq for Visit {}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.partial_cmp(&self.0).unwrap_or(Ordering::Equal)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Cells {
+    width: usize,
+    height: usize,
+}
+
+impl Cells {
+    fn neighbour(self, cell: usize, (dx, dy): (isize, isize)) -> usize {
+        let x = (cell % self.width) as isize + dx;
+        let y = (cell / self.width) as isize + dy;
+        y.rem_euclid(self.height as isize) as usize * self.width
+            + x.rem_euclid(self.width as isize) as usize
+    }
+
+    /// Whether each of the eight neighbours of `cell` is set, clockwise from above.
+    fn ring(self, set: &[bool], cell: usize) -> [bool; 8] {
+        RING.map(|offset| set[self.neighbour(cell, offset)])
+    }
+
+    /// The distance of every cell of the mask from the nearest cell outside it, counting
+    /// diagonal steps as one, 0 outside.
+    fn depth(self, mask: &[bool]) -> Vec<u32> {
+        let mut depth: Vec<u32> = mask.iter().map(|&m| if m { u32::MAX } else { 0 }).collect();
+        let mut queue: VecDeque<usize> = (0..mask.len()).filter(|&i| !mask[i]).collect();
+        while let Some(cell) = queue.pop_front() {
+            for offset in RING {
+                let next = self.neighbour(cell, offset);
+                if depth[next] == u32::MAX {
+                    depth[next] = depth[cell] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+        // Masks covering the whole grid have no outside to measure from.
+        let cap = (self.width.min(self.height) / 2) as u32;
+        depth.iter_mut().for_each(|d| *d = (*d).min(cap));
+        depth
+    }
+
+    /// Zhang-Suen thinning, peeling the mask's borders off alternately from the south-east
+    /// and the north-west until only lines one cell wide are left.
+    fn thin(self, mut set: Vec<bool>) -> Vec<bool> {
+        loop {
+            let mut changed = false;
+            for pass in 0..2 {
+                let peeled: Vec<usize> = (0..set.len())
+                    .into_par_iter()
+                    .filter(|&cell| {
+                        if !set[cell] {
+                            return false;
+                        }
+                        let [p2, p3, p4, p5, p6, p7, p8, p9] = self.ring(&set, cell);
+                        let ring = [p2, p3, p4, p5, p6, p7, p8, p9];
+                        let neighbours = ring.iter().filter(|&&p| p).count();
+                        let crossings = crossings(&ring);
+                        let side = if pass == 0 {
+                            !(p4 && p6 && (p2 || p8))
+                        } else {
+                            !(p2 && p8 && (p4 || p6))
+                        };
+                        (2..=6).contains(&neighbours) && crossings == 1 && side
+                    })
+                    .collect();
+                changed |= !peeled.is_empty();
+                for cell in peeled {
+                    set[cell] = false;
+                }
+            }
+            if !changed {
+                return set;
+            }
+        }
+    }
+
+    /// Follows the lines of `skeleton` from node to node.
+    fn trace(self, skeleton: &[bool], depth: &[u32]) -> Graph {
+        let thickness = |cell: usize| (2 * depth[cell]).saturating_sub(1).max(1) as f32;
+        // Junctions, where more than two lines meet, and loose ends are the nodes.
+        let mut node_of: Vec<Option<usize>> = vec![None; skeleton.len()];
+        let is_node = |cell: usize| {
+            let ring = self.ring(skeleton, cell);
+            let neighbours = ring.iter().filter(|&&p| p).count();
+            skeleton[cell] && (neighbours == 1 || crossings(&ring) >= 3)
+        };
+        let node_cells: Vec<bool> = (0..skeleton.len()).into_par_iter().map(is_node).collect();
+        let mut graph = Graph::default();
+        for cell in 0..skeleton.len() {
+            if node_cells[cell] && node_of[cell].is_none() {
+                self.cluster(cell, &node_cells, &mut node_of, &mut graph);
+            }
+        }
+
+        let mut visited = vec![false; skeleton.len()];
+        let mut direct = HashSet::new();
+        let mut starts: Vec<usize> = (0..skeleton.len()).filter(|&c| node_cells[c]).collect();
+        let mut next_loop = 0;
+        loop {
+            for &start in &starts {
+                let node = node_of[start].unwrap();
+                for offset in RING {
+                    let next = self.neighbour(start, offset);
+                    if !skeleton[next] || visited[next] {
+                        continue;
+                    }
+                    if let Some(other) = node_of[next] {
+                        if other != node && direct.insert((node.min(other), node.max(other))) {
+                            let step = step_length(offset);
+                            let width = 0.5 * (thickness(start) + thickness(next));
+                            graph.add(node, other, step, width);
+                        }
+                        continue;
+                    }
+                    let (end, length, width) =
+                        self.walk(start, next, skeleton, &node_of, &mut visited, &thickness);
+                    if let Some(end) = end {
+                        graph.add(node, end, length, width);
+                    }
+                }
+            }
+            // Loops without any junction get a node of their own to start and end at.
+            let Some(cell) = (next_loop..skeleton.len())
+                .find(|&c| skeleton[c] && !visited[c] && node_of[c].is_none())
+            else {
+                return graph;
+            };
+            next_loop = cell;
+            node_of[cell] = Some(graph.node(cell, self));
+            visited[cell] = true;
+            starts = vec![cell];
+        }
+    }
+
+    /// Gathers the touching node cells around `cell` into one node at their center.
+    fn cluster(
+        self,
+        cell: usize,
+        node_cells: &[bool],
+        node_of: &mut [Option<usize>],
+        graph: &mut Graph,
+    ) {
+        let node = graph.node(cell, self);
+        node_of[cell] = Some(node);
+        let (mut sum_x, mut sum_y, mut count) = (0.0, 0.0, 1.0);
+        let mut pending = vec![(cell, 0isize, 0isize)];
+        while let Some((current, x, y)) = pending.pop() {
+            for offset in RING {
+                let next = self.neighbour(current, offset);
+                if node_cells[next] && node_of[next].is_none() {
+                    node_of[next] = Some(node);
+                    let (x, y) = (x + offset.0, y + offset.1);
+                    sum_x += x as f32;
+                    sum_y += y as f32;
+                    count += 1.0;
+                    pending.push((next, x, y));
+                }
+            }
+        }
+        let center = &mut graph.nodes[node];
+        center.x = (center.x + sum_x / count).rem_euclid(self.width as f32);
+        center.y = (center.y + sum_y / count).rem_euclid(self.height as f32);
+    }
+
+    /// Walks along a line from the node cell `start` through `first` until it reaches
+    /// another node cell, returning that node, the length walked and the mean thickness.
+    fn walk(
+        self,
+        start: usize,
+        first: usize,
+        skeleton: &[bool],
+        node_of: &[Option<usize>],
+        visited: &mut [bool],
+        thickness: &impl Fn(usize) -> f32,
+    ) -> (Option<usize>, f32, f32) {
+        let home = node_of[start];
+        let (mut previous, mut current) = (start, first);
+        let mut length = step_length(self.offset(start, first));
+        let (mut widths, mut cells) = (0.0, 0.0);
+        loop {
+            visited[current] = true;
+            widths += thickness(current);
+            cells += 1.0;
+            // Lines end at the first node cell they reach, which is only their own node
+            // once they have gone some way around a loop.
+            let mut candidates: Vec<(usize, (isize, isize))> = RING
+                .iter()
+                .map(|&offset| (self.neighbour(current, offset), offset))
+                .filter(|&(next, _)| skeleton[next] && next != previous && next != current)
+                .collect();
+            let end = candidates.iter().find(|&&(next, _)| {
+                node_of[next].is_some() && (node_of[next] != home || cells > 2.0)
+            });
+            if let Some(&(next, offset)) = end {
+                return (node_of[next], length + step_length(offset), widths / cells);
+            }
+            // Straight steps first, so that corners of staircases aren't skipped.
+            candidates.retain(|&(next, _)| !visited[next] && node_of[next].is_none());
+            candidates.sort_by_key(|&(_, (dx, dy))| dx != 0 && dy != 0);
+            let Some(&(next, offset)) = candidates.first() else {
+                return (None, length, widths / cells);
+            };
+            length += step_length(offset);
+            (previous, current) = (current, next);
+        }
+    }
+
+    fn offset(self, from: usize, to: usize) -> (isize, isize) {
+        *RING
+            .iter()
+            .find(|&&offset| self.neighbour(from, offset) == to)
+            .unwrap()
+    }
+}
+
+/// The number of runs of set cells around a cell.
+fn crossings(ring: &[bool; 8]) -> usize {
+    (0..8).filter(|&i| !ring[i] && ring[(i + 1) % 8]).count()
+}
+
+fn step_length((dx, dy): (isize, isize)) -> f32 {
+    if dx != 0 && dy != 0 {
+        std::f32::consts::SQRT_2
+    } else {
+        1.0
+    }
+}
+
+/// The network while it is extracted, with removed edges left as `None`.
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Option<Edge>>,
+    /// The edges at every node, a loop appearing twice.
+    incident: Vec<Vec<usize>>,
+}
+
+impl Graph {
+    fn node(&mut self, cell: usize, cells: Cells) -> usize {
+        self.nodes.push(Node {
+            x: (cell % cells.width) as f32 + 0.5,
+            y: (cell / cells.width) as f32 + 0.5,
+        });
+        self.incident.push(vec![]);
+        self.nodes.len() - 1
+    }
+
+    fn add(&mut self, source: usize, target: usize, length: f32, thickness: f32) {
+        self.edges.push(Some(Edge {
+            source,
+            target,
+            length,
+            thickness,
+        }));
+        let edge = self.edges.len() - 1;
+        self.incident[source].push(edge);
+        self.incident[target].push(edge);
+    }
+
+    fn remove(&mut self, edge: usize) {
+        if let Some(Edge { source, target, .. }) = self.edges[edge].take() {
+            self.incident[source].retain(|&e| e != edge);
+            self.incident[target].retain(|&e| e != edge);
+        }
+    }
+
+    /// Drops dead ends and specks shorter than `min_spur`, then joins the lines through the nodes
+    /// only two of them are left at.
+    fn prune(&mut self, min_spur: f32) {
+        let spurs: Vec<usize> = (0..self.edges.len())
+            .filter(|&e| {
+                self.edges[e].is_some_and(|edge| {
+                    let dead_end = |node: usize| self.incident[node].len() == 1;
+                    edge.length < min_spur
+                        && edge.source != edge.target
+                        && (dead_end(edge.source) || dead_end(edge.target))
+                })
+            })
+            .collect();
+        for edge in spurs {
+            self.remove(edge);
+        }
+        for node in 0..self.nodes.len() {
+            let [a, b] = self.incident[node][..] else {
+                continue;
+            };
+            if a == b {
+                continue;
+            }
+            let (first, second) = (self.edges[a].unwrap(), self.edges[b].unwrap());
+            let other = |edge: Edge| {
+                if edge.source == node {
+                    edge.target
+                } else {
+                    edge.source
+                }
+            };
+            let (source, target) = (other(first), other(second));
+            let length = first.length + second.length;
+            let thickness = (first.thickness * first.length + second.thickness * second.length)
+                / length.max(f32::EPSILON);
+            self.remove(a);
+            self.remove(b);
+            self.add(source, target, length, thickness);
+        }
+    }
+
+    /// Keeps the nodes with edges, numbered in order.
+    fn into_network(self, width: usize, height: usize) -> Network {
+        let mut index = vec![usize::MAX; self.nodes.len()];
+        let mut nodes = vec![];
+        for (i, node) in self.nodes.iter().enumerate() {
+            if !self.incident[i].is_empty() {
+                index[i] = nodes.len();
+                nodes.push(*node);
+            }
+        }
+        let edges = self
+            .edges
+            .into_iter()
+            .flatten()
+            .map(|edge| Edge {
+                source: index[edge.source],
+                target: index[edge.target],
+                ..edge
+            })
+            .collect();
+        Network {
+            width,
+            height,
+            nodes,
+            edges,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A field with lines `thickness` cells wide along `lines` of cell coordinates.
+    fn draw(
+        width: usize,
+        height: usize,
+        thickness: isize,
+        lines: &[[(isize, isize); 2]],
+    ) -> Vec<f32> {
+        let mut field = vec![0.0; width * height];
+        for &[(x0, y0), (x1, y1)] in lines {
+            let steps = (x1 - x0).abs().max((y1 - y0).abs());
+            for s in 0..=steps {
+                let x = x0 + (x1 - x0) * s / steps.max(1);
+                let y = y0 + (y1 - y0) * s / steps.max(1);
+                for dy in -thickness / 2..=(thickness - 1) / 2 {
+                    for dx in -thickness / 2..=(thickness - 1) / 2 {
+                        field[(y + dy) as usize * width + (x + dx) as usize] = 1.0;
+                    }
+                }
+            }
+        }
+        field
+    }
+
+    #[test]
+    fn crossings_become_junctions_joined_by_thick_edges() {
+        let (width, height) = (64, 64);
+        // A plus sign whose arms end away from the wrapped edges.
+        let field = draw(
+            width,
+            height,
+            5,
+            &[[(10, 32), (54, 32)], [(32, 10), (32, 54)]],
+        );
+        let network = Network::extract(&field, width, height, 0.5, 3.0);
+        assert_eq!(network.nodes.len(), 5, "{:?}", network);
+        assert_eq!(network.edges.len(), 4);
+        let center = network.nearest_node(32.5, 32.5).unwrap();
+        let node = network.nodes[center];
+        assert!((node.x - 32.5).abs() < 1.5 && (node.y - 32.5).abs() < 1.5);
+        for edge in &network.edges {
+            assert!(edge.source == center || edge.target == center);
+            assert!((15.0..25.0).contains(&edge.length), "{:?}", edge);
+            assert!((3.0..=6.0).contains(&edge.thickness), "{:?}", edge);
+        }
+
+        let left = network.nearest_node(10.0, 32.0).unwrap();
+        let right = network.nearest_node(54.0, 32.0).unwrap();
+        let (path, length) = network.shortest_path(left, right).unwrap();
+        assert_eq!(path, [left, center, right]);
+        assert!((35.0..50.0).contains(&length));
+
+        let graphml = network.to_graphml();
+        assert_eq!(graphml.matches("<node ").count(), 5);
+        assert_eq!(graphml.matches("<edge ").count(), 4);
+    }
+
+    #[test]
+    fn rings_keep_a_single_node_and_empty_fields_none() {
+        let (width, height) = (48, 48);
+        let field = draw(
+            width,
+            height,
+            3,
+            &[
+                [(10, 10), (38, 10)],
+                [(38, 10), (38, 38)],
+                [(38, 38), (10, 38)],
+                [(10, 38), (10, 10)],
+            ],
+        );
+        let network = Network::extract(&field, width, height, 0.5, 3.0);
+        assert_eq!(network.nodes.len(), 1, "{:?}", network);
+        assert_eq!(network.edges.len(), 1);
+        let edge = network.edges[0];
+        assert_eq!(edge.source, edge.target);
+        assert!((100.0..120.0).contains(&edge.length), "{:?}", edge);
+
+        let empty = Network::extract(&vec![0.0; width * height], width, height, 0.5, 3.0);
+        assert!(empty.nodes.is_empty() && empty.edges.is_empty());
+        assert_eq!(network.shortest_path(0, 0).unwrap().0, [0]);
+    }
+}
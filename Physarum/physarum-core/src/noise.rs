@@ -0,0 +1,190 @@
+//! Seeded, smooth randomness for everything that needs more structure than white noise:
+//! Perlin noise, fractal sums of it over several octaves and their curl. The same seed
+//! and coordinates give the same values everywhere, since `libm` does the math.
+
+use super::par::*;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::f32::consts::TAU;
+use serde::{Deserialize, Serialize};
+
+/// Fractal Brownian motion: octaves of Perlin noise, each finer and fainter than the last.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct Fbm {
+    /// Size in cells of the largest features.
+    pub scale: f32,
+    pub octaves: usize,
+    /// Factor the frequency grows by from one octave to the next.
+    pub lacunarity: f32,
+    /// Factor the amplitude shrinks by from one octave to the next.
+    pub gain: f32,
+    pub seed: u64,
+}
+
+impl Default for Fbm {
+    fn default() -> Self {
+        Fbm {
+            scale: 64.0,
+            octaves: 4,
+            lacunarity: 2.0,
+            gain: 0.5,
+            seed: 0,
+        }
+    }
+}
+
+impl Fbm {
+    pub fn new(scale: f32, octaves: usize, seed: u64) -> Self {
+        Fbm {
+            scale,
+            octaves,
+            seed,
+            ..Fbm::default()
+        }
+    }
+
+    /// The noise at (x, y) in cells, roughly in [-1, 1].
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        self.sum(|frequency, seed| perlin(x * frequency, y * frequency, None, seed))
+    }
+
+    /// The curl of the noise at (x, y), a flow without sources or sinks that swirls
+    /// around its peaks and valleys, in cells per cell of the noise's features.
+    pub fn curl(&self, x: f32, y: f32) -> (f32, f32) {
+        let h = self.scale.max(1.0) * 1e-2;
+        let dx = (self.sample(x + h, y) - self.sample(x - h, y)) / (2.0 * h);
+        let dy = (self.sample(x, y + h) - self.sample(x, y - h)) / (2.0 * h);
+        (dy * self.scale, -dx * self.scale)
+    }
+
+    /// The noise over a `width` by `height` grid, row by row, sampled at the cell centers.
+    /// Each octave's frequency is rounded to a whole number of periods across the grid,
+    /// so that the field tiles when the grid wraps around.
+    pub fn field(&self, width: usize, height: usize) -> Vec<f32> {
+        let mut field = vec![0.0; width * height];
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut frequency = 1.0 / self.scale;
+        for octave in 0..self.octaves {
+            let periods = (
+                libm::roundf(width as f32 * frequency).max(1.0) as usize,
+                libm::roundf(height as f32 * frequency).max(1.0) as usize,
+            );
+            let seed = octave_seed(self.seed, octave);
+            field.par_iter_mut().enumerate().for_each(|(i, value)| {
+                let x = ((i % width) as f32 + 0.5) * periods.0 as f32 / width as f32;
+                let y = ((i / width) as f32 + 0.5) * periods.1 as f32 / height as f32;
+                *value += amplitude * perlin(x, y, Some(periods), seed);
+            });
+            total += amplitude;
+            amplitude *= self.gain;
+            frequency *= self.lacunarity;
+        }
+        let norm = NORMALIZATION / total.max(f32::EPSILON);
+        field.par_iter_mut().for_each(|v| *v *= norm);
+        field
+    }
+
+    fn sum(&self, octave: impl Fn(f32, u64) -> f32) -> f32 {
+        let (mut value, mut total) = (0.0, 0.0);
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0 / self.scale;
+        for k in 0..self.octaves {
+            value += amplitude * octave(frequency, octave_seed(self.seed, k));
+            total += amplitude;
+            amplitude *= self.gain;
+            frequency *= self.lacunarity;
+        }
+        value * NORMALIZATION / total.max(f32::EPSILON)
+    }
+}
+
+/// Stretches 2D Perlin noise, which stays within about ±0.7, to about ±1.
+const NORMALIZATION: f32 = 1.4;
+
+fn octave_seed(seed: u64, octave: usize) -> u64 {
+    hash(seed ^ (octave as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15))
+}
+
+/// Perlin gradient noise at (x, y) in lattice units, 0 at every lattice point. With
+/// `periods` it repeats after that many lattice cells along each axis.
+pub fn perlin(x: f32, y: f32, periods: Option<(usize, usize)>, seed: u64) -> f32 {
+    let (x0, y0) = (libm::floorf(x), libm::floorf(y));
+    let (fx, fy) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let corner = |dx: i64, dy: i64| {
+        let (mut i, mut j) = (x0 + dx, y0 + dy);
+        if let Some((px, py)) = periods {
+            i = i.rem_euclid(px as i64);
+            j = j.rem_euclid(py as i64);
+        }
+        let bits = hash(seed ^ hash((i as u64) << 32 ^ j as u64 & 0xffff_ffff));
+        let angle = TAU * (bits >> 40) as f32 / (1u64 << 24) as f32;
+        let (gx, gy) = (libm::cosf(angle), libm::sinf(angle));
+        gx * (fx - dx as f32) + gy * (fy - dy as f32)
+    };
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let (u, v) = (fade(fx), fade(fy));
+    let top = corner(0, 0) + u * (corner(1, 0) - corner(0, 0));
+    let bottom = corner(0, 1) + u * (corner(1, 1) - corner(0, 1));
+    top + v * (bottom - top)
+}
+
+/// White noise in [0, 1) at `index`, without a generator to carry around.
+pub fn white(seed: u64, index: u64) -> f32 {
+    (hash(seed ^ hash(index)) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// SplitMix64's finalizer, spreading every bit of `x` over the whole result.
+pub fn hash(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_tile_and_match_their_samples_in_range() {
+        let fbm = Fbm::new(16.0, 3, 7);
+        let (width, height) = (64, 32);
+        let field = fbm.field(width, height);
+        assert!(field.iter().all(|v| v.abs() <= 1.5));
+        assert!(field.iter().any(|&v| v > 0.3) && field.iter().any(|&v| v < -0.3));
+        // Neighbours across the wrapped edges are as close as neighbours inside.
+        let step = |a: usize, b: usize| (field[a] - field[b]).abs();
+        let across: f32 = (0..height)
+            .map(|y| step(y * width, y * width + width - 1))
+            .sum();
+        let inside: f32 = (0..height)
+            .map(|y| step(y * width + 31, y * width + 32))
+            .sum();
+        assert!(across < 3.0 * inside + 0.1);
+        assert_ne!(Fbm { seed: 8, ..fbm }.field(width, height), field);
+        assert_eq!(fbm.field(width, height), field);
+
+        // Scales dividing the grid evenly need no rounding, so away from the wrapped
+        // lattice cells the field is the noise.
+        let sampled = fbm.sample(10.5, 5.5);
+        assert!((sampled - field[5 * width + 10]).abs() < 1e-5);
+        assert_eq!(perlin(3.0, 5.0, None, 1), 0.0);
+        assert!((0.0..1.0).contains(&white(1, 2)));
+    }
+
+    #[test]
+    fn curl_flows_along_the_contours() {
+        let fbm = Fbm::new(32.0, 2, 3);
+        for (x, y) in [(3.0, 4.0), (40.5, 17.25), (100.0, 2.0)] {
+            let (u, v) = fbm.curl(x, y);
+            let h = 1e-2;
+            let along = fbm.sample(x + u * h, y + v * h) - fbm.sample(x, y);
+            let (gx, gy) = (v, -u);
+            let across = fbm.sample(x + gx * h, y + gy * h) - fbm.sample(x, y);
+            assert!(along.abs() < 0.1 * across.abs() + 1e-6);
+        }
+    }
+}
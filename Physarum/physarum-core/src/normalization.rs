@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+/// How field values are mapped to brightness when rendering.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Normalization {
+    /// Full brightness at `scale` times the `fraction` quantile of each frame. Sharp, but
+    /// flickers in videos when the tail of the distribution jumps.
+    Percentile { fraction: f32, scale: f32 },
+    /// Full brightness at a constant value, steady but needs tuning per config.
+    Fixed { max: f32 },
+    /// Full brightness at the largest value seen, forgotten by `decay` per step.
+    RunningMax { decay: f32 },
+    /// Full brightness at `exposure` times the frame's mean value.
+    MeanExposure { exposure: f32 },
+    /// Every brightness level covers the same number of cells. Always composited on the CPU.
+    HistogramEqualization,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Normalization::Percentile {
+            fraction: 0.999,
+            scale: 1.5,
+        }
+    }
+}
+
+/// Per-layer maxima of `Normalization::RunningMax` and the iteration they were updated at.
+#[derive(Default)]
+pub(super) struct RunningMax {
+    iteration: Option<u64>,
+    values: Vec<f32>,
+}
+
+impl RunningMax {
+    pub(super) fn update(&mut self, iteration: u64, maxima: &[f32], decay: f32) -> Vec<f32> {
+        if self.values.len() != maxima.len() {
+            self.values = maxima.to_vec();
+        }
+        let age = self
+            .iteration
+            .map_or(0, |cached| iteration.abs_diff(cached));
+        let factor = decay.clamp(0.0, 1.0).powi(age as i32);
+        for (value, max) in self.values.iter_mut().zip(maxima) {
+            *value = (*value * factor).max(*max);
+        }
+        self.iteration = Some(iteration);
+        self.values.clone()
+    }
+}
+
+/// Exponential moving average of the per-layer maxima, to keep exported videos from
+/// flickering when the normalization jumps between frames.
+#[derive(Default)]
+pub(super) struct Smoothing {
+    iteration: Option<u64>,
+    values: Vec<f32>,
+}
+
+impl Smoothing {
+    /// Blends `values` into the average, keeping `smoothing` of the old average per step.
+    pub(super) fn update(&mut self, iteration: u64, values: &[f32], smoothing: f32) -> Vec<f32> {
+        if self.values.len() != values.len() {
+            self.values = values.to_vec();
+        }
+        let age = self
+            .iteration
+            .map_or(0, |cached| iteration.abs_diff(cached));
+        let keep = smoothing.clamp(0.0, 1.0).powi(age as i32);
+        for (average, value) in self.values.iter_mut().zip(values) {
+            *average = *average * keep + value * (1.0 - keep);
+        }
+        self.iteration = Some(iteration);
+        self.values.clone()
+    }
+}
+
+pub(super) fn max(field: &[f32]) -> f32 {
+    field.iter().cloned().fold(0.0, f32::max)
+}
+
+pub(super) fn mean(field: &[f32]) -> f32 {
+    field.iter().sum::<f32>() / field.len().max(1) as f32
+}
+
+/// Maps every value to the fraction of `field` below it, estimating the distribution
+/// from `samples` evenly strided cells when nonzero.
+pub(super) fn equalize(field: &[f32], samples: usize) -> Vec<f32> {
+    let stride = if samples == 0 || samples >= field.len() {
+        1
+    } else {
+        (field.len() / samples) | 1
+    };
+    let mut sorted: Vec<f32> = field.iter().step_by(stride).cloned().collect();
+    sorted.sort_unstable_by(f32::total_cmp);
+    let len = sorted.len() as f32;
+    field
+        .iter()
+        .map(|&v| sorted.partition_point(|&x| x < v) as f32 / len)
+        .collect()
+}
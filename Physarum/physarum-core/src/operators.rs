@@ -0,0 +1,311 @@
+//! How agents lay their trails down and how the trails spread. Each population deposits
+//! through a `DepositOp` and diffuses through a `DiffuseOp`, chosen by name in
+//! `SimulationConfig::deposit_ops` and `diffuse_ops`: built-in operators, or with std ones
+//! added through `register_deposit` and `register_diffuse`. Populations without one
+//! deposit and blur like they always have.
+
+use super::agents::Particle;
+use super::blur::Blur;
+use super::error::ConfigError;
+use super::grid;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::{collections::BTreeMap, sync::RwLock};
+
+/// The built-in deposit operators, the first one being how populations without one deposit.
+pub const DEPOSIT_BUILT_IN: [&str; 2] = ["point", "smear"];
+/// The built-in diffuse operators, the first one being how populations without one diffuse.
+pub const DIFFUSE_BUILT_IN: [&str; 3] = ["blur", "horizontal", "vertical"];
+
+/// The trails of one population as operators see them, wrapping around the edges.
+pub struct Trail<'a> {
+    pub width: usize,
+    pub height: usize,
+    /// The trail of every cell, row by row.
+    pub data: &'a mut [f32],
+    /// As many cells as `data` that operators may use as they like, e.g. for a copy of the
+    /// trails while diffusing them. What they hold beforehand is garbage.
+    pub scratch: &'a mut [f32],
+    blur: &'a mut Blur,
+}
+
+impl<'a> Trail<'a> {
+    pub(crate) fn new(
+        width: usize,
+        height: usize,
+        data: &'a mut [f32],
+        scratch: &'a mut [f32],
+        blur: &'a mut Blur,
+    ) -> Self {
+        Trail {
+            width,
+            height,
+            data,
+            scratch,
+            blur,
+        }
+    }
+
+    /// The index in `data` of the cell containing (x, y), wrapping around the edges.
+    pub fn cell(&self, x: f32, y: f32) -> usize {
+        grid::cell(self.width, self.height, x, y)
+    }
+
+    /// Adds `amount` of trail at (x, y), negative amounts erasing it down to nothing.
+    pub fn mark(&mut self, x: f32, y: f32, amount: f32) {
+        let idx = self.cell(x, y);
+        self.data[idx] = (self.data[idx] + amount).max(0.0);
+    }
+
+    /// The Gaussian blur populations diffuse by, over `sigma` cells and multiplied by
+    /// `decay`. Uses `scratch`.
+    pub fn blur(&mut self, sigma: f32, decay: f32) {
+        self.blur.run(
+            self.data,
+            self.scratch,
+            self.width,
+            self.height,
+            sigma,
+            decay,
+        );
+    }
+}
+
+/// Lays down trail where an agent passes. Called for every point of every agent's path,
+/// in order, once for each grid it deposits into.
+pub trait DepositOp: Send + Sync {
+    /// Deposits `amount` into `trail` for `agent` passing (x, y). The amount is already
+    /// scaled to the time step and split over substeps, and negative for erasing trails.
+    fn deposit(&self, trail: &mut Trail, agent: &Particle, x: f32, y: f32, amount: f32);
+}
+
+/// Spreads and decays a population's trails once every step. Grids diffuse in parallel,
+/// so operators are called from many threads at once.
+pub trait DiffuseOp: Send + Sync {
+    /// Spreads `trail` by the model's diffusivity `radius` and multiplies it by `decay`,
+    /// the population's `decay_factor` scaled to the time step.
+    fn diffuse(&self, trail: &mut Trail, radius: usize, decay: f32);
+}
+
+/// All of the amount into the cell the agent passes.
+pub struct Point;
+
+impl DepositOp for Point {
+    fn deposit(&self, trail: &mut Trail, _: &Particle, x: f32, y: f32, amount: f32) {
+        trail.mark(x, y, amount);
+    }
+}
+
+/// Smears the amount evenly over `length` cells back along the agent's heading, making
+/// thin agents draw long strokes.
+pub struct Smear {
+    pub length: usize,
+}
+
+impl DepositOp for Smear {
+    fn deposit(&self, trail: &mut Trail, agent: &Particle, x: f32, y: f32, amount: f32) {
+        let length = self.length.max(1);
+        let (dx, dy) = (libm::cosf(agent.angle), libm::sinf(agent.angle));
+        let share = amount / length as f32;
+        for i in 0..length {
+            trail.mark(x - dx * i as f32, y - dy * i as f32, share);
+        }
+    }
+}
+
+/// The Gaussian blur every population diffuses by without an operator.
+pub struct GaussianBlur;
+
+impl DiffuseOp for GaussianBlur {
+    fn diffuse(&self, trail: &mut Trail, radius: usize, decay: f32) {
+        trail.blur(radius as f32, decay);
+    }
+}
+
+/// Spreads the trails along one axis only, over a box `radius` cells to either side,
+/// which pulls the networks into streaks.
+pub struct AxisBlur {
+    pub vertical: bool,
+}
+
+impl DiffuseOp for AxisBlur {
+    fn diffuse(&self, trail: &mut Trail, radius: usize, decay: f32) {
+        let (width, height) = (trail.width, trail.height);
+        let (lines, len) = if self.vertical {
+            (width, height)
+        } else {
+            (height, width)
+        };
+        let at = |line: usize, i: usize| {
+            if self.vertical {
+                i * width + line
+            } else {
+                line * width + i
+            }
+        };
+        let radius = radius.min((len - 1) / 2);
+        let scale = decay / (2 * radius + 1) as f32;
+        trail.scratch.copy_from_slice(trail.data);
+        let src = &*trail.scratch;
+        for line in 0..lines {
+            let mut sum: f32 = (0..=2 * radius)
+                .map(|i| src[at(line, (i + len - radius) % len)])
+                .sum();
+            for i in 0..len {
+                trail.data[at(line, i)] = sum * scale;
+                let (entering, leaving) = ((i + radius + 1) % len, (i + len - radius) % len);
+                sum += src[at(line, entering)] - src[at(line, leaving)];
+            }
+        }
+    }
+}
+
+fn deposit_built_in(name: &str) -> Option<Arc<dyn DepositOp>> {
+    match name {
+        "point" => Some(Arc::new(Point)),
+        "smear" => Some(Arc::new(Smear { length: 3 })),
+        _ => None,
+    }
+}
+
+fn diffuse_built_in(name: &str) -> Option<Arc<dyn DiffuseOp>> {
+    match name {
+        "blur" => Some(Arc::new(GaussianBlur)),
+        "horizontal" => Some(Arc::new(AxisBlur { vertical: false })),
+        "vertical" => Some(Arc::new(AxisBlur { vertical: true })),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "std")]
+static DEPOSIT_OPS: RwLock<BTreeMap<String, Arc<dyn DepositOp>>> = RwLock::new(BTreeMap::new());
+#[cfg(feature = "std")]
+static DIFFUSE_OPS: RwLock<BTreeMap<String, Arc<dyn DiffuseOp>>> = RwLock::new(BTreeMap::new());
+
+/// Makes `op` available to configs as `name`, replacing any deposit operator registered
+/// under it before. The built-in operators can't be replaced.
+#[cfg(feature = "std")]
+pub fn register_deposit(name: &str, op: impl DepositOp + 'static) {
+    DEPOSIT_OPS
+        .write()
+        .unwrap()
+        .insert(name.into(), Arc::new(op));
+}
+
+/// Like `register_deposit`, for diffuse operators.
+#[cfg(feature = "std")]
+pub fn register_diffuse(name: &str, op: impl DiffuseOp + 'static) {
+    DIFFUSE_OPS
+        .write()
+        .unwrap()
+        .insert(name.into(), Arc::new(op));
+}
+
+/// The deposit operator called `name`, built in or registered.
+pub fn deposit_by_name(name: &str) -> Option<Arc<dyn DepositOp>> {
+    #[cfg(feature = "std")]
+    {
+        deposit_built_in(name).or_else(|| DEPOSIT_OPS.read().unwrap().get(name).cloned())
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        deposit_built_in(name)
+    }
+}
+
+/// The diffuse operator called `name`, built in or registered.
+pub fn diffuse_by_name(name: &str) -> Option<Arc<dyn DiffuseOp>> {
+    #[cfg(feature = "std")]
+    {
+        diffuse_built_in(name).or_else(|| DIFFUSE_OPS.read().unwrap().get(name).cloned())
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        diffuse_built_in(name)
+    }
+}
+
+/// The deposit operators of every population, by name.
+pub fn resolve_deposit(names: &[String]) -> Result<Vec<Arc<dyn DepositOp>>, ConfigError> {
+    names
+        .iter()
+        .map(|name| {
+            deposit_by_name(name)
+                .ok_or_else(|| ConfigError::UnknownDepositOp { name: name.clone() })
+        })
+        .collect()
+}
+
+/// The diffuse operators of every population, by name.
+pub fn resolve_diffuse(names: &[String]) -> Result<Vec<Arc<dyn DiffuseOp>>, ConfigError> {
+    names
+        .iter()
+        .map(|name| {
+            diffuse_by_name(name)
+                .ok_or_else(|| ConfigError::UnknownDiffuseOp { name: name.clone() })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn with_trail(data: &mut [f32], f: impl FnOnce(&mut Trail)) {
+        let mut scratch = vec![0.0; data.len()];
+        let mut blur = Blur::new(8);
+        f(&mut Trail::new(8, 8, data, &mut scratch, &mut blur));
+    }
+
+    #[test]
+    fn smears_spread_the_amount_behind_the_agent() {
+        let agent = Particle {
+            x: 4.5,
+            y: 2.5,
+            angle: 0.0,
+            id: 0,
+        };
+        let mut data = vec![0.0; 64];
+        with_trail(&mut data, |trail| {
+            Smear { length: 3 }.deposit(trail, &agent, agent.x, agent.y, 6.0)
+        });
+        assert_eq!(data[2 * 8..3 * 8], [0.0, 0.0, 2.0, 2.0, 2.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn axis_blurs_only_spread_along_their_axis() {
+        let mut data = vec![0.0; 64];
+        data[3 * 8 + 3] = 9.0;
+        with_trail(&mut data, |trail| {
+            AxisBlur { vertical: true }.diffuse(trail, 1, 0.5)
+        });
+        let spread: Vec<usize> = (0..64).filter(|&i| data[i] != 0.0).collect();
+        assert_eq!(spread, [2 * 8 + 3, 3 * 8 + 3, 4 * 8 + 3]);
+        assert!((data.iter().sum::<f32>() - 4.5).abs() < 1e-5);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn registered_operators_resolve_by_name() {
+        struct Nothing;
+
+        impl DiffuseOp for Nothing {
+            fn diffuse(&self, _: &mut Trail, _: usize, _: f32) {}
+        }
+
+        register_diffuse("nothing", Nothing);
+        assert_eq!(
+            resolve_diffuse(&["vertical".into(), "nothing".into()])
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(matches!(
+            resolve_deposit(&["nothing".into()]),
+            Err(ConfigError::UnknownDepositOp { .. })
+        ));
+    }
+}
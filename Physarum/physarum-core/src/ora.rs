@@ -0,0 +1,142 @@
+//! OpenRaster files, the layered format shared by Krita, GIMP and MyPaint: a zip of PNG
+//! layers described by `stack.xml`.
+
+use image::imageops::{self, FilterType};
+use image::{ImageOutputFormat, RgbaImage};
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// How a layer or group is combined with what lies below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Composite {
+    Over,
+    Plus,
+    Screen,
+    Multiply,
+    Difference,
+}
+
+impl Composite {
+    fn op(self) -> &'static str {
+        match self {
+            Composite::Over => "svg:src-over",
+            Composite::Plus => "svg:plus",
+            Composite::Screen => "svg:screen",
+            Composite::Multiply => "svg:multiply",
+            Composite::Difference => "svg:difference",
+        }
+    }
+}
+
+pub enum Node {
+    Layer {
+        name: String,
+        image: RgbaImage,
+        composite: Composite,
+    },
+    /// Layers flattened together before being composited as one.
+    Group {
+        name: String,
+        children: Vec<Node>,
+        composite: Composite,
+    },
+}
+
+/// Writes `nodes`, topmost first, with `merged` as the flattened preview shown by viewers
+/// that don't understand layers.
+pub fn save(path: &Path, nodes: &[Node], merged: &RgbaImage) -> io::Result<()> {
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(path)?));
+    // PNGs are compressed already, and the mimetype has to be stored uncompressed anyway.
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    zip.start_file("mimetype", options)?;
+    zip.write_all(b"image/openraster")?;
+
+    let mut stack = format!(
+        "<?xml version='1.0' encoding='UTF-8'?>\n<image version=\"0.0.3\" w=\"{}\" h=\"{}\">\n<stack>\n",
+        merged.width(),
+        merged.height()
+    );
+    let mut layers = 0;
+    write_nodes(&mut zip, options, nodes, &mut stack, &mut layers)?;
+    stack += "</stack>\n</image>\n";
+    zip.start_file("stack.xml", options)?;
+    zip.write_all(stack.as_bytes())?;
+
+    zip.start_file("mergedimage.png", options)?;
+    zip.write_all(&png(merged)?)?;
+    let scale = (THUMBNAIL_SIZE as f32 / merged.width().max(merged.height()) as f32).min(1.0);
+    let thumbnail = imageops::resize(
+        merged,
+        ((merged.width() as f32 * scale) as u32).max(1),
+        ((merged.height() as f32 * scale) as u32).max(1),
+        FilterType::Triangle,
+    );
+    zip.start_file("Thumbnails/thumbnail.png", options)?;
+    zip.write_all(&png(&thumbnail)?)?;
+
+    zip.finish()?.flush()
+}
+
+fn write_nodes<W: Write + io::Seek>(
+    zip: &mut ZipWriter<W>,
+    options: FileOptions,
+    nodes: &[Node],
+    stack: &mut String,
+    layers: &mut usize,
+) -> io::Result<()> {
+    for node in nodes {
+        match node {
+            Node::Layer {
+                name,
+                image,
+                composite,
+            } => {
+                let src = format!("data/layer{}.png", layers);
+                *layers += 1;
+                *stack += &format!(
+                    "<layer name=\"{}\" src=\"{}\" composite-op=\"{}\" opacity=\"1.0\" visibility=\"visible\" x=\"0\" y=\"0\"/>\n",
+                    escape(name),
+                    src,
+                    composite.op()
+                );
+                zip.start_file(src, options)?;
+                zip.write_all(&png(image)?)?;
+            }
+            Node::Group {
+                name,
+                children,
+                composite,
+            } => {
+                *stack += &format!(
+                    "<stack name=\"{}\" composite-op=\"{}\" isolation=\"isolate\" opacity=\"1.0\" visibility=\"visible\">\n",
+                    escape(name),
+                    composite.op()
+                );
+                write_nodes(zip, options, children, stack, layers)?;
+                *stack += "</stack>\n";
+            }
+        }
+    }
+    Ok(())
+}
+
+fn png(image: &RgbaImage) -> io::Result<Vec<u8>> {
+    let mut bytes = Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgba8(image.clone())
+        .write_to(&mut bytes, ImageOutputFormat::Png)
+        .map_err(io::Error::other)?;
+    Ok(bytes.into_inner())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
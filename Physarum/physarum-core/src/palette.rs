@@ -0,0 +1,234 @@
+#[cfg(not(any(feature = "std", test)))]
+use super::math::Float;
+use super::util::hex_color;
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+#[cfg(feature = "image-export")]
+pub use image::Rgb;
+
+/// Stand-in for `Rgb` when building without the `image-export` feature.
+#[cfg(not(feature = "image-export"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rgb<T>(pub [T; 3]);
+
+#[derive(Clone, Copy, Debug)]
+pub struct Palette {
+    pub colors: [Rgb<u8>; 5],
+}
+
+impl Palette {
+    /// Color of population `i`. Populations beyond the palette's five colors get
+    /// generated ones, blended between neighbouring palette colors at golden-ratio
+    /// offsets so that each new color lands away from the previous ones.
+    pub fn color(&self, i: usize) -> Rgb<u8> {
+        let n = self.colors.len();
+        if i < n {
+            return self.colors[i];
+        }
+        let t = ((i / n) as f32 * 0.618_034).fract();
+        let (a, b) = (self.colors[i % n].0, self.colors[(i + 1) % n].0);
+        Rgb([0, 1, 2].map(|c| (a[c] as f32 * (1.0 - t) + b[c] as f32 * t) as u8))
+    }
+
+    /// Looks up one of the built-in palettes by its name in `PALETTE_NAMES`, e.g.
+    /// `Palette::by_name("viridis")`.
+    pub fn by_name(name: &str) -> Option<Palette> {
+        PALETTE_NAMES
+            .iter()
+            .position(|&n| n == name)
+            .map(|i| PALETTE_ARRAY[i])
+    }
+}
+
+pub fn random_palette(rng: &mut SmallRng) -> Palette {
+    let mut palette = PALETTE_ARRAY[rng.gen_range(0..PALETTE_ARRAY.len())];
+    palette.colors.shuffle(rng);
+    palette
+}
+
+/// Names of the palettes in `PALETTE_ARRAY`, in the same order.
+pub const PALETTE_NAMES: [&str; 17] = [
+    "sunrise",
+    "meadow",
+    "orchid",
+    "lagoon",
+    "dusk",
+    "autumn",
+    "ember",
+    "forest",
+    "rainbow",
+    "tropic",
+    "viridis",
+    "magma",
+    "turbo",
+    "okabe_ito",
+    "ibm",
+    "tol_bright",
+    "cividis",
+];
+
+/// Palettes whose colors stay distinct with protanopia and deuteranopia, for pieces shown
+/// to a wide audience. `ColorVision` previews how any palette looks to those viewers.
+pub const COLORBLIND_SAFE: [&str; 5] = ["viridis", "okabe_ito", "ibm", "tol_bright", "cividis"];
+
+/// The curated palettes, followed by evenly spaced samples of the perceptually uniform maps
+/// from matplotlib and Google's turbo. The near-black ends of magma and turbo are skipped,
+/// since a population in them would vanish into the background. Last come the colorblind
+/// safe sets of Okabe and Ito, IBM's design library, Paul Tol and cividis.
+pub const PALETTE_ARRAY: [Palette; 17] = [
+    Palette {
+        colors: [
+            hex_color(0xFA2B31),
+            hex_color(0xFFBF1F),
+            hex_color(0xFFF146),
+            hex_color(0xABE319),
+            hex_color(0x00C481),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0x8fb2f7),
+            hex_color(0x1F8A70),
+            hex_color(0xBEDB39),
+            hex_color(0xFFE11A),
+            hex_color(0xFD7400),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0xfac287),
+            hex_color(0x9d1df2),
+            hex_color(0xBEDB39),
+            hex_color(0xFFE11A),
+            hex_color(0xFD7400),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0x4dc6eb),
+            hex_color(0x3be36b),
+            hex_color(0xBEDB39),
+            hex_color(0xFFE11A),
+            hex_color(0xFD7400),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0x334D5C),
+            hex_color(0x45B29D),
+            hex_color(0xEFC94C),
+            hex_color(0xE27A3F),
+            hex_color(0xDF5A49),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0xFF8000),
+            hex_color(0xFFD933),
+            hex_color(0xCCCC52),
+            hex_color(0x8FB359),
+            hex_color(0x192B33),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0x730046),
+            hex_color(0xBFBB11),
+            hex_color(0xFFC200),
+            hex_color(0xE88801),
+            hex_color(0xC93C00),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0xE6DD00),
+            hex_color(0x8CB302),
+            hex_color(0x008C74),
+            hex_color(0x004C66),
+            hex_color(0x332B40),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0xF15A5A),
+            hex_color(0xF0C419),
+            hex_color(0x4EBA6F),
+            hex_color(0x2D95BF),
+            hex_color(0x955BA5),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0xF41C54),
+            hex_color(0xFF9F00),
+            hex_color(0xFBD506),
+            hex_color(0xA8BF12),
+            hex_color(0x00AAB5),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0x440154),
+            hex_color(0x3B528B),
+            hex_color(0x21918C),
+            hex_color(0x5EC962),
+            hex_color(0xFDE725),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0x3B0F70),
+            hex_color(0x8C2981),
+            hex_color(0xDE4968),
+            hex_color(0xFE9F6D),
+            hex_color(0xFCFDBF),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0x4958DD),
+            hex_color(0x27D7C3),
+            hex_color(0x96FA50),
+            hex_color(0xFFA323),
+            hex_color(0xB82008),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0xE69F00),
+            hex_color(0x56B4E9),
+            hex_color(0x009E73),
+            hex_color(0xF0E442),
+            hex_color(0xCC79A7),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0x648FFF),
+            hex_color(0x785EF0),
+            hex_color(0xDC267F),
+            hex_color(0xFE6100),
+            hex_color(0xFFB000),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0x4477AA),
+            hex_color(0xEE6677),
+            hex_color(0x228833),
+            hex_color(0xCCBB44),
+            hex_color(0x66CCEE),
+        ],
+    },
+    Palette {
+        colors: [
+            hex_color(0x00224E),
+            hex_color(0x414D6B),
+            hex_color(0x7C7B78),
+            hex_color(0xBCAF6F),
+            hex_color(0xFEE838),
+        ],
+    },
+];
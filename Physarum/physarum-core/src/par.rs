@@ -0,0 +1,56 @@
+//! Rayon's parallel iterators with the `parallel` feature, otherwise sequential stand-ins
+//! under the same method names so the simulation code reads the same either way.
+
+#[cfg(feature = "parallel")]
+pub(crate) use rayon::prelude::*;
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) use sequential::*;
+
+#[cfg(not(feature = "parallel"))]
+mod sequential {
+    use core::slice::{ChunksExact, ChunksExactMut, Iter, IterMut};
+
+    pub trait ParallelSlice<T> {
+        fn par_iter(&self) -> Iter<'_, T>;
+        fn par_chunks_exact(&self, chunk_size: usize) -> ChunksExact<'_, T>;
+    }
+
+    impl<T> ParallelSlice<T> for [T] {
+        fn par_iter(&self) -> Iter<'_, T> {
+            self.iter()
+        }
+
+        fn par_chunks_exact(&self, chunk_size: usize) -> ChunksExact<'_, T> {
+            self.chunks_exact(chunk_size)
+        }
+    }
+
+    pub trait ParallelSliceMut<T> {
+        fn par_iter_mut(&mut self) -> IterMut<'_, T>;
+        fn par_chunks_exact_mut(&mut self, chunk_size: usize) -> ChunksExactMut<'_, T>;
+        fn par_sort_by_key<K: Ord>(&mut self, f: impl FnMut(&T) -> K);
+    }
+
+    impl<T> ParallelSliceMut<T> for [T] {
+        fn par_iter_mut(&mut self) -> IterMut<'_, T> {
+            self.iter_mut()
+        }
+
+        fn par_chunks_exact_mut(&mut self, chunk_size: usize) -> ChunksExactMut<'_, T> {
+            self.chunks_exact_mut(chunk_size)
+        }
+
+        fn par_sort_by_key<K: Ord>(&mut self, f: impl FnMut(&T) -> K) {
+            self.sort_by_key(f)
+        }
+    }
+
+    pub trait IntoParallelIterator: IntoIterator + Sized {
+        fn into_par_iter(self) -> Self::IntoIter {
+            self.into_iter()
+        }
+    }
+
+    impl<I: IntoIterator> IntoParallelIterator for I {}
+}
@@ -0,0 +1,83 @@
+use super::math::Math;
+use super::util;
+use core::f32::consts::TAU;
+use rand::{rngs::SmallRng, Rng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Particle {
+    pub x: f32,
+    pub y: f32,
+    pub angle: f32,
+    pub id: usize,
+}
+
+impl Particle {
+    pub fn new(width: usize, height: usize, id: usize, rng: &mut SmallRng) -> Self {
+        let (x, y, angle) = rng.gen::<(f32, f32, f32)>();
+        Particle {
+            x: x * width as f32,
+            y: y * height as f32,
+            angle: angle * TAU,
+            id,
+        }
+    }
+
+    /// Turns by `strength` times the sine of the angle to `heading`, so agents facing away
+    /// turn back the fastest and agents already heading there stay on course.
+    pub(crate) fn steer_towards(&mut self, heading: f32, strength: f32, math: Math) {
+        if strength != 0.0 {
+            self.angle += strength * (math.sin)(heading - self.angle);
+        }
+    }
+
+    pub(crate) fn rotate_and_move(
+        &mut self,
+        direction: f32,
+        rotation_angle: f32,
+        step_distance: f32,
+        width: usize,
+        height: usize,
+        math: Math,
+    ) {
+        let delta_angle = rotation_angle * direction;
+        self.angle = util::wrap(self.angle + delta_angle, TAU);
+        self.x = util::wrap(
+            self.x + step_distance * (math.cos)(self.angle),
+            width as f32,
+        );
+        self.y = util::wrap(
+            self.y + step_distance * (math.sin)(self.angle),
+            height as f32,
+        );
+    }
+}
+
+/// Interleaves the bits of the coordinates, `x` in the even bits.
+pub(crate) fn morton(x: u32, y: u32) -> u64 {
+    fn spread(v: u32) -> u64 {
+        let mut v = v as u64;
+        v = (v | v << 16) & 0x0000_ffff_0000_ffff;
+        v = (v | v << 8) & 0x00ff_00ff_00ff_00ff;
+        v = (v | v << 4) & 0x0f0f_0f0f_0f0f_0f0f;
+        v = (v | v << 2) & 0x3333_3333_3333_3333;
+        (v | v << 1) & 0x5555_5555_5555_5555
+    }
+    spread(x) | spread(y) << 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn morton_order_visits_quadrants_in_turn() {
+        let codes: Vec<u64> = [(0, 0), (1, 0), (0, 1), (1, 1), (2, 0), (0, 2), (3, 3)]
+            .iter()
+            .map(|&(x, y)| morton(x, y))
+            .collect();
+        assert_eq!(codes, [0, 1, 2, 3, 4, 8, 15]);
+        assert_eq!(morton(u32::MAX, u32::MAX), u64::MAX);
+    }
+}
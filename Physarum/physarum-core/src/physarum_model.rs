@@ -0,0 +1,2117 @@
+use super::agents::Agents;
+use super::annealing::Annealing;
+use super::background::BackgroundFill;
+#[cfg(feature = "image-export")]
+use super::background::BlendMode;
+use super::bandpass::Bandpass;
+use super::blur::Blur;
+use super::builder::PhysarumModelBuilder;
+use super::cities::{City, CityScent, CitySettings};
+use super::color_vision::ColorVision;
+use super::contour;
+use super::crowding::Crowding;
+use super::distr::{Distribution, Normal};
+#[cfg(feature = "image-export")]
+use super::dither::{self, DitherMethod};
+use super::error::ConfigError;
+use super::flow;
+use super::food::{Food, FoodSource, Pantry};
+use super::grid::{self, Grid};
+use super::heat::{Heat, HeatField};
+#[cfg(feature = "image-export")]
+use super::hooks::Export;
+use super::hooks::{Hooks, Parameter};
+#[cfg(feature = "image-export")]
+use super::info_strip;
+use super::initial::InitialTrails;
+use super::lut::Lut;
+use super::mask::MaskShape;
+use super::math::Math;
+use super::matrix::Matrix;
+use super::network::{Network, NetworkSettings};
+use super::normalization::{self, Normalization, RunningMax, Smoothing};
+use super::operators::{self, DepositOp, DiffuseOp};
+#[cfg(feature = "image-export")]
+use super::ora;
+use super::palette::{Palette, Rgb};
+use super::par::*;
+use super::particle::Particle;
+use super::population_config::PopulationConfig;
+#[cfg(feature = "image-export")]
+use super::postprocess;
+#[cfg(feature = "image-export")]
+use super::print;
+use super::probe::{Probe, ProbedAgent};
+use super::quantile::{self, QuantileCache};
+use super::render::{self, Accumulator, LayerBlend, LayerMode, PopulationColor, RenderSettings};
+use super::sensing::{self, Sensing};
+use super::spacing::Spacing;
+use super::state::{ModelState, STATE_VERSION};
+use super::statistics::{AgentStatistics, GridStatistics};
+use super::steering::{self, SteeringRule};
+use super::step::{self, StepOptions};
+use super::stipple;
+use super::util;
+#[cfg(feature = "image-export")]
+use image::{DynamicImage, GenericImage, GrayImage, Luma, RgbImage, Rgba, RgbaImage};
+use rand::{rngs::SmallRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub struct PhysarumModel {
+    pub grids: Vec<Grid>,
+    agents: Agents,
+    attraction_table: Vec<Vec<f32>>,
+    diffusity: usize,
+    bandpass: Option<Bandpass>,
+    sensing: Vec<Sensing>,
+    /// The names `steering_rules` were resolved from.
+    steering: Vec<String>,
+    steering_rules: Vec<Arc<dyn SteeringRule>>,
+    deposition_table: Option<Vec<Vec<f32>>>,
+    /// The names `deposit_operators` and `diffuse_operators` were resolved from.
+    deposit_ops: Vec<String>,
+    deposit_operators: Vec<Arc<dyn DepositOp>>,
+    diffuse_ops: Vec<String>,
+    diffuse_operators: Vec<Arc<dyn DiffuseOp>>,
+    time_scale: f32,
+    annealing: Option<Annealing>,
+    crowding: Option<Crowding>,
+    spacing: Option<Spacing>,
+    heat: Option<HeatField>,
+    food: Option<Pantry>,
+    cities: Option<CityScent>,
+    iteration: u64,
+    /// When the model was created or restored, for `runtime`.
+    started: Instant,
+    palette: Palette,
+    render_settings: RenderSettings,
+    lut: Option<Lut>,
+    /// `RenderSettings::background` rendered at the grid's size.
+    background: Option<Vec<[f32; 3]>>,
+    /// The coverage of `RenderSettings::mask` at the grid's size, and the cells inside it
+    /// when it confines the agents.
+    mask: Option<Vec<f32>>,
+    confinement: Option<Vec<bool>>,
+    quantile_cache: Mutex<QuantileCache>,
+    running_max: Mutex<RunningMax>,
+    smoothing: Mutex<Smoothing>,
+    accumulator: Mutex<Accumulator>,
+    /// Behind a lock since frames are exported through `&self`.
+    hooks: Mutex<Hooks>,
+    strict_determinism: bool,
+    fast_math: bool,
+    agent_sort_interval: usize,
+}
+
+/// Normal distributions the random attraction tables are drawn from.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct AttractionDistribution {
+    /// How much a population follows its own trails.
+    pub attraction_mean: f32,
+    pub attraction_std: f32,
+    /// How much a population follows the trails of the others, negative to avoid them.
+    pub repulsion_mean: f32,
+    pub repulsion_std: f32,
+}
+
+impl Default for AttractionDistribution {
+    fn default() -> Self {
+        AttractionDistribution {
+            attraction_mean: 1.0,
+            attraction_std: 0.1,
+            repulsion_mean: -1.0,
+            repulsion_std: 0.1,
+        }
+    }
+}
+
+impl AttractionDistribution {
+    pub fn is_valid(&self) -> bool {
+        let finite = [self.attraction_mean, self.repulsion_mean]
+            .iter()
+            .all(|mean| mean.is_finite());
+        let spread = [self.attraction_std, self.repulsion_std]
+            .iter()
+            .all(|std| std.is_finite() && *std >= 0.0);
+        finite && spread
+    }
+}
+
+/// Where the attraction entries of a population joining with `PhysarumModel::add_population`
+/// come from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NewAttraction {
+    /// Drawn like those of a random attraction table, from a distribution that must pass
+    /// `AttractionDistribution::is_valid`.
+    Sampled(AttractionDistribution),
+    /// `row[j]` is how much the new population follows the trails of population `j`, its
+    /// own last, and `column[i]` how much the existing population `i` follows its trails.
+    Supplied { row: Vec<f32>, column: Vec<f32> },
+}
+
+/// What becomes of the agents of a population `PhysarumModel::remove_population` removes.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovedAgents {
+    /// They are removed with it.
+    Freed,
+    /// They join the population with this index, as counted before the removal. Joining
+    /// the removed population itself frees them.
+    Reassigned(usize),
+}
+
+impl PhysarumModel {
+    /// Builds the model from parameters already checked by `PhysarumModelBuilder`.
+    pub(super) fn new(
+        width: usize,
+        height: usize,
+        n_particles: usize,
+        diffusity: usize,
+        palette: Palette,
+        attraction_table: Vec<Vec<f32>>,
+        rng: &mut SmallRng,
+    ) -> Result<Self, ConfigError> {
+        let n_populations = attraction_table.len();
+        let particles_per_grid = (n_particles as f64 / n_populations as f64).ceil() as usize;
+        let n_particles = particles_per_grid * n_populations;
+
+        let agents = (0..n_particles)
+            .map(|i| Particle::new(width, height, i / particles_per_grid, rng))
+            .collect();
+        let grids = (0..n_populations)
+            .map(|_| Grid::new(width, height, PopulationConfig::new(rng), rng))
+            .collect::<Result<_, _>>()?;
+        Ok(PhysarumModel {
+            agents,
+            grids,
+            attraction_table,
+            diffusity,
+            bandpass: None,
+            sensing: vec![],
+            steering: vec![],
+            steering_rules: vec![],
+            deposition_table: None,
+            deposit_ops: vec![],
+            deposit_operators: vec![],
+            diffuse_ops: vec![],
+            diffuse_operators: vec![],
+            time_scale: 1.0,
+            annealing: None,
+            crowding: None,
+            spacing: None,
+            heat: None,
+            food: None,
+            cities: None,
+            iteration: 0,
+            started: Instant::now(),
+            palette,
+            render_settings: RenderSettings::default(),
+            lut: None,
+            background: None,
+            mask: None,
+            confinement: None,
+            quantile_cache: Mutex::default(),
+            running_max: Mutex::default(),
+            smoothing: Mutex::default(),
+            accumulator: Mutex::default(),
+            hooks: Mutex::default(),
+            strict_determinism: false,
+            fast_math: false,
+            agent_sort_interval: 0,
+        })
+    }
+
+    pub fn builder() -> PhysarumModelBuilder {
+        PhysarumModelBuilder::default()
+    }
+
+    /// Draws how much each population follows its own trails and those of the others from
+    /// `distribution`, which must pass `AttractionDistribution::is_valid`.
+    pub fn random_attraction_table(
+        n_populations: usize,
+        distribution: &AttractionDistribution,
+        rng: &mut SmallRng,
+    ) -> Vec<Vec<f32>> {
+        let attraction_distr =
+            Normal::new(distribution.attraction_mean, distribution.attraction_std).unwrap();
+        let repulstion_distr =
+            Normal::new(distribution.repulsion_mean, distribution.repulsion_std).unwrap();
+
+        let mut attraction_table = Vec::with_capacity(n_populations);
+        for i in 0..n_populations {
+            attraction_table.push(Vec::with_capacity(n_populations));
+            for j in 0..n_populations {
+                attraction_table[i].push(if i == j {
+                    attraction_distr.sample(rng)
+                } else {
+                    repulstion_distr.sample(rng)
+                });
+            }
+        }
+        attraction_table
+    }
+
+    /// Replaces the random attraction table, where `table[i][j]` is how much population `i`
+    /// follows the trails of population `j`.
+    pub fn set_attraction_table(&mut self, table: Vec<Vec<f32>>) -> Result<(), ConfigError> {
+        let populations = self.grids.len();
+        if table.len() != populations || table.iter().any(|row| row.len() != populations) {
+            return Err(ConfigError::AttractionTableSize { populations });
+        }
+        self.attraction_table = table;
+        self.changed(Parameter::AttractionTable);
+        Ok(())
+    }
+
+    /// Lets agents deposit into the grids of other populations too, where `table[i][j]` is
+    /// how much agents of population `i` deposit into grid `j`, in place of their
+    /// `deposition_amount`. Negative amounts erase trails. `None` restores depositing only
+    /// into their own grid.
+    pub fn set_deposition_table(
+        &mut self,
+        table: Option<Vec<Vec<f32>>>,
+    ) -> Result<(), ConfigError> {
+        if let Some(table) = &table {
+            step::check_deposition_table(table, self.grids.len())?;
+        }
+        self.deposition_table = table;
+        self.changed(Parameter::DepositionTable);
+        Ok(())
+    }
+
+    pub fn attraction_table(&self) -> &[Vec<f32>] {
+        &self.attraction_table
+    }
+
+    pub fn deposition_table(&self) -> Option<&[Vec<f32>]> {
+        self.deposition_table.as_deref()
+    }
+
+    pub fn set_population_configs(
+        &mut self,
+        configs: Vec<PopulationConfig>,
+    ) -> Result<(), ConfigError> {
+        if configs.len() < self.grids.len() {
+            return Err(ConfigError::MissingPopulationConfigs {
+                expected: self.grids.len(),
+                actual: configs.len(),
+            });
+        }
+
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        for config in &configs[..self.grids.len()] {
+            grid::check_population(width, height, config)?;
+        }
+        self.grids.iter_mut().enumerate().for_each(|(i, grid)| {
+            grid.config = configs[i];
+        });
+        self.changed(Parameter::PopulationConfigs);
+        Ok(())
+    }
+
+    pub fn population_size(&self, population: usize) -> usize {
+        self.agents.of_population(population).count()
+    }
+
+    pub fn agents(&self) -> &Agents {
+        &self.agents
+    }
+
+    /// A copy of the trails of every population, e.g. to compare them with those of a
+    /// later step.
+    pub fn trails(&self) -> Vec<Vec<f32>> {
+        self.grids.iter().map(|grid| grid.data().to_vec()).collect()
+    }
+
+    /// The trails of every population as matrices averaged over blocks of `factor` by
+    /// `factor` cells, 0 or 1 keeping every cell, to save with `Matrix::save`.
+    pub fn trail_matrices(&self, factor: usize) -> Vec<Matrix> {
+        self.grids
+            .iter()
+            .map(|grid| {
+                Matrix::new(grid.width, grid.height, grid.data().to_vec()).downsampled(factor)
+            })
+            .collect()
+    }
+
+    /// The trails of all populations added up, each divided by its 99th percentile so that
+    /// it counts alike however much it deposits.
+    pub fn combined_field(&self) -> Vec<f32> {
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let mut field = vec![0.0; width * height];
+        for grid in &self.grids {
+            let scale = 1.0 / grid.quantile(0.99).max(f32::EPSILON);
+            for (sum, trail) in field.iter_mut().zip(grid.data()) {
+                *sum += trail * scale;
+            }
+        }
+        field
+    }
+
+    /// The network the trails of all populations form together, see `combined_field` and
+    /// `Network`.
+    pub fn network(&self, settings: &NetworkSettings) -> Network {
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let mut field = self.combined_field();
+        if settings.smoothing > 0.0 {
+            let mut buf = vec![0.0; field.len()];
+            let sigma = settings.smoothing;
+            Blur::new(width).run(&mut field, &mut buf, width, height, sigma, 1.0);
+        }
+        let threshold = util::quantile(&field, settings.quantile);
+        Network::extract(&field, width, height, threshold, settings.min_spur)
+    }
+
+    /// Replaces the trails of every population with `pattern`, as when the model starts
+    /// out. `InitialTrails::Uniform` leaves them as they are.
+    pub fn fill_trails(&mut self, pattern: &InitialTrails) -> Result<(), ConfigError> {
+        pattern.check()?;
+        let path = match pattern {
+            InitialTrails::Image { path } => Some(path.as_path()),
+            _ => None,
+        };
+        for (k, grid) in self.grids.iter_mut().enumerate() {
+            let trails = pattern
+                .render(grid.width, grid.height, k)
+                .map_err(|err| unreadable("initial trails", path, err))?;
+            if let Some(trails) = trails {
+                grid.data_mut().copy_from_slice(&trails);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds agents that `set_population_size` spawned before, for replaying it.
+    pub(crate) fn add_agents(
+        &mut self,
+        population: usize,
+        agents: Vec<Particle>,
+    ) -> Result<(), ConfigError> {
+        self.check_population_index(population)?;
+        if agents.iter().any(|agent| agent.id != population) {
+            return Err(ConfigError::ForeignAgents { population });
+        }
+        self.check_agent_capacity(self.agents.len() + agents.len())?;
+        self.agents.extend(agents);
+        self.changed(Parameter::PopulationSize(population));
+        Ok(())
+    }
+
+    /// Grows or shrinks a population while the simulation runs. Surviving agents keep their
+    /// positions and new agents are spawned on top of existing ones so the trail network
+    /// just gets denser instead of being sprinkled with noise.
+    pub fn set_population_size(
+        &mut self,
+        population: usize,
+        n: usize,
+        rng: &mut SmallRng,
+    ) -> Result<(), ConfigError> {
+        self.check_population_index(population)?;
+        let current = self.population_size(population);
+        if n < current {
+            let mut kept = 0;
+            self.agents.retain(|agent| {
+                if agent.id != population {
+                    return true;
+                }
+                kept += 1;
+                kept <= n
+            });
+            self.changed(Parameter::PopulationSize(population));
+            return Ok(());
+        }
+        self.check_agent_capacity(self.agents.len() + (n - current))?;
+
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let existing: Vec<(f32, f32)> = self
+            .agents
+            .of_population(population)
+            .map(|agent| (agent.x, agent.y))
+            .collect();
+        self.agents.extend((current..n).map(|_| {
+            let mut agent = Particle::new(width, height, population, rng);
+            if let Some(&(x, y)) = existing.choose(rng) {
+                agent.x = x;
+                agent.y = y;
+            }
+            agent
+        }));
+        self.changed(Parameter::PopulationSize(population));
+        Ok(())
+    }
+
+    /// Adds a population of `n_agents` agents spread at random over an empty grid while
+    /// the simulation runs, and returns its index. Its trails grow from nothing, so an
+    /// ecosystem can be built up one population at a time. A deposition table gains a row
+    /// depositing only into the new grid, and custom sensing weights ignore it.
+    pub fn add_population(
+        &mut self,
+        config: PopulationConfig,
+        n_agents: usize,
+        attraction: NewAttraction,
+        rng: &mut SmallRng,
+    ) -> Result<usize, ConfigError> {
+        let population = self.grids.len();
+        let (row, column) = match attraction {
+            NewAttraction::Sampled(distribution) => {
+                if !distribution.is_valid() {
+                    return Err(ConfigError::AttractionDistribution);
+                }
+                let table = Self::random_attraction_table(population + 1, &distribution, rng);
+                let column = table[..population]
+                    .iter()
+                    .map(|row| row[population])
+                    .collect();
+                (table[population].clone(), column)
+            }
+            NewAttraction::Supplied { row, column } => (row, column),
+        };
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let agents = (0..n_agents)
+            .map(|_| Particle::new(width, height, population, rng))
+            .collect();
+        self.insert_population(config, row, column, agents)
+    }
+
+    /// Adds a population the way `add_population` did before, with the attraction entries
+    /// and agents it drew, for replaying it.
+    pub(crate) fn insert_population(
+        &mut self,
+        config: PopulationConfig,
+        row: Vec<f32>,
+        column: Vec<f32>,
+        agents: Vec<Particle>,
+    ) -> Result<usize, ConfigError> {
+        let population = self.grids.len();
+        if row.len() != population + 1 || column.len() != population {
+            return Err(ConfigError::AttractionTableSize {
+                populations: population + 1,
+            });
+        }
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        grid::check_population(width, height, &config)?;
+        grid::check_capacity(
+            width,
+            height,
+            population + 1,
+            self.agents.len() + agents.len(),
+        )?;
+        self.grids.push(Grid::from_data(
+            width,
+            height,
+            config,
+            vec![0.0; width * height],
+        )?);
+        for (table_row, entry) in self.attraction_table.iter_mut().zip(column) {
+            table_row.push(entry);
+        }
+        self.attraction_table.push(row);
+        if let Some(table) = &mut self.deposition_table {
+            table.iter_mut().for_each(|amounts| amounts.push(0.0));
+            let mut own = vec![0.0; population + 1];
+            own[population] = config.deposition_amount;
+            table.push(own);
+        }
+        for mode in &mut self.sensing {
+            if let Sensing::Weights { weights } = mode {
+                weights.push(0.0);
+            }
+        }
+        self.agents.extend(agents);
+        self.changed(Parameter::NewPopulation);
+        Ok(population)
+    }
+
+    /// Removes a population while the simulation runs, with its grid and its row and
+    /// column of the attraction and deposition tables. The populations after it move down
+    /// by one. The last population can't be removed.
+    pub fn remove_population(
+        &mut self,
+        population: usize,
+        agents: RemovedAgents,
+    ) -> Result<(), ConfigError> {
+        self.check_population_index(population)?;
+        if self.grids.len() == 1 {
+            return Err(ConfigError::NoPopulations);
+        }
+        match agents {
+            RemovedAgents::Reassigned(target) if target != population => {
+                self.check_population_index(target)?;
+                self.agents
+                    .relabel(|id| if id == population { target } else { id });
+            }
+            _ => self.agents.retain(|agent| agent.id != population),
+        }
+        self.drop_population(population);
+        self.changed(Parameter::RemovedPopulation { population, agents });
+        Ok(())
+    }
+
+    /// Merges population `from` into `into` while the simulation runs: its trails are
+    /// added to those of `into` and its agents join it, following the config and the
+    /// attraction entries of `into` from then on. `from` is removed as with
+    /// `remove_population`.
+    pub fn merge_populations(&mut self, from: usize, into: usize) -> Result<(), ConfigError> {
+        self.check_population_index(from)?;
+        self.check_population_index(into)?;
+        if from == into {
+            return Ok(());
+        }
+        let trails = self.grids[from].data().to_vec();
+        self.grids[into]
+            .data_mut()
+            .par_iter_mut()
+            .zip(trails.par_iter())
+            .for_each(|(cell, trail)| *cell += trail);
+        self.agents.relabel(|id| if id == from { into } else { id });
+        self.drop_population(from);
+        self.changed(Parameter::MergedPopulations { from, into });
+        Ok(())
+    }
+
+    fn check_agent_capacity(&self, agents: usize) -> Result<(), ConfigError> {
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        grid::check_capacity(width, height, self.grids.len(), agents)
+    }
+
+    fn check_population_index(&self, population: usize) -> Result<(), ConfigError> {
+        if population < self.grids.len() {
+            Ok(())
+        } else {
+            Err(ConfigError::UnknownPopulation {
+                population,
+                populations: self.grids.len(),
+            })
+        }
+    }
+
+    /// Removes the grid, table entries, sensing and style of `population`, which has no
+    /// agents left, and moves the agents of the populations after it down by one.
+    fn drop_population(&mut self, population: usize) {
+        self.grids.remove(population);
+        self.agents
+            .relabel(|id| if id > population { id - 1 } else { id });
+        let tables = Some(&mut self.attraction_table)
+            .into_iter()
+            .chain(self.deposition_table.as_mut());
+        for table in tables {
+            table.remove(population);
+            table.iter_mut().for_each(|row| {
+                row.remove(population);
+            });
+        }
+        // Settings given for fewer populations fall back to defaults past their end.
+        fn remove<T>(items: &mut Vec<T>, index: usize) {
+            if index < items.len() {
+                items.remove(index);
+            }
+        }
+        remove(&mut self.sensing, population);
+        for mode in &mut self.sensing {
+            if let Sensing::Weights { weights } = mode {
+                weights.remove(population);
+            }
+        }
+        remove(&mut self.steering, population);
+        remove(&mut self.steering_rules, population);
+        remove(&mut self.deposit_ops, population);
+        remove(&mut self.deposit_operators, population);
+        remove(&mut self.diffuse_ops, population);
+        remove(&mut self.diffuse_operators, population);
+        remove(&mut self.render_settings.populations, population);
+    }
+
+    pub fn state(&self) -> ModelState {
+        ModelState {
+            version: STATE_VERSION,
+            width: self.grids[0].width,
+            height: self.grids[0].height,
+            configs: self.grids.iter().map(|grid| grid.config).collect(),
+            grids: self.trails(),
+            agents: self.agents.to_vec(),
+            attraction_table: self.attraction_table.clone(),
+            diffusity: self.diffusity,
+            iteration: self.iteration,
+            palette: self.palette.colors.map(|color| color.0),
+        }
+    }
+
+    pub fn from_state(state: ModelState) -> Result<Self, ConfigError> {
+        let (width, height) = (state.width, state.height);
+        Ok(PhysarumModel {
+            grids: state
+                .grids
+                .into_iter()
+                .zip(state.configs)
+                .map(|(data, config)| Grid::from_data(width, height, config, data))
+                .collect::<Result<_, _>>()?,
+            agents: Agents::from(state.agents),
+            attraction_table: state.attraction_table,
+            diffusity: state.diffusity,
+            bandpass: None,
+            sensing: vec![],
+            steering: vec![],
+            steering_rules: vec![],
+            deposition_table: None,
+            deposit_ops: vec![],
+            deposit_operators: vec![],
+            diffuse_ops: vec![],
+            diffuse_operators: vec![],
+            time_scale: 1.0,
+            annealing: None,
+            crowding: None,
+            spacing: None,
+            heat: None,
+            food: None,
+            cities: None,
+            iteration: state.iteration,
+            started: Instant::now(),
+            palette: Palette {
+                colors: state.palette.map(Rgb),
+            },
+            render_settings: RenderSettings::default(),
+            lut: None,
+            background: None,
+            mask: None,
+            confinement: None,
+            quantile_cache: Mutex::default(),
+            running_max: Mutex::default(),
+            smoothing: Mutex::default(),
+            accumulator: Mutex::default(),
+            hooks: Mutex::default(),
+            strict_determinism: false,
+            fast_math: false,
+            agent_sort_interval: 0,
+        })
+    }
+
+    pub fn step(&mut self) {
+        let interval = self.agent_sort_interval;
+        if interval > 0 && self.iteration.is_multiple_of(interval as u64) {
+            self.agents.sort_by_cell();
+        }
+        let temperature = self.temperature();
+        step::step(
+            &mut self.grids,
+            &mut self.agents,
+            &sensing::table(&self.sensing, &self.attraction_table),
+            self.diffusity,
+            StepOptions {
+                bandpass: self.bandpass,
+                steering: &self.steering_rules,
+                deposition_table: self.deposition_table.as_deref(),
+                deposit_ops: &self.deposit_operators,
+                diffuse_ops: &self.diffuse_operators,
+                time_scale: self.time_scale,
+                strict_determinism: self.strict_determinism,
+                fast_math: self.fast_math,
+                confinement: self.confinement.as_deref(),
+                temperature,
+                crowding: self.crowding,
+                spacing: self.spacing,
+                iteration: self.iteration,
+            },
+        );
+        if let Some(pantry) = &mut self.food {
+            pantry.step(&mut self.grids, &self.agents, self.time_scale);
+        }
+        if let Some(cities) = &self.cities {
+            cities.lay(&mut self.grids, self.time_scale);
+        }
+        if let Some(heat) = &mut self.heat {
+            heat.cool(&mut self.grids, self.time_scale);
+            heat.step(self.iteration + 1, self.time_scale);
+        }
+        self.iteration += 1;
+        self.emit(|hooks, model| hooks.step.iter_mut().for_each(|hook| hook(model)));
+    }
+
+    /// Calls `hook` with the model after every step, to record statistics or publish the
+    /// trails without changing the loop that steps it. Events hooks cause themselves, like
+    /// a step hook exporting a frame, don't reach any hooks.
+    pub fn on_step(&mut self, hook: impl FnMut(&PhysarumModel) + Send + 'static) {
+        self.hooks.get_mut().unwrap().step.push(Box::new(hook));
+    }
+
+    /// Calls `hook` with the model and what changed whenever a setter changes the
+    /// parameters of the simulation or its rendering, see `Parameter`.
+    pub fn on_parameter_change(
+        &mut self,
+        hook: impl FnMut(&PhysarumModel, Parameter) + Send + 'static,
+    ) {
+        self.hooks
+            .get_mut()
+            .unwrap()
+            .parameter_change
+            .push(Box::new(hook));
+    }
+
+    fn changed(&self, parameter: Parameter) {
+        self.emit(|hooks, model| {
+            for hook in &mut hooks.parameter_change {
+                hook(model, parameter);
+            }
+        });
+    }
+
+    /// Calls hooks with the lock released, so that they can use the model freely.
+    fn emit(&self, call: impl FnOnce(&mut Hooks, &Self)) {
+        let mut hooks = std::mem::take(&mut *self.hooks.lock().unwrap());
+        if hooks.is_empty() {
+            return;
+        }
+        call(&mut hooks, self);
+        *self.hooks.lock().unwrap() = hooks;
+    }
+
+    /// Makes the populations sense each other's trails through `bandpass`, or their raw
+    /// density with `None`, the default.
+    pub fn set_bandpass(&mut self, bandpass: Option<Bandpass>) -> Result<(), ConfigError> {
+        if let Some(bandpass) = bandpass {
+            bandpass.check(self.grids[0].width, self.grids[0].height)?;
+        }
+        self.bandpass = bandpass;
+        self.changed(Parameter::Bandpass);
+        Ok(())
+    }
+
+    pub fn bandpass(&self) -> Option<Bandpass> {
+        self.bandpass
+    }
+
+    /// Chooses which trails each population steers by, see `Sensing`. Populations past
+    /// the end of `sensing` sense the combined field.
+    pub fn set_sensing(&mut self, sensing: Vec<Sensing>) -> Result<(), ConfigError> {
+        sensing::check(&sensing, self.grids.len())?;
+        self.sensing = sensing;
+        self.changed(Parameter::Sensing);
+        Ok(())
+    }
+
+    pub fn sensing(&self) -> &[Sensing] {
+        &self.sensing
+    }
+
+    /// Chooses how each population turns by the names of its `SteeringRule`, see
+    /// `steering::by_name`. Populations past the end of `steering` use the default
+    /// threshold rule.
+    pub fn set_steering(&mut self, steering: Vec<String>) -> Result<(), ConfigError> {
+        self.steering_rules = steering::resolve(&steering)?;
+        self.steering = steering;
+        self.changed(Parameter::Steering);
+        Ok(())
+    }
+
+    pub fn steering(&self) -> &[String] {
+        &self.steering
+    }
+
+    /// Chooses how each population lays down its trails by the names of its `DepositOp`,
+    /// see `operators::deposit_by_name`. Populations past the end of `ops` deposit into
+    /// the cell they pass.
+    pub fn set_deposit_ops(&mut self, ops: Vec<String>) -> Result<(), ConfigError> {
+        self.deposit_operators = operators::resolve_deposit(&ops)?;
+        self.deposit_ops = ops;
+        self.changed(Parameter::DepositOps);
+        Ok(())
+    }
+
+    pub fn deposit_ops(&self) -> &[String] {
+        &self.deposit_ops
+    }
+
+    /// Chooses how each population's trails spread by the names of its `DiffuseOp`, see
+    /// `operators::diffuse_by_name`. Populations past the end of `ops` diffuse by the
+    /// Gaussian blur.
+    pub fn set_diffuse_ops(&mut self, ops: Vec<String>) -> Result<(), ConfigError> {
+        self.diffuse_operators = operators::resolve_diffuse(&ops)?;
+        self.diffuse_ops = ops;
+        self.changed(Parameter::DiffuseOps);
+        Ok(())
+    }
+
+    pub fn diffuse_ops(&self) -> &[String] {
+        &self.diffuse_ops
+    }
+
+    /// Makes every step cover `time_scale` steps of the configs' dynamics, so that a
+    /// preview can run in coarse steps and the final render in fine ones with the same
+    /// patterns emerging. Diffusion still blurs by whole cells each step. 1 by default.
+    pub fn set_time_scale(&mut self, time_scale: f32) -> Result<(), ConfigError> {
+        step::check_time_scale(time_scale)?;
+        self.time_scale = time_scale;
+        self.changed(Parameter::TimeScale);
+        Ok(())
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Adds random turns to the agents' steering that cool down over the iterations, see
+    /// `Annealing`. The schedule counts iterations like `iteration`, so models restored from
+    /// a state pick it up where the state left off. None by default.
+    pub fn set_annealing(&mut self, annealing: Option<Annealing>) -> Result<(), ConfigError> {
+        if let Some(annealing) = &annealing {
+            annealing.check()?;
+        }
+        self.annealing = annealing;
+        self.changed(Parameter::Annealing);
+        Ok(())
+    }
+
+    pub fn annealing(&self) -> Option<Annealing> {
+        self.annealing
+    }
+
+    /// Lets the agents sense how crowded it is around them besides the trails, see
+    /// `Crowding`. None by default.
+    pub fn set_crowding(&mut self, crowding: Option<Crowding>) -> Result<(), ConfigError> {
+        if let Some(crowding) = &crowding {
+            crowding.check(self.grids[0].width, self.grids[0].height)?;
+        }
+        self.crowding = crowding;
+        self.changed(Parameter::Crowding);
+        Ok(())
+    }
+
+    pub fn crowding(&self) -> Option<Crowding> {
+        self.crowding
+    }
+
+    /// Keeps the agents of every population apart, see `Spacing`. None by default.
+    pub fn set_spacing(&mut self, spacing: Option<Spacing>) -> Result<(), ConfigError> {
+        if let Some(spacing) = &spacing {
+            spacing.check()?;
+        }
+        self.spacing = spacing;
+        self.changed(Parameter::Spacing);
+        Ok(())
+    }
+
+    pub fn spacing(&self) -> Option<Spacing> {
+        self.spacing
+    }
+
+    /// Lays a field of heat over the grids that speeds up the decay of the trails where it's
+    /// hot and slows it down where it's cold, see `Heat`. Changing the parameters keeps the
+    /// heat unless the noise changed, while `None`, the default, removes it.
+    pub fn set_heat(&mut self, heat: Option<Heat>) -> Result<(), ConfigError> {
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        if let Some(heat) = &heat {
+            heat.check(width, height)?;
+        }
+        self.heat = match (self.heat.take(), heat) {
+            (Some(mut field), Some(heat)) if field.heat.noise == heat.noise => {
+                field.heat = heat;
+                Some(field)
+            }
+            (_, Some(heat)) => Some(HeatField::new(heat, width, height)),
+            (_, None) => None,
+        };
+        self.changed(Parameter::Heat);
+        Ok(())
+    }
+
+    pub fn heat(&self) -> Option<Heat> {
+        self.heat.as_ref().map(|field| field.heat)
+    }
+
+    /// The heat of every cell, row by row, from 0 to 1.
+    pub fn heat_field(&self) -> Option<&[f32]> {
+        self.heat.as_ref().map(|field| field.field.as_slice())
+    }
+
+    /// Adds `amount` of heat to the cells within `radius` cells of (x, y), or takes it away
+    /// when negative, wrapping around the edges. Does nothing without heat.
+    pub fn paint_heat(&mut self, x: f32, y: f32, radius: f32, amount: f32) {
+        if let Some(field) = &mut self.heat {
+            field.paint(x, y, radius, amount);
+            self.changed(Parameter::HeatPainted {
+                x,
+                y,
+                radius,
+                amount,
+            });
+        }
+    }
+
+    /// Replaces the heat of every cell, clamped to [0, 1]. Does nothing without heat.
+    pub fn set_heat_field(&mut self, values: Vec<f32>) -> Result<(), ConfigError> {
+        let Some(field) = &mut self.heat else {
+            return Ok(());
+        };
+        if values.len() != field.field.len() {
+            return Err(ConfigError::DataLength {
+                expected: field.field.len(),
+                actual: values.len(),
+            });
+        }
+        field.field = values.into_iter().map(|h| h.clamp(0.0, 1.0)).collect();
+        self.changed(Parameter::HeatField);
+        Ok(())
+    }
+
+    /// Scatters food sources over the grids that the agents eat up, see `Food`. Changing
+    /// the parameters keeps the sources, while `None`, the default, clears them away.
+    pub fn set_food(&mut self, food: Option<Food>) -> Result<(), ConfigError> {
+        if let Some(food) = &food {
+            food.check()?;
+        }
+        self.food = match (self.food.take(), food) {
+            (Some(mut pantry), Some(food)) => {
+                pantry.set_food(food);
+                Some(pantry)
+            }
+            (None, Some(food)) => {
+                let (width, height) = (self.grids[0].width, self.grids[0].height);
+                Some(Pantry::new(food, width, height))
+            }
+            (_, None) => None,
+        };
+        self.changed(Parameter::Food);
+        Ok(())
+    }
+
+    pub fn food(&self) -> Option<Food> {
+        self.food.as_ref().map(|pantry| pantry.food)
+    }
+
+    /// The food sources left, none without food.
+    pub fn food_sources(&self) -> &[FoodSource] {
+        self.food
+            .as_ref()
+            .map_or(&[], |pantry| pantry.sources.as_slice())
+    }
+
+    /// Places the cities of `settings` on the grids as food sources that never run out, so
+    /// that the network grows between them, see `CitySettings`. `None`, the default,
+    /// removes them. Fails when the cities can't be read, keeping the ones before.
+    pub fn load_cities(&mut self, settings: Option<CitySettings>) -> Result<(), ConfigError> {
+        let Some(settings) = settings else {
+            self.cities = None;
+            return Ok(());
+        };
+        settings.check()?;
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let cities = settings
+            .load(width, height)
+            .map_err(|err| unreadable("cities", Some(&settings.path), err))?;
+        self.cities = Some(CityScent::new(cities, settings, width, height));
+        Ok(())
+    }
+
+    /// The cities on the grids, in cells.
+    pub fn cities(&self) -> &[City] {
+        self.cities
+            .as_ref()
+            .map_or(&[], |cities| cities.cities.as_slice())
+    }
+
+    /// The settings the cities were loaded with.
+    pub fn city_settings(&self) -> Option<&CitySettings> {
+        self.cities.as_ref().map(|cities| &cities.settings)
+    }
+
+    /// The temperature of the next step, 0 without annealing.
+    pub fn temperature(&self) -> f32 {
+        self.annealing
+            .map_or(0.0, |annealing| annealing.temperature(self.iteration))
+    }
+
+    /// Steps taken so far, carried over by `state` and `from_state`. It is 64 bits wide so
+    /// that even installations stepping for years never wrap around.
+    pub fn iteration(&self) -> u64 {
+        self.iteration
+    }
+
+    /// Wall-clock time since the model was created or restored from a state.
+    pub fn runtime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Makes the same seed step and render to identical frames on every platform and
+    /// thread count, by using portable math and always compositing on the CPU, since GPU
+    /// drivers round differently too. Only the CPU path is affected, at some cost in speed.
+    pub fn set_strict_determinism(&mut self, strict_determinism: bool) {
+        self.strict_determinism = strict_determinism;
+    }
+
+    pub fn strict_determinism(&self) -> bool {
+        self.strict_determinism
+    }
+
+    /// Moves the agents with approximate trigonometry, which speeds up stepping at the
+    /// cost of slightly different trails than exact runs, see `StepOptions::fast_math`.
+    /// Rendering is unaffected. Off by default.
+    pub fn set_fast_math(&mut self, fast_math: bool) {
+        self.fast_math = fast_math;
+    }
+
+    pub fn fast_math(&self) -> bool {
+        self.fast_math
+    }
+
+    /// Sorts the agents by the cells they are in every `interval` steps, or never for 0,
+    /// the default. Agents wander off slowly, so sorting now and then keeps neighbouring
+    /// agents sensing and depositing into neighbouring memory, which saves cache misses on
+    /// large grids. The trails come out slightly different from unsorted runs, since the
+    /// deposits are summed in another order, but runs with the same interval still match.
+    pub fn set_agent_sort_interval(&mut self, interval: usize) {
+        self.agent_sort_interval = interval;
+    }
+
+    pub fn agent_sort_interval(&self) -> usize {
+        self.agent_sort_interval
+    }
+
+    /// What the simulation holds at (x, y), in cells: the trail of every population there
+    /// and the agent closest to it, with what it senses and how it turns next. Senses the
+    /// trails like the next step will, which combines them anew.
+    pub fn probe(&mut self, x: f32, y: f32) -> Probe {
+        grid::combine(
+            &mut self.grids,
+            &sensing::table(&self.sensing, &self.attraction_table),
+            self.bandpass,
+        );
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let cell = grid::cell(width, height, x, y);
+        let (x, y) = (x.rem_euclid(width as f32), y.rem_euclid(height as f32));
+        let wrapped = |d: f32, side: usize| d.abs().min(side as f32 - d.abs());
+        let nearest = self
+            .agents
+            .iter()
+            .enumerate()
+            .map(|(index, agent)| {
+                let (dx, dy) = (wrapped(agent.x - x, width), wrapped(agent.y - y, height));
+                (index, agent, dx.hypot(dy))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2));
+
+        let math = Math::new(self.strict_determinism);
+        let motion = if self.fast_math {
+            math.fast_trig()
+        } else {
+            math
+        };
+        Probe {
+            cell: (cell % width, cell / width),
+            trails: self.grids.iter().map(|grid| grid.data()[cell]).collect(),
+            nearest: nearest.map(|(index, agent, distance)| {
+                let grid = &self.grids[agent.id];
+                let config = grid.config.scaled(self.time_scale, math);
+                let senses = step::sense(grid, &config, &agent, motion);
+                let mut rng = step::agent_rng(&agent, self.iteration);
+                let turn = step::turn(
+                    &self.steering_rules,
+                    &agent,
+                    senses.left,
+                    senses.center,
+                    senses.right,
+                    &mut rng,
+                );
+                ProbedAgent {
+                    index,
+                    agent,
+                    distance,
+                    senses,
+                    turn,
+                }
+            }),
+        }
+    }
+
+    /// Summaries of the trails of every population, with histograms of `bins` bins and the
+    /// cells above `threshold` counted as occupied.
+    pub fn grid_statistics(&self, bins: usize, threshold: f32) -> Vec<GridStatistics> {
+        self.grids
+            .iter()
+            .map(|grid| GridStatistics::of(grid.data(), bins, threshold))
+            .collect()
+    }
+
+    /// Summaries of the agents of every population, with histograms of `bins` bins of
+    /// their headings.
+    pub fn agent_statistics(&self, bins: usize) -> Vec<AgentStatistics> {
+        let math = Math::new(self.strict_determinism);
+        self.grids
+            .iter()
+            .enumerate()
+            .map(|(population, grid)| {
+                let speed = grid.config.scaled(self.time_scale, math).step_distance;
+                AgentStatistics::of(self.agents.of_population(population), speed, bins)
+            })
+            .collect()
+    }
+
+    pub fn print_configurations(&self) {
+        for (i, grid) in self.grids.iter().enumerate() {
+            println!("Grid {}: {}", i, grid.config);
+        }
+        println!("Attraction table: {:#?}", self.attraction_table);
+    }
+
+    /// Renders with `render_settings` from now on, reading the LUT, background and mask
+    /// files they name. Fails when one can't be read, keeping the settings before.
+    pub fn set_render_settings(
+        &mut self,
+        render_settings: RenderSettings,
+    ) -> Result<(), ConfigError> {
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        // Everything is read before anything changes, so a failure keeps the settings before.
+        let lut = if render_settings.lut != self.render_settings.lut || self.lut.is_none() {
+            let lut = render_settings
+                .lut
+                .as_ref()
+                .map(|path| Lut::load(path).map_err(|err| unreadable("LUT", Some(path), err)));
+            Some(lut.transpose()?)
+        } else {
+            None
+        };
+        let background = if render_settings.background != self.render_settings.background
+            || self.background.is_none()
+        {
+            let background = render_settings
+                .background
+                .render(width, height)
+                .map_err(|err| {
+                    let path = match &render_settings.background.fill {
+                        BackgroundFill::Image { path } => Some(path.as_path()),
+                        _ => None,
+                    };
+                    unreadable("background", path, err)
+                })?;
+            Some(background)
+        } else {
+            None
+        };
+        let mask = if render_settings.mask != self.render_settings.mask || self.mask.is_none() {
+            let mask = render_settings.mask.render(width, height).map_err(|err| {
+                let path = match &render_settings.mask.shape {
+                    MaskShape::Svg { path } | MaskShape::Image { path } => Some(path.as_path()),
+                    _ => None,
+                };
+                unreadable("mask", path, err)
+            })?;
+            Some(mask)
+        } else {
+            None
+        };
+
+        if let Some(lut) = lut {
+            self.lut = lut;
+        }
+        if let Some(background) = background {
+            self.background = background;
+        }
+        if let Some(mask) = mask {
+            self.mask = mask;
+            self.confinement = self
+                .mask
+                .as_ref()
+                .filter(|_| render_settings.mask.confine)
+                .map(|mask| mask.iter().map(|&coverage| coverage >= 0.5).collect());
+        }
+        if let Some(colors) = render_settings.colors {
+            self.palette = Palette {
+                colors: colors.map(Rgb),
+            };
+        }
+        self.render_settings = render_settings;
+        self.changed(Parameter::RenderSettings);
+        Ok(())
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.changed(Parameter::Palette);
+    }
+
+    pub fn render_settings(&self) -> &RenderSettings {
+        &self.render_settings
+    }
+
+    /// The field each population contributes to the image: its trail grid, or its agents
+    /// splatted as sprites or traced as flow lines.
+    pub fn layer_fields(&self) -> Vec<Cow<'_, [f32]>> {
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let sprites: Vec<Option<Vec<f32>>> = (0..self.grids.len())
+            .into_par_iter()
+            .map(|i| match self.render_settings.style(i).mode {
+                LayerMode::Trail => None,
+                LayerMode::Sprites => Some(render::splat_sprites(
+                    &self.agents,
+                    i,
+                    width,
+                    height,
+                    self.render_settings.sprite_radius,
+                    Math::new(self.strict_determinism),
+                )),
+                LayerMode::Flow => {
+                    let math = Math::new(self.strict_determinism);
+                    let field = flow::heading_field(&self.agents, i, width, height, math);
+                    let length = self.render_settings.flow_length;
+                    Some(flow::lic(&field, width, height, length))
+                }
+            })
+            .collect();
+        self.grids
+            .iter()
+            .zip(sprites)
+            .map(|(grid, sprite)| match sprite {
+                Some(sprite) => Cow::Owned(sprite),
+                None => Cow::Borrowed(grid.data()),
+            })
+            .collect()
+    }
+
+    /// Field values mapped to full brightness for each layer of `layer_fields`.
+    pub fn layer_max_values(&self, fields: &[Cow<'_, [f32]>]) -> Vec<f32> {
+        let max_values: Vec<f32> = match self.render_settings.normalization {
+            Normalization::Percentile { fraction, scale } => self
+                .layer_quantiles(fields, fraction)
+                .into_iter()
+                .map(|quantile| quantile * scale)
+                .collect(),
+            Normalization::Fixed { max } => vec![max; fields.len()],
+            Normalization::RunningMax { decay } => {
+                let maxima: Vec<f32> = fields.par_iter().map(|f| normalization::max(f)).collect();
+                self.running_max
+                    .lock()
+                    .unwrap()
+                    .update(self.iteration, &maxima, decay)
+            }
+            Normalization::MeanExposure { exposure } => fields
+                .par_iter()
+                .map(|field| normalization::mean(field) * exposure)
+                .collect(),
+            Normalization::HistogramEqualization => {
+                fields.par_iter().map(|f| normalization::max(f)).collect()
+            }
+        };
+        let max_values: Vec<f32> = fields
+            .iter()
+            .zip(max_values)
+            .map(|(field, max_value)| {
+                // Sparse sprite layers can leave the percentile or mean at zero.
+                if max_value > 0.0 {
+                    max_value
+                } else {
+                    field.iter().cloned().fold(f32::EPSILON, f32::max)
+                }
+            })
+            .collect();
+        let smoothing = self.render_settings.brightness_smoothing;
+        if smoothing > 0.0 {
+            self.smoothing
+                .lock()
+                .unwrap()
+                .update(self.iteration, &max_values, smoothing)
+        } else {
+            max_values
+        }
+    }
+
+    fn layer_quantiles(&self, fields: &[Cow<'_, [f32]>], fraction: f32) -> Vec<f32> {
+        let settings = &self.render_settings.quantile;
+        let compute = || {
+            fields
+                .par_iter()
+                .map(|field| quantile::estimate(field, fraction, settings.samples))
+                .collect()
+        };
+        self.quantile_cache.lock().unwrap().get_or_update(
+            self.iteration,
+            fields.len(),
+            settings,
+            compute,
+        )
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Populations past the palette's colors, which get colors generated from the palette.
+    pub fn generated_colors(&self) -> usize {
+        self.grids.len().saturating_sub(self.palette.colors.len())
+    }
+
+    /// How each population is colored, see `PopulationStyle::color`.
+    fn population_colors(&self) -> Vec<PopulationColor> {
+        (0..self.grids.len())
+            .map(|k| self.render_settings.style(k).color)
+            .collect()
+    }
+
+    /// The single color each population is drawn in where no density ramp is needed, e.g.
+    /// by the GPU colorizer.
+    pub fn population_tints(&self) -> Vec<Rgb<u8>> {
+        self.population_colors()
+            .iter()
+            .enumerate()
+            .map(|(k, color)| color.tint(&self.palette, k))
+            .collect()
+    }
+
+    /// Whether the frame needs compositing on the CPU, because post-processing, a LUT or
+    /// an effect the shader lacks is active, rather than being colorized straight from the layer fields.
+    pub fn needs_cpu_compositing(&self) -> bool {
+        self.strict_determinism
+            || self.lut.is_some()
+            || self.background.is_some()
+            || self.mask.is_some()
+            || self.render_settings.color_vision != ColorVision::Normal
+            || self
+                .render_settings
+                .populations
+                .iter()
+                .any(|style| matches!(style.color, PopulationColor::Ramp { .. }))
+            || self.render_settings.postprocess.is_active()
+            || self.render_settings.normalization == Normalization::HistogramEqualization
+            || self.render_settings.accumulation_frames > 1
+            || self.render_settings.contours.enabled
+            || self.render_settings.stipple.enabled
+            || self.render_settings.populations.iter().any(|style| {
+                style.is_defocused() || style.blend != LayerBlend::Add || !style.opacity.is_empty()
+            })
+    }
+
+    /// Brightness of every population's layer in [0, 1], gamma encoded, before any coloring.
+    fn normalized_fields(&self) -> Vec<Vec<f32>> {
+        let fields = self.layer_fields();
+        if self.render_settings.normalization == Normalization::HistogramEqualization {
+            let samples = self.render_settings.quantile.samples;
+            return fields
+                .par_iter()
+                .map(|field| normalization::equalize(field, samples))
+                .collect();
+        }
+        let max_values = self.layer_max_values(&fields);
+        let powf = Math::new(self.strict_determinism).powf;
+        fields
+            .par_iter()
+            .zip(&max_values)
+            .map(|(field, max_value)| {
+                field
+                    .iter()
+                    .map(|v| powf((v / max_value).clamp(0.0, 1.0), 1.0 / 2.2))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The normalized fields as drawn, replaced by their stippled dots or isolines when
+    /// `RenderSettings::stipple` or `RenderSettings::contours` is enabled, and blurred and
+    /// dimmed by the populations' styles.
+    fn drawn_fields(&self) -> Vec<Vec<f32>> {
+        let fields = self.normalized_fields();
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let contours = &self.render_settings.contours;
+        let mut fields = if self.render_settings.stipple.enabled {
+            self.stipple_dots(&fields)
+                .iter()
+                .map(|dots| stipple::rasterize(dots, width, height))
+                .collect()
+        } else if contours.enabled {
+            fields
+                .iter()
+                .map(|field| {
+                    let field = contour::smooth(field, width, height, contours.smoothing);
+                    contour::rasterize(&field, width, height, contours)
+                })
+                .collect()
+        } else {
+            fields
+        };
+        fields
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(k, field)| self.render_settings.style(k).defocus(field, width, height));
+        fields
+    }
+
+    /// The dots of every population for `RenderSettings::stipple`, seeded by the
+    /// population so that they only move where the trails change.
+    fn stipple_dots(&self, fields: &[Vec<f32>]) -> Vec<Vec<stipple::Dot>> {
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let settings = &self.render_settings.stipple;
+        fields
+            .par_iter()
+            .enumerate()
+            .map(|(k, field)| stipple::stipple(field, width, height, settings, k as u64))
+            .collect()
+    }
+}
+
+#[cfg(feature = "image-export")]
+impl PhysarumModel {
+    /// An image of the grid's size for `save_to_image`, with an alpha channel when
+    /// `RenderSettings::alpha` is enabled.
+    pub fn new_image(&self) -> DynamicImage {
+        let (width, height) = (self.grids[0].width as u32, self.grids[0].height as u32);
+        if self.render_settings.alpha.enabled {
+            DynamicImage::new_rgba8(width, height)
+        } else {
+            DynamicImage::new_rgb8(width, height)
+        }
+    }
+
+    /// Calls `hook` with the model and the frame whenever `save_to_image`, `save_ora` or
+    /// `save_svg` renders or writes one, e.g. to post every exported frame somewhere.
+    pub fn on_frame_exported(
+        &mut self,
+        hook: impl for<'a> FnMut(&PhysarumModel, Export<'a>) + Send + 'static,
+    ) {
+        self.hooks
+            .get_mut()
+            .unwrap()
+            .frame_exported
+            .push(Box::new(hook));
+    }
+
+    fn exported(&self, export: Export) {
+        self.emit(|hooks, model| {
+            for hook in &mut hooks.frame_exported {
+                hook(model, export);
+            }
+        });
+    }
+
+    pub fn save_to_image(&self, image: &mut DynamicImage) {
+        self.render_frame(image);
+        self.exported(Export::Image(image));
+    }
+
+    /// Renders like `save_to_image` with the trails `t` of the way from `previous`, the
+    /// `trails` of an earlier step, to the current ones. Slow-motion exports fill the gaps
+    /// between steps with these frames, since trails fade in and out smoothly. Trails of a
+    /// different size are ignored and the current ones rendered instead.
+    pub fn save_interpolated(&mut self, previous: &[Vec<f32>], t: f32, image: &mut DynamicImage) {
+        let matches = previous.len() == self.grids.len()
+            && previous
+                .iter()
+                .zip(&self.grids)
+                .all(|(trail, grid)| trail.len() == grid.data().len());
+        if !matches {
+            self.render_frame(image);
+            return;
+        }
+        let current = self.trails();
+        for ((grid, from), to) in self.grids.iter_mut().zip(previous).zip(&current) {
+            grid.data_mut()
+                .par_iter_mut()
+                .zip(from.par_iter().zip(to.par_iter()))
+                .for_each(|(cell, (from, to))| *cell = from + (to - from) * t);
+        }
+        self.render_frame(image);
+        for (grid, trail) in self.grids.iter_mut().zip(current) {
+            grid.data_mut().copy_from_slice(&trail);
+        }
+    }
+
+    /// Opacity of each of the `layers` population layers at the current iteration.
+    fn opacities(&self, layers: usize) -> Vec<f32> {
+        (0..layers)
+            .map(|k| self.render_settings.style(k).opacity_at(self.iteration))
+            .collect()
+    }
+
+    fn render_frame(&self, image: &mut DynamicImage) {
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let fields = self.drawn_fields();
+        let colors = self.population_colors();
+        let blends: Vec<LayerBlend> = (0..fields.len())
+            .map(|k| self.render_settings.style(k).blend)
+            .collect();
+        let opacities = self.opacities(fields.len());
+        let order = self.render_settings.order.sort(&fields);
+        let mut pixels: Vec<[f32; 3]> = (0..width * height)
+            .into_par_iter()
+            .map(|i| {
+                let mut pixel = [0.0_f32; 3];
+                for &k in &order {
+                    let (color, coverage) = colors[k].shade(&self.palette, k, fields[k][i]);
+                    let coverage = coverage * opacities[k];
+                    blends[k].blend(&mut pixel, color.map(|c| c * coverage), coverage);
+                }
+                pixel
+            })
+            .collect();
+        if self.render_settings.accumulation_frames > 1 {
+            self.accumulator.lock().unwrap().accumulate(
+                self.iteration,
+                &mut pixels,
+                self.render_settings.accumulation_frames,
+            );
+        }
+        if let Some(background) = &self.background {
+            self.render_settings
+                .background
+                .blend(&mut pixels, background);
+        }
+        postprocess::apply(
+            &mut pixels,
+            width,
+            height,
+            &self.render_settings.postprocess,
+            self.iteration,
+            Math::new(self.strict_determinism),
+        );
+
+        if let Some(lut) = &self.lut {
+            pixels
+                .par_iter_mut()
+                .for_each(|pixel| *pixel = lut.apply(*pixel));
+        }
+        self.render_settings
+            .color_vision
+            .simulate(&mut pixels, Math::new(self.strict_determinism));
+        if let Some(mask) = &self.mask {
+            pixels
+                .par_iter_mut()
+                .zip(mask)
+                .for_each(|(pixel, coverage)| *pixel = pixel.map(|c| c * coverage));
+        }
+
+        let alpha = self.render_settings.alpha;
+        let alphas: Option<Vec<f32>> = alpha.enabled.then(|| {
+            (0..width * height)
+                .into_par_iter()
+                .map(|i| {
+                    let density = alpha.alpha(fields.iter().map(|field| field[i]).sum());
+                    self.mask.as_ref().map_or(density, |mask| density * mask[i])
+                })
+                .collect()
+        });
+        for (i, pixel) in pixels.iter().enumerate() {
+            let a = alphas.as_ref().map_or(1.0, |alphas| alphas[i]);
+            let pixel = if a > 0.0 {
+                pixel.map(|c| c / a)
+            } else {
+                *pixel
+            };
+            let [r, g, b] = pixel.map(|c| (c * 255.0).clamp(0.0, 255.0) as u8);
+            let a = (a * 255.0) as u8;
+            image.put_pixel((i % width) as u32, (i / width) as u32, Rgba([r, g, b, a]));
+        }
+    }
+
+    /// Saves the frame as an OpenRaster file with a layer per population, colored and with
+    /// the population's brightness as alpha, added together above the background. Effects
+    /// applied to the whole frame, like post-processing and the LUT, only show in the
+    /// merged preview.
+    pub fn save_ora(&self, path: &Path) -> io::Result<()> {
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let fields = self.drawn_fields();
+        let colors = self.population_colors();
+        let opacities = self.opacities(fields.len());
+        let trails = self
+            .render_settings
+            .order
+            .sort(&fields)
+            .into_iter()
+            .rev()
+            .map(|k| ora::Node::Layer {
+                name: format!("Population {}", k + 1),
+                image: RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+                    let v = fields[k][y as usize * width + x as usize];
+                    let (color, coverage) = colors[k].shade(&self.palette, k, v);
+                    let [r, g, b] = color.map(|c| (c * 255.0).round() as u8);
+                    let alpha = coverage * opacities[k] * 255.0;
+                    Rgba([r, g, b, alpha.clamp(0.0, 255.0) as u8])
+                }),
+                composite: match self.render_settings.style(k).blend {
+                    LayerBlend::Add => ora::Composite::Plus,
+                    LayerBlend::Screen => ora::Composite::Screen,
+                    LayerBlend::Over => ora::Composite::Over,
+                },
+            })
+            .collect();
+        let mut nodes = vec![ora::Node::Group {
+            name: "Trails".to_string(),
+            children: trails,
+            composite: match self.render_settings.background.blend {
+                BlendMode::Add => ora::Composite::Plus,
+                BlendMode::Screen => ora::Composite::Screen,
+                BlendMode::Multiply => ora::Composite::Multiply,
+                // Matches subtracting wherever the trails are darker than the background.
+                BlendMode::Subtract => ora::Composite::Difference,
+            },
+        }];
+        let background = self.background.as_ref().map_or_else(
+            || RgbaImage::from_pixel(width as u32, height as u32, Rgba([0, 0, 0, 255])),
+            |background| {
+                RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+                    let pixel = background[y as usize * width + x as usize];
+                    let [r, g, b] = pixel.map(|c| (c * 255.0).clamp(0.0, 255.0) as u8);
+                    Rgba([r, g, b, 255])
+                })
+            },
+        );
+        nodes.push(ora::Node::Layer {
+            name: "Background".to_string(),
+            image: background,
+            composite: ora::Composite::Over,
+        });
+
+        let mut merged = self.new_image();
+        self.render_frame(&mut merged);
+        ora::save(path, &nodes, &merged.to_rgba8())?;
+        self.exported(Export::Ora(path));
+        Ok(())
+    }
+
+    /// Saves the frame as an SVG for pen plotters, in the populations' colors: their
+    /// stippled dots when `RenderSettings::stipple` is enabled, or else their isolines at
+    /// the levels of `RenderSettings::contours`, whether or not the contours are enabled.
+    pub fn save_svg(&self, path: &Path) -> io::Result<()> {
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let fields = self.normalized_fields();
+        let svg = if self.render_settings.stipple.enabled {
+            let layers: Vec<_> = self
+                .population_tints()
+                .into_iter()
+                .zip(self.stipple_dots(&fields))
+                .collect();
+            stipple::svg(&layers, width, height)
+        } else {
+            let contours = &self.render_settings.contours;
+            let layers: Vec<_> = fields
+                .par_iter()
+                .zip(self.population_tints())
+                .map(|(field, tint)| {
+                    let field = contour::smooth(field, width, height, contours.smoothing);
+                    let segments = contours
+                        .levels
+                        .iter()
+                        .flat_map(|&level| contour::isolines(&field, width, height, level))
+                        .collect();
+                    (tint, segments)
+                })
+                .collect();
+            contour::svg(&layers, width, height, contours.line_width)
+        };
+        std::fs::write(path, svg)?;
+        self.exported(Export::Svg(path));
+        Ok(())
+    }
+
+    /// Saves the frame as a TIFF for printing, with the resolution, bleed and color profile
+    /// of `RenderSettings::print`.
+    pub fn save_tiff(&self, path: &Path) -> io::Result<()> {
+        let mut image = self.new_image();
+        self.render_frame(&mut image);
+        print::save_tiff(&image, path, &self.render_settings.print)?;
+        self.exported(Export::Tiff(path));
+        Ok(())
+    }
+
+    /// The frame with an info strip below it for reviewing exploration runs: brightness
+    /// histograms, every population's config, the palette and the attraction table.
+    pub fn debug_image(&self) -> RgbImage {
+        let mut frame =
+            DynamicImage::new_rgb8(self.grids[0].width as u32, self.grids[0].height as u32);
+        self.render_frame(&mut frame);
+        let tints = self.population_tints();
+        let histograms = self
+            .normalized_fields()
+            .iter()
+            .zip(&tints)
+            .map(|(field, tint)| {
+                let mut counts = vec![0; info_strip::HISTOGRAM_BINS];
+                for v in field {
+                    let bin = (v * info_strip::HISTOGRAM_BINS as f32) as usize;
+                    counts[bin.min(info_strip::HISTOGRAM_BINS - 1)] += 1;
+                }
+                (*tint, counts)
+            })
+            .collect();
+        let mut lines = vec![format!(
+            "Iteration {}  {}x{}  {} agents  diffusivity {}",
+            self.iteration,
+            self.grids[0].width,
+            self.grids[0].height,
+            self.agents.len(),
+            self.diffusity
+        )];
+        lines.extend(self.grids.iter().enumerate().map(|(k, grid)| {
+            let c = &grid.config;
+            format!(
+                "Population {}: sensor {:.1} at {:.1}, turn {:.1}, step {:.2}, decay {:.3}, \
+                 deposit {:.2}, bias {:.1} by {:.2}, repulsion {:.2}, erosion {:.2}",
+                k + 1,
+                c.sensor_distance,
+                c.sensor_angle,
+                c.rotation_angle,
+                c.step_distance,
+                c.decay_factor,
+                c.deposition_amount,
+                c.bias_angle,
+                c.bias_strength,
+                c.self_repulsion,
+                c.erosion
+            )
+        }));
+        info_strip::append(
+            &frame.to_rgb8(),
+            &info_strip::StripInfo {
+                lines,
+                histograms,
+                palette: &self.palette,
+                attraction_table: &self.attraction_table,
+            },
+        )
+    }
+
+    /// Dithers the populations into `layers` one-bit ink separations for risograph or
+    /// screen printing, black where ink goes. With fewer layers than populations, the
+    /// populations are shared out round-robin and their densities added up.
+    pub fn ink_layers(&self, layers: usize, method: DitherMethod) -> Vec<GrayImage> {
+        let (width, height) = (self.grids[0].width, self.grids[0].height);
+        let fields = self.normalized_fields();
+        (0..layers.max(1))
+            .into_par_iter()
+            .map(|layer| {
+                let mut density = vec![0.0; width * height];
+                for field in fields.iter().skip(layer).step_by(layers.max(1)) {
+                    for (d, v) in density.iter_mut().zip(field) {
+                        *d += v;
+                    }
+                }
+                if let Some(mask) = &self.mask {
+                    for (d, coverage) in density.iter_mut().zip(mask) {
+                        *d *= coverage;
+                    }
+                }
+                let ink = dither::dither(&density, width, height, method);
+                GrayImage::from_fn(width as u32, height as u32, |x, y| {
+                    let inked = ink[y as usize * width + x as usize];
+                    Luma([if inked { 0 } else { 255 }])
+                })
+            })
+            .collect()
+    }
+}
+
+/// The error of a file named by `setting` that couldn't be read, at `path` if it names one.
+fn unreadable(setting: &'static str, path: Option<&Path>, err: io::Error) -> ConfigError {
+    let reason = match path {
+        Some(path) => format!("{}: {}", path.display(), err),
+        None => err.to_string(),
+    };
+    ConfigError::Unreadable { setting, reason }
+}
+
+#[cfg(all(test, feature = "image-export"))]
+mod tests {
+    use super::*;
+    use crate::render::{LayerOrder, PopulationStyle};
+    use rand::SeedableRng;
+
+    #[test]
+    fn interpolated_frames_blend_the_trails_and_restore_them() {
+        let mut model = PhysarumModel::builder()
+            .size(32, 32)
+            .particles(200)
+            .seed(1)
+            .build()
+            .unwrap();
+        model.step();
+        let previous = model.trails();
+        model.step();
+        let current = model.trails();
+        let (mut frame, mut blended) = (model.new_image(), model.new_image());
+        model.save_to_image(&mut frame);
+        model.save_interpolated(&previous, 0.5, &mut blended);
+        assert_ne!(frame, blended);
+        assert_eq!(model.trails(), current);
+        model.save_interpolated(&previous, 1.0, &mut blended);
+        assert_eq!(frame, blended);
+        model.save_interpolated(&[], 0.5, &mut blended);
+        assert_eq!(frame, blended);
+    }
+
+    #[test]
+    fn defocused_populations_are_blurred_and_dimmed() {
+        let mut model = PhysarumModel::builder()
+            .size(32, 32)
+            .particles(400)
+            .populations(2)
+            .seed(2)
+            .build()
+            .unwrap();
+        for _ in 0..5 {
+            model.step();
+        }
+        let sharp = model.drawn_fields();
+        let mut settings = model.render_settings().clone();
+        *settings.style_mut(1) = PopulationStyle {
+            blur: 2.0,
+            brightness: 0.5,
+            ..PopulationStyle::default()
+        };
+        model.set_render_settings(settings).unwrap();
+        assert!(model.needs_cpu_compositing());
+        let defocused = model.drawn_fields();
+        assert_eq!(defocused[0], sharp[0]);
+        let sum = |field: &[f32]| field.iter().sum::<f32>();
+        assert!((sum(&defocused[1]) - 0.5 * sum(&sharp[1])).abs() < 1e-2 * sum(&sharp[1]));
+        let roughness = |field: &[f32]| {
+            field
+                .windows(2)
+                .map(|pair| (pair[1] - pair[0]).abs())
+                .sum::<f32>()
+        };
+        assert!(roughness(&defocused[1]) < 0.25 * roughness(&sharp[1]));
+    }
+
+    #[test]
+    fn opaque_layers_cover_the_ones_below_in_order() {
+        let mut model = PhysarumModel::builder()
+            .size(32, 32)
+            .particles(400)
+            .populations(2)
+            .seed(3)
+            .build()
+            .unwrap();
+        for _ in 0..5 {
+            model.step();
+        }
+        let render = |model: &mut PhysarumModel, order: LayerOrder| {
+            let mut settings = model.render_settings().clone();
+            settings.order = order;
+            model.set_render_settings(settings).unwrap();
+            let mut image = model.new_image();
+            model.save_to_image(&mut image);
+            image
+        };
+        let added = render(&mut model, LayerOrder::Grid);
+        let swapped = LayerOrder::Custom { layers: vec![1] };
+        assert_eq!(render(&mut model, swapped.clone()), added);
+
+        let mut settings = model.render_settings().clone();
+        for k in 0..2 {
+            settings.style_mut(k).blend = LayerBlend::Over;
+        }
+        model.set_render_settings(settings).unwrap();
+        let over = render(&mut model, LayerOrder::Grid);
+        assert_ne!(over, added);
+        assert_ne!(render(&mut model, swapped), over);
+
+        let fields = model.drawn_fields();
+        let mean = |k: usize| fields[k].iter().sum::<f32>();
+        let auto = LayerOrder::Auto.sort(&fields);
+        assert!(mean(auto[0]) >= mean(auto[1]));
+        let custom = LayerOrder::Custom {
+            layers: vec![1, 1, 7],
+        };
+        assert_eq!(custom.sort(&fields), vec![1, 0]);
+    }
+
+    #[test]
+    fn populations_join_a_running_model() {
+        let mut model = PhysarumModel::builder()
+            .size(32, 32)
+            .particles(200)
+            .populations(2)
+            .seed(5)
+            .build()
+            .unwrap();
+        model
+            .set_deposition_table(Some(vec![vec![1.0, 0.5], vec![0.0, 1.0]]))
+            .unwrap();
+        for _ in 0..3 {
+            model.step();
+        }
+        let mut rng = SmallRng::seed_from_u64(1);
+        let config = PopulationConfig::new(&mut rng);
+        let wrong = NewAttraction::Supplied {
+            row: vec![1.0, 2.0],
+            column: vec![3.0, 4.0],
+        };
+        assert_eq!(
+            model.add_population(config, 50, wrong, &mut rng),
+            Err(ConfigError::AttractionTableSize { populations: 3 })
+        );
+        assert_eq!(model.grids.len(), 2);
+
+        let supplied = NewAttraction::Supplied {
+            row: vec![-1.0, 0.5, 1.0],
+            column: vec![0.25, -0.5],
+        };
+        assert_eq!(model.add_population(config, 50, supplied, &mut rng), Ok(2));
+        let table = model.attraction_table();
+        assert_eq!(table[2], vec![-1.0, 0.5, 1.0]);
+        assert_eq!((table[0][2], table[1][2]), (0.25, -0.5));
+        let deposition = model.deposition_table().unwrap();
+        assert_eq!(deposition[0], vec![1.0, 0.5, 0.0]);
+        assert_eq!(deposition[2], vec![0.0, 0.0, config.deposition_amount]);
+        assert_eq!(model.population_size(2), 50);
+        assert!(model.grids[2].data().iter().all(|&v| v == 0.0));
+        model.step();
+        assert!(model.grids[2].data().iter().any(|&v| v > 0.0));
+
+        let sampled = NewAttraction::Sampled(AttractionDistribution::default());
+        assert_eq!(model.add_population(config, 10, sampled, &mut rng), Ok(3));
+        assert!(model.attraction_table().iter().all(|row| row.len() == 4));
+        model.step();
+    }
+
+    #[test]
+    fn resizing_rejects_unknown_populations_and_foreign_agents() {
+        let mut model = PhysarumModel::builder()
+            .size(32, 32)
+            .particles(300)
+            .populations(3)
+            .seed(2)
+            .build()
+            .unwrap();
+        let mut rng = SmallRng::seed_from_u64(0);
+        assert_eq!(
+            model.set_population_size(7, 10, &mut rng),
+            Err(ConfigError::UnknownPopulation {
+                population: 7,
+                populations: 3
+            })
+        );
+        assert_eq!(
+            model.set_population_size(0, grid::MAX_PARTICLES, &mut rng),
+            Err(ConfigError::TooManyParticles {
+                particles: grid::MAX_PARTICLES + 200
+            })
+        );
+        let stray = Particle::new(32, 32, 7, &mut rng);
+        assert_eq!(
+            model.add_agents(1, vec![stray]),
+            Err(ConfigError::ForeignAgents { population: 1 })
+        );
+        assert_eq!(model.agents().len(), 300);
+        model.set_population_size(2, 150, &mut rng).unwrap();
+        assert_eq!(model.population_size(2), 150);
+        model.step();
+    }
+
+    #[test]
+    fn populations_leave_or_merge_consistently() {
+        let build = || {
+            let mut model = PhysarumModel::builder()
+                .size(32, 32)
+                .particles(300)
+                .populations(3)
+                .seed(6)
+                .build()
+                .unwrap();
+            let table = vec![
+                vec![1.0, 2.0, 3.0],
+                vec![4.0, 5.0, 6.0],
+                vec![7.0, 8.0, 9.0],
+            ];
+            model.set_attraction_table(table).unwrap();
+            model
+                .set_sensing(vec![Sensing::Weights {
+                    weights: vec![1.0, 0.0, -1.0],
+                }])
+                .unwrap();
+            model.step();
+            model
+        };
+
+        let mut model = build();
+        assert_eq!(
+            model.remove_population(3, RemovedAgents::Freed),
+            Err(ConfigError::UnknownPopulation {
+                population: 3,
+                populations: 3
+            })
+        );
+        let last = model.grids[2].data().to_vec();
+        model.remove_population(1, RemovedAgents::Freed).unwrap();
+        assert_eq!(model.agents().len(), 200);
+        assert_eq!(model.attraction_table(), [vec![1.0, 3.0], vec![7.0, 9.0]]);
+        assert_eq!(
+            model.sensing(),
+            [Sensing::Weights {
+                weights: vec![1.0, -1.0]
+            }]
+        );
+        assert_eq!(model.grids[1].data(), &last[..]);
+        assert!(model.agents().iter().all(|agent| agent.id < 2));
+        model.step();
+
+        let mut model = build();
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        model
+            .set_steering(names(&["threshold", "softmax", "gradient"]))
+            .unwrap();
+        model
+            .remove_population(0, RemovedAgents::Reassigned(2))
+            .unwrap();
+        assert_eq!(
+            (model.population_size(0), model.population_size(1)),
+            (100, 200)
+        );
+        // Every population keeps its own steering rule.
+        assert_eq!(model.steering(), ["softmax", "gradient"]);
+        assert_eq!(model.steering_rules.len(), 2);
+        model.remove_population(1, RemovedAgents::Freed).unwrap();
+        assert_eq!(
+            model.remove_population(0, RemovedAgents::Freed),
+            Err(ConfigError::NoPopulations)
+        );
+
+        let mut model = build();
+        let sum = |model: &PhysarumModel, k: usize| model.grids[k].data().iter().sum::<f32>();
+        let total = sum(&model, 0) + sum(&model, 2);
+        model.merge_populations(2, 0).unwrap();
+        assert!((sum(&model, 0) - total).abs() < 1e-3 * total);
+        assert_eq!(model.population_size(0), 200);
+        assert_eq!(model.attraction_table(), [vec![1.0, 2.0], vec![4.0, 5.0]]);
+        model.step();
+    }
+
+    #[test]
+    fn faded_populations_keep_running_unseen() {
+        let style = PopulationStyle {
+            opacity: vec![(10, 0.0), (20, 1.0), (30, 0.25)],
+            ..PopulationStyle::default()
+        };
+        assert_eq!(style.opacity_at(0), 0.0);
+        assert_eq!(style.opacity_at(15), 0.5);
+        assert_eq!(style.opacity_at(25), 0.625);
+        assert_eq!(style.opacity_at(100), 0.25);
+        assert_eq!(PopulationStyle::default().opacity_at(5), 1.0);
+
+        let mut model = PhysarumModel::builder()
+            .size(32, 32)
+            .particles(400)
+            .populations(2)
+            .seed(4)
+            .build()
+            .unwrap();
+        for _ in 0..5 {
+            model.step();
+        }
+        let mut settings = model.render_settings().clone();
+        settings.style_mut(1).opacity = vec![(0, 0.0)];
+        model.set_render_settings(settings).unwrap();
+        assert!(model.needs_cpu_compositing());
+        let mut faded = model.new_image();
+        model.save_to_image(&mut faded);
+
+        let mut settings = model.render_settings().clone();
+        settings.style_mut(1).opacity.clear();
+        settings.style_mut(1).brightness = 0.0;
+        model.set_render_settings(settings).unwrap();
+        let mut hidden = model.new_image();
+        model.save_to_image(&mut hidden);
+        assert_eq!(faded, hidden);
+        assert!(model.trails()[1].iter().any(|&v| v > 0.0));
+    }
+}
@@ -0,0 +1,174 @@
+#[cfg(not(any(feature = "std", test)))]
+use super::math::Float;
+use super::math::Math;
+use rand::{rngs::SmallRng, Rng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PopulationConfig {
+    pub sensor_distance: f32,
+    pub step_distance: f32,
+    pub sensor_angle: f32,
+    pub rotation_angle: f32,
+
+    pub decay_factor: f32,
+    pub deposition_amount: f32,
+
+    /// Heading the agents are pulled towards, in radians. 0 points along +x and TAU / 4
+    /// along +y, which is down in rendered images.
+    #[serde(default)]
+    pub bias_angle: f32,
+    /// Fraction of the way towards `bias_angle` agents turn every step, 0 for no bias.
+    #[serde(default)]
+    pub bias_strength: f32,
+
+    /// How strongly agents avoid their own trails at close range, 0 to always follow them.
+    /// Past the avoidance they're drawn to their trails blurred over
+    /// `self_attraction_range`, so they fill the space left between their own paths
+    /// instead of collapsing into bundles.
+    #[serde(default)]
+    pub self_repulsion: f32,
+    /// Deviation in cells of the blur the self-attraction acts over with `self_repulsion`.
+    #[serde(default = "default_self_attraction_range")]
+    pub self_attraction_range: f32,
+
+    /// Fraction of their own trail, between 0 and 1, agents consume from every cell they
+    /// step onto. Busy paths wear away the trails that built them, so the network keeps
+    /// rewiring itself instead of thickening forever.
+    #[serde(default)]
+    pub erosion: f32,
+
+    /// Longest move between two deposits, 0 to deposit once per step. Longer steps are
+    /// split into up to `MAX_SUBSTEPS` equal substeps that share the deposit, so fast
+    /// agents leave unbroken trails instead of tunneling across thin filaments.
+    #[serde(default)]
+    pub max_substep: f32,
+}
+
+fn default_self_attraction_range() -> f32 {
+    3.0
+}
+
+impl PopulationConfig {
+    pub const MAX_SUBSTEPS: usize = 64;
+
+    /// Number of moves each step is split into, see `max_substep`.
+    pub(crate) fn substeps(&self) -> usize {
+        if self.max_substep > 0.0 && self.step_distance > self.max_substep {
+            ((self.step_distance / self.max_substep).ceil() as usize).min(Self::MAX_SUBSTEPS)
+        } else {
+            1
+        }
+    }
+
+    /// The config covering `time_scale` steps in one, for running the same dynamics in
+    /// finer or coarser steps. Moving and turning scale linearly, per-step fractions like
+    /// decay and erosion as powers, and the deposit so that `1 / time_scale` steps of
+    /// depositing and decaying leave exactly as much trail as one unscaled step. Sensing
+    /// and substep lengths stay the same.
+    pub(crate) fn scaled(&self, time_scale: f32, math: Math) -> Self {
+        if time_scale == 1.0 {
+            return *self;
+        }
+        PopulationConfig {
+            decay_factor: (math.powf)(self.decay_factor, time_scale),
+            deposition_amount: self.deposition_amount * self.deposit_scale(time_scale, math),
+            step_distance: self.step_distance * time_scale,
+            rotation_angle: self.rotation_angle * time_scale,
+            bias_strength: self.bias_strength * time_scale,
+            erosion: 1.0 - (math.powf)(1.0 - self.erosion, time_scale),
+            ..*self
+        }
+    }
+
+    /// What deposits into trails with this config's decay are multiplied by in `scaled`.
+    pub(crate) fn deposit_scale(&self, time_scale: f32, math: Math) -> f32 {
+        let decay = self.decay_factor;
+        if decay > 0.0 && decay != 1.0 {
+            (math.powf)(decay, 1.0 - time_scale) * (1.0 - (math.powf)(decay, time_scale))
+                / (1.0 - decay)
+        } else {
+            time_scale
+        }
+    }
+
+    const SENSOR_ANGLE_MIN: f32 = 0.0;
+    const SENSOR_ANGLE_MAX: f32 = 120.0;
+    const SENSOR_DISTANCE_MIN: f32 = 0.0;
+    const SENSOR_DISTANCE_MAX: f32 = 64.0;
+    const ROTATION_ANGLE_MIN: f32 = 0.0;
+    const ROTATION_ANGLE_MAX: f32 = 120.0;
+    const STEP_DISTANCE_MIN: f32 = 0.2;
+    const STEP_DISTANCE_MAX: f32 = 2.0;
+    const DEPOSITION_AMOUNT_MIN: f32 = 5.0;
+    const DEPOSITION_AMOUNT_MAX: f32 = 5.0;
+    const DECAY_FACTOR_MIN: f32 = 0.1;
+    const DECAY_FACTOR_MAX: f32 = 0.1;
+
+    pub fn new(rng: &mut SmallRng) -> Self {
+        PopulationConfig {
+            sensor_distance: rng.gen_range(Self::SENSOR_DISTANCE_MIN..=Self::SENSOR_DISTANCE_MAX),
+            step_distance: rng.gen_range(Self::STEP_DISTANCE_MIN..=Self::STEP_DISTANCE_MAX),
+            decay_factor: rng.gen_range(Self::DECAY_FACTOR_MIN..=Self::DECAY_FACTOR_MAX),
+            sensor_angle: rng
+                .gen_range(Self::SENSOR_ANGLE_MIN..=Self::SENSOR_ANGLE_MAX)
+                .to_radians(),
+            rotation_angle: rng
+                .gen_range(Self::ROTATION_ANGLE_MIN..=Self::ROTATION_ANGLE_MAX)
+                .to_radians(),
+            deposition_amount: rng
+                .gen_range(Self::DEPOSITION_AMOUNT_MIN..=Self::DEPOSITION_AMOUNT_MAX),
+            bias_angle: 0.0,
+            bias_strength: 0.0,
+            self_repulsion: 0.0,
+            self_attraction_range: default_self_attraction_range(),
+            erosion: 0.0,
+            max_substep: 0.0,
+        }
+    }
+
+    /// The config `t` of the way from this one to `other`, for gliding between them.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        PopulationConfig {
+            sensor_distance: mix(self.sensor_distance, other.sensor_distance),
+            step_distance: mix(self.step_distance, other.step_distance),
+            sensor_angle: mix(self.sensor_angle, other.sensor_angle),
+            rotation_angle: mix(self.rotation_angle, other.rotation_angle),
+            decay_factor: mix(self.decay_factor, other.decay_factor),
+            deposition_amount: mix(self.deposition_amount, other.deposition_amount),
+            bias_angle: mix(self.bias_angle, other.bias_angle),
+            bias_strength: mix(self.bias_strength, other.bias_strength),
+            self_repulsion: mix(self.self_repulsion, other.self_repulsion),
+            self_attraction_range: mix(self.self_attraction_range, other.self_attraction_range),
+            erosion: mix(self.erosion, other.erosion),
+            max_substep: mix(self.max_substep, other.max_substep),
+        }
+    }
+
+    /// A variation moving each of the parameters `new` draws by up to `strength` of the
+    /// range it draws them from, e.g. for refining a config that turned out well.
+    pub fn mutated(&self, strength: f32, rng: &mut SmallRng) -> Self {
+        let mut nudge = |value: f32, min: f32, max: f32| {
+            (value + rng.gen_range(-1.0..=1.0) * strength * (max - min)).clamp(min, max)
+        };
+        let degrees = |min: f32, max: f32| (min.to_radians(), max.to_radians());
+        let sensor_angle = degrees(Self::SENSOR_ANGLE_MIN, Self::SENSOR_ANGLE_MAX);
+        let rotation_angle = degrees(Self::ROTATION_ANGLE_MIN, Self::ROTATION_ANGLE_MAX);
+        PopulationConfig {
+            sensor_distance: nudge(
+                self.sensor_distance,
+                Self::SENSOR_DISTANCE_MIN,
+                Self::SENSOR_DISTANCE_MAX,
+            ),
+            step_distance: nudge(
+                self.step_distance,
+                Self::STEP_DISTANCE_MIN,
+                Self::STEP_DISTANCE_MAX,
+            ),
+            sensor_angle: nudge(self.sensor_angle, sensor_angle.0, sensor_angle.1),
+            rotation_angle: nudge(self.rotation_angle, rotation_angle.0, rotation_angle.1),
+            ..*self
+        }
+    }
+}
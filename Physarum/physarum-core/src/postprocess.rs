@@ -0,0 +1,261 @@
+use super::math::Math;
+use super::par::*;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_BLOOM_RADIUS: f32 = 64.0;
+
+/// Effects applied to the composited frame before it is displayed or saved.
+/// Every effect is disabled at its default strength of zero.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct PostProcessSettings {
+    /// How much of the blurred highlights is added back onto the frame.
+    pub bloom_intensity: f32,
+    /// Luminance above which pixels start to bloom, in [0, 1].
+    pub bloom_threshold: f32,
+    /// Standard deviation of the bloom blur, in pixels, up to `MAX_BLOOM_RADIUS`.
+    pub bloom_radius: f32,
+    /// Blurred copies of the whole frame added back onto it, for a soft depth around the
+    /// sharp trails. Unlike bloom nothing is cut off, dim areas glow as well.
+    pub glow: Vec<GlowLayer>,
+    /// Darkening of the frame's corners, in [0, 1].
+    pub vignette: f32,
+    /// Offset in pixels of the red and blue channels at the frame's corners.
+    pub chromatic_aberration: f32,
+    /// Amplitude of the per-pixel film grain noise.
+    pub grain: f32,
+}
+
+impl Default for PostProcessSettings {
+    fn default() -> Self {
+        PostProcessSettings {
+            bloom_intensity: 0.0,
+            bloom_threshold: 0.6,
+            bloom_radius: 8.0,
+            glow: vec![],
+            vignette: 0.0,
+            chromatic_aberration: 0.0,
+            grain: 0.0,
+        }
+    }
+}
+
+/// One blurred copy of the frame in `PostProcessSettings::glow`.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct GlowLayer {
+    /// Standard deviation of the blur, in pixels, up to `MAX_BLOOM_RADIUS`.
+    pub radius: f32,
+    /// How much of the copy is added, negative to darken around bright areas instead.
+    pub weight: f32,
+}
+
+impl Default for GlowLayer {
+    fn default() -> Self {
+        GlowLayer {
+            radius: 24.0,
+            weight: 0.5,
+        }
+    }
+}
+
+impl PostProcessSettings {
+    pub fn is_active(&self) -> bool {
+        self.bloom_intensity > 0.0
+            || self.glow.iter().any(|layer| layer.weight != 0.0)
+            || self.vignette > 0.0
+            || self.chromatic_aberration > 0.0
+            || self.grain > 0.0
+    }
+}
+
+/// Runs the enabled effects over a row-major RGB frame with channels in [0, 1].
+/// `seed` drives the grain so that it changes from frame to frame.
+pub(super) fn apply(
+    pixels: &mut [[f32; 3]],
+    width: usize,
+    height: usize,
+    settings: &PostProcessSettings,
+    seed: u64,
+    math: Math,
+) {
+    if settings.chromatic_aberration > 0.0 {
+        chromatic_aberration(pixels, width, height, settings.chromatic_aberration, math);
+    }
+    if settings.glow.iter().any(|layer| layer.weight != 0.0) {
+        glow(pixels, width, height, &settings.glow, math);
+    }
+    if settings.bloom_intensity > 0.0 {
+        bloom(pixels, width, height, settings, math);
+    }
+    if settings.vignette > 0.0 {
+        vignette(pixels, width, height, settings.vignette);
+    }
+    if settings.grain > 0.0 {
+        grain(pixels, width, settings.grain, seed);
+    }
+}
+
+fn luminance(pixel: &[f32; 3]) -> f32 {
+    0.2126 * pixel[0] + 0.7152 * pixel[1] + 0.0722 * pixel[2]
+}
+
+fn bloom(
+    pixels: &mut [[f32; 3]],
+    width: usize,
+    height: usize,
+    settings: &PostProcessSettings,
+    math: Math,
+) {
+    let threshold = settings.bloom_threshold;
+    let mut highlights: Vec<[f32; 3]> = pixels
+        .par_iter()
+        .map(|pixel| {
+            let l = luminance(pixel);
+            if l <= threshold {
+                [0.0; 3]
+            } else {
+                let scale = (l - threshold) / l;
+                pixel.map(|c| c * scale)
+            }
+        })
+        .collect();
+
+    gaussian_blur(&mut highlights, width, height, settings.bloom_radius, math);
+    pixels
+        .par_iter_mut()
+        .zip(&highlights)
+        .for_each(|(pixel, glow)| {
+            for (c, g) in pixel.iter_mut().zip(glow) {
+                *c += settings.bloom_intensity * g;
+            }
+        });
+}
+
+/// Adds the weighted blurs of the frame as it was before any of them.
+fn glow(pixels: &mut [[f32; 3]], width: usize, height: usize, layers: &[GlowLayer], math: Math) {
+    let sharp = pixels.to_vec();
+    for layer in layers.iter().filter(|layer| layer.weight != 0.0) {
+        let mut blurred = sharp.clone();
+        gaussian_blur(&mut blurred, width, height, layer.radius, math);
+        pixels
+            .par_iter_mut()
+            .zip(&blurred)
+            .for_each(|(pixel, glow)| {
+                for (c, g) in pixel.iter_mut().zip(glow) {
+                    *c += layer.weight * g;
+                }
+            });
+    }
+}
+
+/// Blurs the frame in place by its rows, then by its columns.
+fn gaussian_blur(pixels: &mut [[f32; 3]], width: usize, height: usize, sigma: f32, math: Math) {
+    let kernel = gaussian_kernel(sigma, math);
+    let mut buf = vec![[0.0; 3]; pixels.len()];
+    blur_rows(pixels, &mut buf, width, &kernel);
+    transpose(&buf, pixels, width, height);
+    blur_rows(pixels, &mut buf, height, &kernel);
+    transpose(&buf, pixels, height, width);
+}
+
+/// Normalized weights of a Gaussian truncated at three standard deviations.
+fn gaussian_kernel(sigma: f32, math: Math) -> Vec<f32> {
+    let sigma = sigma.clamp(0.5, MAX_BLOOM_RADIUS);
+    let reach = (3.0 * sigma).ceil() as i32;
+    let weights: Vec<f32> = (-reach..=reach)
+        .map(|i| (math.exp)(-((i * i) as f32) / (2.0 * sigma * sigma)))
+        .collect();
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|w| w / total).collect()
+}
+
+/// Convolves every row with `kernel`, clamping at the frame's edges.
+fn blur_rows(src: &[[f32; 3]], dst: &mut [[f32; 3]], width: usize, kernel: &[f32]) {
+    let reach = (kernel.len() / 2) as isize;
+    src.par_chunks_exact(width)
+        .zip(dst.par_chunks_exact_mut(width))
+        .for_each(|(src_row, dst_row)| {
+            for (x, out) in dst_row.iter_mut().enumerate() {
+                let mut sum = [0.0; 3];
+                for (k, weight) in kernel.iter().enumerate() {
+                    let sx = (x as isize + k as isize - reach).clamp(0, width as isize - 1);
+                    for (s, c) in sum.iter_mut().zip(&src_row[sx as usize]) {
+                        *s += weight * c;
+                    }
+                }
+                *out = sum;
+            }
+        });
+}
+
+fn transpose(src: &[[f32; 3]], dst: &mut [[f32; 3]], width: usize, height: usize) {
+    dst.par_chunks_exact_mut(height)
+        .enumerate()
+        .for_each(|(x, column)| {
+            for (y, out) in column.iter_mut().enumerate() {
+                *out = src[y * width + x];
+            }
+        });
+}
+
+/// Squared distance from the frame's center, 1.0 at the corners.
+fn radius2(x: usize, y: usize, width: usize, height: usize) -> f32 {
+    let dx = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+    let dy = (y as f32 + 0.5) / height as f32 * 2.0 - 1.0;
+    (dx * dx + dy * dy) * 0.5
+}
+
+fn chromatic_aberration(
+    pixels: &mut [[f32; 3]],
+    width: usize,
+    height: usize,
+    amount: f32,
+    math: Math,
+) {
+    let src = pixels.to_vec();
+    let (cx, cy) = (width as f32 * 0.5, height as f32 * 0.5);
+    let sample = |x: f32, y: f32, channel: usize| {
+        let x = (x.round() as isize).clamp(0, width as isize - 1) as usize;
+        let y = (y.round() as isize).clamp(0, height as isize - 1) as usize;
+        src[y * width + x][channel]
+    };
+    pixels
+        .par_chunks_exact_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                // Red is pushed away from the center and blue pulled in, more so at the edges.
+                let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+                let scale =
+                    amount * radius2(x, y, width, height).sqrt() / (math.hypot)(dx, dy).max(1.0);
+                pixel[0] = sample(x as f32 - dx * scale, y as f32 - dy * scale, 0);
+                pixel[2] = sample(x as f32 + dx * scale, y as f32 + dy * scale, 2);
+            }
+        });
+}
+
+fn vignette(pixels: &mut [[f32; 3]], width: usize, height: usize, strength: f32) {
+    pixels
+        .par_chunks_exact_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let falloff = 1.0 - strength.min(1.0) * radius2(x, y, width, height);
+                *pixel = pixel.map(|c| c * falloff);
+            }
+        });
+}
+
+fn grain(pixels: &mut [[f32; 3]], width: usize, amplitude: f32, seed: u64) {
+    pixels
+        .par_chunks_exact_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let mut rng = SmallRng::seed_from_u64(seed ^ (y as u64).wrapping_mul(0x9E37_79B9));
+            for pixel in row {
+                let noise = amplitude * (rng.gen::<f32>() - 0.5);
+                *pixel = pixel.map(|c| c + noise);
+            }
+        });
+}
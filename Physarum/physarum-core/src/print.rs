@@ -0,0 +1,327 @@
+//! Print-ready exports: TIFFs that carry their resolution and color profile, with a bleed
+//! around the frame for trimming, so a print shop can take them as they are.
+
+#[cfg(feature = "image-export")]
+use image::{DynamicImage, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+#[cfg(feature = "image-export")]
+use std::fs::File;
+#[cfg(feature = "image-export")]
+use std::io::{self, BufWriter};
+#[cfg(feature = "image-export")]
+use std::path::Path;
+use std::str::FromStr;
+#[cfg(feature = "image-export")]
+use tiff::encoder::{colortype, Rational, TiffEncoder};
+#[cfg(feature = "image-export")]
+use tiff::tags::{ResolutionUnit, Tag};
+
+/// Millimeters per inch.
+const MM_PER_INCH: f32 = 25.4;
+/// TIFF tag holding an embedded ICC profile.
+#[cfg(feature = "image-export")]
+const ICC_PROFILE_TAG: u16 = 34675;
+
+/// The RGB color space the print is encoded in.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorProfile {
+    /// The color space the frames are rendered in.
+    #[default]
+    Srgb,
+    /// Adobe RGB (1998), whose wider greens suit the inks of many print shops.
+    AdobeRgb,
+}
+
+/// `RenderSettings::print`, how `PhysarumModel::save_tiff` lays out a print.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct PrintSettings {
+    /// Pixels per inch the frame is printed at, which sets its physical size.
+    pub dpi: f32,
+    /// Millimeters added on every side to be trimmed off after printing. Since the grids
+    /// wrap around, the frame continues seamlessly into it.
+    pub bleed_mm: f32,
+    pub profile: ColorProfile,
+}
+
+impl Default for PrintSettings {
+    fn default() -> Self {
+        PrintSettings {
+            dpi: 300.0,
+            bleed_mm: 3.0,
+            profile: ColorProfile::Srgb,
+        }
+    }
+}
+
+impl PrintSettings {
+    /// Pixels of bleed on every side.
+    pub fn bleed_pixels(&self) -> usize {
+        (self.bleed_mm.max(0.0) / MM_PER_INCH * self.dpi).round() as usize
+    }
+
+    /// The trimmed size in millimeters of a frame `width` by `height` pixels.
+    pub fn trim_size_mm(&self, width: usize, height: usize) -> (f32, f32) {
+        let mm = |pixels: usize| pixels as f32 / self.dpi * MM_PER_INCH;
+        (mm(width), mm(height))
+    }
+}
+
+/// 3x3 matrices as rows.
+type Matrix = [[f32; 3]; 3];
+
+/// Linear sRGB to linear Adobe RGB, which share their red and blue primaries and white.
+const SRGB_TO_ADOBE_RGB: Matrix = [
+    [0.715_125_6, 0.284_874_4, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, 0.041_161_9, 0.958_838_1],
+];
+
+/// The gamma of Adobe RGB, 563/256.
+const ADOBE_RGB_GAMMA: f32 = 2.199_218_8;
+
+impl ColorProfile {
+    /// Converts an sRGB color in [0, 1] into this color space.
+    pub fn from_srgb(self, color: [f32; 3]) -> [f32; 3] {
+        match self {
+            ColorProfile::Srgb => color,
+            ColorProfile::AdobeRgb => {
+                let linear = color.map(srgb_to_linear);
+                SRGB_TO_ADOBE_RGB.map(|row| {
+                    let c: f32 = row.iter().zip(linear).map(|(m, c)| m * c).sum();
+                    c.clamp(0.0, 1.0).powf(1.0 / ADOBE_RGB_GAMMA)
+                })
+            }
+        }
+    }
+
+    /// An ICC v2 display profile describing the color space, for embedding in the print.
+    pub fn icc(self) -> Vec<u8> {
+        // Primaries adapted to the D50 white of the profile connection space, by Bradford.
+        let (name, primaries, curve) = match self {
+            ColorProfile::Srgb => (
+                "sRGB",
+                [
+                    [0.436_074_7, 0.222_504_5, 0.013_932_2],
+                    [0.385_064_9, 0.716_878_6, 0.097_104_5],
+                    [0.143_080_4, 0.060_616_9, 0.714_173_3],
+                ],
+                curv(
+                    &(0..1024)
+                        .map(|i| srgb_to_linear(i as f32 / 1023.0))
+                        .collect::<Vec<_>>(),
+                ),
+            ),
+            ColorProfile::AdobeRgb => (
+                "Adobe RGB (1998) compatible",
+                [
+                    [0.609_755_9, 0.311_124_2, 0.019_481_1],
+                    [0.205_240_1, 0.625_656, 0.060_890_2],
+                    [0.149_224, 0.063_219_7, 0.744_838_7],
+                ],
+                // A single entry is a gamma, in units of 1/256.
+                [&b"curv\0\0\0\0\0\0\0\x01"[..], &563u16.to_be_bytes()].concat(),
+            ),
+        };
+        let d50 = [0.964_2, 1.0, 0.824_9];
+        let tags: [(&[u8; 4], Vec<u8>); 9] = [
+            (b"desc", desc(name)),
+            (b"cprt", text("No copyright, use freely")),
+            (b"wtpt", xyz(d50)),
+            (b"rXYZ", xyz(primaries[0])),
+            (b"gXYZ", xyz(primaries[1])),
+            (b"bXYZ", xyz(primaries[2])),
+            (b"rTRC", curve.clone()),
+            (b"gTRC", curve.clone()),
+            (b"bTRC", curve),
+        ];
+
+        let table_end = 128 + 4 + 12 * tags.len();
+        let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+        let mut data = vec![];
+        for (signature, tag) in &tags {
+            let offset = table_end + data.len();
+            table.extend_from_slice(&signature[..]);
+            table.extend_from_slice(&(offset as u32).to_be_bytes());
+            table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+            data.extend_from_slice(tag);
+            data.resize(data.len().next_multiple_of(4), 0);
+        }
+
+        let mut header = vec![0; 128];
+        let size = (128 + table.len() + data.len()) as u32;
+        header[0..4].copy_from_slice(&size.to_be_bytes());
+        header[8..12].copy_from_slice(&0x0210_0000u32.to_be_bytes());
+        header[12..16].copy_from_slice(b"mntr");
+        header[16..20].copy_from_slice(b"RGB ");
+        header[20..24].copy_from_slice(b"XYZ ");
+        for (i, field) in [2024u16, 1, 1, 0, 0, 0].iter().enumerate() {
+            header[24 + 2 * i..26 + 2 * i].copy_from_slice(&field.to_be_bytes());
+        }
+        header[36..40].copy_from_slice(b"acsp");
+        header[68..80].copy_from_slice(&xyz(d50)[8..]);
+        [header, table, data].concat()
+    }
+}
+
+impl fmt::Display for ColorProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorProfile::Srgb => write!(f, "srgb"),
+            ColorProfile::AdobeRgb => write!(f, "adobe-rgb"),
+        }
+    }
+}
+
+impl FromStr for ColorProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [ColorProfile::Srgb, ColorProfile::AdobeRgb]
+            .into_iter()
+            .find(|profile| profile.to_string() == s)
+            .ok_or_else(|| format!("Unknown color profile {:?}, expected srgb or adobe-rgb", s))
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// An ICC `s15Fixed16Number`.
+fn s15_fixed16(v: f32) -> [u8; 4] {
+    ((v * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn xyz(v: [f32; 3]) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    for c in v {
+        tag.extend_from_slice(&s15_fixed16(c));
+    }
+    tag
+}
+
+/// A tone curve sampled evenly over [0, 1].
+fn curv(samples: &[f32]) -> Vec<u8> {
+    let mut tag = b"curv\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+    for s in samples {
+        tag.extend_from_slice(&((s.clamp(0.0, 1.0) * 65535.0).round() as u16).to_be_bytes());
+    }
+    tag
+}
+
+fn text(s: &str) -> Vec<u8> {
+    [b"text\0\0\0\0", s.as_bytes(), b"\0"].concat()
+}
+
+/// A v2 `textDescriptionType`, with empty Unicode and ScriptCode descriptions.
+fn desc(s: &str) -> Vec<u8> {
+    let ascii = [s.as_bytes(), b"\0"].concat();
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(ascii.len() as u32).to_be_bytes());
+    tag.extend_from_slice(&ascii);
+    // Unicode language and length, ScriptCode code and length, and the ScriptCode bytes.
+    tag.extend_from_slice(&[0; 8 + 3 + 67]);
+    tag
+}
+
+/// The frame as it goes to print: flattened onto white paper where it's transparent,
+/// wrapped around into the bleed and converted to the profile's color space.
+#[cfg(feature = "image-export")]
+pub fn page(image: &DynamicImage, settings: &PrintSettings) -> RgbImage {
+    let frame = image.to_rgba8();
+    let (width, height) = (frame.width(), frame.height());
+    let bleed = settings.bleed_pixels() as u32;
+    RgbImage::from_fn(width + 2 * bleed, height + 2 * bleed, |x, y| {
+        let x = (x + width - bleed % width) % width;
+        let y = (y + height - bleed % height) % height;
+        let [r, g, b, a] = frame.get_pixel(x, y).0;
+        let alpha = a as f32 / 255.0;
+        let color = [r, g, b].map(|c| (c as f32 / 255.0) * alpha + (1.0 - alpha));
+        Rgb(settings
+            .profile
+            .from_srgb(color)
+            .map(|c| (c * 255.0).round() as u8))
+    })
+}
+
+/// Writes `page` of `image` as an uncompressed TIFF tagged with the print's resolution and
+/// color profile.
+#[cfg(feature = "image-export")]
+pub fn save_tiff(image: &DynamicImage, path: &Path, settings: &PrintSettings) -> io::Result<()> {
+    let page = page(image, settings);
+    let mut tiff =
+        TiffEncoder::new(BufWriter::new(File::create(path)?)).map_err(io::Error::other)?;
+    let mut encoder = tiff
+        .new_image::<colortype::RGB8>(page.width(), page.height())
+        .map_err(io::Error::other)?;
+    // Hundredths of a pixel per inch.
+    let dpi = Rational {
+        n: (settings.dpi * 100.0).round() as u32,
+        d: 100,
+    };
+    encoder.resolution(ResolutionUnit::Inch, dpi);
+    encoder
+        .encoder()
+        .write_tag(Tag::Unknown(ICC_PROFILE_TAG), &settings.profile.icc()[..])
+        .map_err(io::Error::other)?;
+    encoder.write_data(&page).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adobe_rgb_keeps_neutrals_and_desaturates_srgb_greens() {
+        let gray = ColorProfile::AdobeRgb.from_srgb([0.5; 3]);
+        let neutral = srgb_to_linear(0.5).powf(1.0 / ADOBE_RGB_GAMMA);
+        assert!(gray.iter().all(|c| (c - neutral).abs() < 1e-4));
+        let [r, g, b] = ColorProfile::AdobeRgb.from_srgb([0.0, 1.0, 0.0]);
+        // The wider gamut holds the same green with a little of the other primaries.
+        assert!((g - 1.0).abs() < 1e-6 && r > 0.4 && b > 0.2);
+        assert_eq!(
+            ColorProfile::Srgb.from_srgb([0.2, 0.4, 0.6]),
+            [0.2, 0.4, 0.6]
+        );
+    }
+
+    #[test]
+    fn icc_profiles_are_well_formed() {
+        for profile in [ColorProfile::Srgb, ColorProfile::AdobeRgb] {
+            let icc = profile.icc();
+            let read = |at: usize| u32::from_be_bytes(icc[at..at + 4].try_into().unwrap());
+            assert_eq!(read(0) as usize, icc.len());
+            assert_eq!(&icc[36..40], b"acsp");
+            let tags = read(128) as usize;
+            for k in 0..tags {
+                let (offset, size) = (read(132 + 12 * k + 4), read(132 + 12 * k + 8));
+                assert_eq!(offset % 4, 0);
+                assert!((offset + size) as usize <= icc.len());
+            }
+        }
+    }
+
+    #[cfg(feature = "image-export")]
+    #[test]
+    fn pages_wrap_the_frame_into_the_bleed() {
+        let frame = RgbImage::from_fn(4, 4, |x, y| Rgb([x as u8 * 60, y as u8 * 60, 0]));
+        let settings = PrintSettings {
+            dpi: MM_PER_INCH,
+            bleed_mm: 1.0,
+            profile: ColorProfile::Srgb,
+        };
+        let page = page(&DynamicImage::ImageRgb8(frame.clone()), &settings);
+        assert_eq!(page.dimensions(), (6, 6));
+        assert_eq!(page.get_pixel(1, 1), frame.get_pixel(0, 0));
+        assert_eq!(page.get_pixel(0, 0), frame.get_pixel(3, 3));
+        assert_eq!(page.get_pixel(5, 2), frame.get_pixel(0, 1));
+    }
+}
@@ -0,0 +1,92 @@
+use super::agents::Particle;
+use super::steering::Senses;
+use core::fmt::{Display, Formatter};
+
+/// What `PhysarumModel::probe` found at a cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Probe {
+    /// The column and row of the cell.
+    pub cell: (usize, usize),
+    /// The trail of every population in the cell.
+    pub trails: Vec<f32>,
+    /// The agent closest to the probed point, if there are any agents.
+    pub nearest: Option<ProbedAgent>,
+}
+
+/// The agent closest to a probed point, and how it sees the trails.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbedAgent {
+    /// Where the agent is among all agents.
+    pub index: usize,
+    pub agent: Particle,
+    /// How far it is from the probed point, across the edges where that is shorter.
+    pub distance: f32,
+    /// The combined trails its sensors pick up.
+    pub senses: Senses,
+    /// How far its steering rule turns it in the next step, in multiples of its
+    /// population's `rotation_angle`, before any bias.
+    pub turn: f32,
+}
+
+impl Display for Probe {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "Cell ({}, {}):", self.cell.0, self.cell.1)?;
+        for (population, trail) in self.trails.iter().enumerate() {
+            writeln!(f, "  Trail {}: {}", population, trail)?;
+        }
+        let Some(probed) = &self.nearest else {
+            return write!(f, "  No agents");
+        };
+        let Particle { x, y, angle, id } = probed.agent;
+        let Senses {
+            left,
+            center,
+            right,
+        } = probed.senses;
+        writeln!(
+            f,
+            "  Nearest agent {} of population {}, {:.2} cells away:",
+            probed.index, id, probed.distance
+        )?;
+        writeln!(f, "    Position: ({:.2}, {:.2})", x, y)?;
+        writeln!(
+            f,
+            "    Heading: {:.1}°",
+            angle.to_degrees().rem_euclid(360.0)
+        )?;
+        writeln!(
+            f,
+            "    Senses: left {}, center {}, right {}",
+            left, center, right
+        )?;
+        write!(f, "    Turn: {}", probed.turn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::physarum_model::PhysarumModel;
+
+    #[test]
+    fn probes_read_the_trails_and_the_nearest_agent() {
+        let mut model = PhysarumModel::builder()
+            .size(32, 32)
+            .particles(50)
+            .seed(1)
+            .build()
+            .unwrap();
+        model.step();
+        let probe = model.probe(40.5, -3.5);
+        assert_eq!(probe.cell, (8, 28));
+        let trails: Vec<f32> = model
+            .grids
+            .iter()
+            .map(|grid| grid.data()[28 * 32 + 8])
+            .collect();
+        assert_eq!(probe.trails, trails);
+        let probed = probe.nearest.as_ref().unwrap();
+        assert!(probed.distance <= 16.0 * 2f32.sqrt());
+        assert!((-1.0..=1.0).contains(&probed.turn));
+        assert!(probe.to_string().contains("Nearest agent"));
+    }
+}
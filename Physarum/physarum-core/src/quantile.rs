@@ -0,0 +1,66 @@
+use super::util;
+use serde::{Deserialize, Serialize};
+
+/// Trades accuracy of the brightness percentile for speed when exporting many frames.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct QuantileSettings {
+    /// Cells sampled to estimate the percentile, or 0 to use every cell. The rank error of
+    /// the estimate shrinks with the square root of the sample count.
+    pub samples: usize,
+    /// Simulation steps between estimates, reusing the last one in between.
+    pub refresh_interval: usize,
+}
+
+impl Default for QuantileSettings {
+    fn default() -> Self {
+        QuantileSettings {
+            samples: 0,
+            refresh_interval: 1,
+        }
+    }
+}
+
+/// Last percentiles computed for each layer and the iteration they belong to.
+#[derive(Default)]
+pub(super) struct QuantileCache {
+    iteration: Option<u64>,
+    values: Vec<f32>,
+}
+
+impl QuantileCache {
+    /// Returns the cached per-layer values, recomputing them with `compute` once they are
+    /// `refresh_interval` steps old or the number of layers changed.
+    pub(super) fn get_or_update(
+        &mut self,
+        iteration: u64,
+        layers: usize,
+        settings: &QuantileSettings,
+        compute: impl FnOnce() -> Vec<f32>,
+    ) -> Vec<f32> {
+        let stale = match self.iteration {
+            Some(cached) => {
+                let age = iteration.abs_diff(cached) as usize;
+                age >= settings.refresh_interval.max(1) || self.values.len() != layers
+            }
+            None => true,
+        };
+        if stale {
+            self.values = compute();
+            self.iteration = Some(iteration);
+        }
+        self.values.clone()
+    }
+}
+
+/// The `fraction` quantile of `data`, exact or estimated from an evenly strided subset.
+pub(super) fn estimate(data: &[f32], fraction: f32, samples: usize) -> f32 {
+    if samples == 0 || samples >= data.len() {
+        return util::quantile(data, fraction);
+    }
+    // An odd stride is coprime with the power-of-two grid sizes, so the samples sweep
+    // through all columns instead of lining up in a few.
+    let stride = (data.len() / samples) | 1;
+    let subset: Vec<f32> = data.iter().step_by(stride).cloned().collect();
+    util::quantile(&subset, fraction)
+}
@@ -0,0 +1,502 @@
+use super::agents::Agents;
+use super::background::Background;
+use super::blur::Blur;
+use super::color_vision::ColorVision;
+use super::contour::ContourSettings;
+use super::dither::DitherMethod;
+use super::mask::MaskSettings;
+use super::math::Math;
+use super::normalization::Normalization;
+use super::palette::{Palette, Rgb};
+use super::par::*;
+use super::postprocess::PostProcessSettings;
+use super::print::PrintSettings;
+use super::quantile::QuantileSettings;
+use super::stipple::StippleSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+pub const MAX_SPRITE_RADIUS: f32 = 16.0;
+pub const MAX_ACCUMULATION_FRAMES: usize = 64;
+pub const MAX_DEFOCUS_BLUR: f32 = 32.0;
+
+/// What a population contributes to the rendered image.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub enum LayerMode {
+    /// The population's chemical trail field.
+    #[default]
+    Trail,
+    /// Every agent drawn as a soft additive dot, for a glowing-particle look.
+    Sprites,
+    /// Strokes along the agents' headings, brighter where many move together, which shows
+    /// the circulation that the trails hide.
+    Flow,
+}
+
+/// The colors a population is drawn with.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PopulationColor {
+    /// The palette color at the population's own index.
+    #[default]
+    Auto,
+    /// The palette color at `index`, which several populations can share.
+    Palette { index: usize },
+    /// A fixed color, ignoring the palette.
+    Rgb { color: [u8; 3] },
+    /// Brightness mapped along a gradient through `stops`, from bare cells to the densest
+    /// trails, instead of scaling a single tint. Starting from black keeps bare cells dark.
+    Ramp {
+        stops: Vec<[u8; 3]>,
+        /// Brightness in [0, 1] at each stop, ascending. Stops are evenly spaced unless
+        /// there is one position per stop.
+        #[serde(default)]
+        positions: Vec<f32>,
+    },
+}
+
+/// Names of the ramps `PopulationColor::gradient` knows.
+pub const GRADIENT_NAMES: [&str; 4] = ["fire", "ice", "toxic", "bone"];
+
+impl PopulationColor {
+    /// A built-in ramp from `GRADIENT_NAMES`, e.g. "fire" from dark red through orange to
+    /// white.
+    pub fn gradient(name: &str) -> Option<PopulationColor> {
+        let (stops, positions) = match name {
+            "fire" => (
+                vec![[0, 0, 0], [120, 10, 10], [250, 130, 20], [255, 255, 255]],
+                vec![0.0, 0.35, 0.7, 1.0],
+            ),
+            "ice" => (
+                vec![[0, 0, 0], [10, 40, 120], [60, 170, 230], [255, 255, 255]],
+                vec![0.0, 0.4, 0.75, 1.0],
+            ),
+            "toxic" => (
+                vec![[0, 0, 0], [20, 80, 20], [150, 230, 40], [250, 255, 200]],
+                vec![0.0, 0.4, 0.8, 1.0],
+            ),
+            "bone" => (
+                vec![[0, 0, 0], [70, 60, 90], [170, 180, 190], [255, 255, 255]],
+                vec![0.0, 0.45, 0.8, 1.0],
+            ),
+            _ => return None,
+        };
+        Some(PopulationColor::Ramp { stops, positions })
+    }
+
+    /// The single color population `k` is tinted with, or a ramp's densest stop.
+    pub fn tint(&self, palette: &Palette, k: usize) -> Rgb<u8> {
+        match self {
+            PopulationColor::Auto => palette.color(k),
+            PopulationColor::Palette { index } => palette.color(*index),
+            PopulationColor::Rgb { color } => Rgb(*color),
+            PopulationColor::Ramp { stops, .. } => {
+                stops.last().map_or(palette.color(k), |c| Rgb(*c))
+            }
+        }
+    }
+
+    /// Straight color in [0, 1] and coverage of population `k` at `brightness`, so that
+    /// their product is what the population adds to the frame.
+    pub fn shade(&self, palette: &Palette, k: usize, brightness: f32) -> ([f32; 3], f32) {
+        match self {
+            PopulationColor::Ramp { stops, positions } if stops.len() >= 2 => {
+                let brightness = brightness.clamp(0.0, 1.0);
+                let (i, t) = if positions.len() == stops.len() {
+                    let i = positions[1..stops.len() - 1]
+                        .iter()
+                        .take_while(|&&p| p < brightness)
+                        .count();
+                    let span = positions[i + 1] - positions[i];
+                    let t = if span > 0.0 {
+                        (brightness - positions[i]) / span
+                    } else {
+                        1.0
+                    };
+                    (i, t.clamp(0.0, 1.0))
+                } else {
+                    let t = brightness * (stops.len() - 1) as f32;
+                    let i = (t as usize).min(stops.len() - 2);
+                    (i, t - i as f32)
+                };
+                let (a, b) = (stops[i], stops[i + 1]);
+                let color = [0, 1, 2].map(|c| (a[c] as f32 * (1.0 - t) + b[c] as f32 * t) / 255.0);
+                let coverage = color[0].max(color[1]).max(color[2]);
+                if coverage > 0.0 {
+                    (color.map(|c| c / coverage), coverage)
+                } else {
+                    (color, 0.0)
+                }
+            }
+            _ => (
+                self.tint(palette, k).0.map(|c| c as f32 / 255.0),
+                brightness,
+            ),
+        }
+    }
+}
+
+/// How a population is combined with the layers drawn below it, see `RenderSettings::order`.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LayerBlend {
+    /// Adds its light, so the order doesn't matter.
+    #[default]
+    Add,
+    /// Like `Add`, but saturating softly instead of clipping.
+    Screen,
+    /// Covers the layers below as far as it is bright, like paint.
+    Over,
+}
+
+/// The order populations are drawn in, from the bottom up, which decides what the
+/// occluding `LayerBlend::Over` covers.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum LayerOrder {
+    /// By population index, the first at the bottom.
+    #[default]
+    Grid,
+    /// The populations in `layers`, bottom first, and the ones it leaves out on top of
+    /// them by index.
+    Custom { layers: Vec<usize> },
+    /// Densest population at the bottom, by the mean brightness of its layer in each
+    /// frame, so that sparse networks stay visible on top of dense ones.
+    Auto,
+}
+
+impl LayerOrder {
+    /// The indices of the `fields`, one per population, in drawing order.
+    pub fn sort(&self, fields: &[Vec<f32>]) -> Vec<usize> {
+        match self {
+            LayerOrder::Grid => (0..fields.len()).collect(),
+            LayerOrder::Custom { layers } => {
+                let mut order = vec![];
+                for k in layers.iter().copied().chain(0..fields.len()) {
+                    if k < fields.len() && !order.contains(&k) {
+                        order.push(k);
+                    }
+                }
+                order
+            }
+            LayerOrder::Auto => {
+                let means: Vec<f32> = fields
+                    .iter()
+                    .map(|field| field.iter().sum::<f32>() / field.len().max(1) as f32)
+                    .collect();
+                let mut order: Vec<usize> = (0..fields.len()).collect();
+                order.sort_by(|&a, &b| means[b].total_cmp(&means[a]));
+                order
+            }
+        }
+    }
+}
+
+impl LayerBlend {
+    /// Draws `light`, a color times its coverage, over `pixel`.
+    pub fn blend(self, pixel: &mut [f32; 3], light: [f32; 3], coverage: f32) {
+        for (c, l) in pixel.iter_mut().zip(light) {
+            *c = match self {
+                LayerBlend::Add => *c + l,
+                LayerBlend::Screen => 1.0 - (1.0 - c.min(1.0)) * (1.0 - l.min(1.0)),
+                LayerBlend::Over => *c * (1.0 - coverage.clamp(0.0, 1.0)) + l,
+            };
+        }
+    }
+}
+
+/// Per-population rendering options.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct PopulationStyle {
+    pub mode: LayerMode,
+    pub color: PopulationColor,
+    /// Standard deviation in cells of a blur drawing the population out of focus, up to
+    /// `MAX_DEFOCUS_BLUR`. 0 keeps it sharp.
+    pub blur: f32,
+    /// Factor the population's brightness is scaled by. Dimmed, blurred populations seem
+    /// to lie behind the sharp ones, faking depth of field.
+    pub brightness: f32,
+    pub blend: LayerBlend,
+    /// Keyframes of the layer's opacity as `(iteration, opacity)` pairs in order of
+    /// iteration, faded linearly from one to the next and held before the first and after
+    /// the last. The agents keep running while their layer is faded out. No keyframes keep
+    /// the layer opaque.
+    pub opacity: Vec<(u64, f32)>,
+}
+
+impl Default for PopulationStyle {
+    fn default() -> Self {
+        PopulationStyle {
+            mode: LayerMode::default(),
+            color: PopulationColor::default(),
+            blur: 0.0,
+            brightness: 1.0,
+            blend: LayerBlend::default(),
+            opacity: vec![],
+        }
+    }
+}
+
+impl PopulationStyle {
+    /// Whether `defocus` changes the population's layer.
+    pub fn is_defocused(&self) -> bool {
+        self.blur > 0.0 || self.brightness != 1.0
+    }
+
+    /// Opacity of the layer at `iteration`, in [0, 1], following the `opacity` keyframes.
+    pub fn opacity_at(&self, iteration: u64) -> f32 {
+        let (first, last) = match (self.opacity.first(), self.opacity.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return 1.0,
+        };
+        let opacity = if iteration <= first.0 {
+            first.1
+        } else if iteration >= last.0 {
+            last.1
+        } else {
+            let k = self.opacity.partition_point(|&(at, _)| at <= iteration);
+            let ((from, a), (to, b)) = (self.opacity[k - 1], self.opacity[k]);
+            let t = (iteration - from) as f32 / (to - from).max(1) as f32;
+            a + (b - a) * t
+        };
+        opacity.clamp(0.0, 1.0)
+    }
+
+    /// Blurs and dims the population's normalized layer `field` in place, wrapping around
+    /// the edges.
+    pub(super) fn defocus(&self, field: &mut [f32], width: usize, height: usize) {
+        if self.blur > 0.0 {
+            let mut buf = vec![0.0; field.len()];
+            let sigma = self.blur.min(MAX_DEFOCUS_BLUR);
+            Blur::new(width).run(field, &mut buf, width, height, sigma, 1.0);
+        }
+        if self.brightness != 1.0 {
+            let brightness = self.brightness.max(0.0);
+            field
+                .par_iter_mut()
+                .for_each(|v| *v = (*v * brightness).min(1.0));
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub populations: Vec<PopulationStyle>,
+    pub order: LayerOrder,
+    /// Radius in cells of the sprites drawn in `LayerMode::Sprites`, up to
+    /// `MAX_SPRITE_RADIUS`.
+    pub sprite_radius: f32,
+    /// Cells the strokes of `LayerMode::Flow` follow the headings each way, up to
+    /// `MAX_FLOW_LENGTH`.
+    pub flow_length: usize,
+    pub postprocess: PostProcessSettings,
+    /// `.cube` color grading LUT applied to the final image.
+    pub lut: Option<PathBuf>,
+    pub ink: InkSettings,
+    /// Hand-picked colors replacing the palette selected by `palette`.
+    pub colors: Option<[[u8; 3]; 5]>,
+    pub normalization: Normalization,
+    /// Fraction of the previous step's brightness normalization kept each step, from 0
+    /// (none, follow every frame) towards 1 (barely move).
+    pub brightness_smoothing: f32,
+    /// Number of recent frames averaged into each rendered one, for a motion-blurred look,
+    /// up to `MAX_ACCUMULATION_FRAMES`. 0 and 1 disable the accumulation.
+    pub accumulation_frames: usize,
+    pub quantile: QuantileSettings,
+    pub alpha: AlphaSettings,
+    pub background: Background,
+    /// Shows the final image as seen with a color-vision deficiency, as a preview.
+    pub color_vision: ColorVision,
+    pub contours: ContourSettings,
+    pub stipple: StippleSettings,
+    pub mask: MaskSettings,
+    pub print: PrintSettings,
+}
+
+/// Separations written for risograph and screen printing, see `PhysarumModel::ink_layers`.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct InkSettings {
+    /// Number of ink layers, from 1 to 3. Headless runs skip the export when zero.
+    pub layers: usize,
+    pub method: DitherMethod,
+}
+
+/// Transparent renders for compositing onto other backgrounds or printing as stickers. The
+/// alpha follows the combined density of all layers and the colors are stored straight, so
+/// the render composited over black matches the opaque one.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct AlphaSettings {
+    pub enabled: bool,
+    /// Combined brightness of all layers, in [0, 1] per layer, below which pixels are
+    /// fully transparent.
+    pub threshold: f32,
+    /// Brightness above `threshold` over which pixels fade in to fully opaque, 0 for a
+    /// hard cut-out edge.
+    pub falloff: f32,
+}
+
+impl Default for AlphaSettings {
+    fn default() -> Self {
+        AlphaSettings {
+            enabled: false,
+            threshold: 0.05,
+            falloff: 0.3,
+        }
+    }
+}
+
+impl AlphaSettings {
+    pub fn alpha(&self, density: f32) -> f32 {
+        if self.falloff > 0.0 {
+            ((density - self.threshold) / self.falloff).clamp(0.0, 1.0)
+        } else if density >= self.threshold {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            populations: vec![],
+            order: LayerOrder::default(),
+            sprite_radius: 1.5,
+            flow_length: 12,
+            postprocess: PostProcessSettings::default(),
+            lut: None,
+            ink: InkSettings::default(),
+            colors: None,
+            normalization: Normalization::default(),
+            brightness_smoothing: 0.0,
+            accumulation_frames: 1,
+            quantile: QuantileSettings::default(),
+            alpha: AlphaSettings::default(),
+            background: Background::default(),
+            color_vision: ColorVision::Normal,
+            contours: ContourSettings::default(),
+            stipple: StippleSettings::default(),
+            mask: MaskSettings::default(),
+            print: PrintSettings::default(),
+        }
+    }
+}
+
+impl RenderSettings {
+    /// The settings for a frame `factor` times as large, with every length in cells or
+    /// pixels scaled so that it looks the same, only sharper.
+    pub fn scaled(&self, factor: f32) -> RenderSettings {
+        let mut settings = self.clone();
+        settings.sprite_radius *= factor;
+        settings.flow_length = (settings.flow_length as f32 * factor).round() as usize;
+        for style in &mut settings.populations {
+            style.blur *= factor;
+        }
+        let postprocess = &mut settings.postprocess;
+        postprocess.bloom_radius *= factor;
+        postprocess.chromatic_aberration *= factor;
+        for layer in &mut postprocess.glow {
+            layer.radius *= factor;
+        }
+        settings.contours.line_width *= factor;
+        settings.contours.smoothing *= factor;
+        let stipple = &mut settings.stipple;
+        stipple.spacing *= factor;
+        stipple.max_radius *= factor;
+        stipple.min_radius *= factor;
+        settings.mask.feather *= factor;
+        settings
+    }
+
+    pub fn style(&self, population: usize) -> PopulationStyle {
+        self.populations
+            .get(population)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn style_mut(&mut self, population: usize) -> &mut PopulationStyle {
+        if self.populations.len() <= population {
+            self.populations
+                .resize(population + 1, PopulationStyle::default());
+        }
+        &mut self.populations[population]
+    }
+}
+
+/// Accumulates a Gaussian sprite for every agent of `population` into a float buffer,
+/// wrapping around the edges like the grids do.
+pub(super) fn splat_sprites(
+    agents: &Agents,
+    population: usize,
+    width: usize,
+    height: usize,
+    radius: f32,
+    math: Math,
+) -> Vec<f32> {
+    let radius = radius.min(MAX_SPRITE_RADIUS);
+    let reach = radius.ceil().max(0.0) as i64;
+    let sigma = (radius * 0.5).max(0.25);
+    let kernel: Vec<(i64, i64, f32)> = (-reach..=reach)
+        .flat_map(|dy| (-reach..=reach).map(move |dx| (dx, dy)))
+        .filter_map(|(dx, dy)| {
+            let d2 = (dx * dx + dy * dy) as f32;
+            (d2 <= radius * radius).then(|| (dx, dy, (math.exp)(-d2 / (2.0 * sigma * sigma))))
+        })
+        .collect();
+
+    let mut buffer = vec![0.0; width * height];
+    for agent in agents.of_population(population) {
+        let (x, y) = (agent.x as i64, agent.y as i64);
+        for &(dx, dy, weight) in &kernel {
+            let i = (x + dx).rem_euclid(width as i64) as usize;
+            let j = (y + dy).rem_euclid(height as i64) as usize;
+            buffer[j * width + i] += weight;
+        }
+    }
+    buffer
+}
+
+/// The last rendered frames, by iteration, averaged by `RenderSettings::accumulation_frames`.
+#[derive(Default)]
+pub(super) struct Accumulator {
+    frames: VecDeque<(u64, Vec<[f32; 3]>)>,
+}
+
+impl Accumulator {
+    /// Adds `pixels` as the frame of `iteration` and replaces them with the average of the
+    /// last `count` frames. Rendering the same iteration twice replaces its frame.
+    pub(super) fn accumulate(&mut self, iteration: u64, pixels: &mut [[f32; 3]], count: usize) {
+        if self
+            .frames
+            .front()
+            .is_some_and(|(_, frame)| frame.len() != pixels.len())
+        {
+            self.frames.clear();
+        }
+        if self.frames.back().is_some_and(|(i, _)| *i == iteration) {
+            self.frames.pop_back();
+        }
+        self.frames.push_back((iteration, pixels.to_vec()));
+        while self.frames.len() > count.clamp(1, MAX_ACCUMULATION_FRAMES) {
+            self.frames.pop_front();
+        }
+
+        let weight = 1.0 / self.frames.len() as f32;
+        pixels.par_iter_mut().enumerate().for_each(|(i, pixel)| {
+            let mut sum = [0.0; 3];
+            for (_, frame) in &self.frames {
+                for (s, c) in sum.iter_mut().zip(frame[i]) {
+                    *s += c;
+                }
+            }
+            *pixel = sum.map(|s| s * weight);
+        });
+    }
+}
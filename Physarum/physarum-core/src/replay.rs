@@ -0,0 +1,309 @@
+//! Parameter changes as values, for logging a live session and replaying it later. The
+//! events of `PhysarumModel::on_parameter_change` only name what changed, `ChangeRecorder`
+//! turns them into `Change`s holding everything needed to make the same change again.
+
+use super::agents::Particle;
+use super::annealing::Annealing;
+use super::bandpass::Bandpass;
+use super::crowding::Crowding;
+use super::error::ConfigError;
+use super::food::Food;
+use super::heat::Heat;
+use super::hooks::Parameter;
+use super::palette::{Palette, Rgb};
+use super::physarum_model::{PhysarumModel, RemovedAgents};
+use super::population_config::PopulationConfig;
+use super::render::RenderSettings;
+use super::sensing::Sensing;
+use super::spacing::Spacing;
+use rand::{rngs::SmallRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// One change of a model's settings, with the values it changed to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    AttractionTable(Vec<Vec<f32>>),
+    DepositionTable(Option<Vec<Vec<f32>>>),
+    PopulationConfigs(Vec<PopulationConfig>),
+    /// A population shrank to `size` agents, or grew by the `spawned` ones.
+    PopulationSize {
+        population: usize,
+        size: usize,
+        spawned: Vec<Particle>,
+    },
+    /// A population joined with these attraction entries, see
+    /// `NewAttraction::Supplied`, and these agents.
+    NewPopulation {
+        config: PopulationConfig,
+        row: Vec<f32>,
+        column: Vec<f32>,
+        agents: Vec<Particle>,
+    },
+    RemovePopulation {
+        population: usize,
+        agents: RemovedAgents,
+    },
+    MergePopulations {
+        from: usize,
+        into: usize,
+    },
+    Bandpass(Option<Bandpass>),
+    Sensing(Vec<Sensing>),
+    Steering(Vec<String>),
+    DepositOps(Vec<String>),
+    DiffuseOps(Vec<String>),
+    TimeScale(f32),
+    Annealing(Option<Annealing>),
+    Crowding(Option<Crowding>),
+    Spacing(Option<Spacing>),
+    Heat(Option<Heat>),
+    PaintHeat {
+        x: f32,
+        y: f32,
+        radius: f32,
+        amount: f32,
+    },
+    HeatField(Vec<f32>),
+    Food(Option<Food>),
+    RenderSettings(Box<RenderSettings>),
+    Palette([[u8; 3]; 5]),
+}
+
+impl Change {
+    /// Makes the change again, on a model that was where the recorded one was before it.
+    pub fn apply(self, model: &mut PhysarumModel) -> Result<(), ConfigError> {
+        match self {
+            Change::AttractionTable(table) => model.set_attraction_table(table),
+            Change::DepositionTable(table) => model.set_deposition_table(table),
+            Change::PopulationConfigs(configs) => model.set_population_configs(configs),
+            Change::PopulationSize {
+                population,
+                size,
+                spawned,
+            } => {
+                if spawned.is_empty() {
+                    // Shrinking keeps the first agents without drawing from the generator.
+                    model.set_population_size(population, size, &mut SmallRng::seed_from_u64(0))
+                } else {
+                    model.add_agents(population, spawned)
+                }
+            }
+            Change::NewPopulation {
+                config,
+                row,
+                column,
+                agents,
+            } => model
+                .insert_population(config, row, column, agents)
+                .map(|_| ()),
+            Change::RemovePopulation { population, agents } => {
+                model.remove_population(population, agents)
+            }
+            Change::MergePopulations { from, into } => model.merge_populations(from, into),
+            Change::Bandpass(bandpass) => model.set_bandpass(bandpass),
+            Change::Sensing(sensing) => model.set_sensing(sensing),
+            Change::Steering(steering) => model.set_steering(steering),
+            Change::DepositOps(ops) => model.set_deposit_ops(ops),
+            Change::DiffuseOps(ops) => model.set_diffuse_ops(ops),
+            Change::TimeScale(time_scale) => model.set_time_scale(time_scale),
+            Change::Annealing(annealing) => model.set_annealing(annealing),
+            Change::Crowding(crowding) => model.set_crowding(crowding),
+            Change::Spacing(spacing) => model.set_spacing(spacing),
+            Change::Heat(heat) => model.set_heat(heat),
+            Change::PaintHeat {
+                x,
+                y,
+                radius,
+                amount,
+            } => {
+                model.paint_heat(x, y, radius, amount);
+                Ok(())
+            }
+            Change::HeatField(values) => model.set_heat_field(values),
+            Change::Food(food) => model.set_food(food),
+            Change::RenderSettings(settings) => model.set_render_settings(*settings),
+            Change::Palette(colors) => {
+                model.set_palette(Palette {
+                    colors: colors.map(Rgb),
+                });
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Reads the values of a model's changes as they happen. Has to see every change since it
+/// was created from the model, to tell which agents a growing population spawned.
+pub struct ChangeRecorder {
+    sizes: Vec<usize>,
+}
+
+impl ChangeRecorder {
+    pub fn new(model: &PhysarumModel) -> Self {
+        ChangeRecorder {
+            sizes: (0..model.grids.len())
+                .map(|population| model.population_size(population))
+                .collect(),
+        }
+    }
+
+    /// Counts the agents again after the populations were renumbered.
+    fn resize(&mut self, model: &PhysarumModel) {
+        self.sizes = (0..model.grids.len())
+            .map(|population| model.population_size(population))
+            .collect();
+    }
+
+    /// The change `parameter` names, as the model holds it now.
+    pub fn change(&mut self, model: &PhysarumModel, parameter: Parameter) -> Change {
+        match parameter {
+            Parameter::AttractionTable => {
+                Change::AttractionTable(model.attraction_table().to_vec())
+            }
+            Parameter::DepositionTable => {
+                Change::DepositionTable(model.deposition_table().map(<[_]>::to_vec))
+            }
+            Parameter::PopulationConfigs => {
+                Change::PopulationConfigs(model.grids.iter().map(|grid| grid.config).collect())
+            }
+            Parameter::PopulationSize(population) => {
+                let size = model.population_size(population);
+                let grown =
+                    size.saturating_sub(std::mem::replace(&mut self.sizes[population], size));
+                // Spawned agents are appended after all the others.
+                let agents = model.agents();
+                let spawned = (agents.len() - grown..agents.len())
+                    .filter_map(|i| agents.get(i))
+                    .collect();
+                Change::PopulationSize {
+                    population,
+                    size,
+                    spawned,
+                }
+            }
+            Parameter::NewPopulation => {
+                let population = model.grids.len() - 1;
+                let size = model.population_size(population);
+                self.sizes.push(size);
+                // Its agents are appended after all the others.
+                let agents = model.agents();
+                let table = model.attraction_table();
+                Change::NewPopulation {
+                    config: model.grids[population].config,
+                    row: table[population].clone(),
+                    column: table[..population]
+                        .iter()
+                        .map(|row| row[population])
+                        .collect(),
+                    agents: (agents.len() - size..agents.len())
+                        .filter_map(|i| agents.get(i))
+                        .collect(),
+                }
+            }
+            Parameter::RemovedPopulation { population, agents } => {
+                self.resize(model);
+                Change::RemovePopulation { population, agents }
+            }
+            Parameter::MergedPopulations { from, into } => {
+                self.resize(model);
+                Change::MergePopulations { from, into }
+            }
+            Parameter::Bandpass => Change::Bandpass(model.bandpass()),
+            Parameter::Sensing => Change::Sensing(model.sensing().to_vec()),
+            Parameter::Steering => Change::Steering(model.steering().to_vec()),
+            Parameter::DepositOps => Change::DepositOps(model.deposit_ops().to_vec()),
+            Parameter::DiffuseOps => Change::DiffuseOps(model.diffuse_ops().to_vec()),
+            Parameter::TimeScale => Change::TimeScale(model.time_scale()),
+            Parameter::Annealing => Change::Annealing(model.annealing()),
+            Parameter::Crowding => Change::Crowding(model.crowding()),
+            Parameter::Spacing => Change::Spacing(model.spacing()),
+            Parameter::Heat => Change::Heat(model.heat()),
+            Parameter::HeatPainted {
+                x,
+                y,
+                radius,
+                amount,
+            } => Change::PaintHeat {
+                x,
+                y,
+                radius,
+                amount,
+            },
+            Parameter::HeatField => {
+                Change::HeatField(model.heat_field().unwrap_or_default().to_vec())
+            }
+            Parameter::Food => Change::Food(model.food()),
+            Parameter::RenderSettings => {
+                Change::RenderSettings(Box::new(model.render_settings().clone()))
+            }
+            Parameter::Palette => Change::Palette(model.palette().colors.map(|color| color.0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physarum_model::{AttractionDistribution, NewAttraction};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn replayed_changes_step_like_the_recorded_ones() {
+        let build = || {
+            PhysarumModel::builder()
+                .size(32, 32)
+                .particles(60)
+                .seed(3)
+                .build()
+                .unwrap()
+        };
+        let (mut recorded, mut replayed) = (build(), build());
+        let changes = Arc::new(Mutex::new(vec![]));
+        let log = changes.clone();
+        let mut recorder = ChangeRecorder::new(&recorded);
+        recorded.on_parameter_change(move |model, parameter| {
+            let change = recorder.change(model, parameter);
+            log.lock().unwrap().push((model.iteration(), change));
+        });
+
+        let mut rng = SmallRng::seed_from_u64(9);
+        for _ in 0..3 {
+            recorded.step();
+        }
+        recorded.set_population_size(0, 50, &mut rng).unwrap();
+        recorded.set_time_scale(0.5).unwrap();
+        recorded.step();
+        recorded.set_population_size(1, 10, &mut rng).unwrap();
+        recorded.step();
+        let attraction = NewAttraction::Sampled(AttractionDistribution::default());
+        let config = PopulationConfig::new(&mut rng);
+        recorded
+            .add_population(config, 20, attraction, &mut rng)
+            .unwrap();
+        recorded.step();
+        recorded
+            .remove_population(0, RemovedAgents::Reassigned(2))
+            .unwrap();
+        recorded.step();
+        recorded.set_heat(Some(Heat::default())).unwrap();
+        recorded.paint_heat(8.0, 8.0, 4.0, 0.5);
+        recorded.step();
+
+        let changes = changes.lock().unwrap().clone();
+        assert_eq!(changes.len(), 7);
+        let mut pending = changes.into_iter().peekable();
+        for _ in 0..8 {
+            while let Some((_, change)) =
+                pending.next_if(|(iteration, _)| *iteration == replayed.iteration())
+            {
+                change.apply(&mut replayed).unwrap();
+            }
+            replayed.step();
+        }
+        assert_eq!(replayed.agents(), recorded.agents());
+        assert_eq!(replayed.grids[0].data(), recorded.grids[0].data());
+        assert_eq!(replayed.grids[1].data(), recorded.grids[1].data());
+        assert_eq!(replayed.attraction_table(), recorded.attraction_table());
+    }
+}
@@ -0,0 +1,362 @@
+//! Runs told as a sequence of phases, for composing animations with a story: grow a
+//! network with one config, glide into another attraction table, then freeze the trails.
+//!
+//! ```json
+//! {
+//!     "config": "a.json",
+//!     "phases": [
+//!         { "name": "grow", "steps": 2000 },
+//!         { "name": "merge", "steps": 1500, "transition": 500, "config": "b.json" },
+//!         { "name": "freeze", "steps": 1000, "populations": [{ "decay_factor": 1.0 }] }
+//!     ]
+//! }
+//! ```
+
+use super::config::SimulationConfig;
+use super::error::ConfigError;
+use super::physarum_model::PhysarumModel;
+use super::population_config::PopulationConfig;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    /// Config the run starts from, relative to the scenario file.
+    pub config: Option<PathBuf>,
+    pub phases: Vec<Phase>,
+    /// The config named by `config`, loaded by `load`.
+    #[serde(skip)]
+    loaded: Option<Box<SimulationConfig>>,
+}
+
+/// A stretch of the run with its own parameters. Whatever a phase doesn't change is kept
+/// from the phase before.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Phase {
+    /// Shown when the phase begins.
+    pub name: Option<String>,
+    pub steps: usize,
+    /// Steps over which the parameters glide from the previous phase's into this one's,
+    /// 0 to switch at once.
+    pub transition: usize,
+    /// Config whose populations and attraction table the phase switches to, whichever of
+    /// them it has, relative to the scenario file.
+    pub config: Option<PathBuf>,
+    pub attraction_table: Option<Vec<Vec<f32>>>,
+    /// Changes to the population configs, applied after `config`. One per population, or
+    /// a single one for all of them.
+    pub populations: Vec<PopulationPatch>,
+    #[serde(skip)]
+    loaded: Option<Box<SimulationConfig>>,
+}
+
+/// The parameters of a `PopulationConfig` a phase changes, leaving the others as they are.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PopulationPatch {
+    pub sensor_distance: Option<f32>,
+    pub step_distance: Option<f32>,
+    pub sensor_angle: Option<f32>,
+    pub rotation_angle: Option<f32>,
+    pub decay_factor: Option<f32>,
+    pub deposition_amount: Option<f32>,
+    pub bias_angle: Option<f32>,
+    pub bias_strength: Option<f32>,
+    pub self_repulsion: Option<f32>,
+    pub self_attraction_range: Option<f32>,
+    pub erosion: Option<f32>,
+    pub max_substep: Option<f32>,
+}
+
+impl PopulationPatch {
+    pub fn apply(&self, config: &PopulationConfig) -> PopulationConfig {
+        let pick = |value: Option<f32>, current: f32| value.unwrap_or(current);
+        PopulationConfig {
+            sensor_distance: pick(self.sensor_distance, config.sensor_distance),
+            step_distance: pick(self.step_distance, config.step_distance),
+            sensor_angle: pick(self.sensor_angle, config.sensor_angle),
+            rotation_angle: pick(self.rotation_angle, config.rotation_angle),
+            decay_factor: pick(self.decay_factor, config.decay_factor),
+            deposition_amount: pick(self.deposition_amount, config.deposition_amount),
+            bias_angle: pick(self.bias_angle, config.bias_angle),
+            bias_strength: pick(self.bias_strength, config.bias_strength),
+            self_repulsion: pick(self.self_repulsion, config.self_repulsion),
+            self_attraction_range: pick(self.self_attraction_range, config.self_attraction_range),
+            erosion: pick(self.erosion, config.erosion),
+            max_substep: pick(self.max_substep, config.max_substep),
+        }
+    }
+}
+
+impl Scenario {
+    /// Reads a scenario along with the configs it names.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut scenario = Self::from_reader(BufReader::new(File::open(path)?))?;
+        let load = |config: &Option<PathBuf>| {
+            config
+                .as_ref()
+                .map(|config| {
+                    SimulationConfig::load(&dir.join(config)).map_err(|err| {
+                        io::Error::new(
+                            err.kind(),
+                            format!("config {}: {}", dir.join(config).display(), err),
+                        )
+                    })
+                })
+                .transpose()
+                .map(|config| config.map(Box::new))
+        };
+        scenario.loaded = load(&scenario.config)?;
+        for phase in &mut scenario.phases {
+            phase.loaded = load(&phase.config)?;
+        }
+        Ok(scenario)
+    }
+
+    /// Reads a scenario without loading the configs it names, which are then ignored.
+    pub fn from_reader(reader: impl Read) -> io::Result<Self> {
+        let scenario: Scenario = serde_json::from_reader(reader)?;
+        if scenario.phases.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the scenario has no phases",
+            ));
+        }
+        Ok(scenario)
+    }
+
+    /// The config named by `config`, once loaded.
+    pub fn base_config(&self) -> Option<&SimulationConfig> {
+        self.loaded.as_deref()
+    }
+
+    /// Steps of all the phases together.
+    pub fn steps(&self) -> usize {
+        self.phases.iter().map(|phase| phase.steps).sum()
+    }
+}
+
+/// Steps a model through a scenario, changing its parameters before every step.
+pub struct ScenarioRunner {
+    scenario: Scenario,
+    /// The current phase, past the last one once the scenario is over.
+    phase: usize,
+    /// Steps taken in the current phase.
+    step: usize,
+    /// The parameters when the current phase began, and those it glides to.
+    from: (Vec<PopulationConfig>, Vec<Vec<f32>>),
+    to: (Vec<PopulationConfig>, Vec<Vec<f32>>),
+}
+
+impl ScenarioRunner {
+    pub fn new(scenario: Scenario) -> Self {
+        ScenarioRunner {
+            scenario,
+            phase: 0,
+            step: 0,
+            from: (vec![], vec![]),
+            to: (vec![], vec![]),
+        }
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    /// The index of the phase the next step belongs to, None once the scenario is over.
+    pub fn phase(&self) -> Option<usize> {
+        (self.phase < self.scenario.phases.len()).then_some(self.phase)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.phase().is_none()
+    }
+
+    /// Starts the scenario over, e.g. for a model that was reset.
+    pub fn restart(&mut self) {
+        self.phase = 0;
+        self.step = 0;
+    }
+
+    /// Sets the model's parameters for its next step, and returns the index of the phase
+    /// that step begins, if any. Phases without steps are applied and passed through at
+    /// once. Does nothing once the scenario is over.
+    pub fn advance(&mut self, model: &mut PhysarumModel) -> Result<Option<usize>, ConfigError> {
+        let mut began = None;
+        while let Some(index) = self.phase() {
+            let phase = &self.scenario.phases[index];
+            if self.step == 0 {
+                self.from = (configs(model), model.attraction_table().to_vec());
+                self.to = targets(phase, &self.from)?;
+                began = Some(index);
+            }
+            let transition = phase.transition.min(phase.steps);
+            if self.step < transition.max(1) {
+                let t = if transition == 0 {
+                    1.0
+                } else {
+                    (self.step + 1) as f32 / transition as f32
+                };
+                let populations = self
+                    .from
+                    .0
+                    .iter()
+                    .zip(&self.to.0)
+                    .map(|(from, to)| from.lerp(to, t))
+                    .collect();
+                let table = self
+                    .from
+                    .1
+                    .iter()
+                    .zip(&self.to.1)
+                    .map(|(from, to)| from.iter().zip(to).map(|(a, b)| a + (b - a) * t).collect())
+                    .collect();
+                model.set_population_configs(populations)?;
+                model.set_attraction_table(table)?;
+            }
+            if phase.steps == 0 {
+                self.phase += 1;
+                continue;
+            }
+            self.step += 1;
+            if self.step == phase.steps {
+                self.phase += 1;
+                self.step = 0;
+            }
+            break;
+        }
+        Ok(began)
+    }
+}
+
+fn configs(model: &PhysarumModel) -> Vec<PopulationConfig> {
+    model.grids.iter().map(|grid| grid.config).collect()
+}
+
+/// The parameters `phase` switches to from `current`.
+fn targets(
+    phase: &Phase,
+    current: &(Vec<PopulationConfig>, Vec<Vec<f32>>),
+) -> Result<(Vec<PopulationConfig>, Vec<Vec<f32>>), ConfigError> {
+    let (mut populations, mut table) = current.clone();
+    let n_populations = populations.len();
+    if let Some(config) = &phase.loaded {
+        if !config.populations.is_empty() {
+            if config.populations.len() < n_populations {
+                return Err(ConfigError::MissingPopulationConfigs {
+                    expected: n_populations,
+                    actual: config.populations.len(),
+                });
+            }
+            populations = config.populations[..n_populations].to_vec();
+        }
+        if let Some(attraction_table) = &config.attraction_table {
+            table = attraction_table.clone();
+        }
+    }
+    if let Some(attraction_table) = &phase.attraction_table {
+        table = attraction_table.clone();
+    }
+    if table.len() != n_populations || table.iter().any(|row| row.len() != n_populations) {
+        return Err(ConfigError::AttractionTableSize {
+            populations: n_populations,
+        });
+    }
+    match phase.populations.as_slice() {
+        [] => {}
+        [patch] => populations
+            .iter_mut()
+            .for_each(|config| *config = patch.apply(config)),
+        patches => {
+            for (config, patch) in populations.iter_mut().zip(patches) {
+                *config = patch.apply(config);
+            }
+        }
+    }
+    Ok((populations, table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PhysarumModelBuilder;
+
+    fn model() -> PhysarumModel {
+        PhysarumModelBuilder::default()
+            .size(32, 32)
+            .particles(64)
+            .populations(2)
+            .seed(1)
+            .build()
+            .unwrap()
+    }
+
+    fn scenario(json: &str) -> Scenario {
+        Scenario::from_reader(json.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn phases_glide_into_their_parameters() {
+        let mut model = model();
+        let start = model.grids[1].config.sensor_distance;
+        let table = model.attraction_table().to_vec();
+        let mut runner = ScenarioRunner::new(scenario(
+            r#"{"phases": [
+                {"name": "grow", "steps": 3},
+                {"steps": 4, "transition": 2, "attraction_table": [[1, 0], [0, 1]],
+                 "populations": [{"sensor_distance": 40.0}]},
+                {"steps": 0, "populations": [{}, {"decay_factor": 1.0}]},
+                {"steps": 2}
+            ]}"#,
+        ));
+        assert_eq!(runner.scenario().steps(), 9);
+        let mut began = vec![];
+        let mut distances = vec![];
+        while !runner.finished() {
+            began.push(runner.advance(&mut model).unwrap());
+            distances.push(model.grids[1].config.sensor_distance);
+            model.step();
+        }
+        assert_eq!(
+            began,
+            [
+                Some(0),
+                None,
+                None,
+                Some(1),
+                None,
+                None,
+                None,
+                Some(3),
+                None
+            ]
+        );
+        assert!(distances[..3].iter().all(|&distance| distance == start));
+        assert!((distances[3] - (start + 40.0) / 2.0).abs() < 1e-4);
+        assert!(distances[4..].iter().all(|&distance| distance == 40.0));
+        assert_eq!(model.grids[0].config.sensor_distance, 40.0);
+        assert_eq!(model.attraction_table(), [[1.0, 0.0], [0.0, 1.0]]);
+        assert_ne!(table, model.attraction_table());
+        assert_eq!(model.grids[1].config.decay_factor, 1.0);
+        assert_ne!(model.grids[0].config.decay_factor, 1.0);
+        assert_eq!(runner.advance(&mut model).unwrap(), None);
+    }
+
+    #[test]
+    fn mismatched_phases_are_rejected() {
+        assert!(Scenario::from_reader(r#"{"phases": []}"#.as_bytes()).is_err());
+        assert!(Scenario::from_reader(r#"{"phases": [{"stpes": 3}]}"#.as_bytes()).is_err());
+        let mut runner = ScenarioRunner::new(scenario(
+            r#"{"phases": [{"steps": 1, "attraction_table": [[1]]}]}"#,
+        ));
+        assert_eq!(
+            runner.advance(&mut model()),
+            Err(ConfigError::AttractionTableSize { populations: 2 })
+        );
+    }
+}
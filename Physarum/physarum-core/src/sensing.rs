@@ -0,0 +1,106 @@
+use super::error::ConfigError;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Which trails the agents of a population steer by. Only the motion changes, frames are
+/// still composited from every population's own trails.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Sensing {
+    /// The trails of all populations, weighted by the population's attraction table row.
+    #[default]
+    Combined,
+    /// Only its own trails.
+    Own,
+    /// Only the trails of the other populations.
+    Others,
+    /// The trails of all populations with these weights in place of the attraction table.
+    Weights { weights: Vec<f32> },
+}
+
+impl Sensing {
+    fn row(&self, population: usize, attraction: &[f32]) -> Vec<f32> {
+        match self {
+            Sensing::Combined => attraction.to_vec(),
+            Sensing::Own => masked(attraction, |j| j == population),
+            Sensing::Others => masked(attraction, |j| j != population),
+            Sensing::Weights { weights } => weights.clone(),
+        }
+    }
+}
+
+fn masked(row: &[f32], keep: impl Fn(usize) -> bool) -> Vec<f32> {
+    row.iter()
+        .enumerate()
+        .map(|(j, &weight)| if keep(j) { weight } else { 0.0 })
+        .collect()
+}
+
+/// Custom weights need one weight per population.
+pub fn check(sensing: &[Sensing], populations: usize) -> Result<(), ConfigError> {
+    for mode in sensing {
+        if let Sensing::Weights { weights } = mode {
+            if weights.len() != populations {
+                return Err(ConfigError::SensingWeights { populations });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The weights `grid::combine` sums the trails with for each population, where
+/// populations without an entry in `sensing` sense the combined field.
+pub fn table(sensing: &[Sensing], attraction_table: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    attraction_table
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            sensing
+                .get(i)
+                .map_or_else(|| row.clone(), |mode| mode.row(i, row))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn table_masks_rows() {
+        let attraction = vec![
+            vec![1.0, -0.5, -0.7],
+            vec![-0.2, 0.9, -0.4],
+            vec![0.3, 0.1, 1.1],
+        ];
+        let sensing = [
+            Sensing::Own,
+            Sensing::Others,
+            Sensing::Weights {
+                weights: vec![0.5, 0.5, 0.0],
+            },
+        ];
+        assert_eq!(
+            table(&sensing, &attraction),
+            vec![
+                vec![1.0, 0.0, 0.0],
+                vec![-0.2, 0.0, -0.4],
+                vec![0.5, 0.5, 0.0]
+            ]
+        );
+        assert_eq!(table(&sensing[..1], &attraction)[1..], attraction[1..]);
+    }
+
+    #[test]
+    fn weights_need_every_population() {
+        let sensing = [Sensing::Weights {
+            weights: vec![1.0, 0.0],
+        }];
+        assert_eq!(check(&sensing, 2), Ok(()));
+        assert_eq!(
+            check(&sensing, 3),
+            Err(ConfigError::SensingWeights { populations: 3 })
+        );
+    }
+}
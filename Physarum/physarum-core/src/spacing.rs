@@ -0,0 +1,205 @@
+use super::agents::Agents;
+use super::error::ConfigError;
+use super::grid;
+use super::par::*;
+use super::util;
+use alloc::vec;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Largest `Spacing::radius`, beyond which the agents would stop following the trails.
+pub const MAX_SPACING_RADIUS: f32 = 8.0;
+
+/// A short-range repulsion between the agents of each population, pushing apart any two
+/// that come closer than `radius` after they moved. Filaments then keep a width of a few
+/// agents instead of collapsing into lines one cell wide, even when the trails decay fast.
+/// Agents of different populations pass through each other as before.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct Spacing {
+    /// Distance in cells the agents keep, up to `MAX_SPACING_RADIUS`.
+    pub radius: f32,
+    /// Fraction of the overlap of two agents removed each step, from 0 to 1.
+    pub strength: f32,
+}
+
+impl Default for Spacing {
+    fn default() -> Self {
+        Spacing {
+            radius: 1.5,
+            strength: 0.5,
+        }
+    }
+}
+
+impl Spacing {
+    pub(super) fn check(&self) -> Result<(), ConfigError> {
+        if self.radius > 0.0
+            && self.radius <= MAX_SPACING_RADIUS
+            && (0.0..=1.0).contains(&self.strength)
+        {
+            Ok(())
+        } else {
+            Err(ConfigError::Spacing)
+        }
+    }
+
+    /// Pushes apart the agents of the same population closer than `radius`, working out
+    /// every push from the positions before any of them, so the order of the agents and
+    /// the thread count don't change the result. Agents are hashed into cells at least `radius` wide
+    /// and only compared with those of the cells around theirs. Pushes that would take an
+    /// agent out of the `confinement` are dropped.
+    pub(super) fn separate(
+        &self,
+        agents: &mut Agents,
+        width: usize,
+        height: usize,
+        confinement: Option<&[bool]>,
+    ) {
+        let (xs, ys, ids) = agents.columns();
+        let columns = ((width as f32 / self.radius) as usize).clamp(1, width);
+        let rows = ((height as f32 / self.radius) as usize).clamp(1, height);
+        let (cell_width, cell_height) =
+            (width as f32 / columns as f32, height as f32 / rows as f32);
+        let cell_of = |i: usize| {
+            let x = ((xs[i] / cell_width) as usize).min(columns - 1);
+            let y = ((ys[i] / cell_height) as usize).min(rows - 1);
+            y * columns + x
+        };
+
+        // The agents sorted by cell, with the agents of cell `c` at `starts[c]..starts[c + 1]`.
+        let cells: Vec<usize> = (0..ids.len()).map(cell_of).collect();
+        let mut starts = vec![0; columns * rows + 1];
+        for &cell in &cells {
+            starts[cell + 1] += 1;
+        }
+        for c in 0..columns * rows {
+            starts[c + 1] += starts[c];
+        }
+        let mut filled = starts.clone();
+        let mut order = vec![0; ids.len()];
+        for (i, &cell) in cells.iter().enumerate() {
+            order[filled[cell]] = i;
+            filled[cell] += 1;
+        }
+
+        // The distinct cells next to `c` along an axis of `n` cells, wrapping around.
+        let around = |c: usize, n: usize| -> ([usize; 3], usize) {
+            if n >= 3 {
+                ([(c + n - 1) % n, c, (c + 1) % n], 3)
+            } else {
+                ([0, 1, 2], n)
+            }
+        };
+        let wrapped = |d: f32, side: f32| {
+            if d > side / 2.0 {
+                d - side
+            } else if d < -side / 2.0 {
+                d + side
+            } else {
+                d
+            }
+        };
+        let (w, h) = (width as f32, height as f32);
+        let positions: Vec<(f32, f32)> = (0..ids.len())
+            .into_par_iter()
+            .map(|i| {
+                let (cx, cy) = (cells[i] % columns, cells[i] / columns);
+                let (near_x, nx) = around(cx, columns);
+                let (near_y, ny) = around(cy, rows);
+                let (mut push_x, mut push_y) = (0.0, 0.0);
+                for &y in &near_y[..ny] {
+                    for &x in &near_x[..nx] {
+                        let cell = y * columns + x;
+                        for &j in &order[starts[cell]..starts[cell + 1]] {
+                            if j == i || ids[j] != ids[i] {
+                                continue;
+                            }
+                            let dx = wrapped(xs[i] - xs[j], w);
+                            let dy = wrapped(ys[i] - ys[j], h);
+                            let distance = libm::sqrtf(dx * dx + dy * dy);
+                            if distance >= self.radius {
+                                continue;
+                            }
+                            let (ux, uy) = if distance > 0.0 {
+                                (dx / distance, dy / distance)
+                            } else {
+                                // Agents on top of each other part along a direction
+                                // fixed by the pair, one each way.
+                                let angle = i.min(j) as f32 * 2.399_963;
+                                let sign = if i < j { 1.0 } else { -1.0 };
+                                (sign * libm::cosf(angle), sign * libm::sinf(angle))
+                            };
+                            let overlap = (self.radius - distance) * self.strength / 2.0;
+                            push_x += ux * overlap;
+                            push_y += uy * overlap;
+                        }
+                    }
+                }
+                let (x, y) = (xs[i], ys[i]);
+                let to = (util::wrap(x + push_x, w), util::wrap(y + push_y, h));
+                let leaves = confinement.is_some_and(|inside| {
+                    inside[grid::cell(width, height, x, y)]
+                        && !inside[grid::cell(width, height, to.0, to.1)]
+                });
+                if leaves {
+                    (x, y)
+                } else {
+                    to
+                }
+            })
+            .collect();
+        agents.move_to(&positions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    #[test]
+    fn close_agents_of_a_population_part() {
+        let agent = |x, y, id| Particle {
+            x,
+            y,
+            angle: 0.0,
+            id,
+        };
+        let mut agents: Agents = [
+            agent(10.0, 10.0, 0),
+            agent(10.5, 10.0, 0),
+            // Another population passes through.
+            agent(10.25, 10.0, 1),
+            // Across the edge from each other.
+            agent(0.25, 20.0, 0),
+            agent(31.75, 20.0, 0),
+            // On top of each other.
+            agent(20.0, 5.0, 1),
+            agent(20.0, 5.0, 1),
+        ]
+        .into_iter()
+        .collect();
+        let spacing = Spacing {
+            radius: 1.5,
+            strength: 1.0,
+        };
+        spacing.separate(&mut agents, 32, 32, None);
+        let at = |i: usize| {
+            let agent = agents.get(i).unwrap();
+            (agent.x, agent.y)
+        };
+        assert_eq!((at(0), at(1)), ((9.5, 10.0), (11.0, 10.0)));
+        assert_eq!(at(2), (10.25, 10.0));
+        assert_eq!((at(3), at(4)), ((0.75, 20.0), (31.25, 20.0)));
+        let ((x, y), (u, v)) = (at(5), at(6));
+        assert!(((x - u).hypot(y - v) - 1.5).abs() < 1e-5);
+
+        assert!(Spacing::default().check().is_ok());
+        let wide = Spacing {
+            radius: 20.0,
+            ..spacing
+        };
+        assert_eq!(wide.check(), Err(ConfigError::Spacing));
+    }
+}
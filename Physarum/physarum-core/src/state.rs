@@ -0,0 +1,475 @@
+use super::grid;
+use super::particle::Particle;
+use super::population_config::PopulationConfig;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+pub(super) const STATE_VERSION: u32 = 6;
+
+/// Starts a `StateDelta`, where a full state starts with its version.
+const DELTA_MARKER: u32 = u32::from_le_bytes(*b"DLTA");
+
+/// Starts every Zstandard frame, telling compressed states from the plain ones written
+/// before they were compressed or without the `zstd` feature.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[cfg(feature = "zstd")]
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Binary snapshot of everything that evolves while a model runs.
+#[derive(Serialize, Deserialize)]
+pub struct ModelState {
+    pub(super) version: u32,
+    pub(super) width: usize,
+    pub(super) height: usize,
+    pub(super) configs: Vec<PopulationConfig>,
+    pub(super) grids: Vec<Vec<f32>>,
+    pub(super) agents: Vec<Particle>,
+    pub(super) attraction_table: Vec<Vec<f32>>,
+    pub(super) diffusity: usize,
+    pub(super) iteration: u64,
+    pub(super) palette: [[u8; 3]; 5],
+}
+
+/// A state saved as its difference from a full one saved before, which compresses far
+/// better when the two are close, like the states of consecutive frames.
+#[derive(Serialize, Deserialize)]
+struct StateDelta {
+    marker: u32,
+    /// Where the full state is, relative to the delta's directory unless absolute.
+    base: PathBuf,
+    /// The state without its trails and agents.
+    state: ModelState,
+    /// The bits of every trail XORed with those of the base, mostly zeros where the trails
+    /// barely changed.
+    grids: Vec<Vec<u32>>,
+    /// The bits of every agent's position and heading XORed with those of the base's agent
+    /// at the same index, or as they are past the base's agents, with its population.
+    agents: Vec<([u32; 3], usize)>,
+}
+
+/// The bits of the agent's position and heading, XORed with the other agent's if any.
+fn agent_bits(agent: &Particle, other: Option<&Particle>) -> [u32; 3] {
+    let bits = |agent: &Particle| [agent.x, agent.y, agent.angle].map(f32::to_bits);
+    let [x, y, angle] = bits(agent);
+    let [ox, oy, oangle] = other.map_or([0; 3], bits);
+    [x ^ ox, y ^ oy, angle ^ oangle]
+}
+
+impl ModelState {
+    /// Saves the state, compressed with the `zstd` feature.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, encode(self)?)
+    }
+
+    /// Saves the state as its difference from `base`, saved before at `base_path`, which
+    /// takes less space where the two are alike, like trails confined to a mask. A
+    /// relative `base_path` is resolved against the directory of `path` when loading, so
+    /// the two should be moved together. Saves the full state instead when that comes out
+    /// smaller, as it does when all the trails have changed a little, or when the grids
+    /// differ from the base's in size or number.
+    pub fn save_delta(&self, path: &Path, base: &ModelState, base_path: &Path) -> io::Result<()> {
+        if (self.width, self.height, self.grids.len())
+            != (base.width, base.height, base.grids.len())
+        {
+            return self.save(path);
+        }
+        let grids = self
+            .grids
+            .iter()
+            .zip(&base.grids)
+            .map(|(grid, base)| {
+                grid.iter()
+                    .zip(base)
+                    .map(|(v, b)| v.to_bits() ^ b.to_bits())
+                    .collect()
+            })
+            .collect();
+        let agents = self
+            .agents
+            .iter()
+            .enumerate()
+            .map(|(i, agent)| (agent_bits(agent, base.agents.get(i)), agent.id))
+            .collect();
+        let delta = StateDelta {
+            marker: DELTA_MARKER,
+            base: base_path.to_path_buf(),
+            state: ModelState {
+                version: self.version,
+                width: self.width,
+                height: self.height,
+                configs: self.configs.clone(),
+                grids: vec![],
+                agents: vec![],
+                attraction_table: self.attraction_table.clone(),
+                diffusity: self.diffusity,
+                iteration: self.iteration,
+                palette: self.palette,
+            },
+            grids,
+            agents,
+        };
+        let (full, delta) = (encode(self)?, encode(&delta)?);
+        fs::write(
+            path,
+            if delta.len() < full.len() {
+                delta
+            } else {
+                full
+            },
+        )
+    }
+
+    /// Loads a state saved by `save`, or by `save_delta` along with its base.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = decompress(BufReader::new(File::open(path)?))?;
+        if !bytes.starts_with(&DELTA_MARKER.to_le_bytes()) {
+            return Self::from_bytes(&bytes);
+        }
+        let delta: StateDelta = bincode::deserialize(&bytes).map_err(io::Error::other)?;
+        let base_path = path.parent().unwrap_or(Path::new("")).join(&delta.base);
+        let base = Self::from_bytes(&decompress(BufReader::new(File::open(&base_path)?))?)
+            .map_err(|err| {
+                io::Error::new(err.kind(), format!("{}: {}", base_path.display(), err))
+            })?;
+        delta.apply(base)
+    }
+
+    /// Reads and validates a snapshot written by `save`.
+    pub fn from_reader(reader: impl Read) -> io::Result<Self> {
+        Self::from_bytes(&decompress(reader)?)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.starts_with(&DELTA_MARKER.to_le_bytes()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The state is a delta, which only loads from a path next to its base",
+            ));
+        }
+        let state: ModelState = bincode::deserialize(bytes).map_err(io::Error::other)?;
+        if state.version != STATE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported state version {}", state.version),
+            ));
+        }
+        state.validate()?;
+        Ok(state)
+    }
+
+    fn validate(&self) -> io::Result<()> {
+        let invalid = |msg: &str| Err(io::Error::new(io::ErrorKind::InvalidData, msg.to_string()));
+        if !self.width.is_power_of_two() || !self.height.is_power_of_two() {
+            return invalid("Grid dims must be 2^n");
+        }
+        if self.grids.len() != self.configs.len() || self.grids.is_empty() {
+            return invalid("Expected one config per grid");
+        }
+        grid::check_capacity(self.width, self.height, self.grids.len(), self.agents.len())
+            .and_then(|_| grid::check_diffusity(self.width, self.height, self.diffusity))
+            .and_then(|_| {
+                self.configs
+                    .iter()
+                    .try_for_each(|config| grid::check_population(self.width, self.height, config))
+            })
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if self
+            .grids
+            .iter()
+            .any(|grid| grid.len() != self.width * self.height)
+        {
+            return invalid("Grid data doesn't match the state dimensions");
+        }
+        if self.attraction_table.len() != self.grids.len()
+            || self
+                .attraction_table
+                .iter()
+                .any(|row| row.len() != self.grids.len())
+        {
+            return invalid("Attraction table doesn't match the number of grids");
+        }
+        if self.agents.iter().any(|agent| agent.id >= self.grids.len()) {
+            return invalid("Agent refers to a missing population");
+        }
+        Ok(())
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The trails of every population, row by row.
+    pub fn grids(&self) -> &[Vec<f32>] {
+        &self.grids
+    }
+
+    /// The state on grids `factor` times as wide and high, for rendering it at a higher
+    /// resolution. The trails are interpolated bilinearly, wrapping around like the grids
+    /// do, and the agents and the distances they sense and move are scaled along.
+    pub fn upsampled(&self, factor: usize) -> io::Result<ModelState> {
+        if !factor.is_power_of_two() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Can only upsample by a power of two, not {}", factor),
+            ));
+        }
+        let (width, height) = (self.width * factor, self.height * factor);
+        let scale = factor as f32;
+        let source = |u: f32, size: usize| {
+            let i = u.floor();
+            let t = u - i;
+            let i = i as i64;
+            let wrap = |i: i64| i.rem_euclid(size as i64) as usize;
+            (wrap(i), wrap(i + 1), t)
+        };
+        let columns: Vec<_> = (0..width)
+            .map(|x| source((x as f32 + 0.5) / scale - 0.5, self.width))
+            .collect();
+        let grids = self
+            .grids
+            .iter()
+            .map(|grid| {
+                let mut data = Vec::with_capacity(width * height);
+                for y in 0..height {
+                    let (y0, y1, ty) = source((y as f32 + 0.5) / scale - 0.5, self.height);
+                    let (row0, row1) = (y0 * self.width, y1 * self.width);
+                    data.extend(columns.iter().map(|&(x0, x1, tx)| {
+                        let top = grid[row0 + x0] * (1.0 - tx) + grid[row0 + x1] * tx;
+                        let bottom = grid[row1 + x0] * (1.0 - tx) + grid[row1 + x1] * tx;
+                        top * (1.0 - ty) + bottom * ty
+                    }));
+                }
+                data
+            })
+            .collect();
+        let configs = self
+            .configs
+            .iter()
+            .map(|config| PopulationConfig {
+                sensor_distance: config.sensor_distance * scale,
+                step_distance: config.step_distance * scale,
+                ..*config
+            })
+            .collect();
+        let agents = self
+            .agents
+            .iter()
+            .map(|agent| Particle {
+                x: agent.x * scale,
+                y: agent.y * scale,
+                ..*agent
+            })
+            .collect();
+        let state = ModelState {
+            version: self.version,
+            width,
+            height,
+            configs,
+            grids,
+            agents,
+            attraction_table: self.attraction_table.clone(),
+            diffusity: self.diffusity,
+            iteration: self.iteration,
+            palette: self.palette,
+        };
+        state.validate()?;
+        Ok(state)
+    }
+}
+
+impl StateDelta {
+    /// The full state, restored from the base it was saved against.
+    fn apply(self, base: ModelState) -> io::Result<ModelState> {
+        let mismatch = (base.width, base.height) != (self.state.width, self.state.height)
+            || base.grids.len() != self.grids.len()
+            || self
+                .grids
+                .iter()
+                .zip(&base.grids)
+                .any(|(grid, base)| grid.len() != base.len());
+        if mismatch {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The delta doesn't match the size of its base",
+            ));
+        }
+        let grids = self
+            .grids
+            .iter()
+            .zip(&base.grids)
+            .map(|(grid, base)| {
+                grid.iter()
+                    .zip(base)
+                    .map(|(&bits, b)| f32::from_bits(bits ^ b.to_bits()))
+                    .collect()
+            })
+            .collect();
+        let agents = self
+            .agents
+            .iter()
+            .enumerate()
+            .map(|(i, &(bits, id))| {
+                let other = base
+                    .agents
+                    .get(i)
+                    .map_or([0; 3], |agent| agent_bits(agent, None));
+                let [x, y, angle] = [0, 1, 2].map(|k| f32::from_bits(bits[k] ^ other[k]));
+                Particle { x, y, angle, id }
+            })
+            .collect();
+        let state = ModelState {
+            grids,
+            agents,
+            ..self.state
+        };
+        if state.version != STATE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported state version {}", state.version),
+            ));
+        }
+        state.validate()?;
+        Ok(state)
+    }
+}
+
+/// `value` in bincode, shuffled and compressed with the `zstd` feature.
+fn encode(value: &impl Serialize) -> io::Result<Vec<u8>> {
+    let bytes = bincode::serialize(value).map_err(io::Error::other)?;
+    #[cfg(feature = "zstd")]
+    {
+        zstd::encode_all(&shuffle(&bytes)[..], COMPRESSION_LEVEL)
+    }
+    #[cfg(not(feature = "zstd"))]
+    {
+        Ok(bytes)
+    }
+}
+
+/// All of `reader`, decompressed and unshuffled when it is compressed.
+fn decompress(mut reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    if !bytes.starts_with(&ZSTD_MAGIC) {
+        return Ok(bytes);
+    }
+    #[cfg(feature = "zstd")]
+    {
+        Ok(unshuffle(&zstd::decode_all(&bytes[..])?))
+    }
+    #[cfg(not(feature = "zstd"))]
+    {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The state is compressed, which needs the zstd feature",
+        ))
+    }
+}
+
+/// The bytes grouped by their place in 4-byte words, the first bytes of every word first.
+/// The trails and agents are runs of floats whose high bytes vary much less than their low
+/// ones, which compress better together. Bytes past the last whole word stay at the end.
+fn shuffle(bytes: &[u8]) -> Vec<u8> {
+    let whole = bytes.len() / 4 * 4;
+    let mut shuffled = Vec::with_capacity(bytes.len());
+    for k in 0..4 {
+        shuffled.extend(bytes[k..whole].iter().step_by(4));
+    }
+    shuffled.extend(&bytes[whole..]);
+    shuffled
+}
+
+/// The bytes in their order before `shuffle`.
+fn unshuffle(shuffled: &[u8]) -> Vec<u8> {
+    let words = shuffled.len() / 4;
+    let mut bytes = shuffled.to_vec();
+    for (k, plane) in shuffled[..words * 4].chunks(words.max(1)).enumerate() {
+        for (i, &byte) in plane.iter().enumerate() {
+            bytes[i * 4 + k] = byte;
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physarum_model::PhysarumModel;
+
+    #[test]
+    fn upsampling_keeps_the_trails_and_scales_the_agents() {
+        let mut model = PhysarumModel::builder()
+            .size(16, 8)
+            .particles(50)
+            .seed(4)
+            .build()
+            .unwrap();
+        for _ in 0..3 {
+            model.step();
+        }
+        let state = model.state();
+        assert_eq!(state.upsampled(1).unwrap().grids, state.grids);
+        assert!(state.upsampled(3).is_err());
+
+        let large = state.upsampled(4).unwrap();
+        assert_eq!((large.width(), large.height()), (64, 32));
+        let mean = |grid: &[f32]| grid.iter().sum::<f32>() / grid.len() as f32;
+        let (before, after) = (mean(&state.grids[0]), mean(&large.grids[0]));
+        assert!((before - after).abs() < 1e-3 * before);
+        assert_eq!(large.agents[7].x, state.agents[7].x * 4.0);
+        assert_eq!(
+            large.configs[0].step_distance,
+            state.configs[0].step_distance * 4.0
+        );
+        PhysarumModel::from_state(large).unwrap().step();
+    }
+
+    #[test]
+    fn states_round_trip_in_full_as_deltas_and_uncompressed() {
+        let dir = std::env::temp_dir().join("physarum_state_test");
+        fs::create_dir_all(&dir).unwrap();
+        let mut model = PhysarumModel::builder()
+            .size(16, 16)
+            .particles(40)
+            .seed(2)
+            .build()
+            .unwrap();
+        let base = model.state();
+        base.save(&dir.join("base.state")).unwrap();
+        model.step();
+        model.step();
+        let later = model.state();
+        let delta = dir.join("later.state");
+        later
+            .save_delta(&delta, &base, Path::new("base.state"))
+            .unwrap();
+
+        let loaded = ModelState::load(&delta).unwrap();
+        assert_eq!(loaded.grids, later.grids);
+        assert_eq!(loaded.agents, later.agents);
+        assert_eq!(loaded.iteration, later.iteration);
+        let same = dir.join("same.state");
+        base.save_delta(&same, &base, Path::new("base.state"))
+            .unwrap();
+        assert_eq!(ModelState::load(&same).unwrap().agents, base.agents);
+        // Unchanged trails always come out smaller as a delta, which needs its base. Without
+        // zstd deltas are written in full.
+        #[cfg(feature = "zstd")]
+        assert!(ModelState::from_reader(File::open(&same).unwrap()).is_err());
+
+        let bytes: Vec<u8> = (0..11).collect();
+        assert_eq!(unshuffle(&shuffle(&bytes)), bytes);
+        assert_eq!(shuffle(&bytes), [0, 4, 1, 5, 2, 6, 3, 7, 8, 9, 10]);
+
+        let plain = dir.join("plain.state");
+        fs::write(&plain, bincode::serialize(&later).unwrap()).unwrap();
+        assert_eq!(ModelState::load(&plain).unwrap().grids, later.grids);
+    }
+}
@@ -0,0 +1,139 @@
+//! Plain summaries of the trails and agents for analysis outside the simulation, from
+//! `PhysarumModel::grid_statistics` and `PhysarumModel::agent_statistics`.
+
+use super::agents::Particle;
+use super::util;
+use core::f32::consts::TAU;
+use serde::{Deserialize, Serialize};
+
+/// The trails of one population.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct GridStatistics {
+    pub mean: f32,
+    pub variance: f32,
+    pub min: f32,
+    pub max: f32,
+    /// Cells per bin of equal width from 0 to `max`, with `max` in the last bin. Negative
+    /// trails count towards the first one.
+    pub histogram: Vec<usize>,
+    /// Fraction of the cells with more trail than the threshold they were summarized with.
+    pub occupied: f32,
+}
+
+impl GridStatistics {
+    /// Summarizes `trails` in a histogram of `bins` bins, counting the cells above
+    /// `threshold` as occupied.
+    pub fn of(trails: &[f32], bins: usize, threshold: f32) -> Self {
+        let count = trails.len().max(1) as f64;
+        let mean = trails.iter().map(|&v| v as f64).sum::<f64>() / count;
+        let variance = trails
+            .iter()
+            .map(|&v| (v as f64 - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        let (min, max) = trails
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+        let (min, max) = if trails.is_empty() {
+            (0.0, 0.0)
+        } else {
+            (min, max)
+        };
+        let mut histogram = vec![0; bins];
+        if bins > 0 {
+            for &v in trails {
+                let bin = if max > 0.0 {
+                    (v.max(0.0) / max * bins as f32) as usize
+                } else {
+                    0
+                };
+                histogram[bin.min(bins - 1)] += 1;
+            }
+        }
+        let occupied = trails.iter().filter(|&&v| v > threshold).count() as f32 / count as f32;
+        GridStatistics {
+            mean: mean as f32,
+            variance: variance as f32,
+            min,
+            max,
+            histogram,
+            occupied,
+        }
+    }
+}
+
+/// The agents of one population.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct AgentStatistics {
+    pub count: usize,
+    /// Cells every agent moves per step, its population's step distance covering the
+    /// model's time scale. Agents that a confining mask turns back stay put for the step.
+    pub mean_speed: f32,
+    /// Agents per bin of equal width of their headings, from 0 to a full turn, with 0
+    /// pointing along x.
+    pub headings: Vec<usize>,
+    /// Direction of the mean heading in radians, 0 without agents.
+    pub mean_heading: f32,
+    /// Length of the mean heading's unit vector, 1 when all agents head the same way and
+    /// near 0 when they head every way.
+    pub alignment: f32,
+}
+
+impl AgentStatistics {
+    /// Summarizes `agents` moving `speed` cells per step, with `bins` bins of headings.
+    pub fn of(agents: impl Iterator<Item = Particle>, speed: f32, bins: usize) -> Self {
+        let mut headings = vec![0; bins];
+        let (mut count, mut x, mut y) = (0, 0.0f64, 0.0f64);
+        for agent in agents {
+            let angle = util::wrap(agent.angle, TAU);
+            if bins > 0 {
+                let bin = (angle / TAU * bins as f32) as usize;
+                headings[bin.min(bins - 1)] += 1;
+            }
+            x += angle.cos() as f64;
+            y += angle.sin() as f64;
+            count += 1;
+        }
+        let (x, y) = (x / count.max(1) as f64, y / count.max(1) as f64);
+        AgentStatistics {
+            count,
+            mean_speed: speed,
+            headings,
+            mean_heading: util::wrap(y.atan2(x) as f32, TAU),
+            alignment: x.hypot(y) as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summaries_of_trails_and_headings() {
+        let trails = [0.0, 1.0, 2.0, 3.0, 4.0, -1.0];
+        let stats = GridStatistics::of(&trails, 4, 0.5);
+        assert!((stats.mean - 1.5).abs() < 1e-6);
+        assert!((stats.variance - 17.5 / 6.0).abs() < 1e-5);
+        assert_eq!((stats.min, stats.max), (-1.0, 4.0));
+        assert_eq!(stats.histogram, vec![2, 1, 1, 2]);
+        assert!((stats.occupied - 4.0 / 6.0).abs() < 1e-6);
+        assert_eq!(GridStatistics::of(&[], 2, 0.0).histogram, vec![0, 0]);
+
+        let agent = |angle| Particle {
+            x: 0.0,
+            y: 0.0,
+            angle,
+            id: 0,
+        };
+        let aligned = [agent(1.0), agent(1.0 + TAU)];
+        let stats = AgentStatistics::of(aligned.into_iter(), 2.0, 4);
+        assert_eq!((stats.count, stats.mean_speed), (2, 2.0));
+        assert_eq!(stats.headings, vec![2, 0, 0, 0]);
+        assert!((stats.mean_heading - 1.0).abs() < 1e-5 && (stats.alignment - 1.0).abs() < 1e-5);
+        let opposed = [agent(0.0), agent(TAU / 2.0)];
+        let stats = AgentStatistics::of(opposed.into_iter(), 2.0, 4);
+        assert_eq!(stats.headings, vec![1, 0, 1, 0]);
+        assert!(stats.alignment < 1e-6);
+    }
+}
@@ -0,0 +1,199 @@
+//! How agents turn on the trails they sense. Each population steers by a `SteeringRule`,
+//! chosen by name in `SimulationConfig::steering`: one of the built-in rules, or with std
+//! one added through `register`.
+
+use super::agents::Particle;
+use super::error::ConfigError;
+use super::step;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use rand::{rngs::SmallRng, Rng};
+#[cfg(feature = "std")]
+use std::{collections::BTreeMap, sync::RwLock};
+
+/// The built-in rules, the first one being what populations without a rule steer by.
+pub const BUILT_IN: [&str; 3] = ["threshold", "softmax", "gradient"];
+
+/// The trail sampled ahead of an agent and at `sensor_angle` to either side of it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Senses {
+    pub left: f32,
+    pub center: f32,
+    pub right: f32,
+}
+
+/// Decides which way an agent turns, given what it senses. Rules are shared by all agents
+/// of a population and called from many threads at once, so any randomness has to come
+/// from `rng`, which is drawn anew for every agent and step and keeps runs reproducible.
+pub trait SteeringRule: Send + Sync {
+    /// How far to turn in multiples of the population's `rotation_angle`, positive
+    /// towards the right sensor and negative towards the left one.
+    fn turn(&self, senses: Senses, agent: &Particle, rng: &mut SmallRng) -> f32;
+}
+
+/// The classic rule: keep going while the trail is strongest ahead, turn a full rotation
+/// towards the stronger side, and pick a side at random when ahead is weakest.
+pub struct Threshold;
+
+impl SteeringRule for Threshold {
+    fn turn(&self, senses: Senses, _: &Particle, rng: &mut SmallRng) -> f32 {
+        step::pick_direction(senses.center, senses.left, senses.right, rng)
+    }
+}
+
+/// Picks one of the three directions at random, weighted by the softmax of the samples
+/// relative to the strongest one. Low temperatures follow the trails almost like
+/// `Threshold`, high ones wander more and make looser networks.
+pub struct Softmax {
+    pub temperature: f32,
+}
+
+impl SteeringRule for Softmax {
+    fn turn(&self, senses: Senses, _: &Particle, rng: &mut SmallRng) -> f32 {
+        let samples = [senses.left, senses.center, senses.right];
+        let max = samples.iter().copied().fold(f32::MIN, f32::max);
+        let scale = self.temperature * max.abs();
+        if scale <= 0.0 || !scale.is_finite() {
+            return 0.0;
+        }
+        let weights = samples.map(|sample| libm::expf((sample - max) / scale));
+        let mut pick = rng.gen::<f32>() * weights.iter().sum::<f32>();
+        for (direction, weight) in [-1.0, 0.0, 1.0].into_iter().zip(weights) {
+            pick -= weight;
+            if pick < 0.0 {
+                return direction;
+            }
+        }
+        1.0
+    }
+}
+
+/// Turns in proportion to the difference between the sides, so agents glide along the
+/// gradient of the trails instead of zig-zagging between full turns.
+pub struct Gradient;
+
+impl SteeringRule for Gradient {
+    fn turn(&self, senses: Senses, _: &Particle, _: &mut SmallRng) -> f32 {
+        let total = senses.left.abs() + senses.right.abs();
+        if total > 0.0 {
+            ((senses.right - senses.left) / total).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+fn built_in(name: &str) -> Option<Arc<dyn SteeringRule>> {
+    match name {
+        "threshold" => Some(Arc::new(Threshold)),
+        "softmax" => Some(Arc::new(Softmax { temperature: 0.1 })),
+        "gradient" => Some(Arc::new(Gradient)),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "std")]
+static REGISTERED: RwLock<BTreeMap<String, Arc<dyn SteeringRule>>> = RwLock::new(BTreeMap::new());
+
+/// Makes `rule` available to configs as `name`, replacing any rule registered under it
+/// before. The built-in rules can't be replaced.
+#[cfg(feature = "std")]
+pub fn register(name: &str, rule: impl SteeringRule + 'static) {
+    REGISTERED
+        .write()
+        .unwrap()
+        .insert(name.into(), Arc::new(rule));
+}
+
+/// The rule called `name`, built in or registered.
+pub fn by_name(name: &str) -> Option<Arc<dyn SteeringRule>> {
+    #[cfg(feature = "std")]
+    {
+        built_in(name).or_else(|| REGISTERED.read().unwrap().get(name).cloned())
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        built_in(name)
+    }
+}
+
+/// The rules of every population, by name.
+pub fn resolve(names: &[String]) -> Result<Vec<Arc<dyn SteeringRule>>, ConfigError> {
+    names
+        .iter()
+        .map(|name| {
+            by_name(name).ok_or_else(|| ConfigError::UnknownSteeringRule { name: name.clone() })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn rules_turn_towards_the_stronger_side() {
+        let senses = Senses {
+            left: 1.0,
+            center: 2.0,
+            right: 8.0,
+        };
+        let mut rng = SmallRng::seed_from_u64(0);
+        let agent = Particle::new(8, 8, 0, &mut rng);
+        assert_eq!(Threshold.turn(senses, &agent, &mut rng), 1.0);
+        assert!((Gradient.turn(senses, &agent, &mut rng) - 7.0 / 9.0).abs() < 1e-6);
+        let softmax = Softmax { temperature: 0.1 };
+        let turns: Vec<f32> = (0..100)
+            .map(|_| softmax.turn(senses, &agent, &mut rng))
+            .collect();
+        assert!(turns.iter().filter(|&&turn| turn == 1.0).count() > 90);
+    }
+
+    #[test]
+    fn softmax_samples_differently_for_every_agent() {
+        let senses = Senses {
+            left: 1.0,
+            center: 1.0,
+            right: 1.0,
+        };
+        let softmax = Softmax { temperature: 1.0 };
+        let mut rng = SmallRng::seed_from_u64(0);
+        let agents: Vec<Particle> = (0..60)
+            .map(|_| Particle::new(64, 64, 0, &mut rng))
+            .collect();
+        let picks = |iteration| -> Vec<f32> {
+            agents
+                .iter()
+                .map(|agent| softmax.turn(senses, agent, &mut step::agent_rng(agent, iteration)))
+                .collect()
+        };
+        let first = picks(0);
+        for direction in [-1.0, 0.0, 1.0] {
+            assert!(first.contains(&direction));
+        }
+        assert_eq!(picks(0), first);
+        assert_ne!(picks(1), first);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn registered_rules_resolve_by_name() {
+        struct Left;
+
+        impl SteeringRule for Left {
+            fn turn(&self, _: Senses, _: &Particle, _: &mut SmallRng) -> f32 {
+                -1.0
+            }
+        }
+
+        register("always_left", Left);
+        let rules = resolve(&["gradient".into(), "always_left".into()]).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert!(matches!(
+            resolve(&["nonexistent".into()]),
+            Err(ConfigError::UnknownSteeringRule { .. })
+        ));
+    }
+}
@@ -0,0 +1,716 @@
+use super::agents::Agents;
+use super::annealing::{self, Annealing};
+use super::bandpass::Bandpass;
+use super::crowding::{Crowding, Density};
+use super::error::ConfigError;
+use super::grid::{self, Grid};
+use super::math::Math;
+use super::noise;
+use super::operators::{DepositOp, DiffuseOp};
+use super::par::*;
+use super::particle::Particle;
+use super::population_config::PopulationConfig;
+use super::spacing::Spacing;
+use super::steering::{Senses, SteeringRule};
+use super::util;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::f32::consts::{PI, TAU};
+use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
+
+/// Options of `step` beyond the grids and agents themselves.
+#[derive(Clone, Copy)]
+pub struct StepOptions<'a> {
+    /// Agents sense the other populations' trails through it.
+    pub bandpass: Option<Bandpass>,
+    /// How the agents of every population turn, `steering::Threshold` for populations past
+    /// the end.
+    pub steering: &'a [Arc<dyn SteeringRule>],
+    /// Agents deposit into every grid as `deposit` describes.
+    pub deposition_table: Option<&'a [Vec<f32>]>,
+    /// How the agents of every population deposit, into a single cell for populations
+    /// past the end.
+    pub deposit_ops: &'a [Arc<dyn DepositOp>],
+    /// How the grid of every population diffuses, by `Grid::diffuse` for populations past
+    /// the end.
+    pub diffuse_ops: &'a [Arc<dyn DiffuseOp>],
+    /// Steps covered by one step, see `PopulationConfig::scaled`.
+    pub time_scale: f32,
+    /// Makes the result the same on every platform, by using portable trigonometry, at
+    /// some cost in speed.
+    pub strict_determinism: bool,
+    /// Moves the agents with approximate trigonometry, see `trig`, which is faster and
+    /// steers within about a thousandth of the exact directions.
+    pub fast_math: bool,
+    /// Cells agents turn back at instead of leaving once they are in them, one per cell of
+    /// the grids, e.g. the inside of an output mask.
+    pub confinement: Option<&'a [bool]>,
+    /// Adds random turns of up to this many rotation angles to every agent's steering,
+    /// see `Annealing`.
+    pub temperature: f32,
+    /// Agents also sense how crowded it is at their sensors, see `Crowding`.
+    pub crowding: Option<Crowding>,
+    /// Agents of a population keep apart after moving, see `Spacing`.
+    pub spacing: Option<Spacing>,
+    /// Number of the step, which the random choices of the agents are drawn for.
+    pub iteration: u64,
+}
+
+impl Default for StepOptions<'_> {
+    fn default() -> Self {
+        StepOptions {
+            bandpass: None,
+            steering: &[],
+            deposition_table: None,
+            deposit_ops: &[],
+            diffuse_ops: &[],
+            time_scale: 1.0,
+            strict_determinism: false,
+            fast_math: false,
+            confinement: None,
+            temperature: 0.0,
+            crowding: None,
+            spacing: None,
+            iteration: 0,
+        }
+    }
+}
+
+/// Senses, turns and moves every agent, then deposits their trails and diffuses the grids.
+///
+/// Agents only read the grids while moving in parallel and deposit in order afterwards, so
+/// the thread count never changes the result.
+pub fn step(
+    grids: &mut [Grid],
+    agents: &mut Agents,
+    attraction_table: &[Vec<f32>],
+    diffusity: usize,
+    options: StepOptions,
+) {
+    let StepOptions {
+        bandpass,
+        steering,
+        deposition_table,
+        deposit_ops,
+        diffuse_ops,
+        time_scale,
+        strict_determinism,
+        fast_math,
+        confinement,
+        temperature,
+        crowding,
+        spacing,
+        iteration,
+    } = options;
+    grid::combine(grids, attraction_table, bandpass);
+    let math = Math::new(strict_determinism);
+    let motion = if fast_math { math.fast_trig() } else { math };
+    let configs: Vec<_> = grids
+        .iter()
+        .map(|grid| grid.config.scaled(time_scale, math))
+        .collect();
+
+    let density = crowding.map(|crowding| {
+        let (width, height) = (grids[0].width, grids[0].height);
+        (
+            crowding.strength,
+            Density::count(agents, width, height, crowding.bin_size),
+        )
+    });
+
+    let sensing = &*grids;
+    agents.par_for_each(|agent| {
+        let grid = &sensing[agent.id];
+        let config = &configs[agent.id];
+        let PopulationConfig {
+            rotation_angle,
+            step_distance,
+            bias_angle,
+            bias_strength,
+            ..
+        } = *config;
+        let (width, height) = (grid.width, grid.height);
+
+        let Senses {
+            mut left,
+            mut center,
+            mut right,
+        } = sense(grid, config, agent, motion);
+        if let Some((strength, density)) = &density {
+            let [l, c, r] = sensors(config, agent, motion).map(|(x, y)| density.at(x, y));
+            (left, center, right) = (
+                left - strength * l,
+                center - strength * c,
+                right - strength * r,
+            );
+        }
+
+        let mut rng = agent_rng(agent, iteration);
+        let direction = turn(steering, agent, left, center, right, &mut rng)
+            + annealing::jitter(agent, temperature);
+        agent.steer_towards(bias_angle, bias_strength, motion);
+        let (x, y) = (agent.x, agent.y);
+        agent.rotate_and_move(
+            direction,
+            rotation_angle,
+            step_distance,
+            width,
+            height,
+            motion,
+        );
+        if let Some(inside) = confinement {
+            if inside[grid::cell(width, height, x, y)]
+                && !inside[grid::cell(width, height, agent.x, agent.y)]
+            {
+                (agent.x, agent.y) = (x, y);
+                agent.angle = util::wrap(agent.angle + PI, TAU);
+            }
+        }
+    });
+    if let Some(spacing) = spacing {
+        let (width, height) = (grids[0].width, grids[0].height);
+        spacing.separate(agents, width, height, confinement);
+    }
+
+    // Table entries deposit into the grid of their column, so they scale with its decay.
+    let scaled_table: Option<Vec<Vec<f32>>> =
+        deposition_table.filter(|_| time_scale != 1.0).map(|table| {
+            let scales: Vec<_> = grids
+                .iter()
+                .map(|grid| grid.config.deposit_scale(time_scale, math))
+                .collect();
+            table
+                .iter()
+                .map(|row| row.iter().zip(&scales).map(|(a, s)| a * s).collect())
+                .collect()
+        });
+    let deposition_table = scaled_table.as_deref().or(deposition_table);
+    deposit(
+        grids,
+        &configs,
+        agents,
+        deposition_table,
+        deposit_ops,
+        motion,
+    );
+
+    let diffusion: Vec<_> = (0..grids.len()).map(|i| diffuse_ops.get(i)).collect();
+    grids
+        .par_iter_mut()
+        .zip(configs.par_iter())
+        .zip(diffusion.par_iter())
+        .for_each(|((grid, config), op)| match op {
+            Some(op) => op.diffuse(&mut grid.trail(), diffusity, config.decay_factor),
+            None => grid.diffuse(diffusity, config.decay_factor),
+        });
+}
+
+/// Lets every agent deposit its population's `deposition_amount` into its own grid, or
+/// with a table, `deposition_table[i][j]` into grid `j` for agents of population `i`.
+/// Negative amounts erase trails, e.g. for predators wiping out the trails of their prey.
+/// Populations with an `erosion` rate first consume some of their own trail. Agents that
+/// substep split all of this over the points of their path. Populations with an operator
+/// in `deposit_ops` deposit all of it through that operator.
+fn deposit(
+    grids: &mut [Grid],
+    configs: &[PopulationConfig],
+    agents: &Agents,
+    deposition_table: Option<&[Vec<f32>]>,
+    deposit_ops: &[Arc<dyn DepositOp>],
+    math: Math,
+) {
+    if configs.iter().any(|config| config.erosion != 0.0) {
+        for agent in agents.iter() {
+            let config = &configs[agent.id];
+            for (x, y) in path(&agent, config, math) {
+                grids[agent.id].erode(x, y, config.erosion);
+            }
+        }
+    }
+    for agent in agents.iter() {
+        let config = configs[agent.id];
+        let share = 1.0 / config.substeps() as f32;
+        let op = deposit_ops.get(agent.id);
+        for (x, y) in path(&agent, &config, math) {
+            match deposition_table {
+                None if share == 1.0 && op.is_none() => {
+                    grids[agent.id].deposit(x, y, config.deposition_amount)
+                }
+                None => mark(
+                    &mut grids[agent.id],
+                    op,
+                    &agent,
+                    (x, y),
+                    config.deposition_amount * share,
+                ),
+                Some(table) => {
+                    for (grid, &amount) in grids.iter_mut().zip(&table[agent.id]) {
+                        if amount != 0.0 {
+                            mark(grid, op, &agent, (x, y), amount * share);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Marks the trail where `agent` passes, through its population's operator if it has one.
+fn mark(
+    grid: &mut Grid,
+    op: Option<&Arc<dyn DepositOp>>,
+    agent: &Particle,
+    (x, y): (f32, f32),
+    amount: f32,
+) {
+    match op {
+        Some(op) => op.deposit(&mut grid.trail(), agent, x, y, amount),
+        None => grid.mark(x, y, amount),
+    }
+}
+
+/// The points an agent deposits at, its position and with substeps the evenly spaced
+/// points back along its last move.
+fn path(
+    agent: &Particle,
+    config: &PopulationConfig,
+    math: Math,
+) -> impl Iterator<Item = (f32, f32)> {
+    let substeps = config.substeps();
+    let (dx, dy) = if substeps > 1 {
+        let length = config.step_distance / substeps as f32;
+        (
+            (math.cos)(agent.angle) * length,
+            (math.sin)(agent.angle) * length,
+        )
+    } else {
+        (0.0, 0.0)
+    };
+    let (x, y) = (agent.x, agent.y);
+    (0..substeps).map(move |i| (x - dx * i as f32, y - dy * i as f32))
+}
+
+pub(super) fn check_time_scale(time_scale: f32) -> Result<(), ConfigError> {
+    if time_scale > 0.0 && time_scale.is_finite() {
+        Ok(())
+    } else {
+        Err(ConfigError::TimeScale)
+    }
+}
+
+/// Deposition tables need a row of one amount per population for every population.
+pub(super) fn check_deposition_table(
+    table: &[Vec<f32>],
+    populations: usize,
+) -> Result<(), ConfigError> {
+    if table.len() != populations || table.iter().any(|row| row.len() != populations) {
+        return Err(ConfigError::DepositionTableSize { populations });
+    }
+    Ok(())
+}
+
+/// The combined trails `agent` senses ahead and to its sides, which `grid::combine` has
+/// to have stored in the grid's `buf`. `config` is already scaled to the time step.
+#[inline(always)]
+pub(super) fn sense(
+    grid: &Grid,
+    config: &PopulationConfig,
+    agent: &Particle,
+    math: Math,
+) -> Senses {
+    let [left, center, right] = sensors(config, agent, math).map(|(x, y)| grid.get_buf(x, y));
+    Senses {
+        left,
+        center,
+        right,
+    }
+}
+
+/// Where the left, center and right sensors of `agent` are.
+#[inline(always)]
+fn sensors(config: &PopulationConfig, agent: &Particle, math: Math) -> [(f32, f32); 3] {
+    let Math { cos, sin, .. } = math;
+    let PopulationConfig {
+        sensor_distance,
+        sensor_angle,
+        ..
+    } = *config;
+    [-sensor_angle, 0.0, sensor_angle].map(|offset| {
+        (
+            agent.x + cos(agent.angle + offset) * sensor_distance,
+            agent.y + sin(agent.angle + offset) * sensor_distance,
+        )
+    })
+}
+
+/// A generator for the random choices of `agent` at step `iteration`, drawn from its
+/// position, heading and population, so it differs between agents and steps without
+/// depending on the order or the thread they are moved in.
+pub(super) fn agent_rng(agent: &Particle, iteration: u64) -> SmallRng {
+    let bits = (u64::from(agent.x.to_bits()) << 32 | u64::from(agent.y.to_bits()))
+        ^ u64::from(agent.angle.to_bits()).rotate_left(17)
+        ^ (agent.id as u64).rotate_left(49);
+    SmallRng::seed_from_u64(bits ^ noise::hash(iteration))
+}
+
+/// Which way `agent` turns by its population's rule in `steering`.
+pub(super) fn turn(
+    steering: &[Arc<dyn SteeringRule>],
+    agent: &Particle,
+    left: f32,
+    center: f32,
+    right: f32,
+    rng: &mut SmallRng,
+) -> f32 {
+    match steering.get(agent.id) {
+        Some(rule) => rule.turn(
+            Senses {
+                left,
+                center,
+                right,
+            },
+            agent,
+            rng,
+        ),
+        None => pick_direction(center, left, right, rng),
+    }
+}
+
+pub(super) fn pick_direction(center: f32, left: f32, right: f32, rng: &mut SmallRng) -> f32 {
+    if (center > left) && (center > right) {
+        0.0
+    } else if (center < left) && (center < right) {
+        *[-1.0, 1.0].choose(rng).unwrap()
+    } else if left < right {
+        1.0
+    } else if right < left {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+/// Just the agents and trail grids, without the rendering state of `PhysarumModel`, so the
+/// simulation can run with only `alloc`, e.g. on a microcontroller driving an LED matrix.
+pub struct Simulation {
+    pub grids: Vec<Grid>,
+    agents: Agents,
+    attraction_table: Vec<Vec<f32>>,
+    diffusity: usize,
+    bandpass: Option<Bandpass>,
+    steering: Vec<Arc<dyn SteeringRule>>,
+    deposition_table: Option<Vec<Vec<f32>>>,
+    deposit_ops: Vec<Arc<dyn DepositOp>>,
+    diffuse_ops: Vec<Arc<dyn DiffuseOp>>,
+    time_scale: f32,
+    iteration: u64,
+    strict_determinism: bool,
+    fast_math: bool,
+    confinement: Option<Vec<bool>>,
+    annealing: Option<Annealing>,
+    crowding: Option<Crowding>,
+    spacing: Option<Spacing>,
+}
+
+impl Simulation {
+    /// Spreads `n_particles` evenly over one population per config. `attraction_table[i][j]`
+    /// is how much population `i` follows the trails of population `j`.
+    pub fn new(
+        width: usize,
+        height: usize,
+        n_particles: usize,
+        configs: Vec<PopulationConfig>,
+        attraction_table: Vec<Vec<f32>>,
+        diffusity: usize,
+        rng: &mut SmallRng,
+    ) -> Result<Self, ConfigError> {
+        let n_populations = configs.len();
+        if n_populations == 0 {
+            return Err(ConfigError::NoPopulations);
+        }
+        if n_particles < n_populations {
+            return Err(ConfigError::TooFewParticles {
+                particles: n_particles,
+                populations: n_populations,
+            });
+        }
+        if attraction_table.len() != n_populations
+            || attraction_table
+                .iter()
+                .any(|row| row.len() != n_populations)
+        {
+            return Err(ConfigError::AttractionTableSize {
+                populations: n_populations,
+            });
+        }
+
+        grid::check_dimensions(width, height)?;
+        grid::check_capacity(width, height, n_populations, n_particles)?;
+        grid::check_diffusity(width, height, diffusity)?;
+        for config in &configs {
+            grid::check_population(width, height, config)?;
+        }
+
+        let particles_per_grid = n_particles.div_ceil(n_populations);
+        let agents = (0..particles_per_grid * n_populations)
+            .map(|i| Particle::new(width, height, i / particles_per_grid, rng))
+            .collect();
+        let grids = configs
+            .into_iter()
+            .map(|config| Grid::new(width, height, config, rng))
+            .collect::<Result<_, _>>()?;
+        Ok(Simulation {
+            grids,
+            agents,
+            attraction_table,
+            diffusity,
+            bandpass: None,
+            steering: Vec::new(),
+            deposition_table: None,
+            deposit_ops: Vec::new(),
+            diffuse_ops: Vec::new(),
+            time_scale: 1.0,
+            iteration: 0,
+            strict_determinism: false,
+            fast_math: false,
+            confinement: None,
+            annealing: None,
+            crowding: None,
+            spacing: None,
+        })
+    }
+
+    pub fn step(&mut self) {
+        step(
+            &mut self.grids,
+            &mut self.agents,
+            &self.attraction_table,
+            self.diffusity,
+            StepOptions {
+                bandpass: self.bandpass,
+                steering: &self.steering,
+                deposition_table: self.deposition_table.as_deref(),
+                deposit_ops: &self.deposit_ops,
+                diffuse_ops: &self.diffuse_ops,
+                time_scale: self.time_scale,
+                strict_determinism: self.strict_determinism,
+                fast_math: self.fast_math,
+                confinement: self.confinement.as_deref(),
+                temperature: self
+                    .annealing
+                    .map_or(0.0, |annealing| annealing.temperature(self.iteration)),
+                crowding: self.crowding,
+                spacing: self.spacing,
+                iteration: self.iteration,
+            },
+        );
+        self.iteration += 1;
+    }
+
+    /// See `Bandpass`, off by default.
+    pub fn set_bandpass(&mut self, bandpass: Option<Bandpass>) -> Result<(), ConfigError> {
+        if let Some(bandpass) = bandpass {
+            bandpass.check(self.grids[0].width, self.grids[0].height)?;
+        }
+        self.bandpass = bandpass;
+        Ok(())
+    }
+
+    /// How every population turns, see `SteeringRule`. Populations past the end of
+    /// `steering` use `steering::Threshold`, like all of them do by default.
+    pub fn set_steering(&mut self, steering: Vec<Arc<dyn SteeringRule>>) {
+        self.steering = steering;
+    }
+
+    /// See `deposit`, each agent depositing into its own grid by default.
+    pub fn set_deposition_table(
+        &mut self,
+        table: Option<Vec<Vec<f32>>>,
+    ) -> Result<(), ConfigError> {
+        if let Some(table) = &table {
+            check_deposition_table(table, self.grids.len())?;
+        }
+        self.deposition_table = table;
+        Ok(())
+    }
+
+    /// How every population deposits, see `DepositOp`. Populations past the end of `ops`
+    /// deposit into single cells, like all of them do by default.
+    pub fn set_deposit_ops(&mut self, ops: Vec<Arc<dyn DepositOp>>) {
+        self.deposit_ops = ops;
+    }
+
+    /// How every population diffuses, see `DiffuseOp`. Populations past the end of `ops`
+    /// blur by `Grid::diffuse`, like all of them do by default.
+    pub fn set_diffuse_ops(&mut self, ops: Vec<Arc<dyn DiffuseOp>>) {
+        self.diffuse_ops = ops;
+    }
+
+    /// See `StepOptions::confinement`, with a flag for every cell. Agents go anywhere by
+    /// default.
+    pub fn set_confinement(&mut self, inside: Option<Vec<bool>>) -> Result<(), ConfigError> {
+        let cells = self.grids[0].width * self.grids[0].height;
+        if let Some(inside) = &inside {
+            if inside.len() != cells {
+                return Err(ConfigError::DataLength {
+                    expected: cells,
+                    actual: inside.len(),
+                });
+            }
+        }
+        self.confinement = inside;
+        Ok(())
+    }
+
+    /// See `Annealing`, which counts from the simulation's first step.
+    pub fn set_annealing(&mut self, annealing: Option<Annealing>) -> Result<(), ConfigError> {
+        if let Some(annealing) = &annealing {
+            annealing.check()?;
+        }
+        self.annealing = annealing;
+        Ok(())
+    }
+
+    /// See `Crowding`, off by default.
+    pub fn set_crowding(&mut self, crowding: Option<Crowding>) -> Result<(), ConfigError> {
+        if let Some(crowding) = &crowding {
+            crowding.check(self.grids[0].width, self.grids[0].height)?;
+        }
+        self.crowding = crowding;
+        Ok(())
+    }
+
+    /// See `Spacing`, off by default.
+    pub fn set_spacing(&mut self, spacing: Option<Spacing>) -> Result<(), ConfigError> {
+        if let Some(spacing) = &spacing {
+            spacing.check()?;
+        }
+        self.spacing = spacing;
+        Ok(())
+    }
+
+    /// See `PopulationConfig::scaled`, 1 by default.
+    pub fn set_time_scale(&mut self, time_scale: f32) -> Result<(), ConfigError> {
+        check_time_scale(time_scale)?;
+        self.time_scale = time_scale;
+        Ok(())
+    }
+
+    /// See `step`. Builds without std always step portably.
+    pub fn set_strict_determinism(&mut self, strict_determinism: bool) {
+        self.strict_determinism = strict_determinism;
+    }
+
+    /// See `StepOptions::fast_math`, off by default.
+    pub fn set_fast_math(&mut self, fast_math: bool) {
+        self.fast_math = fast_math;
+    }
+
+    pub fn iteration(&self) -> u64 {
+        self.iteration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn substeps_share_the_deposit_along_the_path() {
+        let mut config = PopulationConfig::new(&mut SmallRng::seed_from_u64(0));
+        config.step_distance = 4.0;
+        config.max_substep = 1.0;
+        config.deposition_amount = 8.0;
+        let mut grids = [Grid::from_data(16, 16, config, vec![0.0; 256]).unwrap()];
+        let agent = Particle {
+            x: 6.5,
+            y: 2.5,
+            angle: 0.0,
+            id: 0,
+        };
+        let agents = Agents::from(vec![agent]);
+        deposit(&mut grids, &[config], &agents, None, &[], Math::new(true));
+        let row = &grids[0].data()[2 * 16..3 * 16];
+        assert_eq!(row[3..7], [2.0; 4]);
+        assert_eq!(row.iter().sum::<f32>(), 8.0);
+    }
+
+    #[test]
+    fn half_steps_match_whole_steps() {
+        let mut config = PopulationConfig::new(&mut SmallRng::seed_from_u64(0));
+        config.rotation_angle = 0.0;
+        config.bias_strength = 0.0;
+        config.decay_factor = 0.8;
+        let run = |time_scale: f32, steps: usize| {
+            let mut grids = [Grid::from_data(32, 32, config, vec![1.0; 1024]).unwrap()];
+            let mut agents: Agents = (0..8)
+                .map(|i| Particle {
+                    x: 3.5 * i as f32,
+                    y: 16.5,
+                    angle: 0.3 * i as f32,
+                    id: 0,
+                })
+                .collect();
+            let options = StepOptions {
+                time_scale,
+                strict_determinism: true,
+                ..StepOptions::default()
+            };
+            for _ in 0..steps {
+                step(&mut grids, &mut agents, &[vec![1.0]], 1, options);
+            }
+            let mass: f32 = grids[0].data().iter().sum();
+            (mass, agents)
+        };
+        let (mass, agents) = run(1.0, 10);
+        let (half_mass, half_agents) = run(0.5, 20);
+        assert!((mass - half_mass).abs() < 1e-3 * mass);
+        for (a, b) in agents.iter().zip(half_agents.iter()) {
+            assert!((a.x - b.x).abs() < 1e-3 && (a.y - b.y).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn agents_turn_away_from_crowds() {
+        let mut config = PopulationConfig::new(&mut SmallRng::seed_from_u64(0));
+        config.sensor_distance = 4.0;
+        config.sensor_angle = PI / 4.0;
+        config.rotation_angle = 0.5;
+        config.deposition_amount = 0.0;
+        config.bias_strength = 0.0;
+        config.self_repulsion = 0.0;
+        config.max_substep = 0.0;
+        let turned = |crowding| {
+            let mut grids = [Grid::from_data(32, 32, config, vec![1.0; 1024]).unwrap()];
+            let agent = |x, y| Particle {
+                x,
+                y,
+                angle: 0.0,
+                id: 0,
+            };
+            // Heading along x with a crowd off to the right, towards y.
+            let mut agents: Agents = core::iter::once(agent(16.0, 16.0))
+                .chain((0..40).map(|i| agent(19.0 + (i % 2) as f32, 19.5)))
+                .collect();
+            let options = StepOptions {
+                strict_determinism: true,
+                crowding,
+                ..StepOptions::default()
+            };
+            step(&mut grids, &mut agents, &[vec![1.0]], 0, options);
+            util::wrap(agents.get(0).unwrap().angle + PI, TAU) - PI
+        };
+        assert_eq!(turned(None), 0.0);
+        let crowding = Crowding {
+            bin_size: 4,
+            strength: 1.0,
+        };
+        assert!((turned(Some(crowding)) + 0.5).abs() < 1e-5);
+        let gathering = Crowding {
+            strength: -1.0,
+            ..crowding
+        };
+        assert!((turned(Some(gathering)) - 0.5).abs() < 1e-5);
+    }
+}
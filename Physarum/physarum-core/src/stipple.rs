@@ -0,0 +1,172 @@
+use super::contour;
+use super::palette::Rgb;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Draws every population as dots scattered by its brightness, like a stippled ink
+/// drawing, instead of as filled densities. Takes precedence over the contours.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct StippleSettings {
+    pub enabled: bool,
+    /// Distance in pixels between neighbouring dots at full brightness. Dimmer areas space
+    /// them further apart, so that the number of dots follows the brightness.
+    pub spacing: f32,
+    /// Radius in pixels of the dots at full brightness.
+    pub max_radius: f32,
+    /// Radius in pixels of the dots at the dimmest brightness still stippled.
+    pub min_radius: f32,
+}
+
+impl Default for StippleSettings {
+    fn default() -> Self {
+        StippleSettings {
+            enabled: false,
+            spacing: 3.0,
+            max_radius: 1.2,
+            min_radius: 0.4,
+        }
+    }
+}
+
+/// Darker areas are left blank rather than sprinkled with stray dots.
+const MIN_BRIGHTNESS: f32 = 0.02;
+/// Spacings are capped at this multiple of `StippleSettings::spacing`.
+const MAX_SPACING_SCALE: f32 = 8.0;
+/// Candidate dots thrown per dot that would fit at full brightness.
+const CANDIDATES_PER_DOT: f32 = 12.0;
+
+/// A dot at a position in pixel coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dot {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+}
+
+/// Scatters dots over a row-major brightness field in [0, 1] by dart throwing, rejecting
+/// candidates closer to an earlier dot than the spacing at their position. The spacing
+/// shrinks with the square root of the brightness, so the result has the even look of
+/// blue noise with a density following the brightness, and is the same for the same
+/// `seed`.
+pub fn stipple(
+    field: &[f32],
+    width: usize,
+    height: usize,
+    settings: &StippleSettings,
+    seed: u64,
+) -> Vec<Dot> {
+    let spacing = settings.spacing.max(0.5);
+    let max_spacing = spacing * MAX_SPACING_SCALE;
+    // Buckets of dots a full-brightness spacing wide, searched as far as the spacing reaches.
+    let bucket_columns = (width as f32 / spacing).ceil() as usize;
+    let bucket_rows = (height as f32 / spacing).ceil() as usize;
+    let mut buckets: Vec<Vec<Dot>> = vec![vec![]; bucket_columns * bucket_rows];
+
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let candidates = (width * height) as f32 / (spacing * spacing) * CANDIDATES_PER_DOT;
+    let mut dots = vec![];
+    for _ in 0..candidates as usize {
+        let x = rng.gen::<f32>() * width as f32;
+        let y = rng.gen::<f32>() * height as f32;
+        let brightness = field[(y as usize).min(height - 1) * width + (x as usize).min(width - 1)];
+        if brightness < MIN_BRIGHTNESS {
+            continue;
+        }
+        let distance = (spacing / brightness.sqrt()).min(max_spacing);
+        let (column, row) = ((x / spacing) as isize, (y / spacing) as isize);
+        let reach = (distance / spacing).ceil() as isize;
+        let crowded = (-reach..=reach).any(|dy| {
+            (-reach..=reach).any(|dx| {
+                let (c, r) = (column + dx, row + dy);
+                if c < 0 || r < 0 || c >= bucket_columns as isize || r >= bucket_rows as isize {
+                    return false;
+                }
+                buckets[r as usize * bucket_columns + c as usize]
+                    .iter()
+                    .any(|dot| (dot.x - x).powi(2) + (dot.y - y).powi(2) < distance * distance)
+            })
+        });
+        if crowded {
+            continue;
+        }
+        let dot = Dot {
+            x,
+            y,
+            radius: settings.min_radius + (settings.max_radius - settings.min_radius) * brightness,
+        };
+        buckets[row as usize * bucket_columns + column as usize].push(dot);
+        dots.push(dot);
+    }
+    dots
+}
+
+/// Coverage in [0, 1] of anti-aliased dots over a row-major field.
+pub(super) fn rasterize(dots: &[Dot], width: usize, height: usize) -> Vec<f32> {
+    let mut coverage = vec![0.0_f32; width * height];
+    for dot in dots {
+        let reach = dot.radius + 1.0;
+        let (x0, x1) = ((dot.x - reach).max(0.0), (dot.x + reach).min(width as f32));
+        let (y0, y1) = ((dot.y - reach).max(0.0), (dot.y + reach).min(height as f32));
+        for y in y0 as usize..y1.ceil() as usize {
+            for x in x0 as usize..x1.ceil() as usize {
+                let (dx, dy) = (x as f32 + 0.5 - dot.x, y as f32 + 0.5 - dot.y);
+                let inside = (dot.radius + 0.5 - (dx * dx + dy * dy).sqrt()).clamp(0.0, 1.0);
+                let c = &mut coverage[y * width + x];
+                *c = c.max(inside);
+            }
+        }
+    }
+    coverage
+}
+
+/// An SVG of the dots of each layer, filled in the layer's color over a black background.
+pub fn svg(layers: &[(Rgb<u8>, Vec<Dot>)], width: usize, height: usize) -> String {
+    let mut svg = contour::svg_open(width, height);
+    for (color, dots) in layers {
+        let [r, g, b] = color.0;
+        let _ = writeln!(svg, "<g fill=\"#{:02x}{:02x}{:02x}\">", r, g, b);
+        for dot in dots {
+            let _ = writeln!(
+                svg,
+                "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\"/>",
+                dot.x, dot.y, dot.radius
+            );
+        }
+        svg.push_str("</g>\n");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brighter_halves_get_more_dots() {
+        let (width, height) = (64, 32);
+        let field: Vec<f32> = (0..width * height)
+            .map(|i| if i % width < 32 { 0.2 } else { 1.0 })
+            .collect();
+        let dots = stipple(&field, width, height, &StippleSettings::default(), 1);
+        let bright = dots.iter().filter(|dot| dot.x >= 32.0).count();
+        assert!(bright > 2 * (dots.len() - bright), "{:?}", dots.len());
+        for (i, a) in dots.iter().enumerate() {
+            for b in &dots[i + 1..] {
+                assert!((a.x - b.x).hypot(a.y - b.y) >= 3.0);
+            }
+        }
+        assert_eq!(
+            dots,
+            stipple(&field, width, height, &StippleSettings::default(), 1)
+        );
+    }
+
+    #[test]
+    fn black_fields_get_no_dots() {
+        let dots = stipple(&[0.0; 256], 16, 16, &StippleSettings::default(), 0);
+        assert!(dots.is_empty());
+    }
+}
@@ -0,0 +1,705 @@
+use super::agents::Agents;
+use super::annealing::{self, Annealing};
+use super::blur::Blur;
+use super::error::ConfigError;
+use super::grid;
+use super::math::Math;
+use super::palette::Palette;
+use super::par::*;
+use super::particle::Particle;
+use super::physarum_model::PhysarumModel;
+use super::population_config::PopulationConfig;
+use super::state::{ModelState, STATE_VERSION};
+use super::steering::{self, SteeringRule};
+use super::step;
+use rand::distributions::Uniform;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Rows and migrating agents sent to a neighbouring tile.
+#[derive(Serialize, Deserialize, Default)]
+pub struct HaloMessage {
+    rows: Vec<Vec<f32>>,
+    agents: Vec<Particle>,
+}
+
+/// Connection of a tile to the tiles directly above and below it in the ring of tiles.
+pub trait HaloTransport {
+    /// Sends `up` to the previous tile and `down` to the next one, returning the messages
+    /// received from the previous and the next tile, in that order.
+    fn exchange(
+        &mut self,
+        up: HaloMessage,
+        down: HaloMessage,
+    ) -> io::Result<(HaloMessage, HaloMessage)>;
+
+    /// Index of this tile in the ring and the number of tiles.
+    fn rank(&self) -> (usize, usize);
+}
+
+/// In-process transport, for running all tiles of a decomposition as threads.
+pub struct ChannelTransport {
+    rank: usize,
+    n_tiles: usize,
+    to_up: Sender<HaloMessage>,
+    to_down: Sender<HaloMessage>,
+    from_up: Receiver<HaloMessage>,
+    from_down: Receiver<HaloMessage>,
+}
+
+impl ChannelTransport {
+    pub fn ring(n_tiles: usize) -> Vec<ChannelTransport> {
+        let (down_senders, up_receivers): (Vec<_>, Vec<_>) =
+            (0..n_tiles).map(|_| channel()).unzip();
+        let (up_senders, down_receivers): (Vec<_>, Vec<_>) =
+            (0..n_tiles).map(|_| channel()).unzip();
+
+        // Tile r sends down through channel r, which tile r + 1 reads as coming from up,
+        // and sends up through channel r, which tile r - 1 reads as coming from down.
+        let mut up_receivers: Vec<Option<Receiver<_>>> =
+            up_receivers.into_iter().map(Some).collect();
+        let mut down_receivers: Vec<Option<Receiver<_>>> =
+            down_receivers.into_iter().map(Some).collect();
+        down_senders
+            .into_iter()
+            .zip(up_senders)
+            .enumerate()
+            .map(|(rank, (to_down, to_up))| ChannelTransport {
+                rank,
+                n_tiles,
+                to_up,
+                to_down,
+                from_up: up_receivers[(rank + n_tiles - 1) % n_tiles].take().unwrap(),
+                from_down: down_receivers[(rank + 1) % n_tiles].take().unwrap(),
+            })
+            .collect()
+    }
+}
+
+impl HaloTransport for ChannelTransport {
+    fn exchange(
+        &mut self,
+        up: HaloMessage,
+        down: HaloMessage,
+    ) -> io::Result<(HaloMessage, HaloMessage)> {
+        let disconnected = || io::Error::new(io::ErrorKind::BrokenPipe, "Neighbour tile is gone");
+        self.to_up.send(up).map_err(|_| disconnected())?;
+        self.to_down.send(down).map_err(|_| disconnected())?;
+        let from_up = self.from_up.recv().map_err(|_| disconnected())?;
+        let from_down = self.from_down.recv().map_err(|_| disconnected())?;
+        Ok((from_up, from_down))
+    }
+
+    fn rank(&self) -> (usize, usize) {
+        (self.rank, self.n_tiles)
+    }
+}
+
+/// Transport between processes or machines. Every tile listens on its own address, connects
+/// to the next tile and accepts the connection of the previous one.
+pub struct TcpTransport {
+    rank: usize,
+    n_tiles: usize,
+    up: TcpStream,
+    down: TcpStream,
+}
+
+impl TcpTransport {
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(120);
+    const MAX_MESSAGE_LEN: u64 = 1 << 34;
+
+    pub fn connect(rank: usize, peers: &[SocketAddr]) -> io::Result<Self> {
+        let n_tiles = peers.len();
+        let listener = TcpListener::bind(peers[rank])?;
+        let down_addr = peers[(rank + 1) % n_tiles];
+
+        // The neighbour might not be listening yet, keep retrying for a while.
+        let start = Instant::now();
+        let down = loop {
+            match TcpStream::connect(down_addr) {
+                Ok(stream) => break stream,
+                Err(err) if start.elapsed() > Self::CONNECT_TIMEOUT => return Err(err),
+                Err(_) => std::thread::sleep(Duration::from_millis(200)),
+            }
+        };
+        let (up, _) = listener.accept()?;
+        up.set_nodelay(true)?;
+        down.set_nodelay(true)?;
+        Ok(TcpTransport {
+            rank,
+            n_tiles,
+            up,
+            down,
+        })
+    }
+
+    fn send(mut stream: TcpStream, message: &HaloMessage) -> io::Result<()> {
+        let bytes = bincode::serialize(message).map_err(io::Error::other)?;
+        stream.write_all(&(bytes.len() as u64).to_le_bytes())?;
+        stream.write_all(&bytes)?;
+        stream.flush()
+    }
+
+    fn receive(stream: &mut TcpStream) -> io::Result<HaloMessage> {
+        let mut len = [0; 8];
+        stream.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len);
+        if len > Self::MAX_MESSAGE_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Halo message too large",
+            ));
+        }
+        let mut bytes = vec![0; len as usize];
+        stream.read_exact(&mut bytes)?;
+        bincode::deserialize(&bytes).map_err(io::Error::other)
+    }
+}
+
+impl HaloTransport for TcpTransport {
+    fn exchange(
+        &mut self,
+        up: HaloMessage,
+        down: HaloMessage,
+    ) -> io::Result<(HaloMessage, HaloMessage)> {
+        let (up_stream, down_stream) = (self.up.try_clone()?, self.down.try_clone()?);
+        // Write on separate threads so two tiles sending large halos to each other can't
+        // block on full socket buffers.
+        std::thread::scope(|scope| {
+            let up_writer = scope.spawn(|| Self::send(up_stream, &up));
+            let down_writer = scope.spawn(|| Self::send(down_stream, &down));
+            let from_up = Self::receive(&mut self.up);
+            let from_down = Self::receive(&mut self.down);
+            up_writer.join().unwrap()?;
+            down_writer.join().unwrap()?;
+            Ok((from_up?, from_down?))
+        })
+    }
+
+    fn rank(&self) -> (usize, usize) {
+        (self.rank, self.n_tiles)
+    }
+}
+
+/// One horizontal strip of a simulation decomposed over several tiles.
+///
+/// Each tile owns `rows` rows of the global grid and keeps `halo` ghost rows above and below,
+/// refreshed from the neighbouring tiles, so sensing and diffusion never need remote reads.
+/// Agents leaving the strip are handed over to the neighbour that owns their new position.
+pub struct TiledModel<T: HaloTransport> {
+    width: usize,
+    height: usize,
+    row_start: usize,
+    rows: usize,
+    halo: usize,
+    configs: Vec<PopulationConfig>,
+    attraction_table: Vec<Vec<f32>>,
+    steering: Vec<Arc<dyn SteeringRule>>,
+    deposition_table: Option<Vec<Vec<f32>>>,
+    diffusity: usize,
+    time_scale: f32,
+    annealing: Option<Annealing>,
+    data: Vec<Vec<f32>>,
+    buf: Vec<Vec<f32>>,
+    scratch: Vec<f32>,
+    blur: Blur,
+    agents: Agents,
+    transport: T,
+    iteration: u64,
+    strict_determinism: bool,
+    fast_math: bool,
+}
+
+impl<T: HaloTransport> TiledModel<T> {
+    /// Every tile must be created with the same dimensions, configs, table and seed. Fails
+    /// when the tiles don't split the grid into bands as tall as their halos.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        width: usize,
+        height: usize,
+        n_particles: usize,
+        configs: Vec<PopulationConfig>,
+        attraction_table: Vec<Vec<f32>>,
+        diffusity: usize,
+        seed: u64,
+        transport: T,
+    ) -> Result<Self, ConfigError> {
+        if !width.is_power_of_two() || !height.is_power_of_two() {
+            return Err(ConfigError::InvalidDimensions { width, height });
+        }
+        if configs.is_empty() {
+            return Err(ConfigError::NoPopulations);
+        }
+        let (rank, n_tiles) = transport.rank();
+        if n_tiles == 0 || !height.is_multiple_of(n_tiles) {
+            return Err(ConfigError::TileCount {
+                tiles: n_tiles,
+                height,
+            });
+        }
+        let rows = height / n_tiles;
+        let row_start = rank * rows;
+
+        let boxes = Blur::boxes_for_gaussian::<2>(diffusity as f32);
+        let max_sensor_distance = configs
+            .iter()
+            .map(|config| config.sensor_distance)
+            .fold(0.0_f32, f32::max);
+        let halo = (max_sensor_distance.ceil() as usize + 2).max(boxes[0] + boxes[1] + 1);
+        if halo > rows {
+            return Err(ConfigError::TileHalo { rows, halo });
+        }
+
+        let mut rng = SmallRng::seed_from_u64(seed ^ (rank as u64).wrapping_mul(0x9E37_79B9));
+        let padded = (rows + 2 * halo) * width;
+        let range = Uniform::from(0.0..1.0);
+        let data = configs
+            .iter()
+            .map(|_| (&mut rng).sample_iter(range).take(padded).collect())
+            .collect();
+
+        let n_populations = configs.len();
+        let particles_per_grid = (n_particles as f64 / n_populations as f64).ceil() as usize;
+        let per_tile = (particles_per_grid as f64 / n_tiles as f64).ceil() as usize;
+        let agents = (0..per_tile * n_populations)
+            .map(|i| {
+                let mut agent = Particle::new(width, rows, i / per_tile, &mut rng);
+                agent.y += row_start as f32;
+                agent
+            })
+            .collect();
+
+        Ok(TiledModel {
+            width,
+            height,
+            row_start,
+            rows,
+            halo,
+            attraction_table,
+            steering: vec![],
+            deposition_table: None,
+            diffusity,
+            time_scale: 1.0,
+            annealing: None,
+            data,
+            buf: vec![vec![0.0; padded]; n_populations],
+            scratch: vec![0.0; padded],
+            blur: Blur::new(width),
+            agents,
+            transport,
+            iteration: 0,
+            strict_determinism: false,
+            fast_math: false,
+            configs,
+        })
+    }
+
+    /// See `step::step`. Every tile has to use the same setting.
+    pub fn set_strict_determinism(&mut self, strict_determinism: bool) {
+        self.strict_determinism = strict_determinism;
+    }
+
+    /// See `PhysarumModel::set_steering`. Every tile has to use the same rules.
+    pub fn set_steering(&mut self, steering: Vec<String>) -> Result<(), ConfigError> {
+        self.steering = steering::resolve(&steering)?;
+        Ok(())
+    }
+
+    /// See `StepOptions::fast_math`. Every tile has to use the same setting.
+    pub fn set_fast_math(&mut self, fast_math: bool) {
+        self.fast_math = fast_math;
+    }
+
+    /// See `PhysarumModel::set_time_scale`. Every tile has to use the same scale.
+    pub fn set_time_scale(&mut self, time_scale: f32) -> Result<(), ConfigError> {
+        step::check_time_scale(time_scale)?;
+        self.time_scale = time_scale;
+        Ok(())
+    }
+
+    /// See `PhysarumModel::set_annealing`. Every tile has to use the same schedule.
+    pub fn set_annealing(&mut self, annealing: Option<Annealing>) -> Result<(), ConfigError> {
+        if let Some(annealing) = &annealing {
+            annealing.check()?;
+        }
+        self.annealing = annealing;
+        Ok(())
+    }
+
+    /// See `PhysarumModel::set_deposition_table`. Every tile has to use the same table.
+    pub fn set_deposition_table(
+        &mut self,
+        table: Option<Vec<Vec<f32>>>,
+    ) -> Result<(), ConfigError> {
+        if let Some(table) = &table {
+            step::check_deposition_table(table, self.configs.len())?;
+        }
+        self.deposition_table = table;
+        Ok(())
+    }
+
+    fn strip(&self) -> Strip {
+        Strip {
+            width: self.width,
+            height: self.height,
+            row_start: self.row_start,
+            halo: self.halo,
+        }
+    }
+
+    fn exchange_halos(&mut self) -> io::Result<()> {
+        let (w, g, h) = (self.width, self.halo, self.rows);
+        let top = self
+            .data
+            .iter()
+            .map(|data| data[g * w..2 * g * w].to_vec())
+            .collect();
+        let bottom = self
+            .data
+            .iter()
+            .map(|data| data[h * w..(h + g) * w].to_vec())
+            .collect();
+        let (from_up, from_down) = self.transport.exchange(
+            HaloMessage {
+                rows: top,
+                agents: vec![],
+            },
+            HaloMessage {
+                rows: bottom,
+                agents: vec![],
+            },
+        )?;
+
+        let expected = |message: &HaloMessage| {
+            message.rows.len() == self.data.len()
+                && message.rows.iter().all(|rows| rows.len() == g * w)
+        };
+        if !expected(&from_up) || !expected(&from_down) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Neighbour sent a halo of the wrong size",
+            ));
+        }
+        for (data, (upper, lower)) in self
+            .data
+            .iter_mut()
+            .zip(from_up.rows.iter().zip(from_down.rows.iter()))
+        {
+            data[..g * w].copy_from_slice(upper);
+            data[(h + g) * w..].copy_from_slice(lower);
+        }
+        Ok(())
+    }
+
+    fn migrate_agents(&mut self) -> io::Result<()> {
+        let (height, row_start, rows) = (self.height, self.row_start, self.rows);
+        let offset = |agent: &Particle| {
+            let j = (agent.y + height as f32) as usize & (height - 1);
+            (j + height - row_start) % height
+        };
+        let (mut up, mut down) = (vec![], vec![]);
+        let mut staying = Agents::with_capacity(self.agents.len());
+        for agent in self.agents.iter() {
+            let d = offset(&agent);
+            if d < rows {
+                staying.push(agent);
+            } else if d < rows + (height - rows) / 2 {
+                down.push(agent);
+            } else {
+                up.push(agent);
+            }
+        }
+        self.agents = staying;
+
+        let (from_up, from_down) = self.transport.exchange(
+            HaloMessage {
+                rows: vec![],
+                agents: up,
+            },
+            HaloMessage {
+                rows: vec![],
+                agents: down,
+            },
+        )?;
+        let n_populations = self.configs.len();
+        self.agents.extend(
+            from_up
+                .agents
+                .into_iter()
+                .chain(from_down.agents)
+                .filter(|agent| agent.id < n_populations),
+        );
+        Ok(())
+    }
+
+    fn diffuse(&mut self, configs: &[PopulationConfig]) {
+        let (w, padded_rows) = (self.width, self.rows + 2 * self.halo);
+        let boxes = Blur::boxes_for_gaussian::<2>(self.diffusity as f32);
+        for (data, config) in self.data.iter_mut().zip(configs) {
+            for (pass, &radius) in boxes.iter().enumerate() {
+                let decay = if pass == 0 { 1.0 } else { config.decay_factor };
+                self.blur.box_blur_h(data, &mut self.scratch, w, radius);
+                box_blur_v_open(&self.scratch, data, w, padded_rows, radius, decay);
+            }
+        }
+    }
+
+    pub fn step(&mut self) -> io::Result<()> {
+        self.exchange_halos()?;
+
+        for (i, buf) in self.buf.iter_mut().enumerate() {
+            buf.fill(0.0);
+            for (j, other) in self.data.iter().enumerate() {
+                let multiplier = self.attraction_table[i][j];
+                buf.iter_mut()
+                    .zip(other)
+                    .for_each(|(to, from)| *to += from * multiplier);
+            }
+        }
+
+        let (width, height) = (self.width, self.height);
+        let math = Math::new(self.strict_determinism);
+        let configs: Vec<_> = self
+            .configs
+            .iter()
+            .map(|config| config.scaled(self.time_scale, math))
+            .collect();
+        let motion = if self.fast_math {
+            math.fast_trig()
+        } else {
+            math
+        };
+        let temperature = self
+            .annealing
+            .map_or(0.0, |annealing| annealing.temperature(self.iteration));
+        let (strip, bufs, steering) = (self.strip(), &self.buf, &self.steering);
+        let iteration = self.iteration;
+        self.agents.par_for_each(|agent| {
+            let PopulationConfig {
+                sensor_distance,
+                sensor_angle,
+                rotation_angle,
+                step_distance,
+                bias_angle,
+                bias_strength,
+                ..
+            } = configs[agent.id];
+            let buf = &bufs[agent.id];
+            let sense = |angle: f32| {
+                let x = agent.x + (motion.cos)(angle) * sensor_distance;
+                let y = agent.y + (motion.sin)(angle) * sensor_distance;
+                buf[strip.index(x, y)]
+            };
+            let trail_c = sense(agent.angle);
+            let trail_l = sense(agent.angle - sensor_angle);
+            let trail_r = sense(agent.angle + sensor_angle);
+
+            let mut rng = step::agent_rng(agent, iteration);
+            let direction = step::turn(steering, agent, trail_l, trail_c, trail_r, &mut rng)
+                + annealing::jitter(agent, temperature);
+            agent.steer_towards(bias_angle, bias_strength, motion);
+            agent.rotate_and_move(
+                direction,
+                rotation_angle,
+                step_distance,
+                width,
+                height,
+                motion,
+            );
+        });
+
+        self.migrate_agents()?;
+        let strip = self.strip();
+        if configs.iter().any(|config| config.erosion != 0.0) {
+            for agent in self.agents.iter() {
+                let idx = strip.index(agent.x, agent.y);
+                self.data[agent.id][idx] *= 1.0 - configs[agent.id].erosion;
+            }
+        }
+        let scales: Vec<_> = self
+            .configs
+            .iter()
+            .map(|config| config.deposit_scale(self.time_scale, math))
+            .collect();
+        for agent in self.agents.iter() {
+            let idx = strip.index(agent.x, agent.y);
+            match &self.deposition_table {
+                None => self.data[agent.id][idx] += configs[agent.id].deposition_amount,
+                Some(table) => {
+                    let row = table[agent.id].iter().zip(&scales);
+                    for (data, (&amount, scale)) in self.data.iter_mut().zip(row) {
+                        if amount != 0.0 {
+                            data[idx] = (data[idx] + amount * scale).max(0.0);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.exchange_halos()?;
+        self.diffuse(&configs);
+        self.iteration += 1;
+        Ok(())
+    }
+
+    /// Collects the strips of all tiles on the first tile, returning the full grids there.
+    pub fn gather(&mut self) -> io::Result<Option<Vec<Vec<f32>>>> {
+        let (w, g, h) = (self.width, self.halo, self.rows);
+        let (rank, n_tiles) = self.transport.rank();
+        let own: Vec<Vec<f32>> = self
+            .data
+            .iter()
+            .map(|data| data[g * w..(g + h) * w].to_vec())
+            .collect();
+
+        // Strips travel up the ring one hop per round, so the first tile sees them in order.
+        let mut strips = vec![own.clone()];
+        let mut forward = own;
+        for _ in 1..n_tiles {
+            let (_, from_down) = self.transport.exchange(
+                HaloMessage {
+                    rows: forward,
+                    agents: vec![],
+                },
+                HaloMessage::default(),
+            )?;
+            forward = from_down.rows;
+            if rank == 0 {
+                strips.push(forward.clone());
+            }
+        }
+        if rank != 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            (0..self.data.len())
+                .map(|population| {
+                    strips
+                        .iter()
+                        .flat_map(|strip| strip[population].iter().copied())
+                        .collect()
+                })
+                .collect(),
+        ))
+    }
+
+    /// Builds a plain model from gathered grids, e.g. to render the assembled canvas.
+    pub fn assemble(
+        &self,
+        grids: Vec<Vec<f32>>,
+        palette_name: &str,
+    ) -> Result<PhysarumModel, ConfigError> {
+        let palette = Palette::by_name(palette_name).ok_or(ConfigError::UnknownPalette)?;
+        PhysarumModel::from_state(ModelState {
+            version: STATE_VERSION,
+            width: self.width,
+            height: self.height,
+            configs: self.configs.clone(),
+            grids,
+            agents: vec![],
+            attraction_table: self.attraction_table.clone(),
+            diffusity: self.diffusity,
+            iteration: self.iteration,
+            palette: palette.colors.map(|color| color.0),
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Strip {
+    width: usize,
+    height: usize,
+    row_start: usize,
+    halo: usize,
+}
+
+impl Strip {
+    // Index into the padded strip of a global position, which must lie within the halo.
+    fn index(self, x: f32, y: f32) -> usize {
+        let i = grid::floor(x) as usize & (self.width - 1);
+        let j = grid::floor(y) as usize & (self.height - 1);
+        let local_row = (j + self.height + self.halo - self.row_start) % self.height;
+        local_row * self.width + i
+    }
+}
+
+// Vertical box blur without wrap-around. Rows closer than `radius` to the strip's edges are
+// left unblurred; they are ghost rows which get replaced by the next halo exchange.
+fn box_blur_v_open(
+    src: &[f32],
+    dst: &mut [f32],
+    width: usize,
+    height: usize,
+    radius: usize,
+    decay: f32,
+) {
+    let weight = decay / (2 * radius + 1) as f32;
+    dst.copy_from_slice(src);
+    dst.par_chunks_exact_mut(width)
+        .enumerate()
+        .skip(radius)
+        .take(height.saturating_sub(2 * radius))
+        .for_each(|(i, dst_row)| {
+            dst_row.fill(0.0);
+            for row in src[(i - radius) * width..(i + radius + 1) * width].chunks_exact(width) {
+                dst_row
+                    .iter_mut()
+                    .zip(row)
+                    .for_each(|(dst, src)| *dst += src);
+            }
+            dst_row.iter_mut().for_each(|dst| *dst *= weight);
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_must_split_the_grid_into_bands_taller_than_their_halos() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let configs = vec![PopulationConfig {
+            sensor_distance: 4.0,
+            ..PopulationConfig::new(&mut rng)
+        }];
+        let new = |width, height, n_tiles: usize| {
+            let transport = ChannelTransport::ring(n_tiles).remove(0);
+            TiledModel::new(
+                width,
+                height,
+                64,
+                configs.clone(),
+                vec![vec![1.0]],
+                1,
+                0,
+                transport,
+            )
+            .map(|tile| tile.rows)
+        };
+        assert_eq!(new(64, 64, 2), Ok(32));
+        assert_eq!(
+            new(64, 48, 2),
+            Err(ConfigError::InvalidDimensions {
+                width: 64,
+                height: 48
+            })
+        );
+        assert_eq!(
+            new(64, 64, 3),
+            Err(ConfigError::TileCount {
+                tiles: 3,
+                height: 64
+            })
+        );
+        assert!(matches!(
+            new(64, 64, 16),
+            Err(ConfigError::TileHalo { rows: 4, .. })
+        ));
+    }
+}
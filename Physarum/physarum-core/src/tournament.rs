@@ -0,0 +1,231 @@
+//! Ranking configs by picking the better of two renders at a time, for exploring by taste
+//! rather than by numbers. Every pick moves the pair's Elo ratings, and after every round
+//! the weakest configs make way for variations of the strongest.
+
+use super::config::SimulationConfig;
+use super::population_config::PopulationConfig;
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// The rating every entrant starts from.
+pub const INITIAL_RATING: f32 = 1500.0;
+/// How far a single pick can move a rating.
+const K_FACTOR: f32 = 32.0;
+/// Share of the field replaced with variations after every round.
+const CULLED: f32 = 0.25;
+/// How far variations move the parameters, as a fraction of their ranges.
+const MUTATION: f32 = 0.1;
+
+/// A config competing in a tournament.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Entrant {
+    /// Unique within its tournament, never reused after the entrant is culled.
+    pub id: usize,
+    /// The entrant this one is a variation of.
+    pub parent: Option<usize>,
+    /// With its seed and populations filled in, so it renders the same every time.
+    pub config: SimulationConfig,
+    pub rating: f32,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+impl Entrant {
+    pub fn matches(&self) -> u32 {
+        self.wins + self.losses
+    }
+}
+
+pub struct Tournament {
+    entrants: Vec<Entrant>,
+    next_id: usize,
+    /// Picks made since the field was last refined.
+    picks: usize,
+    /// Rounds of refinement so far.
+    pub generation: usize,
+    /// The ids of the last pair, not to be shown again right away.
+    last_pair: Option<(usize, usize)>,
+    rng: SmallRng,
+}
+
+impl Tournament {
+    /// A field of `size` variations of `base`, each with a random seed and random
+    /// populations.
+    pub fn new(base: &SimulationConfig, size: usize, seed: u64) -> Self {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let entrants = (0..size.max(2))
+            .map(|id| {
+                let mut config = base.clone();
+                config.seed = Some(rng.gen());
+                config.populations = (0..config.n_populations)
+                    .map(|_| PopulationConfig::new(&mut rng))
+                    .collect();
+                config.attraction_table = None;
+                Entrant {
+                    id,
+                    parent: None,
+                    config,
+                    rating: INITIAL_RATING,
+                    wins: 0,
+                    losses: 0,
+                }
+            })
+            .collect::<Vec<_>>();
+        Tournament {
+            next_id: entrants.len(),
+            entrants,
+            picks: 0,
+            generation: 0,
+            last_pair: None,
+            rng,
+        }
+    }
+
+    pub fn entrant(&self, id: usize) -> Option<&Entrant> {
+        self.entrants.iter().find(|entrant| entrant.id == id)
+    }
+
+    /// The entrants, best first.
+    pub fn standings(&self) -> Vec<&Entrant> {
+        let mut standings: Vec<_> = self.entrants.iter().collect();
+        standings.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+        standings
+    }
+
+    /// The ids of the next two entrants to compare: one of those shown least so far,
+    /// against the one rated closest to it, which tells the most about both.
+    pub fn pair(&mut self) -> (usize, usize) {
+        let fewest = self
+            .entrants
+            .iter()
+            .map(Entrant::matches)
+            .min()
+            .unwrap_or(0);
+        let candidates: Vec<&Entrant> = self
+            .entrants
+            .iter()
+            .filter(|entrant| entrant.matches() == fewest)
+            .collect();
+        let first = candidates.choose(&mut self.rng).unwrap();
+        let last_pair = self.last_pair;
+        let repeats = |other: &Entrant| {
+            last_pair.is_some_and(|(a, b)| {
+                (a, b) == (first.id, other.id) || (b, a) == (first.id, other.id)
+            })
+        };
+        let second = self
+            .entrants
+            .iter()
+            .filter(|other| other.id != first.id && !repeats(other))
+            .min_by(|a, b| {
+                let gap = |entrant: &Entrant| (entrant.rating - first.rating).abs();
+                gap(a).total_cmp(&gap(b))
+            })
+            .or_else(|| self.entrants.iter().find(|other| other.id != first.id))
+            .unwrap();
+        let pair = (first.id, second.id);
+        self.last_pair = Some(pair);
+        pair
+    }
+
+    /// Records that `winner` looked better than `loser`, and refines the field once every
+    /// entrant had a pick's worth of matches. Returns whether it was refined.
+    pub fn pick(&mut self, winner: usize, loser: usize) -> bool {
+        let rating = |id| {
+            self.entrant(id)
+                .map_or(INITIAL_RATING, |entrant| entrant.rating)
+        };
+        let (gain, _) = elo(rating(winner), rating(loser));
+        for entrant in &mut self.entrants {
+            if entrant.id == winner {
+                entrant.rating += gain;
+                entrant.wins += 1;
+            } else if entrant.id == loser {
+                entrant.rating -= gain;
+                entrant.losses += 1;
+            }
+        }
+        self.picks += 1;
+        if self.picks < self.entrants.len() {
+            return false;
+        }
+        self.refine();
+        true
+    }
+
+    /// Replaces the lowest rated entrants with variations of the highest rated ones,
+    /// starting from their parent's rating.
+    fn refine(&mut self) {
+        self.picks = 0;
+        self.generation += 1;
+        let culled = ((self.entrants.len() as f32 * CULLED) as usize).max(1);
+        self.entrants.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+        self.entrants.truncate(self.entrants.len() - culled);
+        for k in 0..culled {
+            let parent = &self.entrants[k % self.entrants.len()];
+            let mut config = parent.config.clone();
+            for population in &mut config.populations {
+                *population = population.mutated(MUTATION, &mut self.rng);
+            }
+            let child = Entrant {
+                id: self.next_id,
+                parent: Some(parent.id),
+                config,
+                rating: parent.rating,
+                wins: 0,
+                losses: 0,
+            };
+            self.next_id += 1;
+            self.entrants.push(child);
+        }
+    }
+}
+
+/// How many points the winner of a match between ratings `winner` and `loser` takes from
+/// the loser, and the chance the winner was given beforehand.
+pub fn elo(winner: f32, loser: f32) -> (f32, f32) {
+    let expected = 1.0 / (1.0 + 10f32.powf((loser - winner) / 400.0));
+    (K_FACTOR * (1.0 - expected), expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upsets_move_ratings_further() {
+        let (even, chance) = elo(1500.0, 1500.0);
+        assert_eq!(chance, 0.5);
+        assert_eq!(even, K_FACTOR / 2.0);
+        let (upset, _) = elo(1400.0, 1600.0);
+        let (expected, _) = elo(1600.0, 1400.0);
+        assert!(upset > even && even > expected);
+    }
+
+    #[test]
+    fn preferred_configs_survive_and_get_refined() {
+        let base = SimulationConfig {
+            n_populations: 2,
+            ..SimulationConfig::default()
+        };
+        let mut tournament = Tournament::new(&base, 8, 1);
+        // Lower ids always win, until the field is refined once.
+        let mut refined = false;
+        while !refined {
+            let (a, b) = tournament.pair();
+            assert_ne!(a, b);
+            refined = tournament.pick(a.min(b), a.max(b));
+        }
+        let standings = tournament.standings();
+        assert_eq!(standings.len(), 8);
+        assert!(standings.windows(2).all(|w| w[0].rating >= w[1].rating));
+        for id in [8, 9] {
+            let child = tournament.entrant(id).unwrap();
+            let parent = tournament.entrant(child.parent.unwrap()).unwrap();
+            assert!(parent.rating > INITIAL_RATING && parent.losses == 0);
+            assert_eq!(child.rating, parent.rating);
+            assert_eq!(child.config.seed, parent.config.seed);
+            assert_ne!(child.config.populations, parent.config.populations);
+        }
+    }
+}
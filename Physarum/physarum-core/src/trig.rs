@@ -0,0 +1,65 @@
+//! Approximations of `cos` and `sin` from a parabola through the quadrants refined by one
+//! correction term, for `StepOptions::fast_math`. Both are off by at most `MAX_ERROR` for
+//! any angle and take a handful of multiplications without branches, which vectorizes.
+
+/// Bound on the absolute error of `cos` and `sin`.
+pub(crate) const MAX_ERROR: f32 = 1.1e-3;
+
+#[inline(always)]
+fn abs(x: f32) -> f32 {
+    f32::from_bits(x.to_bits() & 0x7FFF_FFFF)
+}
+
+#[inline(always)]
+fn floor(x: f32) -> f32 {
+    let mut x_trunc = (x as i32) as f32;
+    x_trunc -= (x < x_trunc) as i32 as f32;
+    x_trunc
+}
+
+pub(crate) fn cos(mut x: f32) -> f32 {
+    const ALPHA: f32 = 0.5 * core::f32::consts::FRAC_1_PI;
+    x *= ALPHA;
+    x -= 0.25_f32 + floor(x + 0.25_f32);
+    x *= 16.0_f32 * (abs(x) - 0.5_f32);
+    x += 0.225_f32 * x * (abs(x) - 1.0_f32);
+    x
+}
+
+pub(crate) fn sin(x: f32) -> f32 {
+    cos(x - core::f32::consts::FRAC_PI_2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn angles() -> impl Iterator<Item = f32> {
+        (-100_000..=100_000).map(|i| i as f32 * 1e-4 * core::f32::consts::TAU)
+    }
+
+    #[test]
+    fn cos_and_sin_stay_within_the_bound() {
+        let cos_error = angles()
+            .map(|x| (cos(x) - x.cos()).abs())
+            .fold(0.0, f32::max);
+        let sin_error = angles()
+            .map(|x| (sin(x) - x.sin()).abs())
+            .fold(0.0, f32::max);
+        assert!(cos_error < MAX_ERROR, "cos is off by {}", cos_error);
+        assert!(sin_error < MAX_ERROR, "sin is off by {}", sin_error);
+    }
+
+    #[test]
+    fn turns_stay_on_the_unit_circle() {
+        for x in angles() {
+            let radius = (cos(x) * cos(x) + sin(x) * sin(x)).sqrt();
+            assert!(
+                (radius - 1.0).abs() < 2.0 * MAX_ERROR,
+                "{} at {}",
+                radius,
+                x
+            );
+        }
+    }
+}
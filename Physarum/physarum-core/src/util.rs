@@ -0,0 +1,75 @@
+use super::palette::Rgb;
+
+/// Wraps `x` into [0, max] by any number of periods. Values already in range, by far the
+/// most common case, are returned untouched.
+#[inline(always)]
+pub fn wrap(x: f32, max: f32) -> f32 {
+    if (0.0..=max).contains(&x) {
+        return x;
+    }
+    let r = x % max;
+    if r < 0.0 {
+        r + max
+    } else {
+        r
+    }
+}
+
+pub fn quantile(data: &[f32], fraction: f32) -> f32 {
+    // Fractions outside [0, 1] saturate to the smallest or largest value.
+    let index = ((data.len() as f32 * fraction) as usize).min(data.len() - 1);
+    let mut sorted = data.to_vec();
+    sorted.as_mut_slice().select_nth_unstable_by(index, |a, b| {
+        a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal)
+    });
+    sorted[index]
+}
+
+pub const fn hex_color(c: usize) -> Rgb<u8> {
+    let r = (c >> 16) & 0xff;
+    let g = (c >> 8) & 0xff;
+    let b = c & 0xff;
+    Rgb::<u8>([r as u8, g as u8, b as u8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn wrap_one_period() {
+        assert_eq!(wrap(3.0, 8.0), 3.0);
+        assert_eq!(wrap(8.0, 8.0), 8.0);
+        assert_eq!(wrap(9.5, 8.0), 1.5);
+        assert_eq!(wrap(-0.5, 8.0), 7.5);
+    }
+
+    #[test]
+    fn wrap_many_periods() {
+        assert_eq!(wrap(41.0, 8.0), 1.0);
+        assert_eq!(wrap(-41.0, 8.0), 7.0);
+    }
+
+    proptest! {
+        #[test]
+        fn wrap_in_range(x in -1e9_f32..1e9, max in 0.5_f32..1e4) {
+            let wrapped = wrap(x, max);
+            prop_assert!((0.0..=max).contains(&wrapped), "{} wrapped to {}", x, wrapped);
+        }
+
+        #[test]
+        fn wrap_keeps_values_in_range(max in 0.5_f32..1e4, t in 0.0_f32..=1.0) {
+            let x = max * t;
+            prop_assert_eq!(wrap(x, max), x);
+        }
+
+        #[test]
+        fn wrap_is_periodic(x in -1e4_f32..1e4, k in -100_i32..100) {
+            // Whole numbers and a power of two period keep the arithmetic exact.
+            let (x, max) = (x.round(), 64.0);
+            let (a, b) = (wrap(x, max), wrap(x + max * k as f32, max));
+            prop_assert!(a == b || (a - b).abs() == max, "{} and {}", a, b);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Renders tiny seeded simulations and compares them against the PNGs in `tests/golden`, so
+//! changes to blurring, combining or steering can't silently alter the output. Run with
+//! `UPDATE_GOLDEN=1` to rewrite the images after an intended change. The images hold the
+//! draws of `rand_distr`, which the stand-in without it doesn't reproduce.
+#![cfg(all(feature = "image-export", feature = "rand_distr"))]
+
+use physarum_core::image::{self, DynamicImage, GenericImageView};
+use physarum_core::physarum_model::PhysarumModel;
+use physarum_core::postprocess::GlowLayer;
+use physarum_core::render::{LayerMode, RenderSettings};
+use std::path::PathBuf;
+
+const SIZE: usize = 64;
+const STEPS: usize = 200;
+/// Mean difference per channel tolerated, out of 255.
+const MEAN_TOLERANCE: f64 = 0.5;
+/// Largest difference of any single channel tolerated.
+const MAX_TOLERANCE: u8 = 16;
+
+fn render(name: &str, diffusity: usize, render_settings: RenderSettings) {
+    let mut model = PhysarumModel::builder()
+        .size(SIZE, SIZE)
+        .particles(2000)
+        .populations(2)
+        .diffusity(diffusity)
+        .seed(7)
+        .strict_determinism(true)
+        .build()
+        .unwrap();
+    model.set_render_settings(render_settings).unwrap();
+    for _ in 0..STEPS {
+        model.step();
+    }
+    let mut image = DynamicImage::new_rgb8(SIZE as u32, SIZE as u32);
+    model.save_to_image(&mut image);
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        image.save(&path).unwrap();
+        return;
+    }
+    let golden = image::open(&path)
+        .unwrap_or_else(|err| panic!("Failed to open {}: {}", path.display(), err));
+    assert_eq!(golden.dimensions(), image.dimensions());
+
+    let (golden, image) = (golden.to_rgb8(), image.to_rgb8());
+    let differences: Vec<u8> = golden
+        .as_raw()
+        .iter()
+        .zip(image.as_raw())
+        .map(|(a, b)| a.abs_diff(*b))
+        .collect();
+    let mean = differences.iter().map(|&d| d as f64).sum::<f64>() / differences.len() as f64;
+    let max = differences.iter().copied().max().unwrap_or(0);
+    assert!(
+        mean <= MEAN_TOLERANCE && max <= MAX_TOLERANCE,
+        "{} differs from its golden image: mean {:.3}, max {}",
+        name,
+        mean,
+        max
+    );
+}
+
+#[test]
+fn trails() {
+    render("trails", 1, RenderSettings::default());
+}
+
+#[test]
+fn wide_diffusion() {
+    render("wide_diffusion", 3, RenderSettings::default());
+}
+
+#[test]
+fn sprites_with_bloom() {
+    let mut settings = RenderSettings::default();
+    settings.style_mut(1).mode = LayerMode::Sprites;
+    settings.postprocess.bloom_intensity = 0.5;
+    render("sprites_with_bloom", 1, settings);
+}
+
+#[test]
+fn glow_layers() {
+    let mut settings = RenderSettings::default();
+    settings.postprocess.glow = vec![
+        GlowLayer {
+            radius: 4.0,
+            weight: 0.4,
+        },
+        GlowLayer {
+            radius: 16.0,
+            weight: 0.3,
+        },
+    ];
+    render("glow_layers", 1, settings);
+}
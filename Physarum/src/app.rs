@@ -0,0 +1,859 @@
+pub mod autosave;
+pub mod capture;
+pub mod gpu;
+pub mod installation;
+pub mod schedule;
+pub mod span;
+pub mod tournament;
+pub mod ui;
+
+use crate::dmx::DmxStats;
+use crate::led::LedMatrix;
+use crate::physarum::builder::PhysarumModelBuilder;
+use crate::physarum::config::SimulationConfig;
+use crate::physarum::drift::DriftController;
+use crate::physarum::look::Look;
+use crate::physarum::palette::{Palette, PALETTE_NAMES};
+use crate::physarum::physarum_model::PhysarumModel;
+use crate::physarum::scenario::ScenarioRunner;
+use crate::physarum::sensing::Sensing;
+use crate::replay::Recorder;
+use crate::script::Script;
+use crate::seed::Seed;
+use crate::{
+    apply_settings, cli, exit_over_budget, fill_populations, get_random_configs, load_config,
+    pick_seed, save_ink_layers, warn_generated_colors,
+};
+use autosave::Autosave;
+use capture::Burst;
+use chrono::{Datelike, Timelike};
+use clap::Parser;
+use fps_ticker::Fps;
+use gpu::GpuColorizer;
+use installation::Installation;
+use nannou::{
+    image::{DynamicImage, GenericImageView},
+    prelude::*,
+    wgpu::Texture,
+    window::Fullscreen,
+};
+use nannou_egui::{self, Egui};
+use rand::prelude::*;
+use schedule::Every;
+use span::Viewport;
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+const DRIFT_LOG_INTERVAL: f32 = 60.0;
+const MAX_ZOOM: f32 = 16.0;
+const ZOOM_STEP: f32 = 1.25;
+const SPAN_PAN_STEP: f32 = 1.0 / 32.0;
+
+/// Opens the simulation window and runs until it is closed.
+pub fn run() {
+    nannou::app(model).update(update).exit(exit).run();
+}
+
+struct PhysarumSettings {
+    model: PhysarumModel,
+    config: SimulationConfig,
+    config_changed: bool,
+}
+
+impl PhysarumSettings {
+    /// Renders with the config's settings from now on, or keeps the ones before when a file
+    /// they name can't be read.
+    fn apply_render_settings(&mut self) {
+        if let Err(err) = self.model.set_render_settings(self.config.render.clone()) {
+            eprintln!("Failed to apply the render settings: {}", err);
+        }
+    }
+}
+
+pub struct Model {
+    physarum_settings: PhysarumSettings,
+    fps_counter: Fps,
+    seed: Seed,
+    /// Title last given to the main window, which shows the seed.
+    title: String,
+    egui_visible: bool,
+    steps_per_frame: usize,
+    egui: Egui,
+    main_window_id: WindowId,
+    rng: SmallRng,
+    changed: bool,
+    render: bool,
+    image: DynamicImage,
+    drift: DriftController,
+    drift_enabled: bool,
+    drift_log_timer: f32,
+    installation: Option<Installation>,
+    autosave: Option<Autosave>,
+    /// When to switch to the next built-in palette.
+    palette_every: Option<Every>,
+    script: Option<Script>,
+    scenario: Option<ScenarioRunner>,
+    /// Logs the session for `--replay`.
+    session: Option<Recorder>,
+    viewports: Vec<(WindowId, Viewport)>,
+    span_offset: (f32, f32),
+    burst: Option<Burst>,
+    burst_frames: usize,
+    led: Option<LedMatrix>,
+    dmx: Option<DmxStats>,
+    zoom: f32,
+    smooth_filtering: bool,
+    gpu: Option<GpuColorizer>,
+    gpu_frame: bool,
+    /// The step the last frame was rendered at.
+    rendered_iteration: Option<u64>,
+    /// Whether this update rendered a frame, which the recording saves.
+    frame_rendered: bool,
+}
+
+impl Model {
+    fn new(
+        physarum_settings: PhysarumSettings,
+        drift: DriftController,
+        seed: Seed,
+        rng: SmallRng,
+        egui: Egui,
+        main_window_id: WindowId,
+        image: DynamicImage,
+    ) -> Self {
+        Model {
+            physarum_settings,
+            fps_counter: Fps::with_window_len(60),
+            steps_per_frame: 1,
+            egui_visible: true,
+            changed: true,
+            render: false,
+            main_window_id,
+            drift,
+            seed,
+            title: String::new(),
+            egui,
+            rng,
+            image,
+            drift_enabled: false,
+            drift_log_timer: 0.0,
+            installation: None,
+            autosave: None,
+            palette_every: None,
+            script: None,
+            scenario: None,
+            session: None,
+            viewports: vec![],
+            span_offset: (0.0, 0.0),
+            burst: None,
+            burst_frames: 0,
+            led: None,
+            dmx: None,
+            zoom: 1.0,
+            smooth_filtering: true,
+            gpu: None,
+            gpu_frame: false,
+            rendered_iteration: None,
+            frame_rendered: true,
+        }
+    }
+
+    fn reset_rng(&mut self) {
+        self.rng = SmallRng::seed_from_u64(self.seed.value);
+    }
+
+    fn reset(&mut self) {
+        self.reset_rng();
+        // Follow changes to the number of populations made in the UI.
+        let n_populations = self.physarum_settings.config.n_populations;
+        for mode in &mut self.physarum_settings.config.sensing {
+            if let Sensing::Weights { weights } = mode {
+                weights.resize(n_populations, 0.0);
+            }
+        }
+        let sc = &self.physarum_settings.config;
+        let physarum_model =
+            match PhysarumModelBuilder::from_config(sc).build_with_rng(&mut self.rng) {
+                Ok(physarum_model) => physarum_model,
+                Err(err) => {
+                    eprintln!("Invalid config, keeping the current simulation: {}", err);
+                    return;
+                }
+            };
+        warn_generated_colors(&physarum_model);
+        self.physarum_settings.model = physarum_model;
+        if let Some(scenario) = &mut self.scenario {
+            scenario.restart();
+        }
+        fill_populations(&mut self.physarum_settings.config, &mut self.rng);
+        if let Err(err) = self
+            .physarum_settings
+            .model
+            .set_population_configs(self.physarum_settings.config.populations.clone())
+        {
+            eprintln!("Failed to apply population configs: {}", err);
+        }
+        self.physarum_settings.apply_render_settings();
+        if let Some(session) = &self.session {
+            let settings = &mut self.physarum_settings;
+            if let Err(err) = session.start(&mut settings.model, &settings.config) {
+                eprintln!("Failed to record the reset: {}", err);
+            }
+        }
+    }
+
+    // Starts over with a fresh seed and random population configs.
+    fn reseed(&mut self) {
+        self.seed = Seed::random(&mut thread_rng());
+        self.reset_rng();
+        self.physarum_settings.config.populations = get_random_configs(&mut self.rng);
+        self.drift.reset();
+        self.reset();
+    }
+}
+
+fn model(app: &App) -> Model {
+    let args = cli::Args::parse();
+    let mut config = load_config(&args);
+
+    let image_window = app
+        .new_window()
+        .size(1400, 1024)
+        .view(view)
+        .key_pressed(key_pressed)
+        .mouse_pressed(mouse_pressed)
+        .raw_event(raw_window_event)
+        .title("Physarum")
+        .build()
+        .unwrap();
+
+    let window = app.window(image_window).unwrap();
+    let recovered = if args.recover {
+        match autosave::recover(&args.autosave_dir) {
+            Ok((state, recovered_config)) => {
+                config = recovered_config;
+                Some(state)
+            }
+            Err(err) => {
+                eprintln!("Failed to recover autosave: {}", err);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    exit_over_budget(&args, &config);
+    let seed = pick_seed(&args, &config);
+    let mut rng = SmallRng::seed_from_u64(seed.value);
+    println!("{}", seed);
+
+    fill_populations(&mut config, &mut rng);
+    let built = match recovered {
+        Some(state) => PhysarumModel::from_state(state),
+        None => PhysarumModelBuilder::from_config(&config).build_with_rng(&mut rng),
+    };
+    let mut physarum_model = built.unwrap_or_else(|err| {
+        eprintln!("Invalid config: {}", err);
+        std::process::exit(1);
+    });
+    if let Err(err) = apply_settings(&mut physarum_model, &config) {
+        eprintln!("Invalid config: {}", err);
+        std::process::exit(1);
+    }
+    warn_generated_colors(&physarum_model);
+    let drift_parameters = config
+        .drift
+        .clone()
+        .unwrap_or_else(DriftController::default_parameters);
+    let drift = DriftController::new(drift_parameters, seed.value).unwrap_or_else(|err| {
+        eprintln!("Invalid config: {}", err);
+        std::process::exit(1);
+    });
+    let (width, height) = (config.width, config.height);
+
+    let physarum = PhysarumSettings {
+        model: physarum_model,
+        config,
+        config_changed: true,
+    };
+
+    let mut model = Model::new(
+        physarum,
+        drift,
+        seed,
+        rng,
+        Egui::from_window(&window),
+        image_window,
+        DynamicImage::new_rgb8(width as u32, height as u32),
+    );
+    model.burst_frames = args.burst_frames;
+    match LedMatrix::from_args(&args) {
+        Ok(led) => model.led = led,
+        Err(err) => eprintln!("Failed to open LED matrix: {}", err),
+    }
+    match DmxStats::from_args(&args) {
+        Ok(dmx) => model.dmx = dmx,
+        Err(err) => eprintln!("Failed to open DMX output: {}", err),
+    }
+    if !args.cpu_render {
+        model.gpu = Some(GpuColorizer::new(&window, width as u32, height as u32));
+    }
+    let (columns, rows) = (args.span_columns.max(1), args.span_rows.max(1));
+    if columns * rows > 1 {
+        model.viewports = span_windows(app, image_window, columns, rows, width, height);
+    }
+    model.scenario = crate::load_scenario(&args).map(ScenarioRunner::new);
+    let iteration = model.physarum_settings.model.iteration();
+    if args.autosave_minutes > 0.0 || args.autosave_every.is_some() {
+        model.autosave = Some(Autosave::new(
+            args.autosave_dir,
+            args.autosave_minutes,
+            args.autosave_every,
+            iteration,
+            args.autosave_slots,
+        ));
+    }
+    model.script = args.script.as_deref().map(|path| {
+        Script::load(path).unwrap_or_else(|err| {
+            eprintln!("Failed to load the script: {}", err);
+            std::process::exit(1);
+        })
+    });
+    model.palette_every = args
+        .palette_every
+        .and_then(|every| Every::new(every, iteration));
+    if args.installation {
+        window.set_fullscreen(true);
+        model.egui_visible = false;
+        model.reset();
+        model.installation = Some(Installation::new(args.cycle_minutes, args.archive_dir));
+    }
+    if let Some(path) = &args.record_session {
+        let settings = &mut model.physarum_settings;
+        let recorder = Recorder::create(path).and_then(|recorder| {
+            recorder
+                .start(&mut settings.model, &settings.config)
+                .map(|_| recorder)
+        });
+        match recorder {
+            Ok(recorder) => model.session = Some(recorder),
+            Err(err) => eprintln!("Failed to record the session: {}", err),
+        }
+    }
+    model
+}
+
+fn exit(_app: &App, model: Model) {
+    if let Some(session) = &model.session {
+        if let Err(err) = session.stop(model.physarum_settings.model.iteration()) {
+            eprintln!("Failed to finish the session log: {}", err);
+        }
+    }
+}
+
+// Opens one window per tile of the wall, placing each on its own monitor when available.
+fn span_windows(
+    app: &App,
+    main_window: WindowId,
+    columns: usize,
+    rows: usize,
+    width: usize,
+    height: usize,
+) -> Vec<(WindowId, Viewport)> {
+    let monitors = app.available_monitors();
+    let mut viewports = vec![(main_window, Viewport::new(0, 0, columns, rows))];
+    for k in 1..columns * rows {
+        let mut builder = app
+            .new_window()
+            .size((width / columns) as u32, (height / rows) as u32)
+            .view(view)
+            .key_pressed(key_pressed)
+            .title(format!("Physarum {}", k));
+        if let Some(monitor) = monitors.get(k) {
+            builder = builder.fullscreen_with(Some(Fullscreen::Borderless(Some(monitor.clone()))));
+        }
+        let window = builder.build().unwrap();
+        viewports.push((
+            window,
+            Viewport::new(k % columns, k / columns, columns, rows),
+        ));
+    }
+    viewports
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    model.fps_counter.tick();
+    model.changed = false;
+    if model.egui_visible {
+        ui::update_gui(model, update);
+    }
+    if model.drift_enabled {
+        update_drift(model, update.since_last.as_secs_f32());
+    }
+    update_heat_brush(app, model);
+    if model.installation.is_some() {
+        update_installation(model);
+    } else {
+        step_frame(model);
+    }
+    update_title(app, model);
+    // Paused frames render every change, running ones every `render_every_n_steps` steps.
+    let iteration = model.physarum_settings.model.iteration();
+    let every = model.physarum_settings.config.render_every_n_steps.max(1) as u64;
+    model.frame_rendered = match model.rendered_iteration {
+        Some(rendered) if rendered != iteration => rendered / every != iteration / every,
+        _ => true,
+    };
+    if model.frame_rendered {
+        update_frame(app, model);
+        model.rendered_iteration = Some(iteration);
+    }
+    update_led(model);
+    update_dmx(model);
+    update_burst(model);
+    update_autosave(model);
+    update_palette(model);
+}
+
+fn update_title(app: &App, model: &mut Model) {
+    let title = format!("Physarum - seed {}", model.seed);
+    if title != model.title {
+        if let Some(window) = app.window(model.main_window_id) {
+            window.set_title(&title);
+        }
+        model.title = title;
+    }
+}
+
+// Colorizes the frame on the GPU unless post-processing needs the CPU path. The CPU image
+// is then only rebuilt while something exports every frame.
+fn update_frame(app: &App, model: &mut Model) {
+    let physarum_model = &model.physarum_settings.model;
+    let size = (model.image.width(), model.image.height());
+    model.gpu_frame = false;
+    if let Some(window) = app.window(model.main_window_id) {
+        if model.gpu.as_ref().is_some_and(|gpu| gpu.size() != size) {
+            model.gpu = Some(GpuColorizer::new(&window, size.0, size.1));
+        }
+        if let Some(gpu) = &model.gpu {
+            let layers = physarum_model.grids.len();
+            if !physarum_model.needs_cpu_compositing() && layers <= gpu::MAX_LAYERS {
+                let fields = physarum_model.layer_fields();
+                let max_values = physarum_model.layer_max_values(&fields);
+                let colors = physarum_model.population_tints();
+                gpu.render(&window, &fields, &max_values, &colors);
+                model.gpu_frame = true;
+            }
+        }
+    }
+    if !model.gpu_frame || model.render || model.burst.is_some() || model.led.is_some() {
+        refresh_image(model);
+    }
+}
+
+fn refresh_image(model: &mut Model) {
+    model
+        .physarum_settings
+        .model
+        .save_to_image(&mut model.image);
+}
+
+fn update_burst(model: &mut Model) {
+    let Some(mut burst) = model.burst.take() else {
+        return;
+    };
+    match burst.capture(model) {
+        Ok(false) => model.burst = Some(burst),
+        Ok(true) => {}
+        Err(err) => eprintln!("Burst capture failed: {}", err),
+    }
+}
+
+fn update_led(model: &mut Model) {
+    let Some(led) = &mut model.led else {
+        return;
+    };
+    if let Err(err) = led.send(&model.image) {
+        eprintln!("LED output failed, disabling it: {}", err);
+        model.led = None;
+    }
+}
+
+fn update_dmx(model: &mut Model) {
+    let Some(dmx) = &mut model.dmx else {
+        return;
+    };
+    if let Err(err) = dmx.send(&model.physarum_settings.model) {
+        eprintln!("DMX output failed, disabling it: {}", err);
+        model.dmx = None;
+    }
+}
+
+fn update_autosave(model: &mut Model) {
+    let Some(mut autosave) = model.autosave.take() else {
+        return;
+    };
+    if autosave.due(model.physarum_settings.model.iteration()) {
+        if let Err(err) = autosave.save(model) {
+            eprintln!("Autosave failed: {}", err);
+        }
+    }
+    model.autosave = Some(autosave);
+}
+
+fn update_script(model: &mut Model) {
+    let Some(script) = &mut model.script else {
+        return;
+    };
+    let settings = &mut model.physarum_settings;
+    if let Err(err) = script.step(&mut settings.config, &mut settings.model) {
+        eprintln!("Script failed, disabling it: {}", err);
+        model.script = None;
+    }
+}
+
+fn update_scenario(model: &mut Model) {
+    let Some(scenario) = &mut model.scenario else {
+        return;
+    };
+    if scenario.finished() {
+        return;
+    }
+    let settings = &mut model.physarum_settings;
+    let iteration = settings.model.iteration();
+    match scenario.advance(&mut settings.model) {
+        Ok(Some(phase)) => println!(
+            "{} at step {}",
+            crate::headless::phase_label(scenario, phase),
+            iteration + 1
+        ),
+        Ok(None) => {}
+        Err(err) => {
+            eprintln!("Scenario failed, stopping it: {}", err);
+            model.scenario = None;
+            return;
+        }
+    }
+    // Keep the settings panel showing what the scenario changed.
+    settings.config.populations = settings
+        .model
+        .grids
+        .iter()
+        .map(|grid| grid.config)
+        .collect();
+    settings.config.attraction_table = Some(settings.model.attraction_table().to_vec());
+}
+
+/// Moves on to the built-in palette after the current one when `--palette-every` is due.
+fn update_palette(model: &mut Model) {
+    let iteration = model.physarum_settings.model.iteration();
+    if !model
+        .palette_every
+        .as_mut()
+        .is_some_and(|every| every.due(iteration))
+    {
+        return;
+    }
+    let settings = &mut model.physarum_settings;
+    let current = PALETTE_NAMES
+        .iter()
+        .position(|name| *name == settings.config.palette);
+    let name = PALETTE_NAMES[current.map_or(0, |k| (k + 1) % PALETTE_NAMES.len())];
+    settings.config.palette = name.to_string();
+    if let Some(palette) = Palette::by_name(name) {
+        settings.model.set_palette(palette);
+    }
+}
+
+// Runs the frame's steps, each after the script and scenario changed what they change at it.
+fn step_frame(model: &mut Model) {
+    for _ in 0..model.steps_per_frame {
+        update_script(model);
+        update_scenario(model);
+        model.physarum_settings.model.step();
+    }
+}
+
+fn update_installation(model: &mut Model) {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| step_frame(model)));
+    if result.is_err() {
+        eprintln!("Simulation panicked, starting a new cycle");
+        model.reseed();
+        if let Some(installation) = &mut model.installation {
+            installation.start_cycle();
+        }
+        return;
+    }
+
+    if let Some(installation) = &model.installation {
+        if installation.cycle_finished() {
+            // The displayed frame may live on the GPU only.
+            refresh_image(model);
+            let installation = model.installation.as_ref().unwrap();
+            if let Err(err) = installation.archive(model) {
+                eprintln!("Failed to archive cycle: {}", err);
+            }
+            model.reseed();
+            if let Some(installation) = &mut model.installation {
+                installation.start_cycle();
+            }
+        }
+    }
+}
+
+fn update_drift(model: &mut Model, dt: f32) {
+    let now = chrono::offset::Local::now();
+    let time_of_day = now.num_seconds_from_midnight() as f32 / 86400.0;
+    let settings = &mut model.physarum_settings;
+    model
+        .drift
+        .update(&mut settings.config.populations, dt, time_of_day);
+    if let Err(err) = settings
+        .model
+        .set_population_configs(settings.config.populations.clone())
+    {
+        eprintln!("Failed to apply drifted configs: {}", err);
+    }
+
+    model.drift_log_timer += dt;
+    if model.drift_log_timer >= DRIFT_LOG_INTERVAL {
+        model.drift_log_timer = 0.0;
+        if let Err(err) = log_drift(model) {
+            eprintln!("Failed to write drift log: {}", err);
+        }
+    }
+}
+
+// Appends the current parameters so an interesting moment of a long run can be recovered later.
+fn log_drift(model: &Model) -> std::io::Result<()> {
+    std::fs::create_dir_all("renders")?;
+    let entry = serde_json::json!({
+        "time": chrono::offset::Local::now().to_rfc3339(),
+        "seed": model.seed.value,
+        "seed_name": model.seed.name,
+        "configs": model.physarum_settings.config.populations,
+    });
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open("renders/drift_log.jsonl")?;
+    writeln!(file, "{}", entry)
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let filter = if model.smooth_filtering {
+        wgpu::FilterMode::Linear
+    } else {
+        wgpu::FilterMode::Nearest
+    };
+    let draw = app.draw().sampler(
+        wgpu::SamplerBuilder::new()
+            .min_filter(filter)
+            .mag_filter(filter)
+            .into_descriptor(),
+    );
+    draw.background().color(BLACK);
+    let cpu_texture;
+    let texture = match &model.gpu {
+        Some(gpu) if model.gpu_frame => gpu.texture(),
+        _ => {
+            cpu_texture = Texture::from_image(app, &model.image);
+            &cpu_texture
+        }
+    };
+    let size = texture.size().map(|side| side as f32);
+    let (viewport, rect) = placement(model, frame.window_id(), frame.rect(), size);
+    for (area, dst) in viewport.pieces(model.span_offset, model.zoom) {
+        draw.texture(&texture)
+            .area(area)
+            .x_y(
+                rect.left() + dst.x() * rect.w(),
+                rect.bottom() + dst.y() * rect.h(),
+            )
+            .w_h(dst.w() * rect.w(), dst.h() * rect.h());
+    }
+    draw.to_frame(app, &frame).unwrap();
+    if frame.window_id() != model.main_window_id {
+        return;
+    }
+    if model.egui_visible {
+        model.egui.draw_to_frame(&frame).unwrap();
+    }
+    if model.render && model.frame_rendered {
+        let _ = &model
+            .image
+            .save_with_format(get_path(app, &model.seed), nannou::image::ImageFormat::Png);
+    }
+}
+
+/// Which share of the wall a window shows, and where in the window it draws the `size`
+/// of the texture. A single window letterboxes the whole grid, walls stretch each tile to
+/// its window.
+fn placement(
+    model: &Model,
+    window: WindowId,
+    window_rect: Rect,
+    size: [f32; 2],
+) -> (Viewport, Rect) {
+    let viewport = model
+        .viewports
+        .iter()
+        .find(|(id, _)| *id == window)
+        .map(|(_, viewport)| *viewport);
+    match viewport {
+        Some(viewport) => (viewport, window_rect),
+        None => {
+            let [width, height] = size;
+            let scale = (window_rect.w() / width).min(window_rect.h() / height);
+            (
+                Viewport::new(0, 0, 1, 1),
+                Rect::from_w_h(width * scale, height * scale),
+            )
+        }
+    }
+}
+
+fn raw_window_event(_app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
+    model.egui.handle_raw_event(event);
+}
+
+fn key_pressed(app: &App, model: &mut Model, key: Key) {
+    if key == Key::S {
+        save_frame(app, &model.seed);
+    } else if key == Key::F {
+        model.egui_visible = !model.egui_visible;
+    } else if key == Key::D {
+        model.physarum_settings.model.print_configurations();
+    } else if key == Key::P {
+        refresh_image(model);
+        match capture::screenshot(model) {
+            Ok(path) => println!("Saved {}", path.display()),
+            Err(err) => eprintln!("Failed to save screenshot: {}", err),
+        }
+    } else if key == Key::B && model.burst.is_none() && model.burst_frames > 0 {
+        match Burst::new(&model.seed, model.burst_frames) {
+            Ok(burst) => model.burst = Some(burst),
+            Err(err) => eprintln!("Failed to start burst: {}", err),
+        }
+    } else if key == Key::Equals || key == Key::Plus || key == Key::NumpadAdd {
+        model.zoom = (model.zoom * ZOOM_STEP).min(MAX_ZOOM);
+    } else if key == Key::Minus || key == Key::NumpadSubtract {
+        model.zoom = (model.zoom / ZOOM_STEP).max(1.0);
+    } else if key == Key::Key0 {
+        model.zoom = 1.0;
+        model.span_offset = (0.0, 0.0);
+    } else if key == Key::N {
+        model.smooth_filtering = !model.smooth_filtering;
+    } else if key == Key::I {
+        let path = get_path(app, &model.seed);
+        let ink = &model.physarum_settings.config.render.ink;
+        let layers = model
+            .physarum_settings
+            .model
+            .ink_layers(ink.layers.max(1), ink.method);
+        if let Err(err) = save_ink_layers(Path::new(&path), &layers) {
+            eprintln!("Failed to save ink layers: {}", err);
+        }
+    } else if key == Key::L {
+        let path = Path::new(&get_path(app, &model.seed)).with_extension("look.json");
+        match Look::of(&model.physarum_settings.config).save(&path) {
+            Ok(()) => println!("Saved {}", path.display()),
+            Err(err) => eprintln!("Failed to save the look: {}", err),
+        }
+    } else if key == Key::O {
+        let path = Path::new(&get_path(app, &model.seed)).with_extension("ora");
+        match model.physarum_settings.model.save_ora(&path) {
+            Ok(()) => println!("Saved {}", path.display()),
+            Err(err) => eprintln!("Failed to save layers: {}", err),
+        }
+    } else if let Some((dx, dy)) = match key {
+        Key::Left => Some((-SPAN_PAN_STEP, 0.0)),
+        Key::Right => Some((SPAN_PAN_STEP, 0.0)),
+        Key::Up => Some((0.0, -SPAN_PAN_STEP)),
+        Key::Down => Some((0.0, SPAN_PAN_STEP)),
+        _ => None,
+    } {
+        let (x, y) = model.span_offset;
+        let (dx, dy) = (dx / model.zoom, dy / model.zoom);
+        model.span_offset = ((x + dx).rem_euclid(1.0), (y + dy).rem_euclid(1.0));
+    }
+}
+
+/// Prints what the simulation holds under the cursor on a left click, see
+/// `PhysarumModel::probe`.
+fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
+    if button != MouseButton::Left {
+        return;
+    }
+    if let Some((x, y)) = grid_cursor(app, model) {
+        println!("{}", model.physarum_settings.model.probe(x, y));
+    }
+}
+
+/// Heats the cells under the cursor while the right button is held, or cools them with
+/// shift held too.
+fn update_heat_brush(app: &App, model: &mut Model) {
+    if !app.mouse.buttons.right().is_down() || model.physarum_settings.model.heat().is_none() {
+        return;
+    }
+    let Some((x, y)) = grid_cursor(app, model) else {
+        return;
+    };
+    let physarum_model = &mut model.physarum_settings.model;
+    let radius = physarum_model.grids[0]
+        .width
+        .min(physarum_model.grids[0].height) as f32
+        / 32.0;
+    let amount = if app.keys.mods.shift() { -0.05 } else { 0.05 };
+    physarum_model.paint_heat(x, y, radius, amount);
+}
+
+/// The cell under the cursor, unless it's off the image or over the GUI.
+fn grid_cursor(app: &App, model: &Model) -> Option<(f32, f32)> {
+    if model.egui_visible && model.egui.ctx().wants_pointer_input() {
+        return None;
+    }
+    let window = app.window(model.main_window_id)?;
+    let size = [model.image.width() as f32, model.image.height() as f32];
+    let (viewport, rect) = placement(model, model.main_window_id, window.rect(), size);
+    let cursor = app.mouse.position();
+    if !rect.contains(cursor) {
+        return None;
+    }
+    let position = (
+        (cursor.x - rect.left()) / rect.w(),
+        (rect.top() - cursor.y) / rect.h(),
+    );
+    let (x, y) = viewport.grid_position(model.span_offset, model.zoom, position);
+    let grid = &model.physarum_settings.model.grids[0];
+    Some((x * grid.width as f32, y * grid.height as f32))
+}
+
+fn save_frame(app: &App, seed: &Seed) {
+    let path = get_path(app, seed);
+    app.main_window().capture_frame(path);
+}
+
+fn get_path(app: &App, seed: &Seed) -> String {
+    let now = chrono::offset::Local::now();
+    let time = format!(
+        "{}_{}_{}_{}_{}_{}",
+        now.year(),
+        now.month(),
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    );
+    let path = format!(
+        "{}/{}_{}_{}{}",
+        "renders",
+        app.exe_name().unwrap(),
+        time,
+        seed.label(),
+        ".png"
+    );
+    path
+}
@@ -0,0 +1,105 @@
+use super::schedule::Every;
+use super::Model;
+use crate::physarum::config::SimulationConfig;
+use crate::physarum::state::ModelState;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Periodically writes the model state and active config into a rotating set of slots,
+/// so a long render can be resumed with `--recover` after a crash. Saves are due after an
+/// interval of time, a number of steps, or whichever comes first.
+pub struct Autosave {
+    dir: PathBuf,
+    interval: Option<Duration>,
+    every: Option<Every>,
+    slots: usize,
+    next_slot: usize,
+    last_save: Instant,
+}
+
+impl Autosave {
+    /// Saves every `interval_minutes`, never for 0, and every `every` steps after
+    /// `iteration` if given.
+    pub fn new(
+        dir: PathBuf,
+        interval_minutes: f32,
+        every: Option<u64>,
+        iteration: u64,
+        slots: usize,
+    ) -> Self {
+        Autosave {
+            dir,
+            interval: (interval_minutes > 0.0)
+                .then(|| Duration::from_secs_f32(interval_minutes * 60.0)),
+            every: every.and_then(|every| Every::new(every, iteration)),
+            slots: slots.max(1),
+            next_slot: 0,
+            last_save: Instant::now(),
+        }
+    }
+
+    pub fn due(&mut self, iteration: u64) -> bool {
+        let steps_due = self
+            .every
+            .as_mut()
+            .is_some_and(|every| every.due(iteration));
+        steps_due
+            || self
+                .interval
+                .is_some_and(|interval| self.last_save.elapsed() >= interval)
+    }
+
+    pub fn save(&mut self, model: &Model) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let (state_path, config_path) = slot_paths(&self.dir, self.next_slot);
+
+        // Write to temporary files first so a crash mid-write never clobbers a good slot.
+        let state_tmp = state_path.with_extension("state.tmp");
+        let config_tmp = config_path.with_extension("json.tmp");
+        model.physarum_settings.model.state().save(&state_tmp)?;
+        let mut config = model.physarum_settings.config.clone();
+        config.seed = Some(model.seed.value);
+        config.save(&config_tmp)?;
+        std::fs::rename(state_tmp, state_path)?;
+        std::fs::rename(config_tmp, config_path)?;
+
+        self.next_slot = (self.next_slot + 1) % self.slots;
+        self.last_save = Instant::now();
+        Ok(())
+    }
+}
+
+fn slot_paths(dir: &Path, slot: usize) -> (PathBuf, PathBuf) {
+    (
+        dir.join(format!("autosave_{}.state", slot)),
+        dir.join(format!("autosave_{}.json", slot)),
+    )
+}
+
+/// Loads the most recently written complete autosave slot in `dir`.
+pub fn recover(dir: &Path) -> io::Result<(ModelState, SimulationConfig)> {
+    let mut newest: Option<(SystemTime, PathBuf, PathBuf)> = None;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("state") {
+            continue;
+        }
+        let config_path = path.with_extension("json");
+        if !config_path.exists() {
+            continue;
+        }
+        let modified = std::fs::metadata(&path)?.modified()?;
+        if newest.as_ref().is_none_or(|(time, _, _)| modified > *time) {
+            newest = Some((modified, path, config_path));
+        }
+    }
+
+    let (_, state_path, config_path) =
+        newest.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No autosave found"))?;
+    println!("Recovering from {}", state_path.display());
+    Ok((
+        ModelState::load(&state_path)?,
+        SimulationConfig::load(&config_path)?,
+    ))
+}
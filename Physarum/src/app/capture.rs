@@ -0,0 +1,60 @@
+use super::Model;
+use crate::seed::Seed;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory full-resolution screenshots and bursts are written to.
+const CAPTURE_DIR: &str = "renders";
+
+/// Name shared by screenshots and bursts, tagged with the time and the seed.
+fn capture_stem(seed: &Seed) -> String {
+    let now = chrono::offset::Local::now();
+    format!("{}_seed_{}", now.format("%Y_%m_%d_%H_%M_%S"), seed.label())
+}
+
+fn save_png(model: &Model, path: &Path) -> io::Result<()> {
+    model
+        .image
+        .save_with_format(path, nannou::image::ImageFormat::Png)
+        .map_err(io::Error::other)
+}
+
+/// Saves the current frame at simulation resolution, independent of the window size.
+pub fn screenshot(model: &Model) -> io::Result<PathBuf> {
+    std::fs::create_dir_all(CAPTURE_DIR)?;
+    let path = Path::new(CAPTURE_DIR).join(format!("{}.png", capture_stem(&model.seed)));
+    save_png(model, &path)?;
+    Ok(path)
+}
+
+/// Captures the next `frames` rendered frames into a directory of numbered images.
+pub struct Burst {
+    dir: PathBuf,
+    frame: usize,
+    frames: usize,
+}
+
+impl Burst {
+    pub fn new(seed: &Seed, frames: usize) -> io::Result<Self> {
+        let dir = Path::new(CAPTURE_DIR).join(format!("burst_{}", capture_stem(seed)));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Burst {
+            dir,
+            frame: 0,
+            frames,
+        })
+    }
+
+    /// Saves the model's current frame, returning whether the burst is complete.
+    pub fn capture(&mut self, model: &Model) -> io::Result<bool> {
+        save_png(
+            model,
+            &self.dir.join(format!("frame_{:05}.png", self.frame)),
+        )?;
+        self.frame += 1;
+        if self.frame >= self.frames {
+            println!("Saved {} frames to {}", self.frames, self.dir.display());
+        }
+        Ok(self.frame >= self.frames)
+    }
+}
@@ -0,0 +1,177 @@
+use crate::physarum::palette::Rgb;
+use nannou::prelude::*;
+use nannou::wgpu;
+use std::borrow::Cow;
+use std::num::NonZeroU32;
+
+/// Populations the shader can colorize, more fall back to the CPU path.
+pub const MAX_LAYERS: usize = 8;
+const OUTPUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Uniforms {
+    colors: [[f32; 4]; MAX_LAYERS],
+    max_values: [[f32; 4]; MAX_LAYERS],
+    layers: u32,
+    _padding: [u32; 3],
+}
+
+/// Uploads the raw population fields to the GPU and colorizes them in a fragment shader,
+/// so displaying a frame skips building and copying a `DynamicImage` on the CPU.
+pub struct GpuColorizer {
+    width: u32,
+    height: u32,
+    fields: wgpu::Texture,
+    output: wgpu::Texture,
+    output_view: wgpu::TextureView,
+    uniforms: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl GpuColorizer {
+    pub fn new(window: &Window, width: u32, height: u32) -> Self {
+        let device = window.device();
+        let fields = wgpu::TextureBuilder::new()
+            .size([width, height])
+            .depth(MAX_LAYERS as u32)
+            .dimension(wgpu::TextureDimension::D2)
+            .format(wgpu::TextureFormat::R32Float)
+            .usage(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST)
+            .build(device);
+        let output = wgpu::TextureBuilder::new()
+            .size([width, height])
+            .format(OUTPUT_FORMAT)
+            .usage(
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC,
+            )
+            .build(device);
+        let output_view = output.view().build();
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("colorize uniforms"),
+            size: std::mem::size_of::<Uniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let layout = wgpu::BindGroupLayoutBuilder::new()
+            .uniform_buffer(wgpu::ShaderStages::FRAGMENT, false)
+            .texture(
+                wgpu::ShaderStages::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::D2Array,
+                wgpu::TextureSampleType::Float { filterable: false },
+            )
+            .build(device);
+        let fields_view = fields
+            .view()
+            .dimension(wgpu::TextureViewDimension::D2Array)
+            .build();
+        let bind_group = wgpu::BindGroupBuilder::new()
+            .buffer::<Uniforms>(&uniforms, 0..1)
+            .texture_view(&fields_view)
+            .build(device, &layout);
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("colorize"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/colorize.wgsl").into()),
+        });
+        let pipeline_layout = wgpu::create_pipeline_layout(device, None, &[&layout], &[]);
+        let pipeline = wgpu::RenderPipelineBuilder::from_layout(&pipeline_layout, &shader)
+            .vertex_entry_point("vs_main")
+            .fragment_shader(&shader)
+            .fragment_entry_point("fs_main")
+            .color_format(OUTPUT_FORMAT)
+            .build(device);
+
+        GpuColorizer {
+            width,
+            height,
+            fields,
+            output,
+            output_view,
+            uniforms,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    /// The colorized frame, ready to be drawn like any other texture.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.output
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Uploads `fields` and renders them into `texture()`, each tinted with its color.
+    pub fn render(
+        &self,
+        window: &Window,
+        fields: &[Cow<'_, [f32]>],
+        max_values: &[f32],
+        colors: &[Rgb<u8>],
+    ) {
+        let queue = window.queue();
+        let layers = fields.len().min(MAX_LAYERS);
+        for (layer, field) in fields.iter().take(layers).enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.fields,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                // SAFETY: f32 is plain old data.
+                unsafe { wgpu::bytes::from_slice(field) },
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(self.width * 4),
+                    rows_per_image: NonZeroU32::new(self.height),
+                },
+                wgpu::Extent3d {
+                    width: self.width,
+                    height: self.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let mut uniforms = Uniforms {
+            colors: [[0.0; 4]; MAX_LAYERS],
+            max_values: [[1.0; 4]; MAX_LAYERS],
+            layers: layers as u32,
+            _padding: [0; 3],
+        };
+        for (i, (max_value, color)) in max_values.iter().zip(colors).take(layers).enumerate() {
+            let [r, g, b] = color.0.map(|c| c as f32 / 255.0);
+            uniforms.colors[i] = [r, g, b, 1.0];
+            uniforms.max_values[i][0] = *max_value;
+        }
+        // SAFETY: `Uniforms` is `repr(C)` and made of plain old data.
+        queue.write_buffer(&self.uniforms, 0, unsafe { wgpu::bytes::from(&uniforms) });
+
+        let mut encoder = window
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("colorize"),
+            });
+        {
+            let mut pass = wgpu::RenderPassBuilder::new()
+                .color_attachment(&self.output_view, |color| color)
+                .begin(&mut encoder);
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        queue.submit(Some(encoder.finish()));
+    }
+}
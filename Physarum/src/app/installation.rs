@@ -0,0 +1,55 @@
+use super::Model;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Unattended gallery mode: the simulation is reseeded with fresh configs every cycle and the
+/// last frame of each cycle is archived together with the config that produced it.
+pub struct Installation {
+    cycle: Duration,
+    archive_dir: PathBuf,
+    cycle_start: Instant,
+    cycle_index: usize,
+}
+
+impl Installation {
+    pub fn new(cycle_minutes: f32, archive_dir: PathBuf) -> Self {
+        Installation {
+            cycle: Duration::from_secs_f32(cycle_minutes * 60.0),
+            archive_dir,
+            cycle_start: Instant::now(),
+            cycle_index: 0,
+        }
+    }
+
+    pub fn cycle_finished(&self) -> bool {
+        self.cycle_start.elapsed() >= self.cycle
+    }
+
+    pub fn start_cycle(&mut self) {
+        self.cycle_start = Instant::now();
+        self.cycle_index += 1;
+    }
+
+    pub fn archive(&self, model: &Model) -> io::Result<()> {
+        std::fs::create_dir_all(&self.archive_dir)?;
+        let now = chrono::offset::Local::now();
+        let stem = format!(
+            "cycle_{}_{}_{}",
+            self.cycle_index,
+            model.seed.label(),
+            now.format("%Y_%m_%d_%H_%M_%S")
+        );
+        model
+            .image
+            .save_with_format(
+                self.archive_dir.join(format!("{}.png", stem)),
+                nannou::image::ImageFormat::Png,
+            )
+            .map_err(io::Error::other)?;
+
+        let mut config = model.physarum_settings.config.clone();
+        config.seed = Some(model.seed.value);
+        config.save(&self.archive_dir.join(format!("{}.json", stem)))
+    }
+}
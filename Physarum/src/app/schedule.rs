@@ -0,0 +1,32 @@
+/// Comes due once every `interval` iterations of the model, for actions tied to how far
+/// the simulation has run rather than to the clock. Frames taking several steps at once
+/// may jump past a multiple, which still counts once.
+pub struct Every {
+    interval: u64,
+    next: u64,
+}
+
+impl Every {
+    /// First due at the next multiple of `interval` after `iteration`, or never for 0.
+    pub fn new(interval: u64, iteration: u64) -> Option<Self> {
+        (interval > 0).then(|| Every {
+            interval,
+            next: next_multiple(iteration, interval),
+        })
+    }
+
+    /// Whether `iteration` reached the next multiple, moving on to the one after it.
+    pub fn due(&mut self, iteration: u64) -> bool {
+        if iteration < self.next {
+            return false;
+        }
+        self.next = next_multiple(iteration, self.interval);
+        true
+    }
+}
+
+fn next_multiple(iteration: u64, interval: u64) -> u64 {
+    (iteration / interval)
+        .saturating_add(1)
+        .saturating_mul(interval)
+}
@@ -0,0 +1,53 @@
+// Colorizes the populations' fields into the displayed frame, matching
+// PhysarumModel::save_to_image.
+
+[[block]]
+struct Uniforms {
+    // Palette color of each layer, rgb in [0, 1].
+    colors: array<vec4<f32>, 8>;
+    // Field value shown at full brightness, in x.
+    max_values: array<vec4<f32>, 8>;
+    layers: u32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: Uniforms;
+[[group(0), binding(1)]]
+var fields: texture_2d_array<f32>;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+};
+
+// A single triangle covering the whole target.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c <= vec3<f32>(0.04045));
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let coords = vec2<i32>(in.position.xy);
+    var color = vec3<f32>(0.0, 0.0, 0.0);
+    var i: u32 = 0u;
+    loop {
+        if (i >= uniforms.layers) {
+            break;
+        }
+        let value = textureLoad(fields, coords, i32(i), 0).r;
+        let t = pow(clamp(value / uniforms.max_values[i].x, 0.0, 1.0), 1.0 / 2.2);
+        color = color + uniforms.colors[i].rgb * t;
+        i = i + 1u;
+    }
+    // The CPU path stores these values as sRGB bytes, and the target decodes on write.
+    return vec4<f32>(srgb_to_linear(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0))), 1.0);
+}
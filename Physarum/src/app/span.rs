@@ -0,0 +1,78 @@
+use nannou::geom::Rect;
+
+/// One window's share of a simulation spread across a wall of `columns` x `rows` outputs.
+#[derive(Clone, Copy, Debug)]
+pub struct Viewport {
+    pub column: usize,
+    pub row: usize,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl Viewport {
+    pub fn new(column: usize, row: usize, columns: usize, rows: usize) -> Self {
+        Viewport {
+            column,
+            row,
+            columns,
+            rows,
+        }
+    }
+
+    /// Pieces of the periodic grid visible in this viewport, as pairs of the texture area to
+    /// sample and the destination area inside the window, both normalized to [0, 1].
+    ///
+    /// `offset` pans the whole wall in grid fractions and `zoom` (at least 1) magnifies it
+    /// around the grid's center. Because the grid wraps around, a viewport crossing the grid's
+    /// edge is split into up to four pieces glued along the seam.
+    pub fn pieces(&self, offset: (f32, f32), zoom: f32) -> Vec<(Rect, Rect)> {
+        let zoom = zoom.max(1.0);
+        let margin = (1.0 - 1.0 / zoom) * 0.5;
+        let width = 1.0 / (self.columns as f32 * zoom);
+        let height = 1.0 / (self.rows as f32 * zoom);
+        let xs = split_periodic(margin + self.column as f32 * width + offset.0, width);
+        let ys = split_periodic(margin + self.row as f32 * height + offset.1, height);
+
+        let mut pieces = vec![];
+        for &(src_x, len_x, dst_x) in &xs {
+            for &(src_y, len_y, dst_y) in &ys {
+                // Image rows grow downwards while texture coordinates grow upwards.
+                let area = Rect::from_corners(
+                    [src_x, 1.0 - src_y - len_y].into(),
+                    [src_x + len_x, 1.0 - src_y].into(),
+                );
+                let dst = Rect::from_corners(
+                    [dst_x / width, 1.0 - (dst_y + len_y) / height].into(),
+                    [(dst_x + len_x) / width, 1.0 - dst_y / height].into(),
+                );
+                pieces.push((area, dst));
+            }
+        }
+        pieces
+    }
+
+    /// The point of the grid shown at `position` in this viewport, both normalized to
+    /// [0, 1] with y growing downwards, for the same `offset` and `zoom` as `pieces`.
+    pub fn grid_position(&self, offset: (f32, f32), zoom: f32, position: (f32, f32)) -> (f32, f32) {
+        let zoom = zoom.max(1.0);
+        let margin = (1.0 - 1.0 / zoom) * 0.5;
+        let width = 1.0 / (self.columns as f32 * zoom);
+        let height = 1.0 / (self.rows as f32 * zoom);
+        (
+            (margin + (self.column as f32 + position.0) * width + offset.0).rem_euclid(1.0),
+            (margin + (self.row as f32 + position.1) * height + offset.1).rem_euclid(1.0),
+        )
+    }
+}
+
+/// Splits the range [start, start + len) of the unit circle into non-wrapping pieces
+/// of (source start, length, offset inside the range).
+fn split_periodic(start: f32, len: f32) -> Vec<(f32, f32, f32)> {
+    let start = start.rem_euclid(1.0);
+    if start + len <= 1.0 {
+        vec![(start, len, 0.0)]
+    } else {
+        let first = 1.0 - start;
+        vec![(start, first, 0.0), (0.0, len - first, first)]
+    }
+}
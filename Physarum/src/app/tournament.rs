@@ -0,0 +1,249 @@
+use crate::physarum::config::SimulationConfig;
+use crate::physarum::tournament::{Entrant, Tournament};
+use crate::{apply_settings, cli, headless, load_config, pick_seed};
+use clap::Parser;
+use nannou::{image::DynamicImage, prelude::*, wgpu::Texture};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// Configs saved with the standings, best first.
+const SAVED_CONFIGS: usize = 3;
+
+/// Opens the tournament window and runs until it is closed.
+pub fn run() {
+    nannou::app(model).update(update).exit(exit).run();
+}
+
+/// An entrant's config to render, or its render.
+type Job = (usize, SimulationConfig);
+type Render = (usize, Result<DynamicImage, String>);
+
+struct Model {
+    tournament: Tournament,
+    output: PathBuf,
+    /// The pair on screen, left then right.
+    pair: (usize, usize),
+    textures: HashMap<usize, Texture>,
+    /// Entrants sent to the renderer, rendered or not.
+    requested: HashSet<usize>,
+    jobs: Sender<Job>,
+    renders: Receiver<Render>,
+    picks: usize,
+}
+
+fn model(app: &App) -> Model {
+    let args = cli::Args::parse();
+    let Some(cli::Command::Tournament {
+        entrants,
+        steps,
+        size,
+        output,
+    }) = &args.command
+    else {
+        unreachable!("the tournament is only run for its command");
+    };
+    let (steps, size) = (*steps, *size);
+    let config = load_config(&args);
+    let seed = pick_seed(&args, &config);
+    println!("{}", seed);
+    app.new_window()
+        .size(1400, 760)
+        .view(view)
+        .key_pressed(key_pressed)
+        .title("Physarum tournament: left or right arrow to pick, down to skip")
+        .build()
+        .unwrap();
+
+    let (jobs, requests) = mpsc::channel::<Job>();
+    let (results, renders) = mpsc::channel();
+    thread::spawn(move || {
+        for (id, config) in requests {
+            let image = render(&config, steps, size).map_err(|err| err.to_string());
+            if results.send((id, image)).is_err() {
+                break;
+            }
+        }
+    });
+    let mut tournament = Tournament::new(&config, *entrants, seed.value);
+    let pair = tournament.pair();
+    let mut model = Model {
+        tournament,
+        output: output.clone(),
+        pair,
+        textures: HashMap::new(),
+        requested: HashSet::new(),
+        jobs,
+        renders,
+        picks: 0,
+    };
+    request_renders(&mut model);
+    model
+}
+
+/// Renders a config for `steps` steps at `size` pixels along its longer side, with as many
+/// agents per pixel as at its full size.
+fn render(config: &SimulationConfig, steps: usize, size: usize) -> io::Result<DynamicImage> {
+    let mut config = config.clone();
+    let scale = size as f32 / config.width.max(config.height) as f32;
+    if scale < 1.0 {
+        config.width = ((config.width as f32 * scale) as usize).max(1);
+        config.height = ((config.height as f32 * scale) as usize).max(1);
+        config.n_particles = ((config.n_particles as f32 * scale * scale) as usize).max(1);
+    }
+    let mut model = headless::build(&config, config.seed.unwrap_or_default())?;
+    apply_settings(&mut model, &config).map_err(io::Error::other)?;
+    for _ in 0..steps {
+        model.step();
+    }
+    let mut image = model.new_image();
+    model.save_to_image(&mut image);
+    Ok(image)
+}
+
+/// Sends every entrant not rendered yet to the renderer, the pair on screen first so the
+/// others render while it is judged.
+fn request_renders(model: &mut Model) {
+    let (left, right) = model.pair;
+    let mut ids = vec![left, right];
+    ids.extend(
+        model
+            .tournament
+            .standings()
+            .iter()
+            .map(|entrant| entrant.id),
+    );
+    for id in ids {
+        let Some(entrant) = model.tournament.entrant(id) else {
+            continue;
+        };
+        if model.requested.insert(id) {
+            let _ = model.jobs.send((id, entrant.config.clone()));
+        }
+    }
+}
+
+fn update(app: &App, model: &mut Model, _update: Update) {
+    while let Ok((id, image)) = model.renders.try_recv() {
+        let image = image.unwrap_or_else(|err| {
+            eprintln!("Failed to render entrant {}: {}", id, err);
+            DynamicImage::new_rgb8(1, 1)
+        });
+        model.textures.insert(id, Texture::from_image(app, &image));
+    }
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    draw.background().color(BLACK);
+    let window = frame.rect();
+    let (left, right) = model.pair;
+    let halves = [
+        (left, window.pad_right(window.w() / 2.0)),
+        (right, window.pad_left(window.w() / 2.0)),
+    ];
+    for (id, half) in halves {
+        let area = half.pad(16.0).pad_bottom(40.0);
+        match model.textures.get(&id) {
+            Some(texture) => {
+                let [width, height] = texture.size();
+                let scale = (area.w() / width as f32).min(area.h() / height as f32);
+                draw.texture(texture)
+                    .xy(area.xy())
+                    .w_h(width as f32 * scale, height as f32 * scale);
+            }
+            None => {
+                draw.text("Rendering...").xy(area.xy()).color(GRAY);
+            }
+        }
+        if let Some(entrant) = model.tournament.entrant(id) {
+            draw.text(&describe(entrant))
+                .x_y(half.x(), half.bottom() + 24.0)
+                .w(half.w())
+                .color(WHITE);
+        }
+    }
+    draw.text(&format!(
+        "Generation {}, {} picks",
+        model.tournament.generation, model.picks
+    ))
+    .x_y(0.0, window.top() - 12.0)
+    .w(window.w())
+    .color(GRAY);
+    draw.to_frame(app, &frame).unwrap();
+}
+
+fn describe(entrant: &Entrant) -> String {
+    let parent = entrant
+        .parent
+        .map(|parent| format!(", from #{}", parent))
+        .unwrap_or_default();
+    format!(
+        "#{}{}: {:.0}, {}-{}",
+        entrant.id, parent, entrant.rating, entrant.wins, entrant.losses
+    )
+}
+
+fn key_pressed(_app: &App, model: &mut Model, key: Key) {
+    let (left, right) = model.pair;
+    let rendered = model.textures.contains_key(&left) && model.textures.contains_key(&right);
+    let refined = match key {
+        Key::Left if rendered => model.tournament.pick(left, right),
+        Key::Right if rendered => model.tournament.pick(right, left),
+        Key::Down | Key::Space => false,
+        Key::S => {
+            save(model);
+            return;
+        }
+        _ => return,
+    };
+    if matches!(key, Key::Left | Key::Right) {
+        model.picks += 1;
+    }
+    if refined {
+        let standings = model.tournament.standings();
+        println!(
+            "Generation {}, leading: {}",
+            model.tournament.generation,
+            describe(standings[0])
+        );
+        let alive: HashSet<usize> = standings.iter().map(|entrant| entrant.id).collect();
+        model.textures.retain(|id, _| alive.contains(id));
+        save(model);
+    }
+    model.pair = model.tournament.pair();
+    request_renders(model);
+}
+
+/// Writes the standings to `standings.json` in the output directory, and the configs of
+/// the leading entrants as `rank<n>.json`, ready for `--config`.
+fn save(model: &Model) {
+    let standings = model.tournament.standings();
+    let saved = std::fs::create_dir_all(&model.output)
+        .and_then(|_| {
+            let json = serde_json::to_string_pretty(&standings).map_err(io::Error::other)?;
+            std::fs::write(model.output.join("standings.json"), json)
+        })
+        .and_then(|_| {
+            standings
+                .iter()
+                .take(SAVED_CONFIGS)
+                .enumerate()
+                .try_for_each(|(rank, entrant)| {
+                    let path = model.output.join(format!("rank{}.json", rank + 1));
+                    entrant.config.save(&path)
+                })
+        });
+    match saved {
+        Ok(()) => println!("Saved the standings to {}", model.output.display()),
+        Err(err) => eprintln!("Failed to save the standings: {}", err),
+    }
+}
+
+fn exit(_app: &App, model: Model) {
+    if model.picks > 0 {
+        save(&model);
+    }
+}
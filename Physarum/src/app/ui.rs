@@ -0,0 +1,995 @@
+use super::Model;
+use crate::physarum;
+use crate::physarum::annealing::{Annealing, Cooling};
+use crate::physarum::bandpass::Bandpass;
+use crate::physarum::color_vision::COLOR_VISIONS;
+use crate::physarum::crowding::Crowding;
+use crate::physarum::food::Food;
+use crate::physarum::heat::Heat;
+use crate::physarum::initial::InitialTrails;
+use crate::physarum::mask::MaskShape;
+use crate::physarum::noise::Fbm;
+use crate::physarum::postprocess::GlowLayer;
+use crate::physarum::render::{
+    LayerBlend, LayerMode, LayerOrder, PopulationColor, GRADIENT_NAMES, MAX_DEFOCUS_BLUR,
+};
+use crate::physarum::sensing::Sensing;
+use crate::physarum::spacing::{Spacing, MAX_SPACING_RADIUS};
+use crate::seed::Seed;
+use nannou::prelude::*;
+use nannou_egui::{self, egui};
+use rand::{rngs::SmallRng, thread_rng, RngCore, SeedableRng};
+
+pub fn update_gui(model: &mut Model, update: Update) {
+    model.egui.set_elapsed_time(update.since_start);
+    let ctx = model.egui.begin_frame();
+    egui::Window::new("Settings")
+        .default_height(600.0)
+        .show(&ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::CollapsingHeader::new("General")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        // Picking a number drops the seed's words, which no longer match it.
+                        let mut seed = model.seed.value;
+                        if ui
+                            .add(
+                                egui::Slider::new(&mut seed, 0..=u64::MAX - 1)
+                                    .text("Seed")
+                                    .smart_aim(false),
+                            )
+                            .changed()
+                        {
+                            model.seed = Seed::from(seed);
+                            model.changed = true;
+                        }
+
+                        ui.add(
+                            egui::Slider::new(&mut model.steps_per_frame, 0..=10)
+                                .text("Steps per frame")
+                                .smart_aim(false),
+                        );
+                        ui.add(
+                            egui::Slider::new(
+                                &mut model.physarum_settings.config.render_every_n_steps,
+                                1..=100,
+                            )
+                            .logarithmic(true)
+                            .text("Render every N steps"),
+                        );
+                        model.changed |= ui.button("Redraw").clicked();
+                        ui.checkbox(&mut model.render, "Render");
+                        if ui.checkbox(&mut model.drift_enabled, "Drift").changed() {
+                            model.drift.reset();
+                        }
+                        ui.label(format!("{}{:.2}", "Fps: ", model.fps_counter.avg()));
+                        let physarum_model = &model.physarum_settings.model;
+                        let runtime = physarum_model.runtime().as_secs();
+                        ui.label(format!(
+                            "Step {} after {}:{:02}:{:02}",
+                            physarum_model.iteration(),
+                            runtime / 3600,
+                            runtime / 60 % 60,
+                            runtime % 60
+                        ));
+                    });
+
+                egui::CollapsingHeader::new("Physarum")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        model.changed |= ui
+                            .add(
+                                egui::Slider::new(
+                                    &mut model.physarum_settings.config.n_particles,
+                                    0..=6_000_000,
+                                )
+                                .text("Particles")
+                                .smart_aim(false),
+                            )
+                            .changed();
+
+                        model.changed |= ui
+                            .add(
+                                egui::Slider::new(
+                                    &mut model.physarum_settings.config.n_populations,
+                                    1..=8,
+                                )
+                                .text("Populations")
+                                .smart_aim(false),
+                            )
+                            .changed();
+
+                        model.changed |= ui
+                            .add(
+                                egui::Slider::new(
+                                    &mut model.physarum_settings.config.diffusivity,
+                                    1..=5,
+                                )
+                                .text("Diffusivity")
+                                .smart_aim(false),
+                            )
+                            .changed();
+
+                        model.changed |= initial_trails_ui(
+                            ui,
+                            &mut model.physarum_settings.config.initial_trails,
+                        );
+
+                        bandpass_ui(ui, &mut model.physarum_settings);
+
+                        let settings = &mut model.physarum_settings;
+                        if ui
+                            .add(
+                                egui::Slider::new(&mut settings.config.time_scale, 0.1..=4.0)
+                                    .logarithmic(true)
+                                    .text("Time Scale"),
+                            )
+                            .changed()
+                        {
+                            if let Err(err) =
+                                settings.model.set_time_scale(settings.config.time_scale)
+                            {
+                                eprintln!("Failed to apply the time scale: {}", err);
+                            }
+                        }
+
+                        annealing_ui(ui, &mut model.physarum_settings);
+                        crowding_ui(ui, &mut model.physarum_settings);
+                        spacing_ui(ui, &mut model.physarum_settings);
+                        heat_ui(ui, &mut model.physarum_settings);
+                        food_ui(ui, &mut model.physarum_settings);
+                        cities_ui(ui, &mut model.physarum_settings);
+
+                        let palette = &mut model.physarum_settings.config.palette;
+                        egui::ComboBox::from_label("Palette")
+                            .selected_text(palette.as_str())
+                            .show_ui(ui, |ui| {
+                                for name in physarum::palette::PALETTE_NAMES {
+                                    model.changed |= ui
+                                        .selectable_value(palette, name.to_string(), name)
+                                        .changed();
+                                }
+                            });
+
+                        if ui.button("Shuffle Configs").clicked() {
+                            let mut rng_local = SmallRng::seed_from_u64(thread_rng().next_u64());
+                            model.physarum_settings.config.populations =
+                                crate::get_random_configs(&mut rng_local);
+                            //model.physarum_settings.config.populations = vec![PopulationConfig::new(&mut rng_local); 5];
+                            model.physarum_settings.config_changed = true;
+                            model.changed = true;
+                        }
+
+                        if ui.button("Save Configs").clicked() {
+                            if let Ok(val) =
+                                serde_json::to_string(&model.physarum_settings.config.populations)
+                            {
+                                println!("{}", val);
+                            }
+                        }
+
+                        for i in 0..model.physarum_settings.config.n_populations {
+                            ui.add(egui::Separator::default());
+                            ui.add(egui::Label::new(format!("{}{}", "Grid ", i)));
+                            let mut population_size =
+                                model.physarum_settings.model.population_size(i);
+                            if ui
+                                .add(
+                                    egui::Slider::new(&mut population_size, 0..=2_000_000)
+                                        .text("Agents")
+                                        .smart_aim(false),
+                                )
+                                .changed()
+                            {
+                                if let Err(err) = model.physarum_settings.model.set_population_size(
+                                    i,
+                                    population_size,
+                                    &mut model.rng,
+                                ) {
+                                    eprintln!("Failed to resize the population: {}", err);
+                                }
+                            }
+                            let style = model.physarum_settings.config.render.style_mut(i);
+                            let previous = style.mode;
+                            egui::ComboBox::from_id_source(("layer mode", i))
+                                .selected_text(layer_mode_name(style.mode))
+                                .show_ui(ui, |ui| {
+                                    for mode in
+                                        [LayerMode::Trail, LayerMode::Sprites, LayerMode::Flow]
+                                    {
+                                        ui.selectable_value(
+                                            &mut style.mode,
+                                            mode,
+                                            layer_mode_name(mode),
+                                        );
+                                    }
+                                });
+                            if style.mode != previous {
+                                model.physarum_settings.apply_render_settings();
+                            }
+                            population_color_ui(ui, &mut model.physarum_settings, i);
+                            focus_ui(ui, &mut model.physarum_settings, i);
+                            sensing_ui(ui, &mut model.physarum_settings, i);
+                            model.physarum_settings.config_changed |= ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut model.physarum_settings.config.populations[i]
+                                            .decay_factor,
+                                        0.01..=3.0,
+                                    )
+                                    .text("Decay")
+                                    .smart_aim(false),
+                                )
+                                .changed();
+                            model.physarum_settings.config_changed |= ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut model.physarum_settings.config.populations[i]
+                                            .deposition_amount,
+                                        0.1..=10.0,
+                                    )
+                                    .text("Deposition")
+                                    .smart_aim(false),
+                                )
+                                .changed();
+                            model.physarum_settings.config_changed |= ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut model.physarum_settings.config.populations[i]
+                                            .rotation_angle,
+                                        0.01..=2.1,
+                                    )
+                                    .text("Rotation Angle")
+                                    .smart_aim(false),
+                                )
+                                .changed();
+                            model.physarum_settings.config_changed |= ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut model.physarum_settings.config.populations[i]
+                                            .sensor_angle,
+                                        0.01..=2.1,
+                                    )
+                                    .text("Sensor Angle")
+                                    .smart_aim(false),
+                                )
+                                .changed();
+                            model.physarum_settings.config_changed |= ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut model.physarum_settings.config.populations[i]
+                                            .sensor_distance,
+                                        0.0..=64.0,
+                                    )
+                                    .text("Sensor Distance")
+                                    .smart_aim(false),
+                                )
+                                .changed();
+                            model.physarum_settings.config_changed |= ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut model.physarum_settings.config.populations[i]
+                                            .step_distance,
+                                        0.2..=2.0,
+                                    )
+                                    .text("Step Distance")
+                                    .smart_aim(false),
+                                )
+                                .changed();
+                            model.physarum_settings.config_changed |= ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut model.physarum_settings.config.populations[i]
+                                            .bias_angle,
+                                        0.0..=TAU,
+                                    )
+                                    .text("Bias Direction")
+                                    .smart_aim(false),
+                                )
+                                .changed();
+                            model.physarum_settings.config_changed |= ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut model.physarum_settings.config.populations[i]
+                                            .bias_strength,
+                                        0.0..=0.5,
+                                    )
+                                    .text("Bias Strength")
+                                    .smart_aim(false),
+                                )
+                                .changed();
+                            model.physarum_settings.config_changed |= ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut model.physarum_settings.config.populations[i]
+                                            .self_repulsion,
+                                        0.0..=2.0,
+                                    )
+                                    .text("Self Repulsion")
+                                    .smart_aim(false),
+                                )
+                                .changed();
+                            model.physarum_settings.config_changed |= ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut model.physarum_settings.config.populations[i]
+                                            .self_attraction_range,
+                                        0.0..=32.0,
+                                    )
+                                    .text("Self Attraction Range")
+                                    .smart_aim(false),
+                                )
+                                .changed();
+                            model.physarum_settings.config_changed |= ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut model.physarum_settings.config.populations[i].erosion,
+                                        0.0..=0.5,
+                                    )
+                                    .text("Erosion")
+                                    .smart_aim(false),
+                                )
+                                .changed();
+                            model.physarum_settings.config_changed |= ui
+                                .add(
+                                    egui::Slider::new(
+                                        &mut model.physarum_settings.config.populations[i]
+                                            .max_substep,
+                                        0.0..=2.0,
+                                    )
+                                    .text("Max Substep")
+                                    .smart_aim(false),
+                                )
+                                .changed();
+                        }
+                    });
+
+                egui::CollapsingHeader::new("Palette")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let settings = &mut model.physarum_settings;
+                        let mut colors = settings
+                            .config
+                            .render
+                            .colors
+                            .unwrap_or(settings.model.palette().colors.map(|color| color.0));
+                        let mut changed = false;
+                        for (i, color) in colors
+                            .iter_mut()
+                            .enumerate()
+                            .take(settings.config.n_populations)
+                        {
+                            ui.horizontal(|ui| {
+                                changed |= ui.color_edit_button_srgb(color).changed();
+                                ui.label(format!("Palette color {}", i + 1));
+                            });
+                        }
+                        if changed {
+                            settings.config.render.colors = Some(colors);
+                            settings.apply_render_settings();
+                        }
+                        if ui.button("Reset Palette").clicked() {
+                            settings.config.render.colors = None;
+                            if let Some(palette) =
+                                physarum::palette::Palette::by_name(&settings.config.palette)
+                            {
+                                settings.model.set_palette(palette);
+                            }
+                        }
+
+                        let vision = &mut settings.config.render.color_vision;
+                        let previous = *vision;
+                        egui::ComboBox::from_label("Preview Color Vision")
+                            .selected_text(vision.to_string())
+                            .show_ui(ui, |ui| {
+                                for option in COLOR_VISIONS {
+                                    ui.selectable_value(vision, option, option.to_string());
+                                }
+                            });
+                        if *vision != previous {
+                            settings.apply_render_settings();
+                        }
+                        contours_ui(ui, settings);
+                        stipple_ui(ui, settings);
+                        mask_ui(ui, settings);
+                        layer_order_ui(ui, settings);
+                    });
+
+                egui::CollapsingHeader::new("Post-processing")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let render = &mut model.physarum_settings.config.render;
+                        let post = &mut render.postprocess;
+                        let mut changed = false;
+                        for (value, range, text) in [
+                            (&mut post.bloom_intensity, 0.0..=2.0, "Bloom"),
+                            (&mut post.bloom_threshold, 0.0..=1.0, "Bloom threshold"),
+                            (&mut post.bloom_radius, 0.5..=32.0, "Bloom radius"),
+                            (&mut post.vignette, 0.0..=1.0, "Vignette"),
+                            (&mut post.chromatic_aberration, 0.0..=16.0, "Aberration"),
+                            (&mut post.grain, 0.0..=0.3, "Grain"),
+                            (&mut render.brightness_smoothing, 0.0..=0.99, "Smoothing"),
+                        ] {
+                            changed |= ui
+                                .add(egui::Slider::new(value, range).text(text).smart_aim(false))
+                                .changed();
+                        }
+                        changed |= glow_ui(ui, &mut post.glow);
+                        changed |= ui
+                            .add(
+                                egui::Slider::new(&mut render.accumulation_frames, 1..=16)
+                                    .text("Motion blur frames")
+                                    .smart_aim(false),
+                            )
+                            .changed();
+                        if changed {
+                            model.physarum_settings.apply_render_settings();
+                        }
+                    });
+            });
+        });
+
+    drop(ctx);
+    if model.changed {
+        model.reset();
+    }
+    if model.physarum_settings.config_changed {
+        model.physarum_settings.config_changed = false;
+        if let Err(err) = model
+            .physarum_settings
+            .model
+            .set_population_configs(model.physarum_settings.config.populations.clone())
+        {
+            eprintln!("Failed to apply population configs: {}", err);
+        }
+    }
+}
+
+/// Switches and tunes the band-pass the populations sense each other through, which takes
+/// effect without restarting the simulation.
+fn layer_mode_name(mode: LayerMode) -> &'static str {
+    match mode {
+        LayerMode::Trail => "Trails",
+        LayerMode::Sprites => "Sprites",
+        LayerMode::Flow => "Flow lines",
+    }
+}
+
+/// Picks what the trails start out as, returning whether it changed, which restarts the
+/// simulation. Images can only be picked in a config.
+fn initial_trails_ui(ui: &mut egui::Ui, initial: &mut InitialTrails) -> bool {
+    let previous = initial.clone();
+    let name = |initial: &InitialTrails| match initial {
+        InitialTrails::Uniform => "Random noise",
+        InitialTrails::Zeros => "Empty",
+        InitialTrails::Noise { .. } => "Perlin noise",
+        InitialTrails::Image { .. } => "Image",
+        InitialTrails::Rings { .. } => "Rings",
+        InitialTrails::Stripes { .. } => "Stripes",
+    };
+    egui::ComboBox::from_label("Initial Trails")
+        .selected_text(name(initial))
+        .show_ui(ui, |ui| {
+            for option in [
+                InitialTrails::Uniform,
+                InitialTrails::Zeros,
+                InitialTrails::Noise(Fbm::default()),
+                InitialTrails::Rings { spacing: 32.0 },
+                InitialTrails::Stripes {
+                    spacing: 32.0,
+                    angle: 0.0,
+                },
+            ] {
+                let selected = std::mem::discriminant(initial) == std::mem::discriminant(&option);
+                if ui.selectable_label(selected, name(&option)).clicked() && !selected {
+                    *initial = option;
+                }
+            }
+        });
+    match initial {
+        InitialTrails::Noise(fbm) => {
+            ui.add(
+                egui::Slider::new(&mut fbm.scale, 4.0..=512.0)
+                    .logarithmic(true)
+                    .text("Noise scale"),
+            );
+            ui.add(egui::Slider::new(&mut fbm.octaves, 1..=8).text("Noise octaves"));
+            ui.add(egui::Slider::new(&mut fbm.gain, 0.1..=0.9).text("Noise roughness"));
+        }
+        InitialTrails::Rings { spacing } => {
+            ui.add(
+                egui::Slider::new(spacing, 2.0..=256.0)
+                    .logarithmic(true)
+                    .text("Ring spacing"),
+            );
+        }
+        InitialTrails::Stripes { spacing, angle } => {
+            ui.add(
+                egui::Slider::new(spacing, 2.0..=256.0)
+                    .logarithmic(true)
+                    .text("Stripe spacing"),
+            );
+            ui.add(egui::Slider::new(angle, 0.0..=std::f32::consts::PI).text("Stripe angle"));
+        }
+        _ => {}
+    }
+    *initial != previous
+}
+
+/// Switches to drawing isolines, at evenly spaced levels when their number is changed.
+fn contours_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings) {
+    let contours = &mut settings.config.render.contours;
+    let mut changed = ui.checkbox(&mut contours.enabled, "Contours").changed();
+    if contours.enabled {
+        let mut n_levels = contours.levels.len();
+        if ui
+            .add(egui::Slider::new(&mut n_levels, 1..=16).text("Contour levels"))
+            .changed()
+        {
+            contours.levels = physarum::contour::evenly_spaced(n_levels);
+            changed = true;
+        }
+        changed |= ui
+            .add(egui::Slider::new(&mut contours.line_width, 0.5..=6.0).text("Line width"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut contours.smoothing, 0.0..=8.0).text("Contour smoothing"))
+            .changed();
+    }
+    if changed {
+        settings.apply_render_settings();
+    }
+}
+
+fn stipple_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings) {
+    let stipple = &mut settings.config.render.stipple;
+    let mut changed = ui.checkbox(&mut stipple.enabled, "Stipple").changed();
+    if stipple.enabled {
+        for (value, range, text) in [
+            (&mut stipple.spacing, 1.0..=12.0, "Dot spacing"),
+            (&mut stipple.max_radius, 0.2..=6.0, "Largest dots"),
+            (&mut stipple.min_radius, 0.2..=6.0, "Smallest dots"),
+        ] {
+            changed |= ui.add(egui::Slider::new(value, range).text(text)).changed();
+        }
+    }
+    if changed {
+        settings.apply_render_settings();
+    }
+}
+
+/// Clips the frame to a shape. Paths and SVG files can only be picked in a config.
+fn mask_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings) {
+    let mask = &mut settings.config.render.mask;
+    let previous = mask.clone();
+    egui::ComboBox::from_label("Mask")
+        .selected_text(mask.shape.to_string())
+        .show_ui(ui, |ui| {
+            for shape in [
+                MaskShape::None,
+                MaskShape::Circle,
+                MaskShape::RoundedRect { radius: 0.1 },
+            ] {
+                let text = shape.to_string();
+                ui.selectable_value(&mut mask.shape, shape, text);
+            }
+        });
+    if let MaskShape::RoundedRect { radius } = &mut mask.shape {
+        ui.add(egui::Slider::new(radius, 0.0..=0.5).text("Corner radius"));
+    }
+    if mask.is_active() {
+        ui.add(egui::Slider::new(&mut mask.margin, 0.0..=0.3).text("Mask margin"));
+        ui.add(egui::Slider::new(&mut mask.feather, 0.0..=32.0).text("Mask feather"));
+        ui.checkbox(&mut mask.confine, "Confine agents");
+    }
+    if *mask != previous {
+        settings.apply_render_settings();
+    }
+}
+
+/// Edits the glow layers, returning whether any changed.
+fn glow_ui(ui: &mut egui::Ui, glow: &mut Vec<GlowLayer>) -> bool {
+    let mut changed = false;
+    let mut removed = None;
+    for (k, layer) in glow.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(egui::Slider::new(&mut layer.radius, 0.5..=64.0).text("Glow radius"))
+                .changed();
+            if ui.button("Remove").clicked() {
+                removed = Some(k);
+            }
+        });
+        changed |= ui
+            .add(egui::Slider::new(&mut layer.weight, -1.0..=2.0).text("Glow weight"))
+            .changed();
+    }
+    if let Some(k) = removed {
+        glow.remove(k);
+        changed = true;
+    }
+    if ui.button("Add Glow Layer").clicked() {
+        glow.push(GlowLayer::default());
+        changed = true;
+    }
+    changed
+}
+
+fn bandpass_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings) {
+    let bandpass = &mut settings.config.bandpass;
+    let previous = *bandpass;
+    let mut enabled = bandpass.is_some();
+    if ui.checkbox(&mut enabled, "Band-pass Attraction").changed() {
+        *bandpass = enabled.then(Bandpass::default);
+    }
+    if let Some(bandpass) = bandpass {
+        ui.add(egui::Slider::new(&mut bandpass.inner, 0.5..=16.0).text("Inner Blur"));
+        ui.add(egui::Slider::new(&mut bandpass.outer, 1.0..=32.0).text("Outer Blur"));
+        // Keep the inner blur the narrower one while either slider is dragged.
+        bandpass.inner = bandpass.inner.min(bandpass.outer * 0.9);
+    }
+    if *bandpass != previous {
+        if let Err(err) = settings.model.set_bandpass(*bandpass) {
+            eprintln!("Failed to apply the band-pass: {}", err);
+        }
+    }
+}
+
+/// Switches annealing on from the current iteration, so the run heats up and cools down
+/// again from there, and shows how hot it is.
+fn annealing_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings) {
+    let annealing = &mut settings.config.annealing;
+    let previous = *annealing;
+    let iteration = settings.model.iteration();
+    let mut enabled = annealing.is_some();
+    if ui.checkbox(&mut enabled, "Annealing").changed() {
+        *annealing = enabled.then(|| Annealing {
+            begin: iteration,
+            ..Annealing::default()
+        });
+    }
+    if let Some(annealing) = annealing {
+        ui.add(egui::Slider::new(&mut annealing.start, 0.0..=4.0).text("Start Temperature"));
+        ui.add(egui::Slider::new(&mut annealing.end, 0.0..=1.0).text("End Temperature"));
+        ui.add(
+            egui::Slider::new(&mut annealing.steps, 100..=20000)
+                .logarithmic(true)
+                .text("Cooling Steps"),
+        );
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("cooling")
+                .selected_text(annealing.cooling.to_string())
+                .show_ui(ui, |ui| {
+                    for cooling in [Cooling::Linear, Cooling::Exponential, Cooling::Cosine] {
+                        ui.selectable_value(&mut annealing.cooling, cooling, cooling.to_string());
+                    }
+                });
+            if ui.button("Reheat").clicked() {
+                annealing.begin = iteration;
+            }
+        });
+        ui.label(format!("Temperature {:.3}", settings.model.temperature()));
+    }
+    if *annealing != previous {
+        if let Err(err) = settings.model.set_annealing(*annealing) {
+            eprintln!("Failed to apply the annealing: {}", err);
+        }
+    }
+}
+
+/// Lets the agents sense the crowd around them and tunes how much they avoid it.
+fn crowding_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings) {
+    let crowding = &mut settings.config.crowding;
+    let previous = *crowding;
+    let side = settings.config.width.min(settings.config.height);
+    let mut enabled = crowding.is_some();
+    if ui.checkbox(&mut enabled, "Crowding").changed() {
+        *crowding = enabled.then(Crowding::default);
+    }
+    if let Some(crowding) = crowding {
+        ui.add(egui::Slider::new(&mut crowding.strength, -4.0..=4.0).text("Crowd Avoidance"));
+        egui::ComboBox::from_label("Crowd Bins")
+            .selected_text(format!("{} cells", crowding.bin_size))
+            .show_ui(ui, |ui| {
+                for bin_size in (1..=6).map(|k| 1 << k).filter(|&size| size <= side) {
+                    let text = format!("{} cells", bin_size);
+                    ui.selectable_value(&mut crowding.bin_size, bin_size, text);
+                }
+            });
+    }
+    if *crowding != previous {
+        if let Err(err) = settings.model.set_crowding(*crowding) {
+            eprintln!("Failed to apply the crowding: {}", err);
+        }
+    }
+}
+
+/// Keeps the agents of every population apart, so the filaments stay some agents wide.
+fn spacing_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings) {
+    let spacing = &mut settings.config.spacing;
+    let previous = *spacing;
+    let mut enabled = spacing.is_some();
+    if ui.checkbox(&mut enabled, "Agent Spacing").changed() {
+        *spacing = enabled.then(Spacing::default);
+    }
+    if let Some(spacing) = spacing {
+        ui.add(
+            egui::Slider::new(&mut spacing.radius, 0.25..=MAX_SPACING_RADIUS)
+                .text("Spacing Radius"),
+        );
+        ui.add(egui::Slider::new(&mut spacing.strength, 0.0..=1.0).text("Spacing Strength"));
+    }
+    if *spacing != previous {
+        if let Err(err) = settings.model.set_spacing(*spacing) {
+            eprintln!("Failed to apply the spacing: {}", err);
+        }
+    }
+}
+
+/// Lays heat over the grids that the trails decay faster under where it's hot, painted on
+/// with the right mouse button and cooled with shift held too.
+fn heat_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings) {
+    let heat = &mut settings.config.heat;
+    let previous = *heat;
+    let mut enabled = heat.is_some();
+    if ui.checkbox(&mut enabled, "Heat").changed() {
+        *heat = enabled.then(Heat::default);
+    }
+    if let Some(heat) = heat {
+        let mut noise = heat.noise.is_some();
+        if ui.checkbox(&mut noise, "Heat Noise").changed() {
+            heat.noise = noise.then(|| Heat::default().noise).flatten();
+        }
+        if let Some(fbm) = &mut heat.noise {
+            ui.add(
+                egui::Slider::new(&mut fbm.scale, 8.0..=1024.0)
+                    .logarithmic(true)
+                    .text("Heat Scale"),
+            );
+        }
+        ui.add(egui::Slider::new(&mut heat.strength, 0.0..=2.0).text("Heat Strength"));
+        ui.add(egui::Slider::new(&mut heat.diffusion, 0.0..=4.0).text("Heat Diffusion"));
+        ui.add(
+            egui::Slider::new(&mut heat.relaxation, 0.0..=0.2)
+                .logarithmic(true)
+                .text("Heat Relaxation"),
+        );
+        ui.add(egui::Slider::new(&mut heat.drift, -2.0..=2.0).text("Heat Drift"));
+        ui.label("Right-click to heat, shift right-click to cool");
+    }
+    if *heat != previous {
+        if let Err(err) = settings.model.set_heat(*heat) {
+            eprintln!("Failed to apply the heat: {}", err);
+        }
+    }
+}
+
+/// Switches the food sources on and off and tunes them, keeping the sources already out
+/// while the sliders move, and counts how many are left.
+fn food_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings) {
+    let food = &mut settings.config.food;
+    let previous = *food;
+    let mut enabled = food.is_some();
+    if ui.checkbox(&mut enabled, "Food Sources").changed() {
+        *food = enabled.then(Food::default);
+    }
+    if let Some(food) = food {
+        ui.add(
+            egui::Slider::new(&mut food.spawn_rate, 0.0..=50.0)
+                .logarithmic(true)
+                .text("Spawn Rate"),
+        );
+        ui.add(egui::Slider::new(&mut food.max_sources, 1..=64).text("Max Sources"));
+        ui.add(egui::Slider::new(&mut food.radius, 1.0..=50.0).text("Food Radius"));
+        ui.add(
+            egui::Slider::new(&mut food.capacity, 10.0..=100000.0)
+                .logarithmic(true)
+                .text("Capacity"),
+        );
+        ui.add(egui::Slider::new(&mut food.consumption, 0.0..=10.0).text("Consumption"));
+        ui.add(egui::Slider::new(&mut food.scent, 0.0..=20.0).text("Scent"));
+        ui.label(format!(
+            "{} sources left",
+            settings.model.food_sources().len()
+        ));
+    }
+    if *food != previous {
+        if let Err(err) = settings.model.set_food(*food) {
+            eprintln!("Failed to apply the food sources: {}", err);
+        }
+    }
+}
+
+/// Tunes the cities of a `--cities` run, reading them again when the sliders move.
+fn cities_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings) {
+    let Some(cities) = &mut settings.config.cities else {
+        return;
+    };
+    let previous = cities.clone();
+    ui.add(egui::Slider::new(&mut cities.radius, 1.0..=20.0).text("City Radius"));
+    ui.add(egui::Slider::new(&mut cities.scent, 0.0..=20.0).text("City Scent"));
+    ui.label(format!("{} cities", settings.model.cities().len()));
+    if *cities != previous {
+        if let Err(err) = settings.model.load_cities(Some(cities.clone())) {
+            eprintln!("Failed to load the cities: {}", err);
+        }
+    }
+}
+
+/// Picks which trails population `i` steers by, with a weight per population for custom
+/// weights.
+fn sensing_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings, i: usize) {
+    let n_populations = settings.config.n_populations;
+    let row = settings.model.attraction_table().get(i).cloned();
+    let sensing = &mut settings.config.sensing;
+    if sensing.len() <= i {
+        sensing.resize(i + 1, Sensing::default());
+    }
+    let mode = &mut sensing[i];
+    let previous = mode.clone();
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source(("sensing", i))
+            .selected_text(match mode {
+                Sensing::Combined => "Combined",
+                Sensing::Own => "Own trails",
+                Sensing::Others => "Other trails",
+                Sensing::Weights { .. } => "Custom weights",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(mode, Sensing::Combined, "Combined");
+                ui.selectable_value(mode, Sensing::Own, "Own trails");
+                ui.selectable_value(mode, Sensing::Others, "Other trails");
+                let custom = matches!(mode, Sensing::Weights { .. });
+                if ui.selectable_label(custom, "Custom weights").clicked() && !custom {
+                    *mode = Sensing::Weights {
+                        weights: row.unwrap_or_else(|| vec![0.0; n_populations]),
+                    };
+                }
+            });
+        ui.label("Senses");
+    });
+    if let Sensing::Weights { weights } = mode {
+        weights.resize(n_populations, 0.0);
+        for (j, weight) in weights.iter_mut().enumerate() {
+            ui.add(egui::Slider::new(weight, -2.0..=2.0).text(format!("Grid {} Weight", j)));
+        }
+    }
+    if *mode != previous {
+        if let Err(err) = settings.model.set_sensing(settings.config.sensing.clone()) {
+            eprintln!("Failed to apply sensing: {}", err);
+        }
+    }
+}
+
+/// Blurs and dims population `i` in the render to push it out of focus, behind the others,
+/// and picks how it covers the layers below. Fades from a config can only be stopped here.
+fn focus_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings, i: usize) {
+    let style = settings.config.render.style_mut(i);
+    let previous = style.blend;
+    egui::ComboBox::from_id_source(("layer blend", i))
+        .selected_text(layer_blend_name(style.blend))
+        .show_ui(ui, |ui| {
+            for blend in [LayerBlend::Add, LayerBlend::Screen, LayerBlend::Over] {
+                ui.selectable_value(&mut style.blend, blend, layer_blend_name(blend));
+            }
+        });
+    let blended = style.blend != previous;
+    let blurred = ui
+        .add(egui::Slider::new(&mut style.blur, 0.0..=MAX_DEFOCUS_BLUR).text("Render Blur"))
+        .changed();
+    let dimmed = ui
+        .add(egui::Slider::new(&mut style.brightness, 0.0..=2.0).text("Render Brightness"))
+        .changed();
+    let mut unfaded = false;
+    if !style.opacity.is_empty() {
+        let opacity = style.opacity_at(settings.model.iteration());
+        ui.horizontal(|ui| {
+            ui.label(format!("Fading, {:.0}% opaque", opacity * 100.0));
+            unfaded = ui.button("Stop fading").clicked();
+        });
+        if unfaded {
+            style.opacity.clear();
+        }
+    }
+    if blended || blurred || dimmed || unfaded {
+        settings.apply_render_settings();
+    }
+}
+
+fn layer_blend_name(blend: LayerBlend) -> &'static str {
+    match blend {
+        LayerBlend::Add => "Add light",
+        LayerBlend::Screen => "Screen",
+        LayerBlend::Over => "Cover below",
+    }
+}
+
+/// Picks the order occluding layers are stacked in. Custom orders can only be set in a config.
+fn layer_order_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings) {
+    let n_populations = settings.config.n_populations;
+    let order = &mut settings.config.render.order;
+    let previous = order.clone();
+    let reversed = LayerOrder::Custom {
+        layers: (0..n_populations).rev().collect(),
+    };
+    egui::ComboBox::from_label("Layer order")
+        .selected_text(match order {
+            LayerOrder::Grid => "Population order",
+            LayerOrder::Custom { .. } if *order == reversed => "Reversed",
+            LayerOrder::Custom { .. } => "Custom",
+            LayerOrder::Auto => "Densest at the bottom",
+        })
+        .show_ui(ui, |ui| {
+            ui.selectable_value(order, LayerOrder::Grid, "Population order");
+            ui.selectable_value(order, reversed, "Reversed");
+            ui.selectable_value(order, LayerOrder::Auto, "Densest at the bottom");
+        });
+    if *order != previous {
+        settings.apply_render_settings();
+    }
+}
+
+/// Picks how population `i` is colored: by palette order, a chosen palette color, a custom
+/// color or a density ramp.
+fn population_color_ui(ui: &mut egui::Ui, settings: &mut super::PhysarumSettings, i: usize) {
+    let tint = settings
+        .config
+        .render
+        .style(i)
+        .color
+        .tint(settings.model.palette(), i)
+        .0;
+    let color = &mut settings.config.render.style_mut(i).color;
+    let previous = color.clone();
+    let palette_label = |index: usize| format!("Palette color {}", index + 1);
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source(("population color", i))
+            .selected_text(match color {
+                PopulationColor::Auto => "Palette order".to_string(),
+                PopulationColor::Palette { index } => palette_label(*index),
+                PopulationColor::Rgb { .. } => "Custom".to_string(),
+                PopulationColor::Ramp { .. } => "Ramp".to_string(),
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(color, PopulationColor::Auto, "Palette order");
+                for index in 0..physarum::palette::PALETTE_ARRAY[0].colors.len() {
+                    ui.selectable_value(
+                        color,
+                        PopulationColor::Palette { index },
+                        palette_label(index),
+                    );
+                }
+                let custom = matches!(color, PopulationColor::Rgb { .. });
+                if ui.selectable_label(custom, "Custom").clicked() && !custom {
+                    *color = PopulationColor::Rgb { color: tint };
+                }
+                let ramp = matches!(color, PopulationColor::Ramp { .. });
+                if ui.selectable_label(ramp, "Ramp").clicked() && !ramp {
+                    *color = PopulationColor::Ramp {
+                        stops: vec![[0; 3], tint, [255; 3]],
+                        positions: vec![],
+                    };
+                }
+                for name in GRADIENT_NAMES {
+                    if let Some(gradient) = PopulationColor::gradient(name) {
+                        ui.selectable_value(color, gradient, name);
+                    }
+                }
+            });
+        match color {
+            PopulationColor::Rgb { color } => {
+                ui.color_edit_button_srgb(color);
+            }
+            PopulationColor::Ramp { stops, .. } => {
+                for stop in stops.iter_mut() {
+                    ui.color_edit_button_srgb(stop);
+                }
+            }
+            _ => {}
+        }
+        ui.label("Color");
+    });
+    if *color != previous {
+        settings.apply_render_settings();
+    }
+}
@@ -0,0 +1,61 @@
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+/// Port Art-Net nodes listen on.
+const PORT: u16 = 6454;
+/// Channels in one DMX universe.
+pub const UNIVERSE_SIZE: usize = 512;
+
+/// Sends ArtDmx packets to a single node, or to a broadcast address.
+pub struct ArtNetSender {
+    socket: UdpSocket,
+    address: SocketAddr,
+    sequence: u8,
+}
+
+impl ArtNetSender {
+    /// `host` may carry a port, otherwise the Art-Net port is used.
+    pub fn open(host: &str) -> io::Result<Self> {
+        let address = if host.contains(':') {
+            host.to_string()
+        } else {
+            format!("{}:{}", host, PORT)
+        };
+        let address = address.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("No address for {}", host))
+        })?;
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_broadcast(true)?;
+        Ok(ArtNetSender {
+            socket,
+            address,
+            sequence: 0,
+        })
+    }
+
+    /// Starts a new frame, whose universes share one sequence number.
+    pub fn next_frame(&mut self) {
+        // Sequence 0 turns off reordering on receivers, so wrapping skips it.
+        self.sequence = self.sequence.checked_add(1).unwrap_or(1);
+    }
+
+    /// Sends up to 512 channel values to `universe`.
+    pub fn send(&self, universe: u16, channels: &[u8]) -> io::Result<()> {
+        let channels = &channels[..channels.len().min(UNIVERSE_SIZE)];
+        // DMX data must have an even length.
+        let length = channels.len().next_multiple_of(2);
+        let mut packet = Vec::with_capacity(18 + length);
+        packet.extend_from_slice(b"Art-Net\0");
+        packet.extend_from_slice(&0x5000_u16.to_le_bytes());
+        packet.extend_from_slice(&14_u16.to_be_bytes());
+        packet.push(self.sequence);
+        packet.push(0);
+        packet.extend_from_slice(&universe.to_le_bytes());
+        packet.extend_from_slice(&(length as u16).to_be_bytes());
+        packet.extend_from_slice(channels);
+        packet.resize(18 + length, 0);
+        // Not connecting the socket keeps a node that is still booting from failing sends.
+        self.socket.send_to(&packet, self.address)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+use crate::cli::Args;
+use crate::headless;
+use crate::physarum::config::SimulationConfig;
+use crate::physarum::ensemble::Ensemble;
+use crate::physarum::image::{DynamicImage, ImageFormat};
+use crate::physarum::physarum_model::PhysarumModel;
+use crate::runs::RunIndex;
+use crate::seed::{self, Seed};
+use crate::{check_memory, fill_populations, load_config, override_config, pick_seed};
+use rand::{rngs::SmallRng, SeedableRng};
+use serde_json::Value;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const FIELDS: [&str; 6] = ["seed", "config", "width", "height", "steps", "output"];
+
+/// A render listed in a batch. Whatever is left out comes from the command line, like
+/// for a headless run.
+struct Job {
+    seed: Option<Seed>,
+    config: Option<PathBuf>,
+    width: Option<usize>,
+    height: Option<usize>,
+    steps: Option<usize>,
+    /// Where the final frame is written, with `{seed}` replaced like for `--output`.
+    output: PathBuf,
+}
+
+impl Job {
+    fn from_fields(fields: &[(String, String)]) -> Result<Self, String> {
+        let get = |name: &str| {
+            fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value.as_str())
+        };
+        if let Some((field, _)) = fields.iter().find(|(field, _)| !FIELDS.contains(&&**field)) {
+            return Err(format!(
+                "unknown field {:?}, expected one of {}",
+                field,
+                FIELDS.join(", ")
+            ));
+        }
+        let number = |name: &str| {
+            get(name)
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|_| format!("{} should be a number, got {:?}", name, value))
+                })
+                .transpose()
+        };
+        Ok(Job {
+            seed: get("seed").map(seed::parse).transpose()?,
+            config: get("config").map(PathBuf::from),
+            width: number("width")?,
+            height: number("height")?,
+            steps: number("steps")?,
+            output: get("output")
+                .map(PathBuf::from)
+                .ok_or("output is missing")?,
+        })
+    }
+}
+
+/// Reads the jobs of a CSV file with a header row, or of a JSON array of objects. Empty
+/// cells and nulls are left out.
+fn read_jobs(path: &Path) -> io::Result<Vec<Job>> {
+    let text = std::fs::read_to_string(path)?;
+    let rows: Vec<Vec<(String, String)>> = if path.extension().is_some_and(|ext| ext == "csv") {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header: Vec<&str> = lines
+            .next()
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .collect();
+        lines
+            .map(|line| {
+                header
+                    .iter()
+                    .zip(line.split(',').map(str::trim))
+                    .filter(|(_, value)| !value.is_empty())
+                    .map(|(field, value)| (field.to_string(), value.to_string()))
+                    .collect()
+            })
+            .collect()
+    } else {
+        let jobs: Vec<serde_json::Map<String, Value>> = serde_json::from_str(&text)?;
+        jobs.into_iter()
+            .map(|job| {
+                job.into_iter()
+                    .filter(|(_, value)| !value.is_null())
+                    .map(|(field, value)| match value {
+                        Value::String(value) => (field, value),
+                        value => (field, value.to_string()),
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+    rows.iter()
+        .enumerate()
+        .map(|(k, fields)| {
+            Job::from_fields(fields).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Job {}: {}", k + 1, err),
+                )
+            })
+        })
+        .collect()
+}
+
+/// A job being rendered, kept alongside its model.
+struct Render {
+    /// Position of the job in the file, counting from 0.
+    k: usize,
+    config: SimulationConfig,
+    seed: Seed,
+    steps: usize,
+    step: usize,
+    output: PathBuf,
+    image: DynamicImage,
+    start: Instant,
+}
+
+/// Renders every job of the file at `path`, `concurrent` at a time. The models of those
+/// jobs step together in an `Ensemble` sharing rayon's pool, and a job leaving it makes
+/// room for the next. Every job is recorded in the run index unless `--no-record` is
+/// given. Jobs whose output exists are skipped unless `force` is set, and a failed job
+/// doesn't stop the others, so running a batch again after a failure picks up where it
+/// left off. Frames are written to a temporary file first, so an interrupted job never
+/// leaves a partial image behind to be skipped.
+pub fn run(
+    args: &Args,
+    index: &RunIndex,
+    path: &Path,
+    concurrent: usize,
+    force: bool,
+) -> io::Result<()> {
+    let jobs = read_jobs(path)?;
+    let tag = |k: usize| format!("[{}/{}]", k + 1, jobs.len());
+    let (mut rendered, mut skipped, mut failed) = (0, 0, 0);
+    let mut pending = jobs.iter().enumerate();
+    let mut ensemble = Ensemble::new(vec![]);
+    let mut renders: Vec<Render> = vec![];
+    loop {
+        while renders.len() < concurrent.max(1) {
+            let Some((k, job)) = pending.next() else {
+                break;
+            };
+            match start(args, k, job, force) {
+                Ok(Ok((model, render))) => {
+                    ensemble.push(model);
+                    renders.push(render);
+                }
+                Ok(Err(output)) => {
+                    println!("{} Skipped {}, it exists", tag(k), output.display());
+                    skipped += 1;
+                }
+                Err(err) => {
+                    eprintln!("{} Failed: {}", tag(k), err);
+                    failed += 1;
+                }
+            }
+        }
+        for i in (0..renders.len()).rev() {
+            if renders[i].step < renders[i].steps {
+                continue;
+            }
+            let (model, render) = (ensemble.remove(i), renders.remove(i));
+            let k = render.k;
+            match finish(args, index, &model, render) {
+                Ok(output) => {
+                    println!("{} Saved {}", tag(k), output.display());
+                    rendered += 1;
+                }
+                Err(err) => {
+                    eprintln!("{} Failed: {}", tag(k), err);
+                    failed += 1;
+                }
+            }
+        }
+        if renders.is_empty() {
+            if pending.len() == 0 {
+                break;
+            }
+            continue;
+        }
+        ensemble.each_with(&mut renders, |model, render| {
+            model.step();
+            render.step += 1;
+            // Motion blur averages the frames rendered before the final one.
+            let accumulation_frames = render.config.render.accumulation_frames;
+            if accumulation_frames > 1 && render.step + accumulation_frames > render.steps {
+                model.save_to_image(&mut render.image);
+            }
+        });
+    }
+    println!(
+        "Rendered {}, skipped {}, failed {}",
+        rendered, skipped, failed
+    );
+    if failed > 0 {
+        return Err(io::Error::other(format!(
+            "{} of {} jobs failed, run the batch again to retry them",
+            failed,
+            jobs.len()
+        )));
+    }
+    Ok(())
+}
+
+/// The model of a job ready to step, or the job's output when it exists and the job isn't
+/// `force`d.
+fn start(
+    args: &Args,
+    k: usize,
+    job: &Job,
+    force: bool,
+) -> io::Result<Result<(PhysarumModel, Render), PathBuf>> {
+    let mut config = match &job.config {
+        Some(path) => {
+            let config = SimulationConfig::load(path).map_err(|err| {
+                io::Error::new(err.kind(), format!("config {}: {}", path.display(), err))
+            })?;
+            override_config(args, config)
+        }
+        None => load_config(args),
+    };
+    config.width = job.width.unwrap_or(config.width);
+    config.height = job.height.unwrap_or(config.height);
+    check_memory(args, &config).map_err(io::Error::other)?;
+    let seed = job.seed.clone().unwrap_or_else(|| pick_seed(args, &config));
+    let output = headless::seeded_path(&job.output, &seed);
+    if output.exists() && !force {
+        return Ok(Err(output));
+    }
+
+    let start = Instant::now();
+    fill_populations(&mut config, &mut SmallRng::seed_from_u64(seed.value));
+    let model = headless::build(&config, seed.value)?;
+    let render = Render {
+        k,
+        image: model.new_image(),
+        config,
+        seed,
+        steps: job.steps.unwrap_or(args.steps),
+        step: 0,
+        output,
+        start,
+    };
+    Ok(Ok((model, render)))
+}
+
+/// Saves the final frame of a job that has taken all its steps and records it.
+fn finish(
+    args: &Args,
+    index: &RunIndex,
+    model: &PhysarumModel,
+    mut render: Render,
+) -> io::Result<PathBuf> {
+    model.save_to_image(&mut render.image);
+    let output = render.output;
+    if let Some(dir) = output.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let partial = output.with_extension("partial");
+    render
+        .image
+        .save_with_format(&partial, ImageFormat::Png)
+        .map_err(io::Error::other)?;
+    std::fs::rename(&partial, &output)?;
+
+    if !args.no_record {
+        index.record(
+            &render.config,
+            render.seed.value,
+            render.steps,
+            render.start.elapsed(),
+            std::slice::from_ref(&output),
+        )?;
+    }
+    Ok(output)
+}
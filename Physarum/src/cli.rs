@@ -0,0 +1,732 @@
+use crate::headless::FrameSchedule;
+use crate::led::LedTarget;
+use crate::physarum::annealing::Annealing;
+use crate::physarum::bandpass::Bandpass;
+use crate::physarum::color_vision::ColorVision;
+use crate::physarum::config::SimulationConfig;
+use crate::physarum::crowding::Crowding;
+use crate::physarum::dither::DitherMethod;
+use crate::physarum::food::Food;
+use crate::physarum::heat::Heat;
+use crate::physarum::initial::InitialTrails;
+use crate::physarum::look::Look;
+use crate::physarum::mask::MaskShape;
+use crate::physarum::network::NetworkSettings;
+use crate::physarum::noise::Fbm;
+use crate::physarum::print::ColorProfile;
+use crate::physarum::render::{PopulationColor, GRADIENT_NAMES};
+use crate::physarum::spacing::Spacing;
+use crate::presets;
+use crate::seed::{self, Seed};
+use crate::terminal::TerminalStyle;
+use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[command(about = "Physarum simulation")]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// JSON simulation config to start from
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Curated look to start from instead of a config file: nebula, coral, neural or ink
+    #[arg(long, value_parser = presets::parse, conflicts_with = "config")]
+    pub preset: Option<SimulationConfig>,
+
+    /// Seed overriding the one in the config, a number or words like amber-falcon-dusk
+    #[arg(long, value_parser = seed::parse)]
+    pub seed: Option<Seed>,
+
+    /// Built-in palette to color the populations with, e.g. viridis, overriding the config
+    #[arg(long)]
+    pub palette: Option<String>,
+
+    /// Palette and render settings saved apart from a config, drawing the run with them
+    #[arg(long, value_parser = parse_look)]
+    pub look: Option<Look>,
+
+    /// Color every population through a density gradient: fire, ice, toxic or bone
+    #[arg(long, value_parser = parse_gradient)]
+    pub gradient: Option<PopulationColor>,
+
+    /// Fade a population in or out, as POPULATION:STEP=OPACITY,STEP=OPACITY,... counting
+    /// populations from 1, e.g. 2:0=0,500=1 to bring in the second one over 500 steps.
+    /// Repeatable, once per population
+    #[arg(long, value_parser = parse_fade)]
+    pub fade: Vec<(usize, Vec<(u64, f32)>)>,
+
+    /// `.cube` LUT to color grade the rendered image with, overriding the config
+    #[arg(long)]
+    pub lut: Option<PathBuf>,
+
+    /// Number of dithered ink layers to export alongside renders, overriding the config
+    #[arg(long)]
+    pub ink_layers: Option<usize>,
+
+    /// Dithering used for ink layers: floyd-steinberg or blue-noise
+    #[arg(long)]
+    pub dither: Option<DitherMethod>,
+
+    /// Also save an OpenRaster (.ora) file next to the output, with a layer per population
+    #[arg(long)]
+    pub ora: bool,
+
+    /// Also save the final state next to the output, to render it again with other looks
+    #[arg(long)]
+    pub save_state: bool,
+
+    /// Also save the network the trails form next to the output, as a graph of junctions
+    /// and the lines between them in GraphML or JSON
+    #[arg(long, num_args = 0..=1, default_missing_value = "graphml",
+          value_parser = ["graphml", "json"])]
+    pub network: Option<String>,
+
+    /// Fraction of the cells below the trails that count as the network
+    #[arg(long, default_value_t = NetworkSettings::default().quantile)]
+    pub network_quantile: f32,
+
+    /// Also save the trails of every population next to the output as a NumPy array or
+    /// CSV matrix, for analysis in Python or Julia
+    #[arg(long, num_args = 0..=1, default_missing_value = "npy",
+          value_parser = ["npy", "csv"])]
+    pub matrix: Option<String>,
+
+    /// Average the saved matrices over blocks of this many cells across
+    #[arg(long, default_value_t = 1)]
+    pub matrix_downsample: usize,
+
+    /// Draw the populations as isolines of their density, at the config's contour levels
+    #[arg(long)]
+    pub contours: bool,
+
+    /// Draw the populations as stippled dots following their density
+    #[arg(long)]
+    pub stipple: bool,
+
+    /// Also save the isolines, or the dots when stippling, as an SVG next to the output
+    #[arg(long)]
+    pub svg: bool,
+
+    /// Also save a print-ready TIFF next to the output, with the config's resolution,
+    /// bleed and color profile
+    #[arg(long)]
+    pub tiff: bool,
+
+    /// Pixels per inch the TIFF is printed at
+    #[arg(long)]
+    pub dpi: Option<f32>,
+
+    /// Millimeters of bleed around the TIFF, filled by wrapping the frame around
+    #[arg(long)]
+    pub bleed: Option<f32>,
+
+    /// Color space of the TIFF: srgb or adobe-rgb
+    #[arg(long)]
+    pub color_profile: Option<ColorProfile>,
+
+    /// Also save a copy with histograms, configs, palette and attraction table drawn below
+    /// it, as `<stem>_debug.png`
+    #[arg(long)]
+    pub debug_strip: bool,
+
+    /// Export transparent renders, with alpha following the trail density
+    #[arg(long)]
+    pub transparent: bool,
+
+    /// Render as seen with a color-vision deficiency: protanopia, deuteranopia, tritanopia
+    /// or achromatopsia
+    #[arg(long)]
+    pub color_vision: Option<ColorVision>,
+
+    /// Clip the render to a shape: circle, rounded-rect or the paths of an .svg file
+    #[arg(long)]
+    pub mask: Option<MaskShape>,
+
+    /// Keep the agents inside the mask as well
+    #[arg(long)]
+    pub confine: bool,
+
+    /// Directory whose index.jsonl records every headless render, for `list` and `show`
+    #[arg(long, default_value = "runs")]
+    pub runs_dir: PathBuf,
+
+    /// Don't record this headless render in the runs index
+    #[arg(long)]
+    pub no_record: bool,
+
+    /// Sense the other populations through a difference of blurs, given as the deviations
+    /// INNER,OUTER in cells, e.g. 2,8
+    #[arg(long, value_parser = parse_bandpass)]
+    pub bandpass: Option<Bandpass>,
+
+    /// Cover this many steps of the dynamics per step, e.g. 2 for a quick preview of what
+    /// a render at 1 would look like after twice the steps
+    #[arg(long)]
+    pub time_scale: Option<f32>,
+
+    /// Add random turns that cool down over the run, given as START,STEPS[,COOLING] with
+    /// the temperature at the first step, the steps it takes to reach 0 and linear,
+    /// exponential or cosine cooling, e.g. 1,2000
+    #[arg(long, value_parser = parse_annealing)]
+    pub anneal: Option<Annealing>,
+
+    /// Let agents sense how crowded it is and avoid crowds STRENGTH times as much as a
+    /// trail, counting agents in bins of BIN cells, a power of two: STRENGTH[,BIN].
+    /// Negative strengths gather them instead
+    #[arg(long, value_parser = parse_crowding, allow_hyphen_values = true)]
+    pub crowding: Option<Crowding>,
+
+    /// Keep the agents of each population RADIUS cells apart, up to 8, removing STRENGTH
+    /// of the overlap each step: RADIUS[,STRENGTH]. Keeps filaments from thinning out to
+    /// single cells
+    #[arg(long, value_parser = parse_spacing)]
+    pub spacing: Option<Spacing>,
+
+    /// Lay a heat field of noise with blobs SCALE cells across over the grids, trails
+    /// decaying up to 1 + STRENGTH times as fast where it's hot and down to 1 - STRENGTH
+    /// times where it's cold: STRENGTH[,SCALE]. A SCALE of 0 leaves the heat even, to
+    /// paint on in the window
+    #[arg(long, value_parser = parse_heat)]
+    pub heat: Option<Heat>,
+
+    /// Food sources appearing RATE times per thousand steps, RADIUS cells wide and holding
+    /// CAPACITY food the agents eat up: RATE[,RADIUS[,CAPACITY]]
+    #[arg(long, value_parser = parse_food)]
+    pub food: Option<Food>,
+
+    /// Grow the network between the cities of a CSV of name,longitude,latitude[,weight]
+    /// rows or a GeoJSON of points, drawing the agents like food that never runs out
+    #[arg(long)]
+    pub cities: Option<PathBuf>,
+
+    /// Keep the agents on the land of a map image, white for land and black for sea,
+    /// covering the same bounds as the cities
+    #[arg(long)]
+    pub land: Option<PathBuf>,
+
+    /// What the trails start out as: zeros, noise:SCALE[,OCTAVES[,SEED]] for Perlin noise
+    /// with blobs SCALE cells across, rings:SPACING, stripes:SPACING[,ANGLE] or image:PATH
+    #[arg(long, value_parser = parse_initial_trails)]
+    pub initial_trails: Option<InitialTrails>,
+
+    /// JSON scenario of phases changing the parameters as the run goes, which also sets
+    /// the number of steps of headless runs, see README
+    #[arg(long)]
+    pub scenario: Option<PathBuf>,
+
+    /// Rhai script whose step() function changes the parameters before every step
+    #[arg(long)]
+    pub script: Option<PathBuf>,
+
+    /// Step and render with portable math, so the seed gives identical frames on any machine
+    #[arg(long)]
+    pub strict_determinism: bool,
+
+    /// Move the agents with approximate sines and cosines, faster with slightly different trails
+    #[arg(long)]
+    pub fast_math: bool,
+
+    /// Sort the agents by grid cell every this many steps, which speeds up large runs
+    #[arg(long)]
+    pub agent_sort_interval: Option<usize>,
+
+    /// Run unattended: hide the UI, reseed periodically and archive every cycle
+    #[arg(long)]
+    pub installation: bool,
+
+    /// Switch to the next built-in palette every this many steps of the simulation
+    #[arg(long)]
+    pub palette_every: Option<u64>,
+
+    /// Length of one installation cycle in minutes
+    #[arg(long, default_value_t = 30.0, value_parser = parse_minutes)]
+    pub cycle_minutes: f32,
+
+    /// Directory receiving the final frame and config of every installation cycle
+    #[arg(long, default_value = "archive")]
+    pub archive_dir: PathBuf,
+
+    /// Minutes between autosaves, 0 disables autosaving
+    #[arg(long, default_value_t = 10.0, value_parser = parse_minutes_or_off)]
+    pub autosave_minutes: f32,
+
+    /// Also autosave every this many steps of the simulation
+    #[arg(long)]
+    pub autosave_every: Option<u64>,
+
+    /// Number of rotating autosave slots
+    #[arg(long, default_value_t = 3)]
+    pub autosave_slots: usize,
+
+    /// Directory holding the autosave slots
+    #[arg(long, default_value = "autosave")]
+    pub autosave_dir: PathBuf,
+
+    /// Resume from the newest autosave instead of starting a new simulation
+    #[arg(long)]
+    pub recover: bool,
+
+    /// Number of windows the canvas is split into horizontally, for multi-monitor walls
+    #[arg(long, default_value_t = 1)]
+    pub span_columns: usize,
+
+    /// Number of windows the canvas is split into vertically
+    #[arg(long, default_value_t = 1)]
+    pub span_rows: usize,
+
+    /// Run headless as this many halo-exchanging tiles on local threads
+    #[arg(long, default_value_t = 1)]
+    pub tiles: usize,
+
+    /// Index of this process in `--tile-peers`
+    #[arg(long, default_value_t = 0)]
+    pub tile_rank: usize,
+
+    /// Listen addresses of all tiles of a distributed run, in order, e.g. 10.0.0.1:7000,10.0.0.2:7000
+    #[arg(long, value_delimiter = ',')]
+    pub tile_peers: Vec<String>,
+
+    /// Build every displayed frame on the CPU instead of colorizing it in a shader
+    #[arg(long)]
+    pub cpu_render: bool,
+
+    /// Number of frames captured by the burst hotkey (B)
+    #[arg(long, default_value_t = 60)]
+    pub burst_frames: usize,
+
+    /// Run without a window for `--steps` steps and save the result to `--output`
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Log every change made while the window is open to this file, with the states the
+    /// session started from next to it, for re-rendering it with --replay
+    #[arg(long)]
+    pub record_session: Option<PathBuf>,
+
+    /// Render a session logged with --record-session again without a window, saving its
+    /// final frame to `--output`
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+
+    /// Check the config and print how much memory it would take, then exit without running
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Refuse to start when the estimated memory exceeds this many MiB
+    #[arg(long)]
+    pub memory_budget: Option<usize>,
+
+    /// Preview headless runs in the terminal: half-block, braille, kitty or iterm
+    #[arg(long)]
+    pub preview: Option<TerminalStyle>,
+
+    /// Steps between terminal preview frames
+    #[arg(long, default_value_t = 50)]
+    pub preview_interval: usize,
+
+    /// Mirror the frame on an LED matrix: serial:<device> for Adalight or artnet:<host>
+    #[arg(long)]
+    pub led: Option<LedTarget>,
+
+    /// Number of LED columns
+    #[arg(long, default_value_t = 16)]
+    pub led_columns: usize,
+
+    /// Number of LED rows
+    #[arg(long, default_value_t = 16)]
+    pub led_rows: usize,
+
+    /// Gamma decoding frame colors to the linear light of the LEDs
+    #[arg(long, default_value_t = 2.2)]
+    pub led_gamma: f32,
+
+    /// Scale of the LED output in [0, 1], to stay within the power budget
+    #[arg(long, default_value_t = 0.5)]
+    pub led_brightness: f32,
+
+    /// LED rows are wired in a zigzag, every other row right to left
+    #[arg(long)]
+    pub led_serpentine: bool,
+
+    /// Steps between LED frames of a headless run
+    #[arg(long, default_value_t = 1)]
+    pub led_interval: usize,
+
+    /// Art-Net node receiving population densities and activity levels as DMX channels
+    #[arg(long)]
+    pub dmx: Option<String>,
+
+    /// DMX universe of the statistics channels
+    #[arg(long, default_value_t = 0)]
+    pub dmx_universe: u16,
+
+    /// First DMX channel, counting from 1. Population i uses this plus 2i and 2i + 1
+    #[arg(long, default_value_t = 1)]
+    pub dmx_channel: usize,
+
+    /// Number of simulation steps of a headless run
+    #[arg(long, default_value_t = 1000)]
+    pub steps: usize,
+
+    /// Image written at the end of a headless run. `{seed}` is replaced by the seed's words
+    #[arg(long, default_value = "renders/headless.png")]
+    pub output: PathBuf,
+
+    /// Also save numbered frames of a headless run to this directory, for encoding into a
+    /// video
+    #[arg(long)]
+    pub frames: Option<PathBuf>,
+
+    /// Steps between the frames saved to --frames, by default those between rendering
+    /// frames
+    #[arg(long)]
+    pub frame_every: Option<usize>,
+
+    /// Save the frames at these steps instead: exp for steps 1, 2, 4, 8 and on, exp:RATIO
+    /// for steps growing by another ratio, or a list like 1,10,100,1000
+    #[arg(long, conflicts_with = "frame_every")]
+    pub frame_schedule: Option<FrameSchedule>,
+
+    /// Also write chapters.txt and cuts.csv to --frames, marking the frames where the
+    /// trails change suddenly or the network gains or loses loops, for editing the video
+    #[arg(long)]
+    pub highlights: bool,
+
+    /// Frame rate the videos of --frames play at, for the times of the highlights
+    #[arg(long, default_value_t = 30.0)]
+    pub video_fps: f32,
+
+    /// Steps between rendering frames, in the window, its recording and --frames, for
+    /// time-lapses that skip coloring the steps in between
+    #[arg(long)]
+    pub render_every: Option<usize>,
+
+    /// Frames saved per --frame-every steps, the ones in between interpolated from the
+    /// trails of the steps around them, e.g. 4 to play 30 steps a second at 120 fps
+    #[arg(long, default_value_t = 1)]
+    pub slow_motion: usize,
+
+    /// Also save the state of every simulated frame to --frames, to render the sequence
+    /// again with `rerender`
+    #[arg(long)]
+    pub frame_states: bool,
+}
+
+/// Browsing the runs recorded by headless renders, rendering many at once, and comparing
+/// their results.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List the recorded runs, oldest first
+    List,
+    /// Print everything recorded about a run, including its full config
+    Show { id: u64 },
+    /// Write an HTML page of every run with thumbnails and replay commands
+    Gallery,
+    /// Render every job listed in a JSON or CSV file, skipping those already rendered
+    Batch {
+        /// JSON array of jobs, or CSV with a header, with the fields seed, config, width,
+        /// height, steps and output. Only output is required
+        jobs: PathBuf,
+        /// Number of jobs rendered at the same time, sharing the cores between them
+        #[arg(long, default_value_t = 1)]
+        concurrent: usize,
+        /// Render jobs again even when their output exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Render a signed heatmap per population of how the trails changed between two saved
+    /// states or frames, orange where they grew and blue where they shrank
+    Diff {
+        /// State or frame before the change
+        before: PathBuf,
+        /// State or frame after the change, of the same size
+        after: PathBuf,
+        #[arg(long, default_value = "renders/diff.png")]
+        output: PathBuf,
+    },
+    /// Render a saved state again with each of the looks, palettes and render settings
+    /// saved with L in the window or written by hand
+    Render {
+        /// State saved by `--save-state` or an autosave
+        state: PathBuf,
+        /// Looks to render, `--look` or the look of `--config` when there are none
+        looks: Vec<PathBuf>,
+        /// Where the render goes, with the look's name appended when there are several
+        #[arg(long, default_value = "renders/look.png")]
+        output: PathBuf,
+    },
+    /// Render a series of saved states again with `--look` or the look of `--config`,
+    /// e.g. the frames of a video, without simulating them again
+    Rerender {
+        /// States saved by `--save-state`, `--frame-states` or an autosave
+        #[arg(required = true)]
+        states: Vec<PathBuf>,
+        /// Times the grids are enlarged by before rendering, a power of two, for frames
+        /// larger than the run
+        #[arg(long, default_value_t = 1)]
+        scale: usize,
+        /// Directory the frames are saved to, named after the states
+        #[arg(long, default_value = "renders/rerender")]
+        output: PathBuf,
+    },
+    /// Rank random variations of the config by picking the better of two short renders
+    /// with the arrow keys, refining the field with variations of the favourites
+    Tournament {
+        /// Number of configs competing at a time
+        #[arg(long, default_value_t = 16)]
+        entrants: usize,
+        /// Steps each render runs for
+        #[arg(long, default_value_t = 300)]
+        steps: usize,
+        /// Pixels along the longer side of each render
+        #[arg(long, default_value_t = 256)]
+        size: usize,
+        /// Directory the standings and leading configs are saved to
+        #[arg(long, default_value = "tournament")]
+        output: PathBuf,
+    },
+}
+
+fn parse_gradient(name: &str) -> Result<PopulationColor, String> {
+    PopulationColor::gradient(name).ok_or_else(|| {
+        format!(
+            "Unknown gradient {:?}, expected one of {}",
+            name,
+            GRADIENT_NAMES.join(", ")
+        )
+    })
+}
+
+fn parse_fade(value: &str) -> Result<(usize, Vec<(u64, f32)>), String> {
+    let expected = || format!("Expected POPULATION:STEP=OPACITY,..., got {:?}", value);
+    let (population, keys) = value.split_once(':').ok_or_else(expected)?;
+    let population = match population.trim().parse::<usize>() {
+        Ok(population) if population > 0 => population - 1,
+        _ => return Err(format!("Populations count from 1, got {:?}", population)),
+    };
+    let mut keyframes = keys
+        .split(',')
+        .map(|key| {
+            let (step, opacity) = key.split_once('=').ok_or_else(expected)?;
+            let step = step
+                .trim()
+                .parse::<u64>()
+                .map_err(|err| format!("{:?}: {}", step, err));
+            let opacity = opacity
+                .trim()
+                .parse::<f32>()
+                .map_err(|err| format!("{:?}: {}", opacity, err));
+            Ok((step?, opacity?))
+        })
+        .collect::<Result<Vec<(u64, f32)>, String>>()?;
+    keyframes.sort_by_key(|&(step, _)| step);
+    Ok((population, keyframes))
+}
+
+fn parse_initial_trails(value: &str) -> Result<InitialTrails, String> {
+    let (kind, params) = value.split_once(':').unwrap_or((value, ""));
+    let numbers = || {
+        params
+            .split(',')
+            .map(|p| {
+                p.trim()
+                    .parse::<f32>()
+                    .map_err(|err| format!("{:?}: {}", p, err))
+            })
+            .collect::<Result<Vec<f32>, String>>()
+    };
+    let pattern = match (kind, numbers().as_deref()) {
+        ("zeros", _) if params.is_empty() => InitialTrails::Zeros,
+        ("image", _) if !params.is_empty() => InitialTrails::Image {
+            path: PathBuf::from(params),
+        },
+        ("noise", Ok(&[scale])) => InitialTrails::Noise(Fbm::new(scale, 4, 0)),
+        ("noise", Ok(&[scale, octaves])) => {
+            InitialTrails::Noise(Fbm::new(scale, octaves as usize, 0))
+        }
+        ("noise", Ok(&[scale, octaves, seed])) => {
+            InitialTrails::Noise(Fbm::new(scale, octaves as usize, seed as u64))
+        }
+        ("rings", Ok(&[spacing])) => InitialTrails::Rings { spacing },
+        ("stripes", Ok(&[spacing])) => InitialTrails::Stripes {
+            spacing,
+            angle: 0.0,
+        },
+        ("stripes", Ok(&[spacing, angle])) => InitialTrails::Stripes { spacing, angle },
+        (_, Err(err)) if ["noise", "rings", "stripes"].contains(&kind) => return Err(err.clone()),
+        _ => {
+            return Err(format!(
+                "Expected zeros, noise:SCALE[,OCTAVES[,SEED]], rings:SPACING, \
+                 stripes:SPACING[,ANGLE] or image:PATH, got {:?}",
+                value
+            ))
+        }
+    };
+    Ok(pattern)
+}
+
+fn parse_minutes(value: &str) -> Result<f32, String> {
+    let minutes = value
+        .trim()
+        .parse::<f32>()
+        .map_err(|err| format!("{:?}: {}", value, err))?;
+    match Duration::try_from_secs_f32(minutes * 60.0) {
+        Ok(_) if minutes > 0.0 => Ok(minutes),
+        _ => Err(format!(
+            "Expected a positive, finite number of minutes, got {:?}",
+            value
+        )),
+    }
+}
+
+fn parse_minutes_or_off(value: &str) -> Result<f32, String> {
+    match value.trim().parse::<f32>() {
+        Ok(0.0) => Ok(0.0),
+        _ => parse_minutes(value).map_err(|_| {
+            format!(
+                "Expected a positive, finite number of minutes or 0, got {:?}",
+                value
+            )
+        }),
+    }
+}
+
+fn parse_look(path: &str) -> Result<Look, String> {
+    Look::load(Path::new(path)).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn parse_annealing(value: &str) -> Result<Annealing, String> {
+    let mut parts = value.split(',').map(str::trim);
+    let (Some(start), Some(steps)) = (parts.next(), parts.next()) else {
+        return Err(format!("Expected START,STEPS[,COOLING], got {:?}", value));
+    };
+    let mut annealing = Annealing {
+        start: start
+            .parse()
+            .map_err(|err| format!("{:?}: {}", start, err))?,
+        end: 0.0,
+        steps: steps
+            .parse()
+            .map_err(|err| format!("{:?}: {}", steps, err))?,
+        ..Annealing::default()
+    };
+    if let Some(cooling) = parts.next() {
+        annealing.cooling = cooling.parse()?;
+    }
+    if parts.next().is_some() {
+        return Err(format!("Expected START,STEPS[,COOLING], got {:?}", value));
+    }
+    Ok(annealing)
+}
+
+fn parse_crowding(value: &str) -> Result<Crowding, String> {
+    let (strength, bin_size) = value.split_once(',').unwrap_or((value, ""));
+    let mut crowding = Crowding {
+        strength: strength
+            .trim()
+            .parse()
+            .map_err(|err| format!("{:?}: {}", strength, err))?,
+        ..Crowding::default()
+    };
+    if !bin_size.is_empty() {
+        crowding.bin_size = bin_size
+            .trim()
+            .parse()
+            .map_err(|err| format!("{:?}: {}", bin_size, err))?;
+    }
+    Ok(crowding)
+}
+
+fn parse_spacing(value: &str) -> Result<Spacing, String> {
+    let (radius, strength) = value.split_once(',').unwrap_or((value, ""));
+    let mut spacing = Spacing {
+        radius: radius
+            .trim()
+            .parse()
+            .map_err(|err| format!("{:?}: {}", radius, err))?,
+        ..Spacing::default()
+    };
+    if !strength.is_empty() {
+        spacing.strength = strength
+            .trim()
+            .parse()
+            .map_err(|err| format!("{:?}: {}", strength, err))?;
+    }
+    Ok(spacing)
+}
+
+fn parse_heat(value: &str) -> Result<Heat, String> {
+    let (strength, scale) = value.split_once(',').unwrap_or((value, ""));
+    let mut heat = Heat {
+        strength: strength
+            .trim()
+            .parse()
+            .map_err(|err| format!("{:?}: {}", strength, err))?,
+        ..Heat::default()
+    };
+    if !scale.is_empty() {
+        let scale: f32 = scale
+            .trim()
+            .parse()
+            .map_err(|err| format!("{:?}: {}", scale, err))?;
+        heat.noise = (scale != 0.0).then(|| Fbm::new(scale, 3, 0));
+    }
+    Ok(heat)
+}
+
+fn parse_food(value: &str) -> Result<Food, String> {
+    let numbers = value
+        .split(',')
+        .map(|number| {
+            number
+                .trim()
+                .parse::<f32>()
+                .map_err(|err| format!("{:?}: {}", number, err))
+        })
+        .collect::<Result<Vec<f32>, String>>()?;
+    let food = match numbers[..] {
+        [spawn_rate] => Food {
+            spawn_rate,
+            ..Food::default()
+        },
+        [spawn_rate, radius] => Food {
+            spawn_rate,
+            radius,
+            ..Food::default()
+        },
+        [spawn_rate, radius, capacity] => Food {
+            spawn_rate,
+            radius,
+            capacity,
+            ..Food::default()
+        },
+        _ => {
+            return Err(format!(
+                "Expected RATE[,RADIUS[,CAPACITY]], got {:?}",
+                value
+            ))
+        }
+    };
+    food.check().map_err(|err| err.to_string())?;
+    Ok(food)
+}
+
+fn parse_bandpass(value: &str) -> Result<Bandpass, String> {
+    let (inner, outer) = value
+        .split_once(',')
+        .ok_or_else(|| format!("Expected INNER,OUTER, got {:?}", value))?;
+    let parse = |sigma: &str| {
+        sigma
+            .trim()
+            .parse::<f32>()
+            .map_err(|err| format!("{:?}: {}", sigma, err))
+    };
+    Ok(Bandpass {
+        inner: parse(inner)?,
+        outer: parse(outer)?,
+    })
+}
@@ -0,0 +1,94 @@
+use crate::physarum::difference::{self, Difference};
+use crate::physarum::image::{self, GenericImage, ImageFormat, RgbImage};
+use crate::physarum::state::ModelState;
+use std::io;
+use std::path::Path;
+
+/// Trails to compare, read from a saved state or a frame.
+struct Snapshot {
+    width: usize,
+    height: usize,
+    /// The trails of every population, or the brightness of a frame as a single layer.
+    layers: Vec<Vec<f32>>,
+}
+
+impl Snapshot {
+    /// Frames are recognized by their image extension, anything else has to be a state
+    /// written by an autosave or `PhysarumModel::state`.
+    fn load(path: &Path) -> io::Result<Self> {
+        let named =
+            |err: io::Error| io::Error::new(err.kind(), format!("{}: {}", path.display(), err));
+        if ImageFormat::from_path(path).is_ok() {
+            let frame = image::open(path)
+                .map_err(|err| named(io::Error::new(io::ErrorKind::InvalidData, err)))?
+                .into_luma8();
+            let layer = frame
+                .pixels()
+                .map(|pixel| pixel[0] as f32 / 255.0)
+                .collect();
+            return Ok(Snapshot {
+                width: frame.width() as usize,
+                height: frame.height() as usize,
+                layers: vec![layer],
+            });
+        }
+        let state = ModelState::load(path).map_err(named)?;
+        Ok(Snapshot {
+            width: state.width(),
+            height: state.height(),
+            layers: state.grids().to_vec(),
+        })
+    }
+}
+
+/// Compares two runs of the same size, writing a heatmap of `after - before` for every
+/// population side by side to `output` and printing how much each changed. Every panel
+/// saturates at its own scale, which the summary reports.
+pub fn run(before: &Path, after: &Path, output: &Path) -> io::Result<()> {
+    let (before, after) = (Snapshot::load(before)?, Snapshot::load(after)?);
+    if (before.width, before.height) != (after.width, after.height) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Can't compare a {}x{} grid with a {}x{} one",
+                before.width, before.height, after.width, after.height
+            ),
+        ));
+    }
+    if before.layers.len() != after.layers.len() {
+        eprintln!(
+            "Comparing the first {} populations of {} and {}",
+            before.layers.len().min(after.layers.len()),
+            before.layers.len(),
+            after.layers.len()
+        );
+    }
+
+    let (width, height) = (before.width, before.height);
+    let panels = before.layers.len().min(after.layers.len());
+    let mut canvas = RgbImage::new((width * panels) as u32, height as u32);
+    for (i, (b, a)) in before.layers.iter().zip(&after.layers).enumerate() {
+        let scale = difference::heatmap_scale(b, a);
+        let panel = difference::heatmap(b, a, width, height, scale);
+        canvas
+            .copy_from(&panel, (i * width) as u32, 0)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let Difference {
+            mean,
+            mean_abs,
+            max_abs,
+        } = Difference::between(b, a);
+        println!(
+            "Population {}: mean change {:+.4}, mean size {:.4}, largest {:.4}, saturating at {:.4}",
+            i, mean, mean_abs, max_abs, scale
+        );
+    }
+    if let Some(dir) = output.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    canvas
+        .save_with_format(output, ImageFormat::Png)
+        .map_err(io::Error::other)?;
+    println!("Wrote {}", output.display());
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use crate::cli::Args;
+use crate::physarum::config::SimulationConfig;
+use crate::physarum::initial::InitialTrails;
+use crate::physarum::physarum_model::PhysarumModel;
+use crate::physarum::population_config::PopulationConfig;
+use crate::physarum::sensing;
+use crate::physarum::tiled::{ChannelTransport, HaloTransport, TcpTransport, TiledModel};
+use rand::{rngs::SmallRng, SeedableRng};
+use std::io;
+use std::net::SocketAddr;
+
+/// Runs a domain-decomposed simulation without a window, either as several local threads
+/// (`--tiles`) or as one tile of a ring of processes (`--tile-rank` with `--tile-peers`).
+/// The first tile gathers the canvas at the end and writes it to `--output`.
+pub fn run(args: &Args, config: SimulationConfig, seed: u64) -> io::Result<()> {
+    let configs = config.populations[..config.n_populations].to_vec();
+    let unsupported = |feature: &str| {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Tiled runs don't support {} yet", feature),
+        ))
+    };
+    if config.bandpass.is_some() {
+        return unsupported("the band-pass attraction");
+    }
+    if configs.iter().any(|config| config.self_repulsion != 0.0) {
+        return unsupported("self-repulsion");
+    }
+    if configs.iter().any(|config| config.max_substep != 0.0) {
+        return unsupported("substepping");
+    }
+    if !config.deposit_ops.is_empty() || !config.diffuse_ops.is_empty() {
+        return unsupported("deposit or diffuse operators");
+    }
+    if args.scenario.is_some() {
+        return unsupported("scenarios");
+    }
+    if config.crowding.is_some() {
+        return unsupported("crowding");
+    }
+    if config.spacing.is_some() {
+        return unsupported("spacing the agents");
+    }
+    if config.heat.is_some() {
+        return unsupported("heat");
+    }
+    if config.food.is_some() {
+        return unsupported("food sources");
+    }
+    if config.cities.is_some() {
+        return unsupported("cities");
+    }
+    if config.initial_trails != InitialTrails::Uniform {
+        return unsupported("initial trail patterns");
+    }
+    if config.render.mask.is_active() && config.render.mask.confine {
+        return unsupported("confining the agents to a mask");
+    }
+    let attraction_table = config.attraction_table.clone().unwrap_or_else(|| {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        PhysarumModel::random_attraction_table(config.n_populations, &config.attraction, &mut rng)
+    });
+    sensing::check(&config.sensing, config.n_populations)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let attraction_table = sensing::table(&config.sensing, &attraction_table);
+    if args.tile_peers.is_empty() {
+        let tiles: Vec<_> = ChannelTransport::ring(args.tiles.max(1))
+            .into_iter()
+            .map(|transport| new_tile(&config, &configs, &attraction_table, seed, transport))
+            .collect::<io::Result<_>>()?;
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = tiles
+                .into_iter()
+                .map(|tile| scope.spawn(|| run_tile(tile, args, &config)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        results.into_iter().collect()
+    } else {
+        let peers = args
+            .tile_peers
+            .iter()
+            .map(|peer| {
+                peer.parse::<SocketAddr>()
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        if args.tile_rank >= peers.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Tile rank must index into the peer list",
+            ));
+        }
+        let transport = TcpTransport::connect(args.tile_rank, &peers)?;
+        let tile = new_tile(&config, &configs, &attraction_table, seed, transport)?;
+        run_tile(tile, args, &config)
+    }
+}
+
+fn new_tile<T: HaloTransport>(
+    config: &SimulationConfig,
+    configs: &[PopulationConfig],
+    attraction_table: &[Vec<f32>],
+    seed: u64,
+    transport: T,
+) -> io::Result<TiledModel<T>> {
+    let mut tile = TiledModel::new(
+        config.width,
+        config.height,
+        config.n_particles,
+        configs.to_vec(),
+        attraction_table.to_vec(),
+        config.diffusivity,
+        seed,
+        transport,
+    )
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    tile.set_strict_determinism(config.strict_determinism);
+    tile.set_fast_math(config.fast_math);
+    tile.set_deposition_table(config.deposition_table.clone())
+        .and_then(|_| tile.set_steering(config.steering.clone()))
+        .and_then(|_| tile.set_time_scale(config.time_scale))
+        .and_then(|_| tile.set_annealing(config.annealing))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    Ok(tile)
+}
+
+fn run_tile<T: HaloTransport>(
+    mut tile: TiledModel<T>,
+    args: &Args,
+    config: &SimulationConfig,
+) -> io::Result<()> {
+    for _ in 0..args.steps {
+        tile.step()?;
+    }
+    if let Some(grids) = tile.gather()? {
+        let mut model = tile
+            .assemble(grids, &config.palette)
+            .map_err(io::Error::other)?;
+        model
+            .set_render_settings(config.render.clone())
+            .map_err(io::Error::other)?;
+        let mut image = model.new_image();
+        model.save_to_image(&mut image);
+        image
+            .save_with_format(&args.output, crate::physarum::image::ImageFormat::Png)
+            .map_err(io::Error::other)?;
+        println!("Saved {}", args.output.display());
+        let ink = &config.render.ink;
+        if ink.layers > 0 {
+            crate::save_ink_layers(&args.output, &model.ink_layers(ink.layers, ink.method))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use crate::artnet::ArtNetSender;
+use crate::cli::Args;
+use crate::physarum::physarum_model::PhysarumModel;
+use std::io;
+
+/// Grid cells skipped between the samples statistics are taken from.
+const SAMPLE_STRIDE: usize = 61;
+/// Change in brightness per frame mapped to a full activity channel.
+const FULL_ACTIVITY: f32 = 0.02;
+/// Weight of the previous values, so lights fade instead of flickering.
+const SMOOTHING: f32 = 0.8;
+
+/// Sends two DMX channels per population over Art-Net, so stage lighting can follow the
+/// simulation: the population's mean brightness on screen, then how fast it is changing.
+pub struct DmxStats {
+    sender: ArtNetSender,
+    universe: u16,
+    /// Zero based offset of the first population's density channel.
+    offset: usize,
+    previous: Vec<Vec<f32>>,
+    channels: Vec<f32>,
+}
+
+impl DmxStats {
+    /// The sender configured with `--dmx`, if any.
+    pub fn from_args(args: &Args) -> io::Result<Option<Self>> {
+        args.dmx
+            .as_ref()
+            .map(|host| {
+                Ok(DmxStats {
+                    sender: ArtNetSender::open(host)?,
+                    universe: args.dmx_universe,
+                    offset: args.dmx_channel.saturating_sub(1),
+                    previous: vec![],
+                    channels: vec![],
+                })
+            })
+            .transpose()
+    }
+
+    pub fn send(&mut self, model: &PhysarumModel) -> io::Result<()> {
+        let fields = model.layer_fields();
+        let max_values = model.layer_max_values(&fields);
+        let samples: Vec<Vec<f32>> = fields
+            .iter()
+            .zip(&max_values)
+            .map(|(field, max_value)| {
+                field
+                    .iter()
+                    .step_by(SAMPLE_STRIDE)
+                    .map(|v| (v / max_value).clamp(0.0, 1.0).powf(1.0 / 2.2))
+                    .collect()
+            })
+            .collect();
+
+        let mut values = Vec::with_capacity(2 * samples.len());
+        for (i, sample) in samples.iter().enumerate() {
+            let density = sample.iter().sum::<f32>() / sample.len().max(1) as f32;
+            let activity = match self.previous.get(i) {
+                Some(previous) if previous.len() == sample.len() => {
+                    let change: f32 = sample
+                        .iter()
+                        .zip(previous)
+                        .map(|(a, b)| (a - b).abs())
+                        .sum();
+                    change / sample.len().max(1) as f32 / FULL_ACTIVITY
+                }
+                _ => 0.0,
+            };
+            values.push(density);
+            values.push(activity.min(1.0));
+        }
+        if self.channels.len() != values.len() {
+            self.channels = values;
+        } else {
+            for (channel, value) in self.channels.iter_mut().zip(values) {
+                *channel = SMOOTHING * *channel + (1.0 - SMOOTHING) * value;
+            }
+        }
+        self.previous = samples;
+
+        let mut dmx = vec![0; self.offset];
+        dmx.extend(self.channels.iter().map(|c| (c * 255.0).round() as u8));
+        self.sender.next_frame();
+        self.sender.send(self.universe, &dmx)
+    }
+}
@@ -0,0 +1,122 @@
+use crate::runs::RunIndex;
+use serde_json::Value;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Longest side of the thumbnails, in pixels.
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Writes a static page of every recorded run to `gallery/index.html` in the runs
+/// directory, with a thumbnail, the config and the command replaying it, for picking the
+/// keepers out of a large exploration batch. Thumbnails are only made once per run.
+pub fn generate(index: &RunIndex, runs_dir: &Path) -> io::Result<PathBuf> {
+    let dir = runs_dir.join("gallery");
+    fs::create_dir_all(dir.join("thumbnails"))?;
+    fs::create_dir_all(dir.join("configs"))?;
+
+    let mut cards = String::new();
+    for record in index.records()?.iter().rev() {
+        let id = record["id"].as_u64().unwrap_or(0);
+        let config_path = dir.join("configs").join(format!("{}.json", id));
+        fs::write(
+            &config_path,
+            serde_json::to_string_pretty(&record["config"])?,
+        )?;
+
+        let output = record["outputs"][0].as_str().map(PathBuf::from);
+        let thumbnail = format!("thumbnails/{}.png", id);
+        let image = match &output {
+            Some(output) => match make_thumbnail(output, &dir.join(&thumbnail)) {
+                Ok(()) => format!("<img src=\"{}\" loading=\"lazy\">", escape(&thumbnail)),
+                Err(err) => format!("<div class=\"missing\">{}</div>", escape(&err.to_string())),
+            },
+            None => "<div class=\"missing\">No output</div>".to_string(),
+        };
+        let replay = format!(
+            "cargo run --release -- --headless --config {} --seed {} --steps {} --output {}",
+            config_path.display(),
+            record["seed"],
+            record["steps"],
+            output.as_deref().unwrap_or(Path::new("out.png")).display()
+        );
+        let outputs: Vec<&str> = record["outputs"]
+            .as_array()
+            .map(|outputs| outputs.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        let commit = record["git_commit"].as_str().unwrap_or("?");
+        let commit = &commit[..commit.len().min(7)];
+        let _ = write!(
+            cards,
+            r#"<section>
+{image}
+<h2>Run {id}</h2>
+<p>{time} &middot; {duration:.1}s &middot; seed {seed} &middot; config {hash} &middot; commit {commit}</p>
+<p>{outputs}</p>
+<pre class="replay">{replay}</pre>
+<details><summary>Config</summary><pre>{config}</pre></details>
+</section>
+"#,
+            time = escape(record["time"].as_str().unwrap_or("?")),
+            duration = record["duration_secs"].as_f64().unwrap_or(0.0),
+            seed = record["seed"],
+            hash = escape(record["config_hash"].as_str().unwrap_or("?")),
+            commit = escape(commit),
+            outputs = escape(&outputs.join(", ")),
+            replay = escape(&replay),
+            config = escape(&serde_json::to_string_pretty(&record["config"])?),
+        );
+    }
+
+    let page = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Physarum runs</title>
+<style>
+body {{ background: #111; color: #ddd; font-family: sans-serif; margin: 2em; }}
+main {{ display: grid; grid-template-columns: repeat(auto-fill, minmax({size}px, 1fr)); gap: 1.5em; }}
+section {{ background: #1c1c1c; padding: 1em; border-radius: 4px; overflow: hidden; }}
+img, .missing {{ width: 100%; aspect-ratio: 1; object-fit: contain; background: #000; }}
+.missing {{ display: flex; align-items: center; justify-content: center; color: #777; }}
+h2 {{ margin: 0.5em 0 0; font-size: 1.1em; }}
+p {{ font-size: 0.85em; color: #999; word-break: break-all; }}
+pre {{ font-size: 0.75em; white-space: pre-wrap; word-break: break-all; }}
+.replay {{ background: #000; padding: 0.5em; user-select: all; }}
+</style>
+</head>
+<body>
+<h1>Physarum runs</h1>
+<main>
+{cards}</main>
+</body>
+</html>
+"#,
+        size = THUMBNAIL_SIZE,
+        cards = cards
+    );
+    let path = dir.join("index.html");
+    fs::write(&path, page)?;
+    Ok(path)
+}
+
+fn make_thumbnail(output: &Path, thumbnail: &Path) -> io::Result<()> {
+    if thumbnail.exists() {
+        return Ok(());
+    }
+    let image = crate::physarum::image::open(output)
+        .map_err(|err| io::Error::other(format!("{}: {}", output.display(), err)))?;
+    image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .save(thumbnail)
+        .map_err(io::Error::other)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
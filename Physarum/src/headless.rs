@@ -0,0 +1,427 @@
+use crate::cli::Args;
+use crate::dmx::DmxStats;
+use crate::led::LedMatrix;
+use crate::physarum::builder::PhysarumModelBuilder;
+use crate::physarum::config::SimulationConfig;
+use crate::physarum::highlights::{HighlightDetector, HighlightSettings};
+use crate::physarum::image::DynamicImage;
+use crate::physarum::network::{Network, NetworkSettings};
+use crate::physarum::physarum_model::PhysarumModel;
+use crate::physarum::scenario::ScenarioRunner;
+use crate::physarum::state::ModelState;
+use crate::script::Script;
+use crate::seed::Seed;
+use crate::terminal;
+use rand::{rngs::SmallRng, SeedableRng};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The model of a config with its populations filled in, rendering with its settings.
+pub fn build(config: &SimulationConfig, seed: u64) -> io::Result<PhysarumModel> {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut model = PhysarumModelBuilder::from_config(config)
+        .build_with_rng(&mut rng)
+        .map_err(io::Error::other)?;
+    model
+        .set_population_configs(config.populations.clone())
+        .map_err(io::Error::other)?;
+    model
+        .set_render_settings(config.render.clone())
+        .map_err(io::Error::other)?;
+    crate::warn_generated_colors(&model);
+    Ok(model)
+}
+
+/// Every this many frame states, one is saved in full and the others as their difference
+/// from it, so that loading any of them reads at most two files.
+const KEYFRAME_INTERVAL: usize = 16;
+
+/// The steps that `--frames` saves.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrameSchedule {
+    /// Every this many steps.
+    Every(usize),
+    /// Step 1 and then each step this ratio times the one before, rounded up and at least
+    /// the next step, e.g. 1, 2, 4, 8 for 2, for time-lapses that show the early growth as
+    /// well as the mature network.
+    Exponential(f64),
+    /// These steps.
+    Steps(Vec<usize>),
+}
+
+impl FrameSchedule {
+    fn due(&self, step: usize) -> bool {
+        match self {
+            FrameSchedule::Every(every) => step.is_multiple_of((*every).max(1)),
+            FrameSchedule::Exponential(ratio) => {
+                let mut next = 1;
+                while next < step {
+                    next = (next + 1).max((next as f64 * ratio).ceil() as usize);
+                }
+                next == step
+            }
+            FrameSchedule::Steps(steps) => steps.contains(&step),
+        }
+    }
+}
+
+impl FromStr for FrameSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid frame schedule {:?}, expected exp, exp:RATIO or STEP,STEP,...",
+                s
+            )
+        };
+        match s.split_once(':') {
+            _ if s == "exp" => Ok(FrameSchedule::Exponential(2.0)),
+            Some(("exp", ratio)) => match ratio.parse::<f64>() {
+                Ok(ratio) if ratio > 1.0 && ratio.is_finite() => {
+                    Ok(FrameSchedule::Exponential(ratio))
+                }
+                _ => Err(invalid()),
+            },
+            _ => s
+                .split(',')
+                .map(|step| step.trim().parse::<usize>())
+                .collect::<Result<_, _>>()
+                .map(FrameSchedule::Steps)
+                .map_err(|_| invalid()),
+        }
+    }
+}
+
+/// Numbered frames of a run for `--frames`, with `--slow-motion` frames in between.
+struct FrameSequence {
+    dir: PathBuf,
+    schedule: FrameSchedule,
+    slow_motion: usize,
+    /// Also save the state of every simulated frame, for `rerender`.
+    states: bool,
+    states_saved: usize,
+    /// The last state saved in full and its file name, which the next ones are saved
+    /// against.
+    keyframe: Option<(ModelState, PathBuf)>,
+    frames: usize,
+    /// The trails of the last frame saved, which the next interpolated ones start from.
+    previous: Option<Vec<Vec<f32>>>,
+    highlights: Option<HighlightDetector>,
+}
+
+impl FrameSequence {
+    fn from_args(args: &Args, config: &SimulationConfig) -> io::Result<Option<Self>> {
+        let Some(dir) = &args.frames else {
+            return Ok(None);
+        };
+        std::fs::create_dir_all(dir)?;
+        Ok(Some(FrameSequence {
+            dir: dir.clone(),
+            schedule: args.frame_schedule.clone().unwrap_or_else(|| {
+                FrameSchedule::Every(args.frame_every.unwrap_or(config.render_every_n_steps))
+            }),
+            slow_motion: args.slow_motion.max(1),
+            states: args.frame_states,
+            states_saved: 0,
+            keyframe: None,
+            frames: 0,
+            previous: None,
+            highlights: args
+                .highlights
+                .then(|| HighlightDetector::new(HighlightSettings::default())),
+        }))
+    }
+
+    /// Saves the frames leading up to `step` when it is due.
+    fn capture(
+        &mut self,
+        model: &mut PhysarumModel,
+        image: &mut DynamicImage,
+        step: usize,
+    ) -> io::Result<()> {
+        if !self.schedule.due(step) {
+            return Ok(());
+        }
+        if let Some(previous) = self.previous.take() {
+            for k in 1..self.slow_motion {
+                model.save_interpolated(&previous, k as f32 / self.slow_motion as f32, image);
+                self.save(image)?;
+            }
+        }
+        model.save_to_image(image);
+        if let Some(detector) = &mut self.highlights {
+            let network = model.network(&NetworkSettings::default());
+            let field = model.combined_field();
+            for highlight in
+                detector.observe(self.frames, model.iteration(), &field, Some(&network))
+            {
+                println!(
+                    "Highlight at frame {}, step {}",
+                    highlight.frame, highlight.step
+                );
+            }
+        }
+        if self.states {
+            let name = PathBuf::from(format!("frame_{:05}.state", self.frames));
+            let path = self.dir.join(&name);
+            let state = model.state();
+            match &self.keyframe {
+                Some((base, base_name)) if !self.states_saved.is_multiple_of(KEYFRAME_INTERVAL) => {
+                    state.save_delta(&path, base, base_name)?
+                }
+                _ => {
+                    state.save(&path)?;
+                    self.keyframe = Some((state, name));
+                }
+            }
+            self.states_saved += 1;
+        }
+        self.save(image)?;
+        if self.slow_motion > 1 {
+            self.previous = Some(model.trails());
+        }
+        Ok(())
+    }
+
+    fn save(&mut self, image: &DynamicImage) -> io::Result<()> {
+        let path = self.dir.join(format!("frame_{:05}.png", self.frames));
+        image
+            .save_with_format(&path, crate::physarum::image::ImageFormat::Png)
+            .map_err(io::Error::other)?;
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+/// The phase's name, or its number in the scenario.
+pub fn phase_label(scenario: &ScenarioRunner, phase: usize) -> String {
+    match &scenario.scenario().phases[phase].name {
+        Some(name) => format!("Phase {:?}", name),
+        None => format!("Phase {}", phase + 1),
+    }
+}
+
+/// The path with `{seed}` replaced by the seed's words, or its number.
+pub fn seeded_path(path: &Path, seed: &Seed) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().replace("{seed}", &seed.label()))
+}
+
+/// Runs the simulation without a window for `--steps` steps and writes the final frame
+/// to `--output`, optionally previewing it in the terminal along the way. Returns the
+/// paths of every file written.
+pub fn run(args: &Args, mut config: SimulationConfig, seed: &Seed) -> io::Result<Vec<PathBuf>> {
+    let mut model = build(&config, seed.value)?;
+    let mut script = args.script.as_deref().map(Script::load).transpose()?;
+    let mut scenario = crate::load_scenario(args).map(ScenarioRunner::new);
+    let mut image = model.new_image();
+
+    let mut led = LedMatrix::from_args(args)?;
+    let mut dmx = DmxStats::from_args(args)?;
+    let mut frames = FrameSequence::from_args(args, &config)?;
+    let mut stdout = io::stdout().lock();
+    if args.preview.is_some() {
+        write!(stdout, "{}", terminal::CLEAR)?;
+    }
+    let accumulation_frames = config.render.accumulation_frames;
+    for step in 1..=args.steps {
+        if let Some(script) = &mut script {
+            script
+                .step(&mut config, &mut model)
+                .map_err(io::Error::other)?;
+        }
+        if let Some(scenario) = &mut scenario {
+            if let Some(phase) = scenario.advance(&mut model).map_err(io::Error::other)? {
+                println!("{} at step {}", phase_label(scenario, phase), step);
+            }
+        }
+        model.step();
+        if let Some(dmx) = &mut dmx {
+            dmx.send(&model)?;
+        }
+        if let Some(frames) = &mut frames {
+            frames.capture(&mut model, &mut image, step)?;
+        }
+        // Motion blur averages the frames rendered before the final one.
+        if accumulation_frames > 1 && step + accumulation_frames > args.steps {
+            model.save_to_image(&mut image);
+        }
+        let preview = args
+            .preview
+            .filter(|_| step % args.preview_interval.max(1) == 0 || step == args.steps);
+        let led_due = led.is_some() && step % args.led_interval.max(1) == 0;
+        if preview.is_some() || led_due {
+            model.save_to_image(&mut image);
+        }
+        if let (Some(led), true) = (&mut led, led_due) {
+            led.send(&image)?;
+        }
+        if let Some(style) = preview {
+            let (columns, rows) = terminal::terminal_cells();
+            write!(
+                stdout,
+                "{}{}step {}/{}",
+                terminal::HOME,
+                terminal::render(&image, columns, rows, style),
+                step,
+                args.steps
+            )?;
+            stdout.flush()?;
+        }
+    }
+    if args.preview.is_some() {
+        writeln!(stdout)?;
+    }
+
+    model.save_to_image(&mut image);
+    let output = seeded_path(&args.output, seed);
+    if let Some(dir) = output.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    image
+        .save_with_format(&output, crate::physarum::image::ImageFormat::Png)
+        .map_err(io::Error::other)?;
+    writeln!(stdout, "Saved {}", output.display())?;
+    let mut outputs = vec![output.clone()];
+    if let Some(frames) = &frames {
+        writeln!(
+            stdout,
+            "Saved {} frames to {}",
+            frames.frames,
+            frames.dir.display()
+        )?;
+        outputs.push(frames.dir.clone());
+        if let Some(detector) = &frames.highlights {
+            detector.save(&frames.dir, frames.frames, args.video_fps)?;
+            writeln!(
+                stdout,
+                "Saved {} highlights to {}",
+                detector.highlights().len(),
+                frames.dir.join("chapters.txt").display()
+            )?;
+        }
+    }
+    if args.ora {
+        let path = output.with_extension("ora");
+        model.save_ora(&path)?;
+        writeln!(stdout, "Saved {}", path.display())?;
+        outputs.push(path);
+    }
+    if args.save_state {
+        let path = output.with_extension("state");
+        model.state().save(&path)?;
+        writeln!(stdout, "Saved {}", path.display())?;
+        outputs.push(path);
+    }
+    if let Some(format) = &args.network {
+        let path = output.with_extension(format);
+        let network = model.network(&NetworkSettings {
+            quantile: args.network_quantile,
+            ..NetworkSettings::default()
+        });
+        network.save(&path)?;
+        writeln!(
+            stdout,
+            "Saved {}, {} junctions and ends joined by {} lines {:.0} cells long",
+            path.display(),
+            network.nodes.len(),
+            network.edges.len(),
+            network.length()
+        )?;
+        if !model.cities().is_empty() {
+            writeln!(
+                stdout,
+                "{} of {} cities joined by the network",
+                joined_cities(&model, &network),
+                model.cities().len()
+            )?;
+        }
+        outputs.push(path);
+    }
+    if let Some(format) = &args.matrix {
+        let stem = output.with_extension("");
+        for (k, matrix) in model
+            .trail_matrices(args.matrix_downsample)
+            .iter()
+            .enumerate()
+        {
+            let path = PathBuf::from(format!("{}_trails{}.{}", stem.display(), k, format));
+            matrix.save(&path)?;
+            writeln!(
+                stdout,
+                "Saved {}, {} x {}",
+                path.display(),
+                matrix.width,
+                matrix.height
+            )?;
+            outputs.push(path);
+        }
+    }
+    if args.debug_strip {
+        let path = PathBuf::from(format!("{}_debug.png", output.with_extension("").display()));
+        model.debug_image().save(&path).map_err(io::Error::other)?;
+        writeln!(stdout, "Saved {}", path.display())?;
+        outputs.push(path);
+    }
+    if args.svg {
+        let path = output.with_extension("svg");
+        model.save_svg(&path)?;
+        writeln!(stdout, "Saved {}", path.display())?;
+        outputs.push(path);
+    }
+    if args.tiff {
+        let path = output.with_extension("tif");
+        model.save_tiff(&path)?;
+        let print = &config.render.print;
+        let grid = &model.grids[0];
+        let (width, height) = print.trim_size_mm(grid.width, grid.height);
+        writeln!(
+            stdout,
+            "Saved {}, {:.0} x {:.0} mm at {} dpi with {} mm bleed",
+            path.display(),
+            width,
+            height,
+            print.dpi,
+            print.bleed_mm
+        )?;
+        outputs.push(path);
+    }
+    let ink = &config.render.ink;
+    if ink.layers > 0 {
+        let layers = model.ink_layers(ink.layers, ink.method);
+        outputs.extend(crate::save_ink_layers(&output, &layers)?);
+    }
+    Ok(outputs)
+}
+
+/// The most cities the network joins into one piece, counting the cities with a junction
+/// or end of the network close to them.
+fn joined_cities(model: &PhysarumModel, network: &Network) -> usize {
+    let reach = model
+        .city_settings()
+        .map_or(0.0, |settings| settings.radius * 3.0);
+    let nodes: Vec<usize> = model
+        .cities()
+        .iter()
+        .filter_map(|city| {
+            let node = network.nearest_node(city.x, city.y)?;
+            let (dx, dy) = (
+                network.nodes[node].x - city.x,
+                network.nodes[node].y - city.y,
+            );
+            (dx * dx + dy * dy <= reach * reach).then_some(node)
+        })
+        .collect();
+    let mut pieces: Vec<Vec<usize>> = vec![];
+    for node in nodes {
+        match pieces
+            .iter_mut()
+            .find(|piece| network.shortest_path(piece[0], node).is_some())
+        {
+            Some(piece) => piece.push(node),
+            None => pieces.push(vec![node]),
+        }
+    }
+    pieces.iter().map(Vec::len).max().unwrap_or(0)
+}
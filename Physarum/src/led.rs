@@ -0,0 +1,156 @@
+use crate::artnet::{self, ArtNetSender};
+use crate::cli::Args;
+use crate::physarum::image::DynamicImage;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// RGB pixels fitting in the 512 channels of one DMX universe.
+const PIXELS_PER_UNIVERSE: usize = artnet::UNIVERSE_SIZE / 3;
+
+/// Where LED frames are sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LedTarget {
+    /// Adalight frames written to a serial device, e.g. a microcontroller driving WS2812
+    /// strips. The baud rate has to be set beforehand, e.g. with `stty`.
+    Serial(PathBuf),
+    /// Art-Net DMX packets sent over UDP to a controller, starting at universe 0.
+    ArtNet(String),
+}
+
+impl FromStr for LedTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("serial", path)) => Ok(LedTarget::Serial(PathBuf::from(path))),
+            Some(("artnet", host)) => Ok(LedTarget::ArtNet(host.to_string())),
+            _ => Err(format!(
+                "Unknown LED target {:?}, expected serial:<device> or artnet:<host>",
+                s
+            )),
+        }
+    }
+}
+
+enum Sink {
+    Serial(File),
+    ArtNet(ArtNetSender),
+}
+
+/// Sends the rendered frame to a small LED matrix, averaged down to one color per LED.
+pub struct LedMatrix {
+    sink: Sink,
+    columns: usize,
+    rows: usize,
+    /// Light emitted for each sRGB channel value, scaled by the brightness.
+    linear: [f32; 256],
+    serpentine: bool,
+}
+
+impl LedMatrix {
+    /// The matrix configured with `--led`, if any.
+    pub fn from_args(args: &Args) -> io::Result<Option<Self>> {
+        args.led
+            .as_ref()
+            .map(|target| {
+                LedMatrix::open(
+                    target,
+                    args.led_columns,
+                    args.led_rows,
+                    args.led_gamma,
+                    args.led_brightness,
+                    args.led_serpentine,
+                )
+            })
+            .transpose()
+    }
+
+    /// LEDs are driven linearly, so frame values are decoded with `gamma` before being sent,
+    /// then dimmed by `brightness` in [0, 1] to keep dense matrices within their power budget.
+    /// With `serpentine`, every other row is wired right to left.
+    pub fn open(
+        target: &LedTarget,
+        columns: usize,
+        rows: usize,
+        gamma: f32,
+        brightness: f32,
+        serpentine: bool,
+    ) -> io::Result<Self> {
+        let sink = match target {
+            LedTarget::Serial(path) => Sink::Serial(OpenOptions::new().write(true).open(path)?),
+            LedTarget::ArtNet(host) => Sink::ArtNet(ArtNetSender::open(host)?),
+        };
+        let brightness = brightness.clamp(0.0, 1.0);
+        let mut linear = [0.0; 256];
+        for (i, value) in linear.iter_mut().enumerate() {
+            *value = (i as f32 / 255.0).powf(gamma) * brightness;
+        }
+        Ok(LedMatrix {
+            sink,
+            columns: columns.max(1),
+            rows: rows.max(1),
+            linear,
+            serpentine,
+        })
+    }
+
+    /// Averages `image` down to the matrix in linear light and sends it.
+    pub fn send(&mut self, image: &DynamicImage) -> io::Result<()> {
+        let pixels = self.downsample(image);
+        match &mut self.sink {
+            Sink::Serial(file) => {
+                let count = pixels.len() - 1;
+                let (hi, lo) = ((count >> 8) as u8, count as u8);
+                let mut frame = vec![b'A', b'd', b'a', hi, lo, hi ^ lo ^ 0x55];
+                frame.extend(pixels.iter().flatten());
+                file.write_all(&frame)?;
+                file.flush()
+            }
+            Sink::ArtNet(sender) => {
+                sender.next_frame();
+                for (universe, chunk) in pixels.chunks(PIXELS_PER_UNIVERSE).enumerate() {
+                    let channels: Vec<u8> = chunk.iter().flatten().copied().collect();
+                    sender.send(universe as u16, &channels)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// LED colors in wiring order, each the mean light of the frame's pixels it covers.
+    fn downsample(&self, image: &DynamicImage) -> Vec<[u8; 3]> {
+        let image = image.to_rgb8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let mut light = vec![[0.0_f32; 3]; self.columns * self.rows];
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let column = x as usize * self.columns / width;
+            let row = y as usize * self.rows / height;
+            for (l, c) in light[row * self.columns + column].iter_mut().zip(pixel.0) {
+                *l += self.linear[c as usize];
+            }
+        }
+
+        let mut pixels = Vec::with_capacity(light.len());
+        for (row, line) in light.chunks_exact(self.columns).enumerate() {
+            let y0 = row * height / self.rows;
+            let y1 = (row + 1) * height / self.rows;
+            let mut line: Vec<[u8; 3]> = line
+                .iter()
+                .enumerate()
+                .map(|(column, sum)| {
+                    let x0 = column * width / self.columns;
+                    let x1 = (column + 1) * width / self.columns;
+                    let count = ((x1 - x0) * (y1 - y0)).max(1) as f32;
+                    sum.map(|l| (l / count * 255.0).round().clamp(0.0, 255.0) as u8)
+                })
+                .collect();
+            if self.serpentine && row % 2 == 1 {
+                line.reverse();
+            }
+            pixels.extend(line);
+        }
+        pixels
+    }
+}
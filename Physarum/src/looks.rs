@@ -0,0 +1,109 @@
+use crate::cli;
+use crate::physarum::config::SimulationConfig;
+use crate::physarum::image::ImageFormat;
+use crate::physarum::look::Look;
+use crate::physarum::physarum_model::PhysarumModel;
+use crate::physarum::state::ModelState;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Renders the saved `state` once with each of `looks` next to `output`, named after the
+/// looks, or with the look of `--look` or `--config` to `output` itself when none are
+/// given. The state's palette is kept by looks without one.
+pub fn run(
+    args: &cli::Args,
+    state: &Path,
+    looks: &[PathBuf],
+    output: &Path,
+) -> io::Result<Vec<PathBuf>> {
+    let mut model = load_model(state, 1)?;
+    let looks: Vec<(Look, PathBuf)> = if looks.is_empty() {
+        vec![(given_look(args)?, output.to_path_buf())]
+    } else {
+        let stem = output.with_extension("");
+        looks
+            .iter()
+            .map(|path| {
+                let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let name = file_stem.strip_suffix(".look").unwrap_or(&file_stem);
+                let named = |err: io::Error| {
+                    io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+                };
+                let look = Look::load(path).map_err(named)?;
+                Ok((
+                    look,
+                    PathBuf::from(format!("{}_{}.png", stem.display(), name)),
+                ))
+            })
+            .collect::<io::Result<_>>()?
+    };
+
+    let palette = *model.palette();
+    let mut outputs = vec![];
+    for (look, output) in looks {
+        model.set_palette(palette);
+        apply_look(&look, &mut model)?;
+        save(&model, &output)?;
+        outputs.push(output);
+    }
+    Ok(outputs)
+}
+
+/// Renders every saved state in `states` again with the look of `--look` or `--config`,
+/// on grids `scale` times larger, to `output/<state>.png`.
+pub fn rerender(
+    args: &cli::Args,
+    states: &[PathBuf],
+    scale: usize,
+    output: &Path,
+) -> io::Result<Vec<PathBuf>> {
+    let mut look = given_look(args)?;
+    look.render = look.render.scaled(scale as f32);
+    let mut outputs = vec![];
+    for state in states {
+        let mut model = load_model(state, scale)?;
+        apply_look(&look, &mut model)?;
+        let name = state.file_stem().unwrap_or_default().to_string_lossy();
+        let path = output.join(format!("{}.png", name));
+        save(&model, &path)?;
+        outputs.push(path);
+    }
+    Ok(outputs)
+}
+
+/// The look of `--look`, or else of `--config`, or the default one.
+fn given_look(args: &cli::Args) -> io::Result<Look> {
+    Ok(match (&args.look, &args.config) {
+        (Some(look), _) => look.clone(),
+        (None, Some(path)) => Look::of(&SimulationConfig::load(path)?),
+        (None, None) => Look::default(),
+    })
+}
+
+fn load_model(path: &Path, scale: usize) -> io::Result<PhysarumModel> {
+    let named = |err: io::Error| io::Error::new(err.kind(), format!("{}: {}", path.display(), err));
+    let mut state = ModelState::load(path).map_err(named)?;
+    if scale > 1 {
+        state = state.upsampled(scale)?;
+    }
+    PhysarumModel::from_state(state).map_err(|err| named(io::Error::other(err)))
+}
+
+fn apply_look(look: &Look, model: &mut PhysarumModel) -> io::Result<()> {
+    look.apply_to(model)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+fn save(model: &PhysarumModel, path: &Path) -> io::Result<()> {
+    let mut image = model.new_image();
+    model.save_to_image(&mut image);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    image
+        .save_with_format(path, ImageFormat::Png)
+        .map_err(io::Error::other)?;
+    println!("Saved {}", path.display());
+    Ok(())
+}
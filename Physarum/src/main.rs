@@ -1,240 +1,379 @@
 #![allow(dead_code)]
-pub mod physarum;
-pub mod ui;
+#[cfg(feature = "gui")]
+pub mod app;
+pub mod artnet;
+pub mod batch;
+pub mod cli;
+pub mod diff;
+pub mod distributed;
+pub mod dmx;
+pub mod gallery;
+pub mod headless;
+pub mod led;
+pub mod looks;
+pub use physarum_core as physarum;
+pub mod presets;
+pub mod replay;
+pub mod runs;
+pub mod script;
+pub mod seed;
+pub mod terminal;
 
-use chrono::{Datelike, Timelike};
-use fps_ticker::Fps;
-use nannou::{image::DynamicImage, prelude::*, wgpu::Texture};
-use nannou_egui::{self, Egui};
+use clap::Parser;
+use physarum::builder::PhysarumModelBuilder;
+use physarum::cities::CitySettings;
+use physarum::config::SimulationConfig;
+use physarum::error::ConfigError;
+use physarum::image::GrayImage;
+use physarum::mask::MaskShape;
 use physarum::physarum_model::PhysarumModel;
 use physarum::population_config::PopulationConfig;
+use physarum::scenario::Scenario;
 use rand::prelude::*;
+use seed::Seed;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+// None for random seed every run
+const SEED: Option<u64> = Some(0);
+const DEFAULT_CONFIGS: &str = r#"[{"sensor_distance":49.44998,"step_distance":1.4334296,"sensor_angle":1.4103054,"rotation_angle":1.3992796,"decay_factor":0.1,"deposition_amount":5.0},{"sensor_distance":16.194483,"step_distance":1.0892088,"sensor_angle":0.6044981,"rotation_angle":1.3636005,"decay_factor":0.1,"deposition_amount":5.0},{"sensor_distance":49.455574,"step_distance":0.92719734,"sensor_angle":2.0906055,"rotation_angle":0.57098943,"decay_factor":0.1,"deposition_amount":5.0},{"sensor_distance":25.101528,"step_distance":1.8175551,"sensor_angle":1.9417597,"rotation_angle":0.30816564,"decay_factor":0.1,"deposition_amount":5.0},{"sensor_distance":51.51055,"step_distance":1.4250815,"sensor_angle":0.14544931,"rotation_angle":1.485748,"decay_factor":0.1,"deposition_amount":5.0}]"#;
 
 fn main() {
-    nannou::app(model).update(update).run();
+    let mut args = cli::Args::parse();
+    if let Some(scenario) = load_scenario(&args) {
+        args.steps = scenario.steps();
+    }
+    if args.dry_run {
+        let config = load_config(&args);
+        let builder = PhysarumModelBuilder::from_config(&config);
+        println!(
+            "{}x{} with {} populations and {} agents",
+            config.width, config.height, config.n_populations, config.n_particles
+        );
+        println!("Needs {}", builder.memory_estimate());
+        let checked = builder
+            .validate()
+            .map_err(|err| format!("Invalid config: {}", err))
+            .and_then(|_| check_memory(&args, &config));
+        if let Err(err) = checked {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        println!("The config is valid");
+        return;
+    }
+    if args.tiles > 1 || !args.tile_peers.is_empty() {
+        let mut config = load_config(&args);
+        exit_over_budget(&args, &config);
+        let seed = pick_seed(&args, &config).value;
+        fill_populations(&mut config, &mut SmallRng::seed_from_u64(seed));
+        if let Err(err) = distributed::run(&args, config, seed) {
+            eprintln!("Distributed run failed: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(command) = &args.command {
+        let index = runs::RunIndex::new(&args.runs_dir);
+        let result = match command {
+            cli::Command::List => index.list(),
+            cli::Command::Show { id } => index.show(*id),
+            cli::Command::Gallery => gallery::generate(&index, &args.runs_dir)
+                .map(|path| println!("Wrote {}", path.display())),
+            cli::Command::Batch {
+                jobs,
+                concurrent,
+                force,
+            } => batch::run(&args, &index, jobs, *concurrent, *force),
+            cli::Command::Diff {
+                before,
+                after,
+                output,
+            } => diff::run(before, after, output),
+            cli::Command::Render {
+                state,
+                looks,
+                output,
+            } => looks::run(&args, state, looks, output).map(|_| ()),
+            cli::Command::Rerender {
+                states,
+                scale,
+                output,
+            } => looks::rerender(&args, states, *scale, output).map(|_| ()),
+            #[cfg(feature = "gui")]
+            cli::Command::Tournament { .. } => {
+                app::tournament::run();
+                Ok(())
+            }
+            #[cfg(not(feature = "gui"))]
+            cli::Command::Tournament { .. } => Err(std::io::Error::other(
+                "Built without the gui feature, tournaments need a window",
+            )),
+        };
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(path) = &args.replay {
+        if let Err(err) = replay::run(&args, path) {
+            eprintln!("Replay failed: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.headless {
+        let mut config = load_config(&args);
+        exit_over_budget(&args, &config);
+        let seed = pick_seed(&args, &config);
+        fill_populations(&mut config, &mut SmallRng::seed_from_u64(seed.value));
+        let start = Instant::now();
+        let outputs = headless::run(&args, config.clone(), &seed).unwrap_or_else(|err| {
+            eprintln!("Headless run failed: {}", err);
+            std::process::exit(1);
+        });
+        if !args.no_record {
+            let index = runs::RunIndex::new(&args.runs_dir);
+            match index.record(&config, seed.value, args.steps, start.elapsed(), &outputs) {
+                Ok(id) => println!("Recorded run {} in {}", id, args.runs_dir.display()),
+                Err(err) => eprintln!("Failed to record the run: {}", err),
+            }
+        }
+        return;
+    }
+    #[cfg(feature = "gui")]
+    app::run();
+    #[cfg(not(feature = "gui"))]
+    {
+        eprintln!("Built without the gui feature, run with --headless");
+        std::process::exit(1);
+    }
 }
 
-struct PhysarumSettings {
-    model: PhysarumModel,
-    configs: Vec<PopulationConfig>,
-    config_changed: bool,
-    width: usize,
-    height: usize,
-    n_particles: usize,
-    n_populations: usize,
-    diffusivity: usize,
-    palette_idx: usize,
+fn load_config(args: &cli::Args) -> SimulationConfig {
+    let config = match &args.config {
+        Some(path) => SimulationConfig::load(path).unwrap_or_else(|err| {
+            eprintln!("Failed to load the config {}: {}", path.display(), err);
+            std::process::exit(1);
+        }),
+        None => args
+            .preset
+            .clone()
+            .or_else(|| load_scenario(args)?.base_config().cloned())
+            .unwrap_or_default(),
+    };
+    override_config(args, config)
 }
 
-pub struct Model {
-    physarum_settings: PhysarumSettings,
-    fps_counter: Fps,
-    seed: u64,
-    egui_visible: bool,
-    steps_per_frame: usize,
-    egui: Egui,
-    main_window_id: WindowId,
-    rng: SmallRng,
-    changed: bool,
-    render: bool,
-    image: DynamicImage,
+/// The scenario of `--scenario`, exiting when it can't be loaded.
+pub fn load_scenario(args: &cli::Args) -> Option<Scenario> {
+    let path = args.scenario.as_deref()?;
+    Some(Scenario::load(path).unwrap_or_else(|err| {
+        eprintln!("Failed to load the scenario {}: {}", path.display(), err);
+        std::process::exit(1);
+    }))
 }
 
-impl Model {
-    fn new(
-        physarum_settings: PhysarumSettings,
-        seed: u64,
-        rng: SmallRng,
-        egui: Egui,
-        main_window_id: WindowId,
-        image: DynamicImage,
-    ) -> Self {
-        Model {
-            physarum_settings,
-            fps_counter: Fps::with_window_len(60),
-            steps_per_frame: 1,
-            egui_visible: true,
-            changed: true,
-            render: false,
-            main_window_id,
-            seed,
-            egui,
-            rng,
-            image,
+/// Applies the command line flags that override parts of a config.
+fn override_config(args: &cli::Args, mut config: SimulationConfig) -> SimulationConfig {
+    if let Some(look) = &args.look {
+        look.apply(&mut config);
+    }
+    if let Some(palette) = &args.palette {
+        config.palette = palette.clone();
+    }
+    if let Some(gradient) = &args.gradient {
+        for i in 0..config.n_populations {
+            config.render.style_mut(i).color = gradient.clone();
         }
     }
-
-    fn reset_rng(&mut self) {
-        self.rng = SmallRng::seed_from_u64(self.seed);
+    for (population, keyframes) in &args.fade {
+        config.render.style_mut(*population).opacity = keyframes.clone();
     }
-
-    fn reset(&mut self) {
-        self.reset_rng();
-        let sc = &self.physarum_settings;
-        let physarum_model = PhysarumModel::new(
-            sc.width,
-            sc.height,
-            sc.n_particles,
-            sc.n_populations,
-            sc.diffusivity,
-            sc.palette_idx,
-            &mut self.rng,
-        );
-        self.physarum_settings.model = physarum_model;
-        self.physarum_settings
-            .model
-            .set_population_configs(self.physarum_settings.configs.clone());
+    if args.lut.is_some() {
+        config.render.lut = args.lut.clone();
     }
+    if let Some(layers) = args.ink_layers {
+        config.render.ink.layers = layers;
+    }
+    if let Some(method) = args.dither {
+        config.render.ink.method = method;
+    }
+    if args.contours {
+        config.render.contours.enabled = true;
+    }
+    if args.stipple {
+        config.render.stipple.enabled = true;
+    }
+    if args.transparent {
+        config.render.alpha.enabled = true;
+    }
+    if let Some(vision) = args.color_vision {
+        config.render.color_vision = vision;
+    }
+    if let Some(shape) = &args.mask {
+        config.render.mask.shape = shape.clone();
+    }
+    if args.confine {
+        config.render.mask.confine = true;
+    }
+    if let Some(dpi) = args.dpi {
+        config.render.print.dpi = dpi;
+    }
+    if let Some(bleed) = args.bleed {
+        config.render.print.bleed_mm = bleed;
+    }
+    if let Some(profile) = args.color_profile {
+        config.render.print.profile = profile;
+    }
+    if args.bandpass.is_some() {
+        config.bandpass = args.bandpass;
+    }
+    if let Some(time_scale) = args.time_scale {
+        config.time_scale = time_scale;
+    }
+    if args.anneal.is_some() {
+        config.annealing = args.anneal;
+    }
+    if args.crowding.is_some() {
+        config.crowding = args.crowding;
+    }
+    if args.spacing.is_some() {
+        config.spacing = args.spacing;
+    }
+    if args.heat.is_some() {
+        config.heat = args.heat;
+    }
+    if args.food.is_some() {
+        config.food = args.food;
+    }
+    if let Some(path) = &args.cities {
+        config.cities = Some(CitySettings {
+            path: path.clone(),
+            ..config.cities.clone().unwrap_or_default()
+        });
+    }
+    if let Some(path) = &args.land {
+        config.render.mask.shape = MaskShape::Image { path: path.clone() };
+        config.render.mask.confine = true;
+    }
+    if let Some(initial_trails) = &args.initial_trails {
+        config.initial_trails = initial_trails.clone();
+    }
+    if let Some(every) = args.render_every {
+        config.render_every_n_steps = every;
+    }
+    if args.strict_determinism {
+        config.strict_determinism = true;
+    }
+    if args.fast_math {
+        config.fast_math = true;
+    }
+    if let Some(interval) = args.agent_sort_interval {
+        config.agent_sort_interval = interval;
+    }
+    config
 }
 
-pub fn get_random_configs(rng: &mut SmallRng) -> Vec<PopulationConfig> {
-    let mut configs = vec![];
-    (0..5).for_each(|_| {
-        configs.push(PopulationConfig::new(rng));
-    });
-    configs
-}
-
-fn model(app: &App) -> Model {
-    let seed_set: Option<u64> = Some(0);
-    let config_set: Option<String> = Some(
-        r#"[{"sensor_distance":49.44998,"step_distance":1.4334296,"sensor_angle":1.4103054,"rotation_angle":1.3992796,"decay_factor":0.1,"deposition_amount":5.0},{"sensor_distance":16.194483,"step_distance":1.0892088,"sensor_angle":0.6044981,"rotation_angle":1.3636005,"decay_factor":0.1,"deposition_amount":5.0},{"sensor_distance":49.455574,"step_distance":0.92719734,"sensor_angle":2.0906055,"rotation_angle":0.57098943,"decay_factor":0.1,"deposition_amount":5.0},{"sensor_distance":25.101528,"step_distance":1.8175551,"sensor_angle":1.9417597,"rotation_angle":0.30816564,"decay_factor":0.1,"deposition_amount":5.0},{"sensor_distance":51.51055,"step_distance":1.4250815,"sensor_angle":0.14544931,"rotation_angle":1.485748,"decay_factor":0.1,"deposition_amount":5.0}]"#.to_string(),
-    );
-
-    let image_window = app
-        .new_window()
-        .size(1400, 1024)
-        .view(view)
-        .key_pressed(key_pressed)
-        .mouse_pressed(mouse_pressed)
-        .raw_event(raw_window_event)
-        .title("Physarum")
-        .build()
-        .unwrap();
-
-    let window = app.window(image_window).unwrap();
-    let seed = seed_set.unwrap_or(thread_rng().next_u64());
-    let mut rng = SmallRng::seed_from_u64(seed);
-    println!("{}", seed);
-
-    // Physarum
-    let (width, height) = (1024, 1024);
-    let n_particles = 200000;
-    let n_populations = 2;
-    let diffusivity = 1;
-    let palette_idx = 0;
-    let configs: Vec<PopulationConfig> = {
-        match config_set {
-            Some(val) => serde_json::from_str(&val).unwrap_or(get_random_configs(&mut rng)),
-            None => get_random_configs(&mut rng),
-        }
+/// Fails when the config's estimated memory exceeds `--memory-budget`, before anything is
+/// allocated.
+fn check_memory(args: &cli::Args, config: &SimulationConfig) -> Result<(), String> {
+    let Some(budget) = args.memory_budget else {
+        return Ok(());
     };
-    let physarum_model = PhysarumModel::new(
-        width,
-        height,
-        n_particles,
-        n_populations,
-        diffusivity,
-        palette_idx,
-        &mut rng,
-    );
-
-    let physarum = PhysarumSettings {
-        width,
-        height,
-        n_particles,
-        n_populations,
-        diffusivity,
-        model: physarum_model,
-        palette_idx,
-        configs,
-        config_changed: true,
-    };
-
-    Model::new(
-        physarum,
-        seed,
-        rng,
-        Egui::from_window(&window),
-        image_window,
-        DynamicImage::new_rgb8(width as u32, height as u32),
-    )
+    let estimate = PhysarumModelBuilder::from_config(config).memory_estimate();
+    if estimate.total() > budget.saturating_mul(1 << 20) {
+        return Err(format!(
+            "The config needs {}, more than the budget of {} MiB",
+            estimate, budget
+        ));
+    }
+    Ok(())
 }
 
-fn update(_app: &App, model: &mut Model, update: Update) {
-    model.fps_counter.tick();
-    model.changed = false;
-    if model.egui_visible {
-        ui::update_gui(model, update);
-    }
-    for _ in 0..model.steps_per_frame {
-        model.physarum_settings.model.step();
-    }
-    model
-        .physarum_settings
-        .model
-        .save_to_image(&mut model.image);
+fn exit_over_budget(args: &cli::Args, config: &SimulationConfig) {
+    if let Err(err) = check_memory(args, config) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 }
 
-fn view(app: &App, model: &Model, frame: Frame) {
-    let draw = app.draw();
-    draw.background().color(BLACK);
-    let texture = Texture::from_image(app, &model.image);
-    let tx_w = texture.size()[1] as f32 * 0.5;
-    let right = app.window_rect().right();
-    let offset = right - tx_w;
-    draw.texture(&texture).x(offset);
-    draw.to_frame(app, &frame).unwrap();
-    if model.egui_visible {
-        model.egui.draw_to_frame(&frame).unwrap();
-    }
-    if model.render {
-        let _ = &model
-            .image
-            .save_with_format(get_path(app), nannou::image::ImageFormat::Png);
-    }
+fn pick_seed(args: &cli::Args, config: &SimulationConfig) -> Seed {
+    args.seed
+        .clone()
+        .or_else(|| config.seed.or(SEED).map(Seed::from))
+        .unwrap_or_else(|| Seed::random(&mut thread_rng()))
 }
 
-fn raw_window_event(_app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
-    model.egui.handle_raw_event(event);
+/// Applies the settings of `config` that a model doesn't keep in its state, for models
+/// restored from one.
+pub fn apply_settings(
+    model: &mut PhysarumModel,
+    config: &SimulationConfig,
+) -> Result<(), ConfigError> {
+    model.set_render_settings(config.render.clone())?;
+    model.set_bandpass(config.bandpass)?;
+    model.set_sensing(config.sensing.clone())?;
+    model.set_steering(config.steering.clone())?;
+    model.set_deposit_ops(config.deposit_ops.clone())?;
+    model.set_diffuse_ops(config.diffuse_ops.clone())?;
+    model.set_deposition_table(config.deposition_table.clone())?;
+    model.set_time_scale(config.time_scale)?;
+    model.set_annealing(config.annealing)?;
+    model.set_crowding(config.crowding)?;
+    model.set_spacing(config.spacing)?;
+    model.set_heat(config.heat)?;
+    model.set_food(config.food)?;
+    model.load_cities(config.cities.clone())?;
+    model.set_strict_determinism(config.strict_determinism);
+    model.set_fast_math(config.fast_math);
+    model.set_agent_sort_interval(config.agent_sort_interval);
+    Ok(())
 }
 
-fn key_pressed(app: &App, model: &mut Model, key: Key) {
-    if key == Key::S {
-        save_frame(app);
-    } else if key == Key::F {
-        model.egui_visible = !model.egui_visible;
-    } else if key == Key::D {
-        model.physarum_settings.model.print_configurations();
+/// Tells when there are more populations than palette colors, so that some are drawn in
+/// generated colors.
+pub fn warn_generated_colors(model: &PhysarumModel) {
+    let generated = model.generated_colors();
+    if generated > 0 {
+        eprintln!(
+            "{} populations but only {} palette colors, generating the rest",
+            model.grids.len(),
+            model.grids.len() - generated
+        );
     }
 }
 
-fn mouse_pressed(_app: &App, _model: &mut Model, button: MouseButton) {
-    if button == MouseButton::Left {}
+/// Gives a config without populations the default ones, and one listing too few random
+/// ones for the rest.
+fn fill_populations(config: &mut SimulationConfig, rng: &mut SmallRng) {
+    if config.populations.is_empty() {
+        config.populations =
+            serde_json::from_str(DEFAULT_CONFIGS).unwrap_or_else(|_| get_random_configs(rng));
+    }
+    while config.populations.len() < config.n_populations {
+        config.populations.push(PopulationConfig::new(rng));
+    }
 }
 
-fn save_frame(app: &App) {
-    let path = get_path(app);
-    app.main_window().capture_frame(path);
+pub fn get_random_configs(rng: &mut SmallRng) -> Vec<PopulationConfig> {
+    let mut configs = vec![];
+    (0..5).for_each(|_| {
+        configs.push(PopulationConfig::new(rng));
+    });
+    configs
 }
 
-fn get_path(app: &App) -> String {
-    let now = chrono::offset::Local::now();
-    let time = format!(
-        "{}_{}_{}_{}_{}_{}",
-        now.year(),
-        now.month(),
-        now.day(),
-        now.hour(),
-        now.minute(),
-        now.second()
-    );
-    let path = format!(
-        "{}/{}_{}{}",
-        "renders",
-        app.exe_name().unwrap(),
-        time,
-        ".png"
-    );
-    path
+/// Writes each ink layer next to `path` as `<stem>_ink<k>.png` and returns where.
+pub fn save_ink_layers(path: &Path, layers: &[GrayImage]) -> std::io::Result<Vec<PathBuf>> {
+    let stem = path.with_extension("");
+    let mut paths = Vec::with_capacity(layers.len());
+    for (k, layer) in layers.iter().enumerate() {
+        let layer_path = PathBuf::from(format!("{}_ink{}.png", stem.display(), k));
+        layer.save(&layer_path).map_err(std::io::Error::other)?;
+        println!("Saved {}", layer_path.display());
+        paths.push(layer_path);
+    }
+    Ok(paths)
 }
@@ -1,8 +0,0 @@
-mod blur;
-mod grid;
-pub mod palette;
-mod particle;
-pub mod physarum_model;
-pub mod population_config;
-mod trig;
-mod util;
@@ -0,0 +1,24 @@
+use crate::physarum::config::SimulationConfig;
+
+/// Curated looks embedded in the binary and selected with `--preset`. Each bundles population
+/// configs, an attraction table, a palette and diffusion settings that work well together.
+pub const PRESETS: [(&str, &str); 4] = [
+    ("nebula", include_str!("../presets/nebula.json")),
+    ("coral", include_str!("../presets/coral.json")),
+    ("neural", include_str!("../presets/neural.json")),
+    ("ink", include_str!("../presets/ink.json")),
+];
+
+/// The preset called `name`, for `--preset`.
+pub fn parse(name: &str) -> Result<SimulationConfig, String> {
+    let (_, json) = PRESETS.iter().find(|(n, _)| *n == name).ok_or_else(|| {
+        let names: Vec<_> = PRESETS.iter().map(|(n, _)| *n).collect();
+        format!(
+            "Unknown preset {:?}, expected one of {}",
+            name,
+            names.join(", ")
+        )
+    })?;
+    SimulationConfig::from_reader(json.as_bytes())
+        .map_err(|err| format!("Preset {} is broken: {}", name, err))
+}
@@ -0,0 +1,189 @@
+//! `--record-session` and `--replay`: logging every change of a live session, and
+//! rendering the session again without a window.
+
+use crate::apply_settings;
+use crate::cli::Args;
+use crate::physarum::config::SimulationConfig;
+use crate::physarum::image::ImageFormat;
+use crate::physarum::physarum_model::PhysarumModel;
+use crate::physarum::replay::{Change, ChangeRecorder};
+use crate::physarum::state::ModelState;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// One line of a replay log.
+#[derive(Serialize, Deserialize)]
+struct Event {
+    /// The steps the model had taken, the change applying before the next one.
+    iteration: u64,
+    /// Seconds since the recording started.
+    seconds: f64,
+    #[serde(flatten)]
+    entry: Entry,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Entry {
+    /// The session started over from a state saved next to the log, on launch and after
+    /// every reset.
+    Start {
+        state: PathBuf,
+        config: Box<SimulationConfig>,
+    },
+    Change(Change),
+    /// The window was closed.
+    Stop,
+}
+
+struct Log {
+    file: File,
+    path: PathBuf,
+    started: Instant,
+    segments: usize,
+}
+
+impl Log {
+    fn write(&mut self, iteration: u64, entry: Entry) -> io::Result<()> {
+        let event = Event {
+            iteration,
+            seconds: self.started.elapsed().as_secs_f64(),
+            entry,
+        };
+        writeln!(self.file, "{}", serde_json::to_string(&event)?)
+    }
+}
+
+/// Writes a replay log of a live session, one JSON event per line, so that `--replay` can
+/// render it again at full quality. Every segment of the session starts from a state
+/// saved beside the log, and the changes are applied at the step they were made in, which
+/// reproduces the session exactly.
+#[derive(Clone)]
+pub struct Recorder {
+    log: Arc<Mutex<Log>>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(Recorder {
+            log: Arc::new(Mutex::new(Log {
+                file: File::create(path)?,
+                path: path.to_path_buf(),
+                started: Instant::now(),
+                segments: 0,
+            })),
+        })
+    }
+
+    /// Starts a segment from the model as it is now and logs its changes from here on.
+    /// Has to be called again for every new model, e.g. after a reset.
+    pub fn start(&self, model: &mut PhysarumModel, config: &SimulationConfig) -> io::Result<()> {
+        let mut log = self.log.lock().unwrap();
+        let state = log.path.with_extension(format!("{}.state", log.segments));
+        model.state().save(&state)?;
+        log.segments += 1;
+        let entry = Entry::Start {
+            state: state.file_name().map(PathBuf::from).unwrap_or(state),
+            config: Box::new(config.clone()),
+        };
+        log.write(model.iteration(), entry)?;
+        drop(log);
+
+        let log = self.log.clone();
+        let mut recorder = ChangeRecorder::new(model);
+        model.on_parameter_change(move |model, parameter| {
+            let change = recorder.change(model, parameter);
+            if let Err(err) = log
+                .lock()
+                .unwrap()
+                .write(model.iteration(), Entry::Change(change))
+            {
+                eprintln!("Failed to record a change: {}", err);
+            }
+        });
+        Ok(())
+    }
+
+    pub fn stop(&self, iteration: u64) -> io::Result<()> {
+        self.log.lock().unwrap().write(iteration, Entry::Stop)
+    }
+}
+
+/// Renders every segment of a log written by `Recorder` again, stepping to where the
+/// segment ended and writing its final frame to `--output`, or with several segments to
+/// `<output>_<k>.png`. Returns the paths written.
+pub fn run(args: &Args, path: &Path) -> io::Result<Vec<PathBuf>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut segments: Vec<(Event, Vec<Event>)> = vec![];
+    for (number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let event: Event = serde_json::from_str(&line?)
+            .map_err(|err| invalid(format!("{} line {}: {}", path.display(), number + 1, err)))?;
+        match (&event.entry, segments.last_mut()) {
+            (Entry::Start { .. }, _) => segments.push((event, vec![])),
+            (_, Some((_, events))) => events.push(event),
+            (_, None) => {
+                return Err(invalid(format!(
+                    "{} doesn't start a session",
+                    path.display()
+                )))
+            }
+        }
+    }
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut outputs = vec![];
+    let count = segments.len();
+    for (k, (start, events)) in segments.into_iter().enumerate() {
+        let Entry::Start { state, config } = start.entry else {
+            unreachable!("segments begin with their start");
+        };
+        let mut model = PhysarumModel::from_state(ModelState::load(&dir.join(state))?)
+            .map_err(io::Error::other)?;
+        apply_settings(&mut model, &config).map_err(io::Error::other)?;
+        let end = events
+            .last()
+            .map_or(start.iteration, |event| event.iteration);
+        let mut image = model.new_image();
+        let accumulation_frames = config.render.accumulation_frames as u64;
+        let mut step_to = |model: &mut PhysarumModel, iteration: u64| {
+            while model.iteration() < iteration {
+                model.step();
+                // Motion blur averages the frames rendered before the final one.
+                if accumulation_frames > 1 && model.iteration() + accumulation_frames > end {
+                    model.save_to_image(&mut image);
+                }
+            }
+        };
+        for event in events {
+            step_to(&mut model, event.iteration);
+            if let Entry::Change(change) = event.entry {
+                change.apply(&mut model).map_err(io::Error::other)?;
+            }
+        }
+        step_to(&mut model, end);
+        model.save_to_image(&mut image);
+
+        let output = if count > 1 {
+            let stem = args.output.with_extension("");
+            PathBuf::from(format!("{}_{}.png", stem.display(), k))
+        } else {
+            args.output.clone()
+        };
+        if let Some(dir) = output.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        image
+            .save_with_format(&output, ImageFormat::Png)
+            .map_err(io::Error::other)?;
+        println!("Saved {} at step {}", output.display(), end);
+        outputs.push(output);
+    }
+    Ok(outputs)
+}
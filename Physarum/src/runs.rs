@@ -0,0 +1,126 @@
+use crate::physarum::config::SimulationConfig;
+use serde_json::Value;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Commit the binary was built from, set by the build script.
+const GIT_COMMIT: &str = match option_env!("PHYSARUM_GIT_COMMIT") {
+    Some(commit) => commit,
+    None => "unknown",
+};
+
+/// Appends headless renders to `index.jsonl` in a runs directory, one JSON record per line,
+/// so past experiments can be browsed and reproduced.
+pub struct RunIndex {
+    dir: PathBuf,
+}
+
+impl RunIndex {
+    pub fn new(dir: &Path) -> Self {
+        RunIndex {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.dir.join("index.jsonl")
+    }
+
+    /// Every recorded run, oldest first.
+    pub fn records(&self) -> io::Result<Vec<Value>> {
+        let file = match File::open(self.index_path()) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+        BufReader::new(file)
+            .lines()
+            .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect()
+    }
+
+    /// Appends a run and returns its id, one more than the last recorded one.
+    pub fn record(
+        &self,
+        config: &SimulationConfig,
+        seed: u64,
+        steps: usize,
+        duration: Duration,
+        outputs: &[PathBuf],
+    ) -> io::Result<u64> {
+        let id = self
+            .records()?
+            .last()
+            .and_then(|record| record["id"].as_u64())
+            .map_or(1, |id| id + 1);
+        let entry = serde_json::json!({
+            "id": id,
+            "time": chrono::offset::Local::now().to_rfc3339(),
+            "seed": seed,
+            "config_hash": config_hash(config)?,
+            "git_commit": GIT_COMMIT,
+            "steps": steps,
+            "duration_secs": duration.as_secs_f64(),
+            "outputs": outputs,
+            "config": config,
+        });
+        fs::create_dir_all(&self.dir)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.index_path())?;
+        writeln!(file, "{}", entry)?;
+        Ok(id)
+    }
+
+    /// Prints a line per run for `list`.
+    pub fn list(&self) -> io::Result<()> {
+        let records = self.records()?;
+        let mut stdout = io::stdout().lock();
+        if records.is_empty() {
+            writeln!(stdout, "No runs recorded in {}", self.dir.display())?;
+        }
+        for record in records {
+            writeln!(
+                stdout,
+                "{:>4}  {}  seed {:<20}  config {}  {:>8.1}s  {}",
+                record["id"],
+                record["time"].as_str().unwrap_or("?"),
+                record["seed"],
+                record["config_hash"].as_str().unwrap_or("?"),
+                record["duration_secs"].as_f64().unwrap_or(0.0),
+                record["outputs"][0].as_str().unwrap_or("")
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Prints the full record of run `id` for `show`.
+    pub fn show(&self, id: u64) -> io::Result<()> {
+        let record = self
+            .records()?
+            .into_iter()
+            .find(|record| record["id"].as_u64() == Some(id))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No run {} in {}", id, self.dir.display()),
+                )
+            })?;
+        writeln!(io::stdout(), "{}", serde_json::to_string_pretty(&record)?)
+    }
+}
+
+/// FNV-1a hash of the config's JSON, which stays the same across builds and platforms,
+/// unlike the std hashers, so runs of the same config can be matched up.
+fn config_hash(config: &SimulationConfig) -> io::Result<String> {
+    let hash = serde_json::to_vec(config)?
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    Ok(format!("{:016x}", hash))
+}
@@ -0,0 +1,199 @@
+//! `--script`, with a stand-in failing to load scripts when built without the scripting
+//! feature.
+
+#[cfg(feature = "scripting")]
+pub use rhai_script::Script;
+#[cfg(not(feature = "scripting"))]
+pub use stand_in::Script;
+
+#[cfg(feature = "scripting")]
+mod rhai_script {
+    use crate::physarum::config::SimulationConfig;
+    use crate::physarum::noise::Fbm;
+    use crate::physarum::physarum_model::PhysarumModel;
+    use crate::physarum::population_config::PopulationConfig;
+    use rhai::serde::{from_dynamic, to_dynamic};
+    use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST, FLOAT, INT};
+    use std::io;
+    use std::path::Path;
+
+    /// A Rhai script choreographing the parameters while the simulation runs. Its `step`
+    /// function is called before every step with `this` bound to a map of the parameters:
+    ///
+    /// - `iteration`, the steps taken so far, which is only read
+    /// - `time_scale`, see `PhysarumModel::set_time_scale`
+    /// - `populations`, an array with the fields of every `PopulationConfig`
+    /// - `attraction`, the attraction table as an array of rows
+    ///
+    /// Whatever it changes is applied to the model, and other keys it adds to `this` are kept
+    /// for the next call. Numbers are 32-bit floats, so parameters are set as `2.0` rather
+    /// than `2`. An optional `init` function is called the same way once before the
+    /// first step. For example, to sweep the sensor angle of the first population:
+    ///
+    /// ```rhai
+    /// fn init() { this.base = this.populations[0].sensor_angle; }
+    /// fn step() {
+    ///     let swing = 1.0 + 0.5 * sin(this.iteration / 200.0);
+    ///     this.populations[0].sensor_angle = this.base * swing;
+    /// }
+    /// ```
+    ///
+    /// Scripts can also call `noise(x, y)` or `noise(x, y, seed)` for smooth noise in about
+    /// [-1, 1] with features one unit across, such as `noise(this.iteration / 300.0, 0.0)`
+    /// for a parameter wandering rather than swinging.
+    pub struct Script {
+        engine: Engine,
+        ast: AST,
+        scope: Scope<'static>,
+        this: Map,
+        initialized: bool,
+    }
+
+    impl Script {
+        pub fn load(path: &Path) -> io::Result<Self> {
+            let mut engine = Engine::new();
+            engine.register_fn("noise", |x: FLOAT, y: FLOAT| noise(x, y, 0));
+            engine.register_fn("noise", noise);
+            let error = |err: Box<rhai::EvalAltResult>| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("script {}: {}", path.display(), err),
+                )
+            };
+            let ast = engine.compile_file(path.into()).map_err(error)?;
+            if !has_function(&ast, "step") {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("script {} has no step() function", path.display()),
+                ));
+            }
+            let mut scope = Scope::new();
+            engine.run_ast_with_scope(&mut scope, &ast).map_err(error)?;
+            Ok(Script {
+                engine,
+                ast,
+                scope,
+                this: Map::new(),
+                initialized: false,
+            })
+        }
+
+        /// Runs the script's `step`, and its `init` first on the first call, then applies the
+        /// parameters it changed to the model and the config it was built from.
+        pub fn step(
+            &mut self,
+            config: &mut SimulationConfig,
+            model: &mut PhysarumModel,
+        ) -> Result<(), String> {
+            let populations: Vec<PopulationConfig> =
+                model.grids.iter().map(|grid| grid.config).collect();
+            self.this
+                .insert("iteration".into(), (model.iteration() as INT).into());
+            self.this
+                .insert("time_scale".into(), (model.time_scale() as FLOAT).into());
+            self.this.insert(
+                "populations".into(),
+                to_dynamic(&populations).map_err(|err| err.to_string())?,
+            );
+            self.this.insert(
+                "attraction".into(),
+                to_dynamic(model.attraction_table()).map_err(|err| err.to_string())?,
+            );
+
+            if !self.initialized {
+                self.initialized = true;
+                if has_function(&self.ast, "init") {
+                    self.call("init")?;
+                }
+            }
+            self.call("step")?;
+
+            let time_scale: f32 = self.read("time_scale")?;
+            if time_scale != model.time_scale() {
+                model
+                    .set_time_scale(time_scale)
+                    .map_err(|err| err.to_string())?;
+                config.time_scale = time_scale;
+            }
+            let changed: Vec<PopulationConfig> = self.read("populations")?;
+            if changed != populations {
+                model
+                    .set_population_configs(changed.clone())
+                    .map_err(|err| err.to_string())?;
+                config.populations = changed;
+            }
+            let attraction: Vec<Vec<f32>> = self.read("attraction")?;
+            if attraction != model.attraction_table() {
+                model
+                    .set_attraction_table(attraction.clone())
+                    .map_err(|err| err.to_string())?;
+                config.attraction_table = Some(attraction);
+            }
+            Ok(())
+        }
+
+        fn call(&mut self, name: &str) -> Result<(), String> {
+            let mut this = Dynamic::from_map(std::mem::take(&mut self.this));
+            let options = CallFnOptions::new()
+                .eval_ast(false)
+                .bind_this_ptr(&mut this);
+            let result = self.engine.call_fn_with_options::<Dynamic>(
+                options,
+                &mut self.scope,
+                &self.ast,
+                name,
+                (),
+            );
+            self.this = this.try_cast::<Map>().ok_or_else(|| {
+                format!("{}() replaced this with something other than a map", name)
+            })?;
+            result
+                .map(|_| ())
+                .map_err(|err| format!("{}(): {}", name, err))
+        }
+
+        fn read<T: serde::de::DeserializeOwned>(&self, key: &str) -> Result<T, String> {
+            let value = self
+                .this
+                .get(key)
+                .ok_or_else(|| format!("the script removed {}", key))?;
+            from_dynamic(value).map_err(|err| format!("{}: {}", key, err))
+        }
+    }
+
+    fn noise(x: FLOAT, y: FLOAT, seed: INT) -> FLOAT {
+        Fbm::new(1.0, 3, seed as u64).sample(x, y)
+    }
+
+    fn has_function(ast: &AST, name: &str) -> bool {
+        ast.iter_functions()
+            .any(|function| function.name == name && function.params.is_empty())
+    }
+}
+
+#[cfg(not(feature = "scripting"))]
+mod stand_in {
+    use crate::physarum::config::SimulationConfig;
+    use crate::physarum::physarum_model::PhysarumModel;
+    use std::io;
+    use std::path::Path;
+
+    pub enum Script {}
+
+    impl Script {
+        pub fn load(_: &Path) -> io::Result<Self> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "built without the scripting feature",
+            ))
+        }
+
+        pub fn step(
+            &mut self,
+            _: &mut SimulationConfig,
+            _: &mut PhysarumModel,
+        ) -> Result<(), String> {
+            match *self {}
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use rand::Rng;
+use std::fmt;
+
+const COLORS: [&str; 32] = [
+    "amber", "azure", "coral", "crimson", "cobalt", "copper", "dusky", "ebony", "golden", "hazel",
+    "indigo", "ivory", "jade", "lilac", "misty", "ochre", "olive", "opal", "pearl", "plum", "rosy",
+    "ruby", "russet", "sable", "scarlet", "silver", "slate", "smoky", "tawny", "teal", "umber",
+    "violet",
+];
+const CREATURES: [&str; 32] = [
+    "badger", "beetle", "bison", "crane", "falcon", "ferret", "finch", "gecko", "heron", "ibex",
+    "jackal", "koala", "lynx", "marten", "moth", "newt", "otter", "owl", "panda", "puffin",
+    "raven", "salmon", "shrew", "stoat", "swift", "tapir", "toad", "trout", "vole", "walrus",
+    "wasp", "wren",
+];
+const MOMENTS: [&str; 32] = [
+    "autumn", "blizzard", "breeze", "dawn", "drizzle", "dusk", "eclipse", "equinox", "evening",
+    "frost", "gale", "harvest", "haze", "midnight", "monsoon", "moonrise", "morning", "night",
+    "noon", "rain", "solstice", "spring", "squall", "storm", "summer", "sunrise", "sunset", "thaw",
+    "thunder", "twilight", "winter", "zenith",
+];
+
+/// A seed for the random number generators, optionally given as a phrase of words such as
+/// `amber-falcon-dusk` that is easier to remember and share than a number. The phrase is
+/// hashed to the seed, so anything typed the same way gives the same run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Seed {
+    pub value: u64,
+    /// The phrase `value` was hashed from, in lower case and joined by dashes.
+    pub name: Option<String>,
+}
+
+impl Seed {
+    /// A fresh phrase of a color, a creature and a moment.
+    pub fn random(rng: &mut impl Rng) -> Self {
+        let words = [
+            COLORS[rng.gen_range(0..COLORS.len())],
+            CREATURES[rng.gen_range(0..CREATURES.len())],
+            MOMENTS[rng.gen_range(0..MOMENTS.len())],
+        ];
+        Seed::named(&words.join("-"))
+    }
+
+    /// The seed hashed from a phrase, with its words split at dashes, underscores or spaces
+    /// and compared ignoring case.
+    pub fn named(phrase: &str) -> Self {
+        let name = phrase
+            .split(['-', '_', ' '])
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>()
+            .join("-");
+        Seed {
+            value: fnv1a(name.as_bytes()),
+            name: Some(name),
+        }
+    }
+
+    /// The phrase, or the number for seeds without one, for use in file names.
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self.value.to_string(),
+        }
+    }
+}
+
+impl From<u64> for Seed {
+    fn from(value: u64) -> Self {
+        Seed { value, name: None }
+    }
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} ({})", name, self.value),
+            None => write!(f, "{}", self.value),
+        }
+    }
+}
+
+/// A number or a phrase, for `--seed`.
+pub fn parse(value: &str) -> Result<Seed, String> {
+    if let Ok(number) = value.parse::<u64>() {
+        return Ok(Seed::from(number));
+    }
+    let seed = Seed::named(value);
+    match &seed.name {
+        Some(name) if name.chars().all(|c| c.is_alphanumeric() || c == '-') && !name.is_empty() => {
+            Ok(seed)
+        }
+        _ => Err(format!(
+            "Invalid seed {:?}, expected a number or words like amber-falcon-dusk",
+            value
+        )),
+    }
+}
+
+/// 64-bit FNV-1a, which unlike the std hashers is guaranteed to stay the same across
+/// versions and platforms.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
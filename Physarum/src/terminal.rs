@@ -0,0 +1,200 @@
+use crate::physarum::image::{
+    imageops, imageops::FilterType, DynamicImage, GenericImageView, ImageOutputFormat, RgbImage,
+};
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// How frames are drawn with text when previewing in a terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerminalStyle {
+    /// `▀` cells with their own foreground and background colors, two pixels per cell.
+    HalfBlock,
+    /// Braille patterns, eight dots per cell in a single color each.
+    Braille,
+    /// A real image through the Kitty graphics protocol.
+    Kitty,
+    /// A real image through iTerm2's inline images (OSC 1337), also understood by WezTerm.
+    Iterm,
+}
+
+impl FromStr for TerminalStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half-block" => Ok(TerminalStyle::HalfBlock),
+            "braille" => Ok(TerminalStyle::Braille),
+            "kitty" => Ok(TerminalStyle::Kitty),
+            "iterm" => Ok(TerminalStyle::Iterm),
+            _ => Err(format!(
+                "Unknown terminal style {:?}, expected half-block, braille, kitty or iterm",
+                s
+            )),
+        }
+    }
+}
+
+/// Number of character columns and rows available, leaving a line for the status.
+pub fn terminal_cells() -> (usize, usize) {
+    match terminal_size::terminal_size() {
+        Some((terminal_size::Width(w), terminal_size::Height(h))) => {
+            (w as usize, (h as usize).saturating_sub(1).max(1))
+        }
+        None => (80, 23),
+    }
+}
+
+/// Renders `image` as escape sequences fitting in `columns` x `rows` cells, keeping the
+/// image's aspect ratio with terminal cells assumed twice as tall as wide.
+pub fn render(image: &DynamicImage, columns: usize, rows: usize, style: TerminalStyle) -> String {
+    let aspect = image.height() as f32 / image.width() as f32;
+    let columns = columns.min((rows as f32 * 2.0 / aspect) as usize).max(1);
+    let rows = ((columns as f32 * aspect / 2.0).round() as usize).clamp(1, rows.max(1));
+    let (dots_x, dots_y) = match style {
+        TerminalStyle::HalfBlock => (1, 2),
+        TerminalStyle::Braille => (2, 4),
+        TerminalStyle::Kitty | TerminalStyle::Iterm => {
+            return inline_image(image, columns, rows, style)
+        }
+    };
+    let small = imageops::resize(
+        &image.to_rgb8(),
+        (columns * dots_x) as u32,
+        (rows * dots_y) as u32,
+        FilterType::Triangle,
+    );
+
+    let mut out = String::new();
+    for row in 0..rows as u32 {
+        for column in 0..columns as u32 {
+            match style {
+                TerminalStyle::HalfBlock => {
+                    let top = small.get_pixel(column, row * 2).0;
+                    let bottom = small.get_pixel(column, row * 2 + 1).0;
+                    let _ = write!(
+                        out,
+                        "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                        top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+                    );
+                }
+                TerminalStyle::Braille => braille_cell(&mut out, &small, column, row),
+                TerminalStyle::Kitty | TerminalStyle::Iterm => unreachable!(),
+            }
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+/// Lights the dots of a 2x4 block brighter than the block's mean and colors the
+/// cell with the average of the lit dots.
+fn braille_cell(out: &mut String, image: &RgbImage, column: u32, row: u32) {
+    // Bit of each dot in the Unicode Braille pattern, indexed by [y][x].
+    const BITS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+    let dots: Vec<(u32, [u8; 3])> = (0..4)
+        .flat_map(|y| (0..2).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let pixel = image.get_pixel(column * 2 + x, row * 4 + y).0;
+            (BITS[y as usize][x as usize], pixel)
+        })
+        .collect();
+    let luminance =
+        |p: &[u8; 3]| 0.2126 * p[0] as f32 + 0.7152 * p[1] as f32 + 0.0722 * p[2] as f32;
+    let mean = dots.iter().map(|(_, p)| luminance(p)).sum::<f32>() / dots.len() as f32;
+
+    let mut pattern = 0;
+    let mut color = [0_u32; 3];
+    let mut lit = 0;
+    for (bit, pixel) in &dots {
+        if luminance(pixel) >= mean && luminance(pixel) > 8.0 {
+            pattern |= bit;
+            lit += 1;
+            for (c, p) in color.iter_mut().zip(pixel) {
+                *c += *p as u32;
+            }
+        }
+    }
+    let color = color.map(|c| c / lit.max(1));
+    let glyph = char::from_u32(0x2800 + pattern).unwrap_or(' ');
+    let _ = write!(
+        out,
+        "\x1b[38;2;{};{};{}m{}",
+        color[0], color[1], color[2], glyph
+    );
+}
+
+/// Largest side in pixels of images sent inline, to keep previews light over SSH.
+const INLINE_MAX_SIZE: u32 = 512;
+
+/// Sends `image` as a PNG through the terminal's inline image protocol, scaled to span
+/// `columns` x `rows` cells.
+fn inline_image(image: &DynamicImage, columns: usize, rows: usize, style: TerminalStyle) -> String {
+    let image = if image.width().max(image.height()) > INLINE_MAX_SIZE {
+        image.thumbnail(INLINE_MAX_SIZE, INLINE_MAX_SIZE)
+    } else {
+        image.clone()
+    };
+    let mut png = vec![];
+    if let Err(err) = image.write_to(&mut png, ImageOutputFormat::Png) {
+        return format!("Failed to encode preview: {}", err);
+    }
+    let data = base64(&png);
+
+    match style {
+        TerminalStyle::Kitty => {
+            // Kitty limits escape payloads to 4096 bytes, so the image goes out in chunks
+            // with `m=1` on every chunk but the last. Reusing the image id replaces the
+            // previous frame instead of piling up images in the terminal.
+            let chunks: Vec<_> = data.as_bytes().chunks(4096).collect();
+            let mut out = String::new();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = (i + 1 < chunks.len()) as u8;
+                let chunk = std::str::from_utf8(chunk).unwrap();
+                if i == 0 {
+                    let _ = write!(
+                        out,
+                        "\x1b_Ga=T,f=100,q=2,i=1,p=1,c={},r={},m={};{}\x1b\\",
+                        columns, rows, more, chunk
+                    );
+                } else {
+                    let _ = write!(out, "\x1b_Gm={};{}\x1b\\", more, chunk);
+                }
+            }
+            out.push('\n');
+            out
+        }
+        _ => format!(
+            "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1:{}\x07\n",
+            png.len(),
+            columns,
+            rows,
+            data
+        ),
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Clears the screen and moves the cursor home.
+pub const CLEAR: &str = "\x1b[2J\x1b[H";
+/// Moves the cursor home so a frame overwrites the previous one without flicker.
+pub const HOME: &str = "\x1b[H";